@@ -0,0 +1,184 @@
+#! /bin/bash
+
+# Measures PUT/GET/LIST latencies of a running gateway and compares them against a stored baseline.
+
+set -e
+
+usage() {
+    echo "$(basename ${0}) -a access_key -s secret_key [-e endpoint] [-z sizes] [-c concurrency] [-n requests] [-b] [-t threshold]"
+    echo "- a script to benchmark the gateway and catch performance regressions."
+    echo ""
+    echo "Options:"
+    echo "  -a access_key: access key configured in the gateway"
+    echo "  -s secret_key: secret key configured in the gateway"
+    echo "  -e endpoint: URL of the gateway (default ${ENDPOINT})"
+    echo "  -z sizes: comma separated object sizes in bytes (default ${SIZES})"
+    echo "  -c concurrency: comma separated numbers of concurrent clients (default ${CONCURRENCY})"
+    echo "  -n requests: requests of every operation per size and concurrency (default ${REQUESTS})"
+    echo "  -b: store the results as the new baseline (${BASELINE})"
+    echo "  -t threshold: allowed p99 regression against the baseline in percent (default ${THRESHOLD})"
+    echo "  -h: to see this text"
+    echo ""
+    echo "The results are written to ${RESULTS}. The script fails if a p99 latency regresses above the threshold."
+}
+
+prepare() {
+    if [ ! -d "${VENV}" ]; then
+        python3 -m venv "${VENV}"
+        "${VENV}/bin/pip" install -q boto3
+    fi
+}
+
+run() {
+    "${VENV}/bin/python" - <<'PY'
+import concurrent.futures
+import json
+import os
+import time
+import uuid
+
+import boto3
+from botocore.config import Config
+
+endpoint = os.environ["ENDPOINT"]
+requests = int(os.environ["REQUESTS"])
+s3 = boto3.client(
+    "s3",
+    endpoint_url=endpoint,
+    aws_access_key_id=os.environ["S3_ACCESS_KEY"],
+    aws_secret_access_key=os.environ["S3_SECRET_KEY"],
+    region_name=os.environ["REGION"],
+    config=Config(max_pool_connections=256, retries={"max_attempts": 1}),
+)
+bucket = f"bench-{uuid.uuid4().hex[:12]}"
+s3.create_bucket(Bucket=bucket)
+
+
+def measure(operation, concurrency):
+    def timed(i):
+        started = time.perf_counter()
+        operation(i)
+        return time.perf_counter() - started
+
+    with concurrent.futures.ThreadPoolExecutor(concurrency) as pool:
+        started = time.perf_counter()
+        latencies = sorted(pool.map(timed, range(requests)))
+        elapsed = time.perf_counter() - started
+    return {
+        "p50_ms": latencies[len(latencies) // 2] * 1000,
+        "p99_ms": latencies[min(len(latencies) - 1, len(latencies) * 99 // 100)] * 1000,
+        "ops": requests / elapsed,
+    }
+
+
+results = {}
+try:
+    for size in map(int, os.environ["SIZES"].split(",")):
+        body = os.urandom(size)
+        for concurrency in map(int, os.environ["CONCURRENCY"].split(",")):
+            prefix = f"{size}-{concurrency}/"
+            key = lambda i: f"{prefix}{i:08}"
+            results[f"put/{size}/{concurrency}"] = measure(lambda i: s3.put_object(Bucket=bucket, Key=key(i), Body=body), concurrency)
+            results[f"get/{size}/{concurrency}"] = measure(lambda i: s3.get_object(Bucket=bucket, Key=key(i))["Body"].read(), concurrency)
+            results[f"list/{size}/{concurrency}"] = measure(
+                lambda i: s3.list_objects_v2(Bucket=bucket, Prefix=prefix, StartAfter=key(i), MaxKeys=100), concurrency
+            )
+finally:
+    paginator = s3.get_paginator("list_objects_v2")
+    for page in paginator.paginate(Bucket=bucket):
+        keys = [{"Key": o["Key"]} for o in page.get("Contents", [])]
+        if keys:
+            s3.delete_objects(Bucket=bucket, Delete={"Objects": keys, "Quiet": True})
+    s3.delete_bucket(Bucket=bucket)
+
+with open(os.environ["RESULTS"], "w") as f:
+    json.dump(results, f, indent=2, sort_keys=True)
+for name, r in sorted(results.items()):
+    print(f"{name:<24} p50 {r['p50_ms']:9.2f} ms  p99 {r['p99_ms']:9.2f} ms  {r['ops']:9.1f} op/s")
+PY
+}
+
+compare() {
+    python3 - "${BASELINE}" "${RESULTS}" "${THRESHOLD}" <<'PY'
+import json
+import sys
+
+baseline = json.load(open(sys.argv[1]))
+results = json.load(open(sys.argv[2]))
+threshold = float(sys.argv[3])
+regressions = []
+for name, r in sorted(results.items()):
+    base = baseline.get(name)
+    if base is None:
+        continue
+    change = (r["p99_ms"] / base["p99_ms"] - 1) * 100
+    if change > threshold:
+        regressions.append(f"{name}: p99 {base['p99_ms']:.2f} ms -> {r['p99_ms']:.2f} ms (+{change:.0f}%)")
+for line in regressions:
+    print(line)
+sys.exit(1 if regressions else 0)
+PY
+}
+
+export ENDPOINT="http://localhost:8014"
+export REGION="us-east-1"
+export SIZES="4096,1048576,16777216"
+export CONCURRENCY="1,16,64"
+export REQUESTS="200"
+export RESULTS="${PWD}/target/bench/results.json"
+BASELINE="${PWD}/bench/baseline.json"
+VENV="${PWD}/target/bench/.venv"
+THRESHOLD="20"
+UPDATE_BASELINE=""
+
+while getopts "ha:s:e:z:c:n:bt:" OPT; do
+    case "$OPT" in
+        h)
+            usage
+            exit 0
+            ;;
+        a)
+            export S3_ACCESS_KEY="${OPTARG}"
+            ;;
+        s)
+            export S3_SECRET_KEY="${OPTARG}"
+            ;;
+        e)
+            ENDPOINT="${OPTARG}"
+            ;;
+        z)
+            SIZES="${OPTARG}"
+            ;;
+        c)
+            CONCURRENCY="${OPTARG}"
+            ;;
+        n)
+            REQUESTS="${OPTARG}"
+            ;;
+        b)
+            UPDATE_BASELINE="1"
+            ;;
+        t)
+            THRESHOLD="${OPTARG}"
+            ;;
+    esac
+done
+
+if [ -z "$S3_ACCESS_KEY" -o -z "$S3_SECRET_KEY" ]; then
+    echo "Please provide the access key and the secret key of the gateway."
+    exit 1
+fi
+
+mkdir -p "$(dirname "${RESULTS}")"
+prepare
+run
+
+if [ -n "$UPDATE_BASELINE" ]; then
+    mkdir -p "$(dirname "${BASELINE}")"
+    cp "${RESULTS}" "${BASELINE}"
+    echo "baseline has been updated"
+elif [ -e "${BASELINE}" ]; then
+    compare
+else
+    echo "no baseline to compare with, store one with -b"
+fi
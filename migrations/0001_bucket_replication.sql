@@ -0,0 +1,26 @@
+ALTER TABLE buckets ADD COLUMN replication_role varchar;
+
+CREATE TABLE bucket_replication (
+    bucket varchar(63) not null,
+    rule_id varchar not null,
+    priority integer not null,
+    prefix varchar not null,
+    enabled boolean not null,
+    destination_bucket varchar not null,
+    storage_class varchar,
+
+    PRIMARY KEY(bucket, rule_id),
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE
+);
+
+ALTER TABLE objects ADD COLUMN replication_status varchar;
+
+-- outbox of committed blobs waiting to be copied to the secondary backend
+CREATE TABLE replication_queue (
+    blob_id uuid PRIMARY KEY,
+    bucket varchar not null,
+    oid varchar not null,
+    destination_bucket varchar not null,
+    created_at timestamp not null
+);
+CREATE INDEX replication_queue_created_at ON replication_queue(created_at);
@@ -0,0 +1,45 @@
+CREATE TABLE multipart_uploads (
+    upload_id uuid PRIMARY KEY,
+    bucket varchar not null,
+    oid varchar not null,
+    created_at timestamp not null,
+
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE RESTRICT
+);
+CREATE INDEX multipart_uploads_bucket ON multipart_uploads(bucket, oid);
+
+CREATE TABLE multipart_parts (
+    upload_id uuid not null,
+    part_number integer not null,
+    blob_id uuid not null,
+    size bigint not null,
+    etag varchar not null,
+    uploaded_at timestamp not null,
+
+    PRIMARY KEY(upload_id, part_number),
+    CONSTRAINT upload_id_fk FOREIGN KEY (upload_id) REFERENCES multipart_uploads(upload_id) ON DELETE CASCADE
+);
+
+-- backend objects that form a multipart blob
+CREATE TABLE blob_parts (
+    blob_id uuid not null,
+    part_index integer not null,
+    part_blob_id uuid not null,
+    size bigint not null,
+
+    PRIMARY KEY(blob_id, part_index),
+    CONSTRAINT blob_id_fk FOREIGN KEY (blob_id) REFERENCES blobs(id) ON DELETE CASCADE
+);
+
+-- allows to replay CompleteMultipartUpload after the upload has been committed
+CREATE TABLE completed_multipart_uploads (
+    upload_id uuid PRIMARY KEY,
+    bucket varchar not null,
+    oid varchar not null,
+    blob_id uuid not null,
+    completed_at timestamp not null
+);
+CREATE INDEX completed_multipart_uploads_completed_at ON completed_multipart_uploads(completed_at);
+
+-- up to 10000 parts are allowed
+ALTER TABLE blobs ALTER COLUMN parts TYPE integer;
@@ -0,0 +1,3 @@
+ALTER TABLE buckets ADD COLUMN object_ownership varchar;
+ALTER TABLE buckets ADD COLUMN acl varchar not null DEFAULT 'private';
+ALTER TABLE buckets ADD COLUMN object_lock_enabled boolean not null DEFAULT false;
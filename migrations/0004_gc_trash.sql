@@ -0,0 +1,7 @@
+-- blobs are removed from the backend only after not_before
+ALTER TABLE blobs_gc ADD COLUMN not_before timestamp not null DEFAULT CURRENT_TIMESTAMP;
+-- object the blob was attached to, allows to undelete it during the trash window
+ALTER TABLE blobs_gc ADD COLUMN bucket varchar;
+ALTER TABLE blobs_gc ADD COLUMN oid varchar;
+CREATE INDEX blobs_gc_not_before ON blobs_gc(not_before);
+CREATE INDEX blobs_gc_object ON blobs_gc(bucket, oid);
@@ -0,0 +1,16 @@
+CREATE TABLE bucket_inventory (
+    bucket varchar(63) not null,
+    inventory_id varchar not null,
+    enabled boolean not null,
+    prefix varchar,
+    destination_bucket varchar not null,
+    destination_prefix varchar,
+    format varchar not null,
+    frequency varchar not null,
+    included_versions varchar not null,
+    -- start of the last report, NULL if the report has never been generated
+    last_run timestamp,
+
+    PRIMARY KEY(bucket, inventory_id),
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE
+);
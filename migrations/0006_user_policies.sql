@@ -0,0 +1,9 @@
+CREATE TABLE user_policies (
+    user_id varchar not null,
+    name varchar not null,
+    -- JSON policy document
+    document varchar not null,
+
+    PRIMARY KEY(user_id, name),
+    CONSTRAINT user_id_fk FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- region of the gateway which created the bucket, NULL for buckets created before regions were tracked
+ALTER TABLE buckets ADD COLUMN region varchar;
@@ -0,0 +1,4 @@
+-- user metadata as a JSON object
+ALTER TABLE objects ADD COLUMN metadata varchar;
+-- tag set in the x-amz-tagging form (URL-encoded query string)
+ALTER TABLE objects ADD COLUMN tagging varchar;
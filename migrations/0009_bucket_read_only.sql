@@ -0,0 +1,2 @@
+-- mutating requests to the bucket are rejected while set, used during migrations and maintenance
+ALTER TABLE buckets ADD COLUMN read_only boolean NOT NULL DEFAULT false;
@@ -0,0 +1,10 @@
+-- progress of online data migrations, see src/data_migration.rs
+CREATE TABLE migration_jobs (
+    name varchar PRIMARY KEY,
+    -- last key processed by the migration, NULL before the first batch
+    checkpoint varchar,
+    processed bigint NOT NULL DEFAULT 0,
+    started_at timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at timestamp
+);
@@ -0,0 +1,14 @@
+-- settings which are only stored for clients to read back, the gateway has no acceleration or billing
+ALTER TABLE buckets ADD COLUMN accelerate_status varchar;
+ALTER TABLE buckets ADD COLUMN request_payer varchar NOT NULL DEFAULT 'BucketOwner';
+
+CREATE TABLE bucket_public_access_block (
+    bucket varchar(63) not null,
+    block_public_acls boolean not null,
+    ignore_public_acls boolean not null,
+    block_public_policy boolean not null,
+    restrict_public_buckets boolean not null,
+
+    PRIMARY KEY(bucket),
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- the bucket is hidden from clients while its objects are removed in the background
+ALTER TABLE buckets ADD COLUMN deleting boolean NOT NULL DEFAULT false;
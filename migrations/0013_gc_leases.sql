@@ -0,0 +1,6 @@
+-- a blob is removed by the worker holding the lease, an expired lease can be claimed by another one
+ALTER TABLE blobs_gc ADD COLUMN leased_until timestamp;
+-- number of times the blob has been claimed
+ALTER TABLE blobs_gc ADD COLUMN attempts integer NOT NULL DEFAULT 0;
+-- dead letter: the removal has failed too many times and is not retried until requeued through the admin API
+ALTER TABLE blobs_gc ADD COLUMN failed boolean NOT NULL DEFAULT false;
@@ -0,0 +1,15 @@
+-- timestamps have always been written and read as UTC, store them with the time zone
+ALTER TABLE buckets ALTER COLUMN creation_date TYPE timestamptz USING creation_date AT TIME ZONE 'UTC';
+ALTER TABLE blobs ALTER COLUMN uploaded_at TYPE timestamptz USING uploaded_at AT TIME ZONE 'UTC';
+ALTER TABLE objects ALTER COLUMN last_modified TYPE timestamptz USING last_modified AT TIME ZONE 'UTC';
+ALTER TABLE temp_blobs ALTER COLUMN uploaded_at TYPE timestamptz USING uploaded_at AT TIME ZONE 'UTC';
+ALTER TABLE blobs_gc ALTER COLUMN not_before TYPE timestamptz USING not_before AT TIME ZONE 'UTC';
+ALTER TABLE blobs_gc ALTER COLUMN leased_until TYPE timestamptz USING leased_until AT TIME ZONE 'UTC';
+ALTER TABLE replication_queue ALTER COLUMN created_at TYPE timestamptz USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE multipart_uploads ALTER COLUMN created_at TYPE timestamptz USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE multipart_parts ALTER COLUMN uploaded_at TYPE timestamptz USING uploaded_at AT TIME ZONE 'UTC';
+ALTER TABLE completed_multipart_uploads ALTER COLUMN completed_at TYPE timestamptz USING completed_at AT TIME ZONE 'UTC';
+ALTER TABLE bucket_inventory ALTER COLUMN last_run TYPE timestamptz USING last_run AT TIME ZONE 'UTC';
+ALTER TABLE migration_jobs ALTER COLUMN started_at TYPE timestamptz USING started_at AT TIME ZONE 'UTC';
+ALTER TABLE migration_jobs ALTER COLUMN updated_at TYPE timestamptz USING updated_at AT TIME ZONE 'UTC';
+ALTER TABLE migration_jobs ALTER COLUMN finished_at TYPE timestamptz USING finished_at AT TIME ZONE 'UTC';
@@ -0,0 +1,5 @@
+-- blobs moved to the cold tier, blobs in the standard backend have no class
+ALTER TABLE blobs ADD COLUMN storage_class varchar;
+-- last read of the object, sampled so that a hot object is written at most once per interval
+ALTER TABLE objects ADD COLUMN last_accessed timestamptz;
+CREATE INDEX objects_last_use ON objects ((COALESCE(last_accessed, last_modified)));
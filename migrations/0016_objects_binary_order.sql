@@ -0,0 +1,2 @@
+-- listings scan the keys of a bucket in binary order
+CREATE INDEX objects_bucket_oid_binary ON objects (bucket, oid COLLATE "C");
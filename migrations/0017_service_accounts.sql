@@ -0,0 +1,2 @@
+-- keys of service accounts may list the buckets of all users, used by backup tooling
+ALTER TABLE keys ADD COLUMN service_account boolean NOT NULL DEFAULT false;
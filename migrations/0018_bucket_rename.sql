@@ -0,0 +1,11 @@
+-- buckets are renamed by the operator, the rows of the bucket follow the new name
+ALTER TABLE objects DROP CONSTRAINT bucket_id_fk,
+    ADD CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE RESTRICT ON UPDATE CASCADE;
+ALTER TABLE multipart_uploads DROP CONSTRAINT bucket_id_fk,
+    ADD CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE RESTRICT ON UPDATE CASCADE;
+ALTER TABLE bucket_replication DROP CONSTRAINT bucket_id_fk,
+    ADD CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE bucket_inventory DROP CONSTRAINT bucket_id_fk,
+    ADD CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE bucket_public_access_block DROP CONSTRAINT bucket_id_fk,
+    ADD CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE;
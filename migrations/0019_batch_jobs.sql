@@ -0,0 +1,34 @@
+-- maintenance jobs submitted through the admin API and executed in the background
+CREATE TABLE batch_jobs (
+    id uuid PRIMARY KEY,
+    bucket varchar not null,
+    -- copy, tag, delete or restore_tier
+    operation varchar not null,
+    destination_bucket varchar,
+    destination_prefix varchar,
+    tagging varchar,
+    report_bucket varchar,
+    report_prefix varchar not null DEFAULT '',
+    -- Active, Complete or Failed
+    status varchar not null DEFAULT 'Active',
+    total bigint not null DEFAULT 0,
+    succeeded bigint not null DEFAULT 0,
+    failed bigint not null DEFAULT 0,
+    report_key varchar,
+    created_at timestamptz not null,
+    finished_at timestamptz,
+    -- a job is executed by the worker holding the lease, an expired lease can be claimed by another one
+    leased_until timestamptz
+);
+CREATE INDEX batch_jobs_active ON batch_jobs(created_at) WHERE status = 'Active';
+
+-- keys of the manifest, the error is kept for the report
+CREATE TABLE batch_job_tasks (
+    job_id uuid not null,
+    oid varchar not null,
+    done boolean not null DEFAULT false,
+    error varchar,
+
+    PRIMARY KEY(job_id, oid),
+    CONSTRAINT job_id_fk FOREIGN KEY (job_id) REFERENCES batch_jobs(id) ON DELETE CASCADE
+);
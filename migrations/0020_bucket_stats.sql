@@ -0,0 +1,100 @@
+-- usage of every bucket, maintained by triggers in the transaction which changes the bucket
+CREATE TABLE bucket_stats (
+    bucket varchar(63) PRIMARY KEY,
+    objects bigint not null DEFAULT 0,
+    -- size of the current objects
+    bytes bigint not null DEFAULT 0,
+    -- size of the uploaded parts of unfinished multipart uploads
+    multipart_bytes bigint not null DEFAULT 0,
+    -- deleted and overwritten objects waiting for the GC
+    gc_objects bigint not null DEFAULT 0,
+    gc_bytes bigint not null DEFAULT 0,
+
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE FUNCTION bucket_stats_create() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO bucket_stats (bucket) VALUES (NEW.name);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER bucket_stats_create AFTER INSERT ON buckets
+    FOR EACH ROW EXECUTE FUNCTION bucket_stats_create();
+
+CREATE FUNCTION bucket_stats_objects() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('DELETE', 'UPDATE') THEN
+        UPDATE bucket_stats SET objects = objects - 1,
+            bytes = bytes - COALESCE((SELECT size FROM blobs WHERE id = OLD.blob), 0)
+            WHERE bucket = OLD.bucket;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE bucket_stats SET objects = objects + 1,
+            bytes = bytes + COALESCE((SELECT size FROM blobs WHERE id = NEW.blob), 0)
+            WHERE bucket = NEW.bucket;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+-- renaming a bucket updates the key of its objects, the statistics follow the bucket by cascade
+CREATE TRIGGER bucket_stats_objects AFTER INSERT OR DELETE OR UPDATE OF blob ON objects
+    FOR EACH ROW EXECUTE FUNCTION bucket_stats_objects();
+
+CREATE FUNCTION bucket_stats_parts() RETURNS trigger AS $$
+BEGIN
+    -- parts removed together with their upload are subtracted by bucket_stats_uploads
+    IF TG_OP = 'DELETE' THEN
+        UPDATE bucket_stats SET multipart_bytes = multipart_bytes - OLD.size
+            WHERE bucket = (SELECT bucket FROM multipart_uploads WHERE upload_id = OLD.upload_id);
+    ELSE
+        UPDATE bucket_stats SET multipart_bytes = multipart_bytes + NEW.size
+            WHERE bucket = (SELECT bucket FROM multipart_uploads WHERE upload_id = NEW.upload_id);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER bucket_stats_parts AFTER INSERT OR DELETE ON multipart_parts
+    FOR EACH ROW EXECUTE FUNCTION bucket_stats_parts();
+
+CREATE FUNCTION bucket_stats_uploads() RETURNS trigger AS $$
+BEGIN
+    UPDATE bucket_stats SET multipart_bytes = multipart_bytes
+            - COALESCE((SELECT sum(size) FROM multipart_parts WHERE upload_id = OLD.upload_id), 0)
+        WHERE bucket = OLD.bucket;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER bucket_stats_uploads BEFORE DELETE ON multipart_uploads
+    FOR EACH ROW EXECUTE FUNCTION bucket_stats_uploads();
+
+CREATE FUNCTION bucket_stats_gc() RETURNS trigger AS $$
+BEGIN
+    -- only the trash of objects knows its bucket
+    IF TG_OP = 'DELETE' THEN
+        UPDATE bucket_stats SET gc_objects = gc_objects - 1,
+            gc_bytes = gc_bytes - COALESCE((SELECT size FROM blobs WHERE id = OLD.id), 0)
+            WHERE bucket = OLD.bucket;
+    ELSE
+        UPDATE bucket_stats SET gc_objects = gc_objects + 1,
+            gc_bytes = gc_bytes + COALESCE((SELECT size FROM blobs WHERE id = NEW.id), 0)
+            WHERE bucket = NEW.bucket;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER bucket_stats_gc AFTER INSERT OR DELETE ON blobs_gc
+    FOR EACH ROW EXECUTE FUNCTION bucket_stats_gc();
+
+INSERT INTO bucket_stats (bucket, objects, bytes, multipart_bytes, gc_objects, gc_bytes)
+    SELECT name,
+        (SELECT count(*) FROM objects WHERE objects.bucket = buckets.name),
+        (SELECT COALESCE(sum(blobs.size), 0) FROM objects JOIN blobs ON objects.blob = blobs.id
+            WHERE objects.bucket = buckets.name),
+        (SELECT COALESCE(sum(multipart_parts.size), 0) FROM multipart_uploads
+            JOIN multipart_parts ON multipart_uploads.upload_id = multipart_parts.upload_id
+            WHERE multipart_uploads.bucket = buckets.name),
+        (SELECT count(*) FROM blobs_gc WHERE blobs_gc.bucket = buckets.name),
+        (SELECT COALESCE(sum(blobs.size), 0) FROM blobs_gc JOIN blobs ON blobs_gc.id = blobs.id
+            WHERE blobs_gc.bucket = buckets.name)
+    FROM buckets;
@@ -0,0 +1,17 @@
+-- representation headers provided at upload and returned on reads
+ALTER TABLE objects
+    ADD COLUMN content_type varchar,
+    ADD COLUMN content_encoding varchar,
+    ADD COLUMN content_language varchar,
+    ADD COLUMN content_disposition varchar,
+    ADD COLUMN cache_control varchar,
+    ADD COLUMN expires timestamptz;
+
+-- applied to the object when the upload is completed
+ALTER TABLE multipart_uploads
+    ADD COLUMN content_type varchar,
+    ADD COLUMN content_encoding varchar,
+    ADD COLUMN content_language varchar,
+    ADD COLUMN content_disposition varchar,
+    ADD COLUMN cache_control varchar,
+    ADD COLUMN expires timestamptz;
@@ -0,0 +1,9 @@
+-- additional checksums, values are base64 encoded digests
+ALTER TABLE multipart_uploads ADD COLUMN checksum_algorithm varchar;
+ALTER TABLE multipart_parts ADD COLUMN checksum varchar;
+
+-- composite checksum `<digest>-N` of a multipart blob and the checksums of its parts
+ALTER TABLE blobs
+    ADD COLUMN checksum_algorithm varchar,
+    ADD COLUMN checksum varchar;
+ALTER TABLE blob_parts ADD COLUMN checksum varchar;
@@ -0,0 +1,6 @@
+-- workers which must run on a single gateway at a time, the holder renews the lease while it is alive
+CREATE TABLE worker_leases (
+    name varchar PRIMARY KEY,
+    holder uuid not null,
+    leased_until timestamptz not null
+);
@@ -0,0 +1,2 @@
+-- failed replications are counted by the backlog alerts
+CREATE INDEX objects_replication_failed ON objects(bucket) WHERE replication_status = 'FAILED';
@@ -0,0 +1,33 @@
+-- Keys of a bucket can be spread over hash shards so that sequential keys are not all inserted into the same
+-- leaf of the key index. Listings merge the shards in key order. Set through the admin API.
+ALTER TABLE buckets ADD COLUMN key_shards smallint not null DEFAULT 1 CHECK (key_shards BETWEEN 1 AND 256);
+ALTER TABLE objects ADD COLUMN shard smallint not null DEFAULT 0;
+
+CREATE FUNCTION key_shard(key varchar, shards smallint) RETURNS smallint AS $$
+    SELECT ((hashtext(key)::bigint & 2147483647) % shards)::smallint
+$$ LANGUAGE sql IMMUTABLE;
+
+-- shard of a key in its bucket, point lookups compare it first to use the index
+CREATE FUNCTION object_shard(bucket varchar, key varchar) RETURNS smallint AS $$
+    SELECT key_shard(key, key_shards) FROM buckets WHERE name = bucket
+$$ LANGUAGE sql STABLE;
+
+-- all shards of a bucket for the scans of a key range
+CREATE FUNCTION object_shards(bucket varchar) RETURNS smallint[] AS $$
+    SELECT array_agg(shard::smallint ORDER BY shard) FROM buckets, generate_series(0, key_shards - 1) AS shard
+        WHERE name = bucket
+$$ LANGUAGE sql STABLE;
+
+CREATE FUNCTION objects_shard() RETURNS trigger AS $$
+BEGIN
+    -- waits for a change of the number of shards which moves the existing keys
+    NEW.shard := COALESCE((SELECT key_shard(NEW.oid, key_shards) FROM buckets WHERE name = NEW.bucket FOR SHARE), 0);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER objects_shard BEFORE INSERT OR UPDATE OF oid ON objects
+    FOR EACH ROW EXECUTE FUNCTION objects_shard();
+
+ALTER TABLE objects DROP CONSTRAINT objects_pkey, ADD PRIMARY KEY (bucket, shard, oid, last_modified);
+DROP INDEX objects_bucket_oid_binary;
+CREATE INDEX objects_bucket_shard_oid_binary ON objects (bucket, shard, oid COLLATE "C");
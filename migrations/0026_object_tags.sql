@@ -0,0 +1,38 @@
+-- tags of the objects by key for the search through the admin API, maintained from objects.tagging which keeps
+-- them in the canonical x-amz-tagging form. Keys and values are stored form-urlencoded as well.
+CREATE TABLE object_tags (
+    bucket varchar not null,
+    shard smallint not null,
+    oid varchar not null,
+    last_modified timestamptz not null,
+    key varchar not null,
+    value varchar not null,
+
+    PRIMARY KEY (bucket, shard, oid, last_modified, key),
+    -- removed with the object, renamed buckets and moved shards follow
+    CONSTRAINT object_fk FOREIGN KEY (bucket, shard, oid, last_modified)
+        REFERENCES objects(bucket, shard, oid, last_modified) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX object_tags_key ON object_tags (bucket, key, oid COLLATE "C");
+CREATE INDEX object_tags_key_value ON object_tags (bucket, key, value, oid COLLATE "C");
+
+CREATE FUNCTION object_tags_update() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        DELETE FROM object_tags WHERE bucket = NEW.bucket AND shard = NEW.shard AND oid = NEW.oid
+            AND last_modified = NEW.last_modified;
+    END IF;
+    INSERT INTO object_tags (bucket, shard, oid, last_modified, key, value)
+        SELECT NEW.bucket, NEW.shard, NEW.oid, NEW.last_modified, split_part(tag, '=', 1), split_part(tag, '=', 2)
+        FROM unnest(string_to_array(NEW.tagging, '&')) AS tag
+        WHERE tag <> '';
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER object_tags_update AFTER INSERT OR UPDATE OF tagging ON objects
+    FOR EACH ROW EXECUTE FUNCTION object_tags_update();
+
+INSERT INTO object_tags (bucket, shard, oid, last_modified, key, value)
+    SELECT bucket, shard, oid, last_modified, split_part(tag, '=', 1), split_part(tag, '=', 2)
+    FROM objects, unnest(string_to_array(tagging, '&')) AS tag
+    WHERE tagging IS NOT NULL AND tag <> '';
@@ -0,0 +1,2 @@
+-- tag set of the bucket in the x-amz-tagging form
+ALTER TABLE buckets ADD COLUMN tagging varchar;
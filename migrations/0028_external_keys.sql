@@ -0,0 +1,2 @@
+-- keys resolved by an external identity provider are linked to their user here, the secret is never stored
+ALTER TABLE keys ADD COLUMN provider varchar;
@@ -0,0 +1,2 @@
+-- region of buckets the user creates without a location constraint, the region of the gateway if not set
+ALTER TABLE users ADD COLUMN default_region varchar;
@@ -0,0 +1,2 @@
+-- user who has created the upload, uploads created before it was recorded stay accessible to every user
+ALTER TABLE multipart_uploads ADD COLUMN initiator varchar;
@@ -0,0 +1,15 @@
+-- temporary copies of cold objects in the standard backend, requested with RestoreObject
+CREATE TABLE object_restores (
+    bucket varchar(63) not null,
+    oid varchar not null,
+    -- blob of the object which is restored, the copy once it has been restored
+    blob uuid not null,
+    days integer not null,
+    requested_at timestamptz not null,
+    -- NULL while the restore is pending
+    expires_at timestamptz,
+
+    PRIMARY KEY(bucket, oid),
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE
+);
+CREATE INDEX object_restores_expires_at ON object_restores(expires_at);
@@ -0,0 +1,11 @@
+CREATE TABLE bucket_metrics (
+    bucket varchar(63) not null,
+    metrics_id varchar not null,
+    -- NULL matches every key
+    prefix varchar,
+    -- tags an object must carry in the x-amz-tagging form, NULL matches every object
+    tagging varchar,
+
+    PRIMARY KEY(bucket, metrics_id),
+    CONSTRAINT bucket_id_fk FOREIGN KEY (bucket) REFERENCES buckets(name) ON DELETE CASCADE ON UPDATE CASCADE
+);
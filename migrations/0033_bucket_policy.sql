@@ -0,0 +1,2 @@
+-- bucket policy document as sent by the client
+ALTER TABLE buckets ADD COLUMN policy varchar;
@@ -0,0 +1,141 @@
+#! /bin/bash
+
+# Runs the ceph/s3-tests conformance suite against a running gateway and writes a compatibility matrix.
+
+set -e
+
+usage() {
+    echo "$(basename ${0}) -a access_key -s secret_key [-e endpoint] [-d s3_tests_dir] [-o output_dir] [-k filter]"
+    echo "- a script to check the S3 compatibility of the gateway with the ceph/s3-tests suite."
+    echo ""
+    echo "Options:"
+    echo "  -a access_key: access key configured in the gateway"
+    echo "  -s secret_key: secret key configured in the gateway"
+    echo "  -e endpoint: host:port of the gateway (default ${ENDPOINT})"
+    echo "  -d s3_tests_dir: checkout of ceph/s3-tests, cloned if missing (default ${S3_TESTS_DIR})"
+    echo "  -o output_dir: directory of the report (default ${OUTPUT_DIR})"
+    echo "  -k filter: pytest expression selecting the tests (default ${FILTER})"
+    echo "  -h: to see this text"
+    echo ""
+    echo "The report consists of junit.xml and matrix.json with the status of every test by S3 feature."
+}
+
+checkout() {
+    if [ ! -d "${S3_TESTS_DIR}" ]; then
+        git clone --depth 1 https://github.com/ceph/s3-tests.git "${S3_TESTS_DIR}"
+    fi
+    if [ ! -d "${S3_TESTS_DIR}/.venv" ]; then
+        python3 -m venv "${S3_TESTS_DIR}/.venv"
+        "${S3_TESTS_DIR}/.venv/bin/pip" install -q -r "${S3_TESTS_DIR}/requirements.txt"
+    fi
+}
+
+write_config() {
+    local host="${ENDPOINT%:*}"
+    local port="${ENDPOINT##*:}"
+    # the gateway serves a single user, so it is used for every role of the suite
+    local user="
+user_id = s3s-rados
+display_name = s3s-rados
+email = s3s-rados@example.com
+access_key = ${S3_ACCESS_KEY}
+secret_key = ${S3_SECRET_KEY}"
+    cat > "${OUTPUT_DIR}/s3tests.conf" <<CONF
+[DEFAULT]
+host = ${host}
+port = ${port}
+is_secure = False
+ssl_verify = False
+
+[fixtures]
+bucket prefix = s3tests-{random}-
+
+[s3 main]${user}
+
+[s3 alt]${user}
+
+[s3 tenant]${user}
+tenant = s3s-rados
+
+[iam]${user}
+CONF
+}
+
+run_tests() {
+    # failing tests are the point of the report, the exit code of pytest is ignored
+    (
+        cd "${S3_TESTS_DIR}"
+        S3TEST_CONF="${OUTPUT_DIR}/s3tests.conf" .venv/bin/python -m pytest s3tests_boto3/functional/test_s3.py \
+            -k "${FILTER}" -q -p no:cacheprovider --junitxml="${OUTPUT_DIR}/junit.xml" || true
+    )
+}
+
+write_matrix() {
+    python3 - "${OUTPUT_DIR}/junit.xml" "${OUTPUT_DIR}/matrix.json" <<'PY'
+import json
+import sys
+import xml.etree.ElementTree as ET
+
+tests = {}
+for case in ET.parse(sys.argv[1]).iter("testcase"):
+    name = case.get("name")
+    if case.find("skipped") is not None:
+        status = "skipped"
+    elif case.find("failure") is not None or case.find("error") is not None:
+        status = "failed"
+    else:
+        status = "passed"
+    # test names start with the feature they cover, e.g. test_bucket_list_delimiter_basic
+    feature = name.removeprefix("test_").split("_")[0]
+    tests.setdefault(feature, {})[name] = status
+
+summary = {status: sum(s == status for f in tests.values() for s in f.values()) for status in ("passed", "failed", "skipped")}
+with open(sys.argv[2], "w") as f:
+    json.dump({"summary": summary, "features": tests}, f, indent=2, sort_keys=True)
+print(json.dumps(summary))
+PY
+}
+
+ENDPOINT="localhost:8014"
+S3_TESTS_DIR="${PWD}/target/s3-tests"
+OUTPUT_DIR="${PWD}/target/s3-tests-report"
+FILTER="not fails_on_rgw and not sse_s3 and not lifecycle_expiration and not appendobject"
+
+while getopts "ha:s:e:d:o:k:" OPT; do
+    case "$OPT" in
+        h)
+            usage
+            exit 0
+            ;;
+        a)
+            S3_ACCESS_KEY="${OPTARG}"
+            ;;
+        s)
+            S3_SECRET_KEY="${OPTARG}"
+            ;;
+        e)
+            ENDPOINT="${OPTARG}"
+            ;;
+        d)
+            S3_TESTS_DIR="${OPTARG}"
+            ;;
+        o)
+            OUTPUT_DIR="${OPTARG}"
+            ;;
+        k)
+            FILTER="${OPTARG}"
+            ;;
+    esac
+done
+
+if [ -z "$S3_ACCESS_KEY" -o -z "$S3_SECRET_KEY" ]; then
+    echo "Please provide the access key and the secret key of the gateway."
+    exit 1
+fi
+
+mkdir -p "${OUTPUT_DIR}"
+OUTPUT_DIR="$(cd "${OUTPUT_DIR}" && pwd)"
+checkout
+write_config
+run_tests
+write_matrix
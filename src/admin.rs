@@ -0,0 +1,1035 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use s3s::S3Error;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+use crate::admin_v1::{self, Operation};
+use crate::blob_store::BlobStore;
+use crate::export::{ExportProgress, Exporter};
+use crate::import::Importer;
+use crate::meta_store::{BatchJob, BatchManifest, BatchOperation, MetaStore, Timestamp, UserPolicy};
+use crate::policy::PolicyDocument;
+use crate::presign::{self, Presigner};
+use crate::reload::RuntimeConfig;
+use crate::tiering::ColdTier;
+
+/// Compare tokens in constant time. The digests hide the length of the expected token as well.
+fn tokens_match(expected: &str, given: &str) -> bool {
+    let (expected, given) = (Sha256::digest(expected), Sha256::digest(given));
+    expected.iter().zip(given.iter()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// keys listed or restored from the trash at once
+const TRASH_PAGE_SIZE: i64 = 1000;
+/// keys returned by a single page of the tag search
+const TAG_SEARCH_PAGE_SIZE: i64 = 1000;
+/// limit of the key_shards column of buckets
+const MAX_KEY_SHARDS: i16 = 256;
+
+/// Operator API served on a separate listener or for a dedicated host name of the S3 listener. It is reachable by
+/// S3 clients in the latter case, so it must require a bearer token then.
+#[derive(Clone)]
+pub struct AdminApi {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    /// gateway-wide read-only mode shared with the S3 service
+    read_only: Arc<AtomicBool>,
+    /// store with data written outside of the gateway, imports are disabled if not set
+    import_source: Option<Arc<dyn BlobStore>>,
+    /// store which receives exported buckets, exports are disabled if not set
+    export_target: Option<Arc<dyn BlobStore>>,
+    export_concurrency: usize,
+    /// blobs moved out of the standard backend by tiering
+    cold: Option<ColdTier>,
+    /// signs presigned URLs, their generation is disabled if not set
+    presigner: Option<Presigner>,
+    /// progress of running and finished exports by bucket
+    exports: Arc<Mutex<HashMap<String, Arc<ExportProgress>>>>,
+    /// expected bearer token, requests are not authenticated if neither it nor tokens in the runtime config are set
+    /// and no token is required
+    token: Option<String>,
+    /// runtime config with additional admin tokens
+    config: Option<watch::Receiver<RuntimeConfig>>,
+    /// reject all requests while no token is configured instead of accepting them unauthenticated
+    token_required: bool,
+}
+
+impl AdminApi {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>, read_only: Arc<AtomicBool>, token: Option<String>) -> Self {
+        Self {
+            db,
+            blob,
+            read_only,
+            import_source: None,
+            export_target: None,
+            export_concurrency: 1,
+            cold: None,
+            presigner: None,
+            exports: Default::default(),
+            token,
+            config: None,
+            token_required: false,
+        }
+    }
+
+    /// Reject requests while no token is configured, e.g. when a reload has removed all tokens of the runtime config
+    pub fn with_required_token(mut self) -> Self {
+        self.token_required = true;
+        self
+    }
+
+    /// Whether the token given to `new` or the runtime config holds a token
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+            || self
+                .config
+                .as_ref()
+                .is_some_and(|config| !config.borrow().admin_tokens.is_empty())
+    }
+
+    /// Accept the admin tokens of the runtime config besides the token given to `new`
+    pub fn with_runtime_config(mut self, config: watch::Receiver<RuntimeConfig>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    pub fn with_export_target(mut self, target: Arc<dyn BlobStore>, concurrency: usize) -> Self {
+        self.export_target = Some(target);
+        self.export_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_cold_tier(mut self, cold: Option<ColdTier>) -> Self {
+        self.cold = cold;
+        self
+    }
+
+    pub fn with_import_source(mut self, source: Arc<dyn BlobStore>) -> Self {
+        self.import_source = Some(source);
+        self
+    }
+
+    pub fn with_presigner(mut self, presigner: Presigner) -> Self {
+        self.presigner = Some(presigner);
+        self
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> hyper::Result<()> {
+        let make_service = make_service_fn(move |_| {
+            let api = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let api = api.clone();
+                    async move { Ok::<_, Infallible>(api.handle(req).await) }
+                }))
+            }
+        });
+        hyper::Server::try_bind(&addr)?.serve(make_service).await
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(method = %req.method(), path = %req.uri().path()))]
+    pub(crate) async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if !self.is_authorized(&req) {
+            return json_response(StatusCode::UNAUTHORIZED, json!({"error": "Unauthorized"}));
+        }
+
+        let query: HashMap<String, String> = req
+            .uri()
+            .query()
+            .map(|q| form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+            .unwrap_or_default();
+
+        if req.uri().path().starts_with(admin_v1::PREFIX) {
+            return self.handle_v1(req, query).await;
+        }
+
+        match (req.method(), req.uri().path()) {
+            (&Method::GET, "/admin/health") => self.health().await,
+            (&Method::POST, "/admin/undelete") => self.undelete(&query).await,
+            (&Method::GET, "/admin/trash") => self.list_trash(&query).await,
+            (&Method::GET, "/admin/tagged-objects") => self.find_tagged_objects(&query).await,
+            (&Method::POST, "/admin/trash/restore") => self.restore_trash(&query).await,
+            (&Method::POST, "/admin/gc/requeue") => self.requeue_gc().await,
+            (&Method::GET, "/admin/user-policy") => self.list_user_policies(&query).await,
+            (&Method::PUT, "/admin/user-policy") => self.put_user_policy(&query, req.into_body()).await,
+            (&Method::DELETE, "/admin/user-policy") => self.delete_user_policy(&query).await,
+            (&Method::PUT, "/admin/user-region") => self.put_user_default_region(&query).await,
+            (&Method::DELETE, "/admin/user-region") => self.delete_user_default_region(&query).await,
+            (&Method::PUT, "/admin/service-account") => self.put_service_account(&query).await,
+            (&Method::GET, "/admin/read-only") => self.get_read_only().await,
+            (&Method::PUT, "/admin/read-only") => self.put_read_only(&query).await,
+            (&Method::PUT, "/admin/key-shards") => self.put_key_shards(&query).await,
+            (&Method::GET, "/admin/bucket-stats") => self.bucket_stats(&query).await,
+            (&Method::POST, "/admin/delete-bucket") => self.delete_bucket(&query).await,
+            (&Method::POST, "/admin/delete-prefix") => self.delete_prefix(&query).await,
+            (&Method::POST, "/admin/transfer-bucket") => self.transfer_bucket(&query).await,
+            (&Method::POST, "/admin/rename-bucket") => self.rename_bucket(&query).await,
+            (&Method::POST, "/admin/presign") => self.presign(&query).await,
+            (&Method::POST, "/admin/import") => self.import(&query).await,
+            (&Method::POST, "/admin/export") => self.export(&query).await,
+            (&Method::GET, "/admin/export") => self.export_progress(&query),
+            (&Method::POST, "/admin/batch-job") => self.create_batch_job(&query, req.into_body()).await,
+            (&Method::GET, "/admin/batch-job") => self.get_batch_job(&query).await,
+            #[cfg(feature = "fault-injection")]
+            (&Method::GET, "/admin/faults") => list_faults(),
+            #[cfg(feature = "fault-injection")]
+            (&Method::PUT, "/admin/faults") => put_fault(&query),
+            #[cfg(feature = "fault-injection")]
+            (&Method::DELETE, "/admin/faults") => {
+                crate::faults::faults().clear();
+                json_response(StatusCode::OK, json!({}))
+            }
+            _ => json_response(StatusCode::NOT_FOUND, json!({"error": "NotFound"})),
+        }
+    }
+
+    /// Routes of the versioned API, path parameters are passed to the handlers as query parameters
+    async fn handle_v1(&self, req: Request<Body>, mut query: HashMap<String, String>) -> Response<Body> {
+        let Some((operation, params)) = admin_v1::route(req.method(), req.uri().path()) else {
+            return json_response(StatusCode::NOT_FOUND, json!({"error": "NotFound"}));
+        };
+        query.extend(params);
+
+        match operation {
+            Operation::OpenApi => json_response(StatusCode::OK, admin_v1::openapi()),
+            Operation::Health => self.health().await,
+            Operation::GetReadOnly => self.get_read_only().await,
+            Operation::PutReadOnly => self.put_read_only(&query).await,
+            Operation::ListUserPolicies => self.list_user_policies(&query).await,
+            Operation::PutUserPolicy => self.put_user_policy(&query, req.into_body()).await,
+            Operation::DeleteUserPolicy => self.delete_user_policy(&query).await,
+            Operation::PutUserDefaultRegion => self.put_user_default_region(&query).await,
+            Operation::DeleteUserDefaultRegion => self.delete_user_default_region(&query).await,
+            Operation::PutServiceAccount => self.put_service_account(&query).await,
+            Operation::BucketStats => self.bucket_stats(&query).await,
+            Operation::DeleteBucket => self.delete_bucket(&query).await,
+            Operation::DeletePrefix => self.delete_prefix(&query).await,
+            Operation::TransferBucket => self.transfer_bucket(&query).await,
+            Operation::RenameBucket => self.rename_bucket(&query).await,
+            Operation::PutKeyShards => self.put_key_shards(&query).await,
+            Operation::FindTaggedObjects => self.find_tagged_objects(&query).await,
+            Operation::ListTrash => self.list_trash(&query).await,
+            Operation::RestoreTrash => self.restore_trash(&query).await,
+            Operation::Undelete => self.undelete(&query).await,
+            Operation::Presign => self.presign(&query).await,
+            Operation::RequeueGc => self.requeue_gc().await,
+            Operation::Import => self.import(&query).await,
+            Operation::Export => self.export(&query).await,
+            Operation::ExportProgress => self.export_progress(&query),
+            Operation::CreateBatchJob => self.create_batch_job(&query, req.into_body()).await,
+            Operation::GetBatchJob => self.get_batch_job(&query).await,
+        }
+    }
+
+    fn is_authorized(&self, req: &Request<Body>) -> bool {
+        let config = self.config.as_ref().map(|config| config.borrow());
+        let mut tokens = self
+            .token
+            .iter()
+            .chain(config.iter().flat_map(|config| config.admin_tokens.iter()))
+            .peekable();
+        if tokens.peek().is_none() {
+            return !self.token_required;
+        }
+        let Some(given) = req
+            .headers()
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        tokens.any(|token| tokens_match(token, given))
+    }
+
+    /// Status of the components for external monitoring. Responds with 503 if any of them is unavailable.
+    async fn health(&self) -> Response<Body> {
+        let (db, backend) = tokio::join!(self.db.get_health(), async {
+            let started = std::time::Instant::now();
+            self.blob.health_check().await.map(|()| started.elapsed())
+        });
+
+        let mut healthy = true;
+        let (database, gc) = match db {
+            Ok(db) => (
+                json!({
+                    "status": "ok",
+                    "latency_ms": db.latency.as_secs_f64() * 1000.0,
+                    "pool": {"size": db.pool_size, "idle": db.pool_idle, "max_size": db.pool_max_size},
+                }),
+                json!({
+                    "blobs_gc": db.blobs_gc,
+                    "blobs_trash": db.blobs_trash,
+                    "blobs_gc_failed": db.blobs_gc_failed,
+                    "temp_blobs": db.temp_blobs,
+                    "replication_failed": db.replication_failed,
+                }),
+            ),
+            Err(err) => {
+                healthy = false;
+                tracing::error!(error = %err, "database health check has failed");
+                (json!({"status": "unavailable", "error": err.to_string()}), serde_json::Value::Null)
+            }
+        };
+        let backend = match backend {
+            Ok(latency) => json!({"status": "ok", "latency_ms": latency.as_secs_f64() * 1000.0}),
+            Err(err) => {
+                healthy = false;
+                json!({"status": "unavailable", "error": err.message()})
+            }
+        };
+
+        let status = if healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        json_response(
+            status,
+            json!({
+                "status": if healthy { "ok" } else { "unavailable" },
+                "build": {"name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION"), "commit": env!("GIT_COMMIT")},
+                "database": database,
+                "backend": backend,
+                "gc": gc,
+            }),
+        )
+    }
+
+    /// Restore an object from the trash while its retention has not expired yet
+    async fn undelete(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(bucket), Some(key)) = (query.get("bucket"), query.get("key")) else {
+            return json_response(
+                StatusCode::BAD_REQUEST,
+                json!({"error": "InvalidArgument", "message": "bucket and key are required"}),
+            );
+        };
+        if let Err(response) = self.check_writable(bucket).await {
+            return response;
+        }
+
+        match self.db.undelete_object(bucket, key).await {
+            Ok(blob_id) => {
+                tracing::info!(bucket, key, blob = %blob_id, "object has been restored from the trash");
+                json_response(StatusCode::OK, json!({"bucket": bucket, "key": key, "blob": blob_id.to_string()}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Keys of the bucket with the tag `key`, limited to the tag value `value` if it is given
+    async fn find_tagged_objects(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(bucket), Some(key)) = (query.get("bucket"), query.get("key")) else {
+            return invalid_argument("bucket and key are required");
+        };
+        let max_keys = match query.get("max_keys").map(|v| v.parse::<i64>()) {
+            None => TAG_SEARCH_PAGE_SIZE,
+            Some(Ok(max_keys)) if (1..=TAG_SEARCH_PAGE_SIZE).contains(&max_keys) => max_keys,
+            Some(_) => return invalid_argument("max_keys must be between 1 and 1000"),
+        };
+        let value = query.get("value").map(String::as_str);
+        let marker = query.get("marker").map(String::as_str);
+
+        match self.db.find_objects_by_tag(bucket, key, value, marker, max_keys).await {
+            Ok(keys) => {
+                let next_marker = match keys.last() {
+                    Some(last) if keys.len() as i64 == max_keys => Some(last.clone()),
+                    _ => None,
+                };
+                json_response(StatusCode::OK, json!({"bucket": bucket, "keys": keys, "next_marker": next_marker}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Deleted objects under the prefix which can still be restored, `since` limits them to the objects
+    /// deleted after the unix timestamp
+    async fn list_trash(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let TrashFilter { bucket, prefix, since } = match trash_filter(query) {
+            Ok(filter) => filter,
+            Err(response) => return *response,
+        };
+        let max_keys = match query.get("max_keys").map(|v| v.parse::<i64>()) {
+            None => TRASH_PAGE_SIZE,
+            Some(Ok(max_keys)) if (1..=TRASH_PAGE_SIZE).contains(&max_keys) => max_keys,
+            Some(_) => return invalid_argument("max_keys must be between 1 and 1000"),
+        };
+        let marker = query.get("marker").map(String::as_str);
+
+        match self.db.list_trash(bucket, prefix, since, marker, max_keys).await {
+            Ok(entries) => {
+                let next_marker = match entries.last() {
+                    Some(last) if entries.len() as i64 == max_keys => Some(last.oid.clone()),
+                    _ => None,
+                };
+                let objects: Vec<_> = entries
+                    .into_iter()
+                    .map(|entry| {
+                        json!({
+                            "key": entry.oid,
+                            "blob": entry.blob_id.to_string(),
+                            "size": entry.size,
+                            "etag": entry.etag,
+                            "deleted_at": entry.deleted_at.unix_timestamp(),
+                        })
+                    })
+                    .collect();
+                json_response(StatusCode::OK, json!({"bucket": bucket, "objects": objects, "next_marker": next_marker}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Restore every object listed by `GET /admin/trash` which has not been written again since
+    async fn restore_trash(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let TrashFilter { bucket, prefix, since } = match trash_filter(query) {
+            Ok(filter) => filter,
+            Err(response) => return *response,
+        };
+        if let Err(response) = self.check_writable(bucket).await {
+            return response;
+        }
+
+        let (mut restored, mut skipped) = (0, 0);
+        let mut marker = None;
+        loop {
+            let batch = match self
+                .db
+                .restore_trash(bucket, prefix, since, marker.as_deref(), TRASH_PAGE_SIZE)
+                .await
+            {
+                Ok(batch) => batch,
+                Err(err) => {
+                    tracing::error!(error = %err, bucket, prefix, restored, "unable to restore objects from the trash");
+                    return error_response(&err);
+                }
+            };
+            restored += batch.restored;
+            skipped += batch.skipped;
+            if batch.marker.is_none() {
+                break;
+            }
+            marker = batch.marker;
+        }
+
+        tracing::info!(bucket, prefix, restored, skipped, "objects have been restored from the trash");
+        json_response(
+            StatusCode::OK,
+            json!({"bucket": bucket, "prefix": prefix, "restored": restored, "skipped": skipped}),
+        )
+    }
+
+    async fn list_user_policies(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(user) = query.get("user") else {
+            return invalid_argument("user is required");
+        };
+
+        match self.db.list_user_policies(user).await {
+            Ok(policies) => {
+                let policies: Vec<_> = policies
+                    .into_iter()
+                    .map(|p| {
+                        let document = serde_json::from_str::<serde_json::Value>(&p.document).unwrap_or_default();
+                        json!({"name": p.name, "document": document})
+                    })
+                    .collect();
+                json_response(StatusCode::OK, json!({"user": user, "policies": policies}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Create or replace a policy of the user. The body is the JSON policy document.
+    async fn put_user_policy(&self, query: &HashMap<String, String>, body: Body) -> Response<Body> {
+        let (Some(user), Some(name)) = (query.get("user"), query.get("name")) else {
+            return invalid_argument("user and name are required");
+        };
+        let document = match hyper::body::to_bytes(body).await {
+            Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+            Err(err) => return invalid_argument(&err.to_string()),
+        };
+        if let Err(err) = PolicyDocument::parse(&document) {
+            return json_response(StatusCode::BAD_REQUEST, json!({"error": "MalformedPolicy", "message": err}));
+        }
+
+        let policy = UserPolicy {
+            name: name.clone(),
+            document,
+        };
+        match self.db.put_user_policy(user, &policy).await {
+            Ok(()) => {
+                tracing::info!(user, policy = name, "user policy has been updated");
+                json_response(StatusCode::OK, json!({"user": user, "name": name}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    async fn delete_user_policy(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(user), Some(name)) = (query.get("user"), query.get("name")) else {
+            return invalid_argument("user and name are required");
+        };
+
+        match self.db.delete_user_policy(user, name).await {
+            Ok(()) => {
+                tracing::info!(user, policy = name, "user policy has been deleted");
+                json_response(StatusCode::OK, json!({"user": user, "name": name}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Region of the buckets the user creates without a location constraint
+    async fn put_user_default_region(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(user), Some(region)) = (query.get("user"), query.get("region")) else {
+            return invalid_argument("user and region are required");
+        };
+        if region.is_empty() {
+            return invalid_argument("region must not be empty");
+        }
+
+        match self.db.set_user_default_region(user, Some(region)).await {
+            Ok(()) => {
+                tracing::info!(user, region, "default region of the user has been changed");
+                json_response(StatusCode::OK, json!({"user": user, "region": region}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// New buckets of the user are created in the region of the gateway again
+    async fn delete_user_default_region(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(user) = query.get("user") else {
+            return invalid_argument("user is required");
+        };
+
+        match self.db.set_user_default_region(user, None).await {
+            Ok(()) => {
+                tracing::info!(user, "default region of the user has been removed");
+                json_response(StatusCode::OK, json!({"user": user, "region": null}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Give the blobs the GC has given up on another chance, e.g. after the backend has been fixed
+    async fn requeue_gc(&self) -> Response<Body> {
+        match self.db.requeue_failed_blob_gc().await {
+            Ok(requeued) => {
+                tracing::info!(requeued, "failed blobs have been requeued for removal");
+                json_response(StatusCode::OK, json!({"requeued": requeued}))
+            }
+            Err(err) => {
+                tracing::error!(error = %err, "unable to requeue failed blobs");
+                json_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    json!({"error": "InternalError", "message": err.to_string()}),
+                )
+            }
+        }
+    }
+
+    /// Allow or forbid the key to list the buckets of all users
+    async fn put_service_account(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(access_key) = query.get("access_key") else {
+            return invalid_argument("access_key is required");
+        };
+        let enabled = match query.get("enabled").map(String::as_str) {
+            Some("true") => true,
+            Some("false") => false,
+            _ => return invalid_argument("enabled must be either true or false"),
+        };
+
+        match self.db.set_service_account_key(access_key, enabled).await {
+            Ok(()) => {
+                tracing::info!(access_key, enabled, "service account of the key has been changed");
+                json_response(StatusCode::OK, json!({"access_key": access_key, "enabled": enabled}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    async fn get_read_only(&self) -> Response<Body> {
+        match self.db.list_read_only_buckets().await {
+            Ok(buckets) => json_response(
+                StatusCode::OK,
+                json!({"gateway": self.read_only.load(Ordering::Relaxed), "buckets": buckets}),
+            ),
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Switch the read-only mode of the bucket, or of the whole gateway if no bucket is given
+    async fn put_read_only(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let enabled = match query.get("enabled").map(String::as_str) {
+            Some("true") => true,
+            Some("false") => false,
+            _ => return invalid_argument("enabled must be either true or false"),
+        };
+
+        let Some(bucket) = query.get("bucket") else {
+            self.read_only.store(enabled, Ordering::Relaxed);
+            tracing::info!(enabled, "gateway read-only mode has been changed");
+            return json_response(StatusCode::OK, json!({"gateway": enabled}));
+        };
+        match self.db.set_bucket_read_only(bucket, enabled).await {
+            Ok(()) => {
+                tracing::info!(bucket, enabled, "bucket read-only mode has been changed");
+                json_response(StatusCode::OK, json!({"bucket": bucket, "enabled": enabled}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Spread the keys of a bucket with sequential key names over hash shards of the key index. The existing
+    /// keys are moved in a single transaction which blocks uploads to the bucket meanwhile.
+    async fn put_key_shards(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+        let shards = match query.get("shards").map(|v| v.parse::<i16>()) {
+            Some(Ok(shards)) if (1..=MAX_KEY_SHARDS).contains(&shards) => shards,
+            _ => return invalid_argument("shards must be between 1 and 256"),
+        };
+
+        match self.db.set_bucket_key_shards(bucket, shards).await {
+            Ok(moved) => {
+                tracing::info!(bucket, shards, moved, "key shards of the bucket have been changed");
+                json_response(StatusCode::OK, json!({"bucket": bucket, "shards": shards, "moved": moved}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    async fn bucket_stats(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+
+        match self.db.get_bucket_stats(bucket).await {
+            Ok(Some(stats)) => json_response(
+                StatusCode::OK,
+                json!({
+                    "bucket": bucket,
+                    "objects": stats.objects,
+                    "bytes": stats.bytes,
+                    "multipart_bytes": stats.multipart_bytes,
+                    "gc_objects": stats.gc_objects,
+                    "gc_bytes": stats.gc_bytes,
+                }),
+            ),
+            Ok(None) => error_response(&S3Error::new(s3s::S3ErrorCode::NoSuchBucket)),
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Delete a bucket which is not empty. It disappears for clients at once, the objects are removed
+    /// in the background and can not be undeleted.
+    async fn delete_bucket(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+
+        match self.db.schedule_bucket_deletion(bucket).await {
+            Ok(()) => {
+                tracing::info!(bucket, "bucket has been scheduled for deletion");
+                json_response(StatusCode::ACCEPTED, json!({"bucket": bucket}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Remove every object under the prefix at once. The objects can be undeleted until the trash retention
+    /// expires, whole buckets are removed with `delete-bucket` instead.
+    async fn delete_prefix(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(bucket), Some(prefix)) = (query.get("bucket"), query.get("prefix")) else {
+            return invalid_argument("bucket and prefix are required");
+        };
+        if prefix.is_empty() {
+            return invalid_argument("prefix must not be empty");
+        }
+
+        if let Err(response) = self.check_writable(bucket).await {
+            return response;
+        }
+
+        match self.db.delete_objects_by_prefix(bucket, prefix).await {
+            Ok(deleted) => {
+                tracing::info!(bucket, prefix, deleted, "objects under the prefix have been deleted");
+                json_response(StatusCode::OK, json!({"bucket": bucket, "prefix": prefix, "deleted": deleted}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Mutating admin operations are rejected for read-only buckets
+    async fn check_writable(&self, bucket: &str) -> Result<(), Response<Body>> {
+        match self.db.get_bucket_metadata(bucket).await {
+            Ok(Some(bucket)) if bucket.read_only => Err(json_response(
+                StatusCode::CONFLICT,
+                json!({"error": "BucketReadOnly", "message": "bucket is read-only"}),
+            )),
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(error_response(&S3Error::new(s3s::S3ErrorCode::NoSuchBucket))),
+            Err(err) => Err(error_response(&err)),
+        }
+    }
+
+    /// Make another user the owner of the bucket
+    async fn transfer_bucket(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(bucket), Some(owner)) = (query.get("bucket"), query.get("owner")) else {
+            return invalid_argument("bucket and owner are required");
+        };
+
+        match self.db.transfer_bucket(bucket, owner).await {
+            Ok(()) => {
+                tracing::info!(bucket, owner, "bucket has been transferred");
+                json_response(StatusCode::OK, json!({"bucket": bucket, "owner": owner}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Rename the bucket in the metadata only, the data stays where it is. Clients using the old name
+    /// get `NoSuchBucket` right away.
+    async fn rename_bucket(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let (Some(bucket), Some(new_name)) = (query.get("bucket"), query.get("new_name")) else {
+            return invalid_argument("bucket and new_name are required");
+        };
+        if !s3s::path::check_bucket_name(new_name) {
+            return invalid_argument("new_name is not a valid bucket name");
+        }
+
+        match self.db.rename_bucket(bucket, new_name).await {
+            Ok(()) => {
+                tracing::info!(bucket, new_name, "bucket has been renamed");
+                json_response(StatusCode::OK, json!({"bucket": new_name}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Presigned GET or PUT URL of an object signed with the secret of the given access key
+    async fn presign(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(presigner) = &self.presigner else {
+            return invalid_argument("presign endpoint is not configured");
+        };
+        let (Some(bucket), Some(key), Some(access_key)) = (query.get("bucket"), query.get("key"), query.get("access_key")) else {
+            return invalid_argument("bucket, key and access_key are required");
+        };
+        let method = match query.get("method").map(String::as_str) {
+            None | Some("GET") => Method::GET,
+            Some("PUT") => Method::PUT,
+            Some(_) => return invalid_argument("method must be GET or PUT"),
+        };
+        let expires = match query.get("expires").map(|e| e.parse::<u64>()) {
+            None => std::time::Duration::from_secs(3600),
+            Some(Ok(secs)) if secs > 0 && secs <= presign::MAX_EXPIRES.as_secs() => std::time::Duration::from_secs(secs),
+            Some(_) => return invalid_argument("expires must be between 1 and 604800 seconds"),
+        };
+        if key.is_empty() {
+            return invalid_argument("key must not be empty");
+        }
+
+        let now = Timestamp::now_utc().replace_nanosecond(0).expect("valid nanosecond");
+        match presigner.presign(&method, bucket, key, access_key, now, expires).await {
+            Ok(url) => json_response(
+                StatusCode::OK,
+                json!({"url": url, "method": method.as_str(), "expires_at": (now + expires).unix_timestamp()}),
+            ),
+            Err(err) => error_response(&err),
+        }
+    }
+
+    /// Start importing the objects of the import source into the bucket, the result is logged
+    async fn import(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(source) = self.import_source.clone() else {
+            return invalid_argument("import source is not configured");
+        };
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+        let prefix = query.get("prefix").cloned().unwrap_or_default();
+        let bucket = match self.db.get_bucket_metadata(bucket).await {
+            Ok(Some(bucket)) => bucket,
+            Ok(None) => return error_response(&S3Error::new(s3s::S3ErrorCode::NoSuchBucket)),
+            Err(err) => return error_response(&err),
+        };
+
+        let body = json!({"bucket": bucket.name, "prefix": prefix});
+        let importer = Importer::new(self.db.clone(), self.blob.clone(), source);
+        tokio::spawn(async move {
+            let name = bucket.name.clone();
+            match importer.run(bucket, prefix).await {
+                Ok(stats) => tracing::info!(
+                    bucket = name,
+                    imported = stats.imported,
+                    skipped = stats.skipped,
+                    failed = stats.failed,
+                    "import has finished"
+                ),
+                Err(err) => tracing::error!(error = %err, bucket = name, "import has failed"),
+            }
+        });
+        json_response(StatusCode::ACCEPTED, body)
+    }
+
+    /// Start copying the objects of the bucket to the export target under their keys
+    async fn export(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(target) = self.export_target.clone() else {
+            return invalid_argument("export target is not configured");
+        };
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+        let prefix = query.get("prefix").cloned();
+        match self.db.get_bucket_metadata(bucket).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return error_response(&S3Error::new(s3s::S3ErrorCode::NoSuchBucket)),
+            Err(err) => return error_response(&err),
+        }
+
+        let progress = Arc::new(ExportProgress::default());
+        {
+            let mut exports = self.exports.lock().expect("unable to lock mutex");
+            if exports.get(bucket).is_some_and(|p| !p.finished.load(Ordering::Relaxed)) {
+                return json_response(
+                    StatusCode::CONFLICT,
+                    json!({"error": "ExportInProgress", "message": "the bucket is already being exported"}),
+                );
+            }
+            exports.insert(bucket.clone(), progress.clone());
+        }
+
+        let exporter =
+            Exporter::new(self.db.clone(), self.blob.clone(), target, self.export_concurrency).with_cold_tier(self.cold.clone());
+        let name = bucket.clone();
+        tokio::spawn(async move {
+            match exporter.run(name.clone(), prefix, progress.clone()).await {
+                Ok(()) => tracing::info!(bucket = name, progress = %progress.to_json(), "export has finished"),
+                Err(err) => tracing::error!(error = %err, bucket = name, "export has failed"),
+            }
+        });
+        json_response(StatusCode::ACCEPTED, json!({"bucket": bucket}))
+    }
+
+    fn export_progress(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+        match self.exports.lock().expect("unable to lock mutex").get(bucket) {
+            Some(progress) => json_response(StatusCode::OK, json!({"bucket": bucket, "progress": progress.to_json()})),
+            None => json_response(StatusCode::NOT_FOUND, json!({"error": "NotFound", "message": "no export of the bucket"})),
+        }
+    }
+
+    /// Submit a batch job for the objects under `prefix` or the keys of the CSV manifest in the body.
+    /// The job is executed in the background, its progress is returned by `GET /admin/batch-job`.
+    async fn create_batch_job(&self, query: &HashMap<String, String>, body: Body) -> Response<Body> {
+        let Some(bucket) = query.get("bucket") else {
+            return invalid_argument("bucket is required");
+        };
+        let operation = match query.get("operation").map(String::as_str) {
+            Some("copy") => {
+                let Some(destination_bucket) = query.get("destination_bucket") else {
+                    return invalid_argument("destination_bucket is required for copy");
+                };
+                BatchOperation::Copy {
+                    destination_bucket: destination_bucket.clone(),
+                    destination_prefix: query.get("destination_prefix").cloned().unwrap_or_default(),
+                }
+            }
+            Some("tag") => match crate::service::parse_tagging(query.get("tagging").cloned()) {
+                Ok(tagging) => BatchOperation::Tag { tagging },
+                Err(err) => return error_response(&err),
+            },
+            Some("delete") => BatchOperation::Delete,
+            Some("restore-tier") => BatchOperation::RestoreTier,
+            _ => return invalid_argument("operation must be one of copy, tag, delete or restore-tier"),
+        };
+        let manifest = match query.get("prefix") {
+            Some(prefix) => BatchManifest::Prefix(prefix.clone()),
+            None => {
+                let body = match hyper::body::to_bytes(body).await {
+                    Ok(body) => body,
+                    Err(err) => return invalid_argument(&err.to_string()),
+                };
+                match parse_manifest(bucket, &String::from_utf8_lossy(&body)) {
+                    Ok(keys) => BatchManifest::Keys(keys),
+                    Err(message) => return invalid_argument(&message),
+                }
+            }
+        };
+
+        match self.db.get_bucket_metadata(bucket).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return error_response(&S3Error::new(s3s::S3ErrorCode::NoSuchBucket)),
+            Err(err) => return error_response(&err),
+        }
+
+        let job = BatchJob {
+            id: Uuid::new_v4(),
+            bucket: bucket.clone(),
+            operation,
+            report_bucket: query.get("report_bucket").cloned(),
+            report_prefix: query.get("report_prefix").cloned().unwrap_or_default(),
+            status: "Active".to_owned(),
+            total: 0,
+            succeeded: 0,
+            failed: 0,
+            report_key: None,
+            created_at: Timestamp::now_utc(),
+            finished_at: None,
+        };
+        match self.db.create_batch_job(&job, &manifest).await {
+            Ok(total) => {
+                tracing::info!(job = %job.id, bucket, operation = job.operation.name(), total, "batch job has been submitted");
+                json_response(StatusCode::CREATED, json!({"id": job.id.to_string(), "total": total}))
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+
+    async fn get_batch_job(&self, query: &HashMap<String, String>) -> Response<Body> {
+        let Some(id) = query.get("id").and_then(|id| Uuid::parse_str(id).ok()) else {
+            return invalid_argument("id must be a batch job id");
+        };
+        match self.db.get_batch_job(&id).await {
+            Ok(Some(job)) => json_response(
+                StatusCode::OK,
+                json!({
+                    "id": job.id.to_string(),
+                    "bucket": job.bucket,
+                    "operation": job.operation.name(),
+                    "status": job.status,
+                    "total": job.total,
+                    "succeeded": job.succeeded,
+                    "failed": job.failed,
+                    "report_bucket": job.report_bucket,
+                    "report_key": job.report_key,
+                    "created_at": job.created_at.unix_timestamp(),
+                    "finished_at": job.finished_at.map(Timestamp::unix_timestamp),
+                }),
+            ),
+            Ok(None) => json_response(StatusCode::NOT_FOUND, json!({"error": "NotFound", "message": "no such batch job"})),
+            Err(err) => error_response(&err),
+        }
+    }
+}
+
+/// Bucket, prefix and deletion time the trash operations are limited to
+struct TrashFilter<'a> {
+    bucket: &'a str,
+    prefix: &'a str,
+    since: Option<Timestamp>,
+}
+
+fn trash_filter(query: &HashMap<String, String>) -> Result<TrashFilter<'_>, Box<Response<Body>>> {
+    let Some(bucket) = query.get("bucket") else {
+        return Err(Box::new(invalid_argument("bucket is required")));
+    };
+    let prefix = query.get("prefix").map(String::as_str).unwrap_or_default();
+    let since = match query.get("since") {
+        None => None,
+        Some(since) => match since
+            .parse()
+            .ok()
+            .and_then(|since| Timestamp::from_unix_timestamp(since).ok())
+        {
+            Some(since) => Some(since),
+            None => return Err(Box::new(invalid_argument("since must be a unix timestamp"))),
+        },
+    };
+    Ok(TrashFilter { bucket, prefix, since })
+}
+
+/// Keys of a CSV manifest in the S3 Batch Operations layout: `bucket,key` per line with URL encoded keys.
+/// Lines with the key only are accepted as well.
+fn parse_manifest(bucket: &str, manifest: &str) -> Result<Vec<String>, String> {
+    let mut keys = Vec::new();
+    for line in manifest.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let mut columns = line.split(',').map(|column| column.trim().trim_matches('"'));
+        let key = match (columns.next(), columns.next()) {
+            (Some(key), None) => key,
+            (Some(line_bucket), Some(key)) if line_bucket == bucket => key,
+            (Some(line_bucket), Some(_)) => return Err(format!("manifest refers to another bucket: {line_bucket}")),
+            (None, _) => continue,
+        };
+        let key = urlencoding::decode(key).map_err(|_| format!("invalid key in manifest: {key}"))?;
+        keys.push(key.into_owned());
+    }
+    if keys.is_empty() {
+        return Err("manifest is empty".to_owned());
+    }
+    Ok(keys)
+}
+
+#[cfg(feature = "fault-injection")]
+fn list_faults() -> Response<Body> {
+    let faults: serde_json::Map<_, _> = crate::faults::faults()
+        .list()
+        .into_iter()
+        .map(|(operation, fault)| {
+            let fault = json!({
+                "latency_ms": fault.latency.as_millis() as u64,
+                "error_rate": fault.error_rate,
+                "fail_after_bytes": fault.fail_after_bytes,
+            });
+            (operation, fault)
+        })
+        .collect();
+    json_response(StatusCode::OK, json!({"faults": faults}))
+}
+
+/// Inject faults into a database query or a blob store call, see `faults` for the operation names
+#[cfg(feature = "fault-injection")]
+fn put_fault(query: &HashMap<String, String>) -> Response<Body> {
+    let Some(operation) = query.get("operation") else {
+        return invalid_argument("operation is required");
+    };
+    let Ok(latency_ms) = query.get("latency_ms").map_or(Ok(0), |v| v.parse::<u64>()) else {
+        return invalid_argument("latency_ms must be a number of milliseconds");
+    };
+    let error_rate = match query.get("error_rate").map_or(Ok(0.0), |v| v.parse::<f64>()) {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+        _ => return invalid_argument("error_rate must be between 0 and 1"),
+    };
+    let Ok(fail_after_bytes) = query.get("fail_after_bytes").map(|v| v.parse::<u64>()).transpose() else {
+        return invalid_argument("fail_after_bytes must be a number of bytes");
+    };
+
+    let fault = crate::faults::Fault {
+        latency: std::time::Duration::from_millis(latency_ms),
+        error_rate,
+        fail_after_bytes,
+    };
+    crate::faults::faults().set(operation, fault);
+    tracing::warn!(operation, latency_ms, error_rate, ?fail_after_bytes, "fault has been injected");
+    json_response(StatusCode::OK, json!({"operation": operation}))
+}
+
+fn invalid_argument(message: &str) -> Response<Body> {
+    json_response(StatusCode::BAD_REQUEST, json!({"error": "InvalidArgument", "message": message}))
+}
+
+fn error_response(err: &S3Error) -> Response<Body> {
+    let status = err.status_code().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    json_response(status, json!({"error": err.code().as_str(), "message": err.message()}))
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("valid response")
+}
@@ -0,0 +1,481 @@
+//! Versioned REST routes of the admin API.
+//!
+//! Resources are addressed by their path (`/admin/v1/buckets/{bucket}/stats`) instead of query parameters.
+//! Path parameters are merged into the query parameters, so the routes are served by the same handlers as the
+//! unversioned ones. The OpenAPI document served at `/admin/v1/openapi.json` is generated from the route table.
+
+use std::collections::HashMap;
+
+use hyper::Method;
+use serde_json::{json, Map, Value};
+
+pub const PREFIX: &str = "/admin/v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    OpenApi,
+    Health,
+    GetReadOnly,
+    PutReadOnly,
+    ListUserPolicies,
+    PutUserPolicy,
+    DeleteUserPolicy,
+    PutUserDefaultRegion,
+    DeleteUserDefaultRegion,
+    PutServiceAccount,
+    BucketStats,
+    DeleteBucket,
+    DeletePrefix,
+    TransferBucket,
+    RenameBucket,
+    PutKeyShards,
+    FindTaggedObjects,
+    ListTrash,
+    RestoreTrash,
+    Undelete,
+    Presign,
+    RequeueGc,
+    Import,
+    Export,
+    ExportProgress,
+    CreateBatchJob,
+    GetBatchJob,
+}
+
+#[derive(Clone, Copy)]
+enum Type {
+    String,
+    Integer,
+    Boolean,
+}
+
+struct Param {
+    name: &'static str,
+    ty: Type,
+    required: bool,
+    description: &'static str,
+}
+
+const fn query(name: &'static str, ty: Type, required: bool, description: &'static str) -> Param {
+    Param {
+        name,
+        ty,
+        required,
+        description,
+    }
+}
+
+struct Route {
+    method: Method,
+    /// relative to the prefix, `{name}` segments are path parameters
+    path: &'static str,
+    operation: Operation,
+    id: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    /// query parameters
+    params: &'static [Param],
+    /// media type of the request body
+    body: Option<&'static str>,
+}
+
+const BUCKET: Param = query("bucket", Type::String, true, "bucket name");
+const KEY: Param = query("key", Type::String, true, "object key");
+const ENABLED: Param = query("enabled", Type::Boolean, true, "true or false");
+const MARKER: Param = query("marker", Type::String, false, "key after which the page starts");
+const MAX_KEYS: Param = query("max_keys", Type::Integer, false, "page size, at most 1000");
+const TRASH_PREFIX: Param = query("prefix", Type::String, false, "key prefix");
+const REGION: Param = query("region", Type::String, true, "region name");
+const SINCE: Param = query("since", Type::Integer, false, "unix timestamp, only objects deleted after it");
+
+static ROUTES: &[Route] = &[
+    Route {
+        method: Method::GET,
+        path: "/openapi.json",
+        operation: Operation::OpenApi,
+        id: "getOpenApi",
+        tag: "system",
+        summary: "This document",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/health",
+        operation: Operation::Health,
+        id: "getHealth",
+        tag: "system",
+        summary: "Status of the database and the backend, 503 if any of them is unavailable",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/read-only",
+        operation: Operation::GetReadOnly,
+        id: "getReadOnly",
+        tag: "system",
+        summary: "Read-only mode of the gateway and the read-only buckets",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/read-only",
+        operation: Operation::PutReadOnly,
+        id: "putReadOnly",
+        tag: "system",
+        summary: "Switch the read-only mode of the gateway",
+        params: &[ENABLED],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/users/{user}/policies",
+        operation: Operation::ListUserPolicies,
+        id: "listUserPolicies",
+        tag: "users",
+        summary: "Policies attached to the user",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/users/{user}/policies/{name}",
+        operation: Operation::PutUserPolicy,
+        id: "putUserPolicy",
+        tag: "users",
+        summary: "Attach or replace a policy of the user",
+        params: &[],
+        body: Some("application/json"),
+    },
+    Route {
+        method: Method::DELETE,
+        path: "/users/{user}/policies/{name}",
+        operation: Operation::DeleteUserPolicy,
+        id: "deleteUserPolicy",
+        tag: "users",
+        summary: "Detach a policy from the user",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/users/{user}/default-region",
+        operation: Operation::PutUserDefaultRegion,
+        id: "putUserDefaultRegion",
+        tag: "users",
+        summary: "Region of the buckets the user creates without a location constraint",
+        params: &[REGION],
+        body: None,
+    },
+    Route {
+        method: Method::DELETE,
+        path: "/users/{user}/default-region",
+        operation: Operation::DeleteUserDefaultRegion,
+        id: "deleteUserDefaultRegion",
+        tag: "users",
+        summary: "Create new buckets of the user in the region of the gateway",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/keys/{access_key}/service-account",
+        operation: Operation::PutServiceAccount,
+        id: "putServiceAccount",
+        tag: "keys",
+        summary: "Allow or forbid the key to list the buckets of all users",
+        params: &[ENABLED],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/buckets/{bucket}/stats",
+        operation: Operation::BucketStats,
+        id: "getBucketStats",
+        tag: "buckets",
+        summary: "Object count and sizes of the bucket",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::DELETE,
+        path: "/buckets/{bucket}",
+        operation: Operation::DeleteBucket,
+        id: "deleteBucket",
+        tag: "buckets",
+        summary: "Delete the bucket with all its objects in the background",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/buckets/{bucket}/read-only",
+        operation: Operation::PutReadOnly,
+        id: "putBucketReadOnly",
+        tag: "buckets",
+        summary: "Switch the read-only mode of the bucket",
+        params: &[ENABLED],
+        body: None,
+    },
+    Route {
+        method: Method::DELETE,
+        path: "/buckets/{bucket}/objects",
+        operation: Operation::DeletePrefix,
+        id: "deletePrefix",
+        tag: "buckets",
+        summary: "Delete the objects under the prefix",
+        params: &[query("prefix", Type::String, true, "key prefix")],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/buckets/{bucket}/owner",
+        operation: Operation::TransferBucket,
+        id: "transferBucket",
+        tag: "buckets",
+        summary: "Transfer the bucket to another user",
+        params: &[query("owner", Type::String, true, "access key of the new owner")],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/buckets/{bucket}/rename",
+        operation: Operation::RenameBucket,
+        id: "renameBucket",
+        tag: "buckets",
+        summary: "Rename the bucket",
+        params: &[query("new_name", Type::String, true, "new bucket name")],
+        body: None,
+    },
+    Route {
+        method: Method::PUT,
+        path: "/buckets/{bucket}/key-shards",
+        operation: Operation::PutKeyShards,
+        id: "putKeyShards",
+        tag: "buckets",
+        summary: "Spread the key index of the bucket over hash shards",
+        params: &[query("shards", Type::Integer, true, "number of shards, 1 to 256")],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/buckets/{bucket}/tagged-objects",
+        operation: Operation::FindTaggedObjects,
+        id: "findTaggedObjects",
+        tag: "buckets",
+        summary: "Keys of the objects with the tag",
+        params: &[
+            query("key", Type::String, true, "tag key"),
+            query("value", Type::String, false, "tag value"),
+            MARKER,
+            MAX_KEYS,
+        ],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/buckets/{bucket}/trash",
+        operation: Operation::ListTrash,
+        id: "listTrash",
+        tag: "buckets",
+        summary: "Deleted objects which can still be restored",
+        params: &[TRASH_PREFIX, SINCE, MARKER, MAX_KEYS],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/buckets/{bucket}/trash/restore",
+        operation: Operation::RestoreTrash,
+        id: "restoreTrash",
+        tag: "buckets",
+        summary: "Restore the deleted objects which have not been written again",
+        params: &[TRASH_PREFIX, SINCE],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/buckets/{bucket}/undelete",
+        operation: Operation::Undelete,
+        id: "undeleteObject",
+        tag: "buckets",
+        summary: "Restore a deleted object",
+        params: &[KEY],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/buckets/{bucket}/presign",
+        operation: Operation::Presign,
+        id: "presignUrl",
+        tag: "buckets",
+        summary: "Presigned URL of an object",
+        params: &[
+            KEY,
+            query("access_key", Type::String, true, "key the URL is signed with"),
+            query("method", Type::String, false, "GET or PUT"),
+            query("expires", Type::Integer, false, "lifetime in seconds"),
+        ],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/gc/requeue",
+        operation: Operation::RequeueGc,
+        id: "requeueGc",
+        tag: "gc",
+        summary: "Retry the blobs the GC has given up on",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/jobs/import",
+        operation: Operation::Import,
+        id: "createImport",
+        tag: "jobs",
+        summary: "Import the objects of the import pool into the bucket",
+        params: &[BUCKET, query("prefix", Type::String, false, "object name prefix")],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/jobs/export",
+        operation: Operation::Export,
+        id: "createExport",
+        tag: "jobs",
+        summary: "Copy the objects of the bucket to the export pool in the background",
+        params: &[BUCKET, query("prefix", Type::String, false, "key prefix")],
+        body: None,
+    },
+    Route {
+        method: Method::GET,
+        path: "/jobs/export/{bucket}",
+        operation: Operation::ExportProgress,
+        id: "getExport",
+        tag: "jobs",
+        summary: "Progress of the last export of the bucket",
+        params: &[],
+        body: None,
+    },
+    Route {
+        method: Method::POST,
+        path: "/jobs/batch",
+        operation: Operation::CreateBatchJob,
+        id: "createBatchJob",
+        tag: "jobs",
+        summary: "Submit a batch job for the objects under the prefix or the keys of the CSV manifest in the body",
+        params: &[
+            BUCKET,
+            query("operation", Type::String, true, "copy, tag, delete or restore-tier"),
+            query("prefix", Type::String, false, "key prefix, the manifest is read from the body if not set"),
+            query("destination_bucket", Type::String, false, "target of copy"),
+            query("destination_prefix", Type::String, false, "key prefix of the copies"),
+            query("tagging", Type::String, false, "tag set of the tag operation in the x-amz-tagging form"),
+            query("report_bucket", Type::String, false, "bucket which receives the completion report"),
+            query("report_prefix", Type::String, false, "key prefix of the completion report"),
+        ],
+        body: Some("text/csv"),
+    },
+    Route {
+        method: Method::GET,
+        path: "/jobs/batch/{id}",
+        operation: Operation::GetBatchJob,
+        id: "getBatchJob",
+        tag: "jobs",
+        summary: "Progress of a batch job",
+        params: &[],
+        body: None,
+    },
+];
+
+/// Operation of the route and its path parameters, `path` starts with the prefix
+pub fn route(method: &Method, path: &str) -> Option<(Operation, HashMap<String, String>)> {
+    let segments: Vec<&str> = path.strip_prefix(PREFIX)?.split('/').collect();
+    ROUTES.iter().filter(|route| route.method == *method).find_map(|route| {
+        let templates: Vec<&str> = route.path.split('/').collect();
+        if templates.len() != segments.len() {
+            return None;
+        }
+        let mut params = HashMap::new();
+        for (template, segment) in templates.iter().zip(&segments) {
+            match template.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                Some(name) => {
+                    let value = urlencoding::decode(segment).ok()?;
+                    if value.is_empty() {
+                        return None;
+                    }
+                    params.insert(name.to_owned(), value.into_owned());
+                }
+                None if template == segment => {}
+                None => return None,
+            }
+        }
+        Some((route.operation, params))
+    })
+}
+
+/// OpenAPI 3.0 document of the versioned routes
+pub fn openapi() -> Value {
+    let mut paths = Map::new();
+    for route in ROUTES {
+        let path_params = route
+            .path
+            .split('/')
+            .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+            .map(|name| json!({"name": name, "in": "path", "required": true, "schema": {"type": "string"}}));
+        let query_params = route.params.iter().map(|param| {
+            let ty = match param.ty {
+                Type::String => "string",
+                Type::Integer => "integer",
+                Type::Boolean => "boolean",
+            };
+            json!({
+                "name": param.name,
+                "in": "query",
+                "required": param.required,
+                "description": param.description,
+                "schema": {"type": ty},
+            })
+        });
+
+        let mut operation = json!({
+            "operationId": route.id,
+            "tags": [route.tag],
+            "summary": route.summary,
+            "parameters": path_params.chain(query_params).collect::<Vec<_>>(),
+            "responses": {
+                "200": {"description": "OK", "content": {"application/json": {"schema": {"type": "object"}}}},
+                "400": {"$ref": "#/components/responses/Error"},
+                "401": {"$ref": "#/components/responses/Error"},
+                "404": {"$ref": "#/components/responses/Error"},
+            },
+        });
+        if let Some(media_type) = route.body {
+            operation["requestBody"] = json!({"required": true, "content": {media_type: {"schema": {"type": "string"}}}});
+        }
+        let item = paths.entry(format!("{PREFIX}{}", route.path)).or_insert_with(|| json!({}));
+        item[route.method.as_str().to_ascii_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": "s3s-rados admin API", "version": env!("CARGO_PKG_VERSION")},
+        "security": [{"bearer": []}],
+        "paths": paths,
+        "components": {
+            "securitySchemes": {"bearer": {"type": "http", "scheme": "bearer"}},
+            "responses": {
+                "Error": {
+                    "description": "Error",
+                    "content": {"application/json": {"schema": {
+                        "type": "object",
+                        "properties": {"error": {"type": "string"}, "message": {"type": "string"}},
+                        "required": ["error"],
+                    }}},
+                },
+            },
+        },
+    })
+}
@@ -0,0 +1,138 @@
+//! Soft limits on the backlogs of the background workers.
+//!
+//! Leaks grow slowly: blobs the GC can not keep up with, temp blobs of uploads which are never committed and
+//! objects which fail to replicate. The monitor compares them with the configured limits and reports when a
+//! limit is exceeded and when the backlog is back within it, through the log, the `alerts.triggered` metric
+//! and optionally a webhook.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, Uri};
+use opentelemetry::KeyValue;
+use serde_json::json;
+
+use crate::meta_store::MetaStore;
+
+const INTERVAL: Duration = Duration::from_secs(60);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limits which are not set are not checked
+#[derive(Debug, Clone, Default)]
+pub struct AlertThresholds {
+    /// blobs waiting for the GC whose trash retention has expired
+    pub gc_backlog: Option<i64>,
+    /// age of the oldest temp blob
+    pub temp_blob_age: Option<Duration>,
+    /// objects whose replication has failed
+    pub replication_failed: Option<i64>,
+}
+
+/// Last observed backlogs, exported as gauges
+#[derive(Debug, Default)]
+pub struct Backlog {
+    pub gc: AtomicI64,
+    /// in seconds
+    pub temp_blob_age: AtomicI64,
+    pub replication_failed: AtomicI64,
+}
+
+pub struct BacklogMonitor {
+    db: Arc<dyn MetaStore>,
+    thresholds: AlertThresholds,
+    backlog: Arc<Backlog>,
+    /// receives a JSON POST for every change of an alert
+    webhook: Option<Uri>,
+    client: Client<HttpConnector>,
+}
+
+impl BacklogMonitor {
+    pub fn new(db: Arc<dyn MetaStore>, thresholds: AlertThresholds, backlog: Arc<Backlog>) -> Self {
+        Self {
+            db,
+            thresholds,
+            backlog,
+            webhook: None,
+            client: Client::new(),
+        }
+    }
+
+    /// Only plain HTTP endpoints are supported, e.g. a relay next to the gateway
+    pub fn with_webhook(mut self, webhook: Uri) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    pub async fn run(self) {
+        let mut firing = HashSet::new();
+        loop {
+            if let Err(err) = self.check(&mut firing).await {
+                tracing::error!(error = %err, "unable to check the backlogs");
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+    }
+
+    async fn check(&self, firing: &mut HashSet<&'static str>) -> anyhow::Result<()> {
+        let health = self.db.get_health().await?;
+        let gc = health.blobs_gc - health.blobs_trash;
+        let temp_blob_age = health
+            .oldest_temp_blob
+            .map_or(0, |t| (time::OffsetDateTime::now_utc() - t).whole_seconds().max(0));
+        self.backlog.gc.store(gc, Ordering::Relaxed);
+        self.backlog.temp_blob_age.store(temp_blob_age, Ordering::Relaxed);
+        self.backlog
+            .replication_failed
+            .store(health.replication_failed, Ordering::Relaxed);
+
+        let checks = [
+            ("gc_backlog", gc, self.thresholds.gc_backlog),
+            (
+                "temp_blob_age",
+                temp_blob_age,
+                self.thresholds.temp_blob_age.map(|age| age.as_secs() as i64),
+            ),
+            ("replication_failed", health.replication_failed, self.thresholds.replication_failed),
+        ];
+        for (alert, value, limit) in checks {
+            let Some(limit) = limit else {
+                continue;
+            };
+            if value > limit {
+                if firing.insert(alert) {
+                    tracing::warn!(alert, value, limit, "backlog has exceeded the limit");
+                    crate::telemetry::metrics()
+                        .alerts_triggered
+                        .add(1, &[KeyValue::new("alert", alert)]);
+                    self.notify(alert, "firing", value, limit).await;
+                }
+            } else if firing.remove(alert) {
+                tracing::info!(alert, value, limit, "backlog is within the limit again");
+                self.notify(alert, "resolved", value, limit).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn notify(&self, alert: &str, status: &str, value: i64, limit: i64) {
+        let Some(webhook) = &self.webhook else {
+            return;
+        };
+        let body = json!({"alert": alert, "status": status, "value": value, "limit": limit});
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(webhook)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("a valid webhook request");
+        match tokio::time::timeout(WEBHOOK_TIMEOUT, self.client.request(req)).await {
+            Ok(Ok(res)) if res.status().is_success() => {}
+            Ok(Ok(res)) => tracing::warn!(alert, status = %res.status(), "alert webhook has been rejected"),
+            Ok(Err(err)) => tracing::warn!(error = %err, alert, "unable to call the alert webhook"),
+            Err(_) => tracing::warn!(alert, "alert webhook has timed out"),
+        }
+    }
+}
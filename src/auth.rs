@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use s3s::auth::{S3Auth, S3AuthContext, SecretKey};
+use s3s::path::S3Path;
+use s3s::{s3_error, S3Error, S3ErrorCode, S3Result};
+use tokio::sync::watch;
+
+use crate::meta_store::MetaStore;
+use crate::policy::{self, PolicyDocument};
+use crate::reload::RuntimeConfig;
+
+/// Rejects SigV4 requests signed for a region other than the one served by the gateway.
+///
+/// AWS SDKs rely on the `AuthorizationHeaderMalformed` error to discover the bucket region.
+pub struct RegionAuth<A> {
+    inner: A,
+    region: String,
+}
+
+impl<A: S3Auth> RegionAuth<A> {
+    pub fn new(inner: A, region: String) -> Self {
+        Self { inner, region }
+    }
+
+    fn check_region(&self, cx: &S3AuthContext<'_>) -> S3Result<()> {
+        if let Some(authorization) = cx.headers().get(hyper::header::AUTHORIZATION) {
+            let Some(region) = authorization.to_str().ok().and_then(header_region) else {
+                return Ok(()); // SigV2 or malformed, the latter is handled by s3s
+            };
+            if region != self.region {
+                return Err(self.region_error("AuthorizationHeaderMalformed", "authorization header", region));
+            }
+            return Ok(());
+        }
+
+        if let Some(region) = cx.uri().query().and_then(query_region) {
+            if region != self.region {
+                return Err(self.region_error("AuthorizationQueryParametersError", "X-Amz-Credential parameter", &region));
+            }
+        }
+        Ok(())
+    }
+
+    fn region_error(&self, code: &'static str, place: &str, region: &str) -> S3Error {
+        let mut err = S3Error::with_message(
+            S3ErrorCode::Custom(code.into()),
+            format!("The {place} is malformed; the region '{region}' is wrong; expecting '{}'", self.region),
+        );
+        err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+        err
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: S3Auth> S3Auth for RegionAuth<A> {
+    async fn get_secret_key(&self, access_key: &str) -> S3Result<SecretKey> {
+        self.inner.get_secret_key(access_key).await
+    }
+
+    async fn check_access(&self, cx: &mut S3AuthContext<'_>) -> S3Result<()> {
+        self.check_region(cx)?;
+        // SDKs discover the bucket region with anonymous HeadBucket, the handler decides between 403 and 404
+        if cx.credentials().is_none() && cx.method() == hyper::Method::HEAD && matches!(cx.s3_path(), S3Path::Bucket { .. }) {
+            return Ok(());
+        }
+        self.inner.check_access(cx).await
+    }
+}
+
+/// Evaluates the policies attached to the user who signed the request.
+pub struct PolicyAuth<A> {
+    inner: A,
+    db: Arc<dyn MetaStore>,
+}
+
+impl<A: S3Auth> PolicyAuth<A> {
+    pub fn new(inner: A, db: Arc<dyn MetaStore>) -> Self {
+        Self { inner, db }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: S3Auth> S3Auth for PolicyAuth<A> {
+    async fn get_secret_key(&self, access_key: &str) -> S3Result<SecretKey> {
+        self.inner.get_secret_key(access_key).await
+    }
+
+    async fn check_access(&self, cx: &mut S3AuthContext<'_>) -> S3Result<()> {
+        self.inner.check_access(cx).await?;
+        let Some(creds) = cx.credentials() else {
+            return Ok(());
+        };
+
+        let policies = self.db.get_policies_by_access_key(&creds.access_key).await?;
+        if policies.is_empty() {
+            return Ok(());
+        }
+        let mut documents = Vec::with_capacity(policies.len());
+        for p in &policies {
+            match PolicyDocument::parse(&p.document) {
+                Ok(document) => documents.push(document),
+                // documents are validated when stored, deny everything if one got broken anyway
+                Err(err) => {
+                    tracing::error!(policy = %p.name, error = %err, "unable to parse the user policy");
+                    return Err(s3_error!(AccessDenied));
+                }
+            }
+        }
+
+        let actions = policy::request_actions(cx.method(), cx.s3_path(), cx.uri().query(), cx.headers());
+        if !policy::is_allowed(&documents, &actions) {
+            tracing::debug!(access_key = %creds.access_key, ?actions, "request is denied by the user policy");
+            return Err(s3_error!(AccessDenied));
+        }
+        Ok(())
+    }
+}
+
+/// Requires requesters other than the bucket owner to acknowledge that they pay for requests to a
+/// requester-pays bucket with `x-amz-request-payer: requester`, like S3 does. Anonymous requests to such
+/// buckets are rejected. Requests are not billed, so nothing is charged to the requester.
+pub struct RequesterPaysAuth<A> {
+    inner: A,
+    db: Arc<dyn MetaStore>,
+}
+
+impl<A: S3Auth> RequesterPaysAuth<A> {
+    pub fn new(inner: A, db: Arc<dyn MetaStore>) -> Self {
+        Self { inner, db }
+    }
+}
+
+#[async_trait::async_trait]
+impl<A: S3Auth> S3Auth for RequesterPaysAuth<A> {
+    async fn get_secret_key(&self, access_key: &str) -> S3Result<SecretKey> {
+        self.inner.get_secret_key(access_key).await
+    }
+
+    async fn check_access(&self, cx: &mut S3AuthContext<'_>) -> S3Result<()> {
+        self.inner.check_access(cx).await?;
+        let bucket = match cx.s3_path() {
+            S3Path::Bucket { bucket } | S3Path::Object { bucket, .. } => bucket,
+            S3Path::Root => return Ok(()),
+        };
+        let Some(bucket) = self.db.get_bucket_metadata(bucket).await? else {
+            return Ok(());
+        };
+        if bucket.request_payer != s3s::dto::Payer::REQUESTER {
+            return Ok(());
+        }
+
+        let Some(creds) = cx.credentials() else {
+            return Err(s3_error!(AccessDenied, "Anonymous requests to a requester-pays bucket are not allowed"));
+        };
+        let header = cx.headers().get("x-amz-request-payer").and_then(|v| v.to_str().ok());
+        let query = cx.uri().query().and_then(|q| {
+            form_urlencoded::parse(q.as_bytes())
+                .find(|(k, _)| k == "x-amz-request-payer")
+                .map(|(_, v)| v.into_owned())
+        });
+        let acknowledged = header
+            .or(query.as_deref())
+            .is_some_and(|v| v.eq_ignore_ascii_case("requester"));
+        if acknowledged || self.db.get_user_by_access_key(&creds.access_key).await?.id == bucket.owner {
+            return Ok(());
+        }
+        Err(s3_error!(
+            AccessDenied,
+            "Requests to a requester-pays bucket must set x-amz-request-payer"
+        ))
+    }
+}
+
+/// The gateway user from the runtime config, its keys can be changed without a restart.
+pub struct ConfigAuth {
+    config: watch::Receiver<RuntimeConfig>,
+}
+
+impl ConfigAuth {
+    pub fn new(config: watch::Receiver<RuntimeConfig>) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait::async_trait]
+impl S3Auth for ConfigAuth {
+    async fn get_secret_key(&self, access_key: &str) -> S3Result<SecretKey> {
+        match &self.config.borrow().credentials {
+            Some((key, secret)) if key == access_key => Ok(secret.clone().into()),
+            _ => Err(s3_error!(NotSignedUp, "Your account is not signed up")),
+        }
+    }
+}
+
+/// Credential scope has the form `<access key>/<date>/<region>/s3/aws4_request`
+fn scope_region(credential: &str) -> Option<&str> {
+    credential.split('/').nth(2)
+}
+
+fn header_region(authorization: &str) -> Option<&str> {
+    let params = authorization.strip_prefix("AWS4-HMAC-SHA256")?;
+    params
+        .split(',')
+        .find_map(|p| p.trim().strip_prefix("Credential="))
+        .and_then(scope_region)
+}
+
+fn query_region(query: &str) -> Option<String> {
+    let credential = query.split('&').find_map(|p| p.strip_prefix("X-Amz-Credential="))?;
+    let credential = urlencoding::decode(credential).ok()?;
+    scope_region(&credential).map(ToOwned::to_owned)
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use md5::{Digest, Md5};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{BatchJob, BatchOperation, BatchTaskResult, Blob, Bucket, ContentHeaders, MetaStore, Object, Timestamp};
+use crate::tiering::{self, ColdTier};
+
+const IDLE_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 100;
+/// a batch must be finished before the lease expires, otherwise another worker takes over the job
+const LEASE: Duration = Duration::from_secs(300);
+/// rows of the completion report fetched at once
+const REPORT_PAGE_SIZE: i64 = 1000;
+
+/// Executes batch jobs submitted through the admin API.
+///
+/// The keys of the manifest are stored in `batch_job_tasks` when the job is submitted and processed in key
+/// order, the outcome of every key is recorded so that a job continues where it stopped after a restart.
+/// A job is held by one worker at a time through a lease on the job. Failing keys do not stop the job, they
+/// are listed in the completion report `<report prefix>job-<id>/report.csv` together with the error.
+pub struct BatchWorker {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    cold: Option<ColdTier>,
+}
+
+impl BatchWorker {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>) -> Self {
+        Self { db, blob, cold: None }
+    }
+
+    pub fn with_cold_tier(mut self, cold: Option<ColdTier>) -> Self {
+        self.cold = cold;
+        self
+    }
+
+    pub async fn run(self) {
+        loop {
+            let job = match self.db.claim_batch_job(LEASE).await {
+                Ok(Some(job)) => job,
+                Ok(None) => {
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to claim batch job");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            // the lease expires and the job is picked up again
+            if let Err(err) = self.execute(&job).await {
+                tracing::error!(error = %err, job = %job.id, "batch job has been interrupted");
+                tokio::time::sleep(IDLE_INTERVAL).await;
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "info", skip_all, fields(job = %job.id, operation = job.operation.name()))]
+    async fn execute(&self, job: &BatchJob) -> anyhow::Result<()> {
+        loop {
+            let keys = self.db.list_pending_batch_tasks(&job.id, BATCH_SIZE).await?;
+            if keys.is_empty() {
+                break;
+            }
+
+            let mut results = Vec::with_capacity(keys.len());
+            for oid in keys {
+                let error = match self.apply(job, &oid).await {
+                    Ok(()) => None,
+                    Err(err) => {
+                        tracing::debug!(error = %err, key = oid, "batch operation has failed");
+                        Some(err.to_string())
+                    }
+                };
+                results.push(BatchTaskResult { oid, error });
+            }
+            self.db.complete_batch_tasks(&job.id, &results, LEASE).await?;
+        }
+
+        let (status, report_key) = match &job.report_bucket {
+            None => ("Complete", None),
+            Some(bucket) => match self.write_report(job, bucket).await {
+                Ok(key) => ("Complete", Some(key)),
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to write batch job report");
+                    ("Failed", None)
+                }
+            },
+        };
+        self.db.finish_batch_job(&job.id, status, report_key.as_deref()).await?;
+        tracing::info!(status, "batch job has finished");
+        Ok(())
+    }
+
+    async fn apply(&self, job: &BatchJob, oid: &str) -> anyhow::Result<()> {
+        match &job.operation {
+            BatchOperation::Copy {
+                destination_bucket,
+                destination_prefix,
+            } => {
+                let Some(destination) = self.db.get_bucket_metadata(destination_bucket).await? else {
+                    anyhow::bail!("destination bucket {destination_bucket} does not exist");
+                };
+                let (object, source) = self.load(&job.bucket, oid).await?;
+                let mut blob = self.new_blob().await?;
+                let res = self.copy(&source, &blob).await.map(|etag| {
+                    blob.etag = etag;
+                    blob.size = source.size;
+                });
+                let object = Object {
+                    bucket_name: destination.name.clone(),
+                    oid: format!("{destination_prefix}{oid}"),
+                    blob_id: Some(blob.id),
+                    replication_status: None,
+                    last_accessed: None,
+                    ..object
+                };
+                self.commit(&destination, &object, &blob, res).await
+            }
+            BatchOperation::Tag { tagging } => Ok(self.db.set_object_tagging(&job.bucket, oid, tagging.as_deref()).await?),
+            BatchOperation::Delete => Ok(self.db.delete_object_metadata(&job.bucket, oid, &None).await?),
+            BatchOperation::RestoreTier => {
+                let (_, source) = self.load(&job.bucket, oid).await?;
+                if source.storage_class.is_none() {
+                    return Ok(());
+                }
+                let Some(cold) = &self.cold else {
+                    anyhow::bail!("blob is in the cold tier which is not configured");
+                };
+                match tiering::move_to_standard(self.db.as_ref(), self.blob.as_ref(), cold, &source).await? {
+                    Some(_) => Ok(()),
+                    None => anyhow::bail!("object has been replaced during the restore"),
+                }
+            }
+        }
+    }
+
+    async fn load(&self, bucket: &str, oid: &str) -> anyhow::Result<(Object, Blob)> {
+        match self.db.load_object_metadata(bucket, oid, &None).await? {
+            Some((object, Some(blob))) => Ok((object, blob)),
+            _ => anyhow::bail!("object does not exist"),
+        }
+    }
+
+    /// Copy the data of the source blob into the standard backend, returns the etag of the copy
+    async fn copy(&self, source: &Blob, target: &Blob) -> anyhow::Result<String> {
+        let size = source.size as u64;
+        let store = tiering::blob_store(&self.blob, self.cold.as_ref(), source.storage_class.as_deref())?;
+        let parts = self.db.get_blob_parts(&source.id).await?;
+        let mut reader = if parts.is_empty() {
+            store.get_reader(&source.id.to_string(), 0, size).await?
+        } else {
+            let parts = parts.iter().map(|p| (p.blob_id.to_string(), p.size as u64)).collect();
+            store.get_parts_reader(parts, 0, size).await?
+        };
+
+        let mut writer = self.blob.get_writer(&target.id.to_string()).await?;
+        let mut md5_hash = <Md5 as Digest>::new();
+        let mut copied = 0;
+        while let Some(chunk) = reader.next().await {
+            let chunk = chunk?;
+            copied += chunk.len() as u64;
+            md5_hash.update(&chunk);
+            writer.write_all(&chunk).await?;
+        }
+        writer.flush().await?;
+        if copied != size {
+            anyhow::bail!("blob has {copied} bytes instead of {size}");
+        }
+        Ok(hex_simd::encode_to_string(md5_hash.finalize(), hex_simd::AsciiCase::Lower))
+    }
+
+    /// Write `<bucket>,<key>,<status>,<error>` for every key of the job
+    async fn write_report(&self, job: &BatchJob, bucket: &str) -> anyhow::Result<String> {
+        let Some(destination) = self.db.get_bucket_metadata(bucket).await? else {
+            anyhow::bail!("report bucket {bucket} does not exist");
+        };
+        let key = format!("{}job-{}/report.csv", job.report_prefix, job.id);
+
+        let mut blob = self.new_blob().await?;
+        let res = async {
+            let mut writer = self.blob.get_writer(&blob.id.to_string()).await?;
+            let mut md5_hash = <Md5 as Digest>::new();
+            let mut after = None;
+            loop {
+                let page = self
+                    .db
+                    .list_batch_task_results(&job.id, after.as_deref(), REPORT_PAGE_SIZE)
+                    .await?;
+                let Some(last) = page.last() else {
+                    break;
+                };
+                after = Some(last.oid.clone());
+
+                let mut chunk = String::new();
+                for result in &page {
+                    chunk.push_str(&report_row(&job.bucket, result));
+                }
+                md5_hash.update(chunk.as_bytes());
+                blob.size += chunk.len() as i64;
+                writer.write_all(chunk.as_bytes()).await?;
+            }
+            writer.flush().await?;
+            blob.etag = hex_simd::encode_to_string(md5_hash.finalize(), hex_simd::AsciiCase::Lower);
+            Ok(())
+        }
+        .await;
+
+        let object = Object {
+            bucket_name: destination.name.clone(),
+            oid: key.clone(),
+            version_id: None,
+            last_modified: Timestamp::UNIX_EPOCH,
+            blob_id: Some(blob.id),
+            metadata: None,
+            tagging: None,
+            replication_status: None,
+            last_accessed: None,
+            content_headers: ContentHeaders::default(),
+        };
+        self.commit(&destination, &object, &blob, res).await?;
+        Ok(key)
+    }
+
+    async fn new_blob(&self) -> anyhow::Result<Blob> {
+        let blob = Blob::temporary(Uuid::new_v4());
+        self.db.write_temp_blob(&blob).await?;
+        Ok(blob)
+    }
+
+    /// Attach the written blob to the object or clean it up if writing has failed
+    async fn commit(&self, destination: &Bucket, object: &Object, blob: &Blob, res: anyhow::Result<()>) -> anyhow::Result<()> {
+        let res = match res {
+            Ok(()) => self
+                .db
+                .write_object_metadata_with_blob(destination, object, blob, None)
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from),
+            Err(err) => Err(err),
+        };
+        if res.is_err() {
+            self.db.clean_temp_blob(blob).await;
+        }
+        res
+    }
+}
+
+fn report_row(bucket: &str, result: &BatchTaskResult) -> String {
+    // keys are URL encoded so they never contain quotes or line breaks
+    let (status, error) = match &result.error {
+        None => ("succeeded", String::new()),
+        Some(error) => ("failed", error.replace('"', "'")),
+    };
+    format!("\"{}\",\"{}\",\"{}\",\"{}\"\n", bucket, urlencoding::encode(&result.oid), status, error)
+}
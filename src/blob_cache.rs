@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::meta_store::Blob;
+
+/// size of a single read of a cached file
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+/// chunks queued for the file writer, a fill is abandoned if the disk falls behind
+const FILL_QUEUE_DEPTH: usize = 64;
+
+type BlobStream = Pin<Box<dyn Stream<Item = Result<Bytes, s3s::S3Error>> + Send + Sync>>;
+
+/// Size-bounded copy of hot blobs on the local disk, the least recently used blobs are evicted.
+///
+/// Blobs are filled while they are read from the backend (read-through) and while new objects are
+/// uploaded (write-through). Blob ids are never reused, but an entry is still served only if its etag and
+/// size match the blob of the object, a mismatching entry is dropped. The index is kept in memory, so the
+/// directory is emptied on start.
+#[derive(Debug)]
+pub struct BlobCache {
+    dir: PathBuf,
+    max_size: u64,
+    /// larger blobs are not cached
+    max_entry_size: u64,
+    index: Mutex<Index>,
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<Uuid, Entry>,
+    /// entries by the last use, the first one is evicted first
+    lru: BTreeMap<u64, Uuid>,
+    size: u64,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    etag: String,
+    size: u64,
+    last_used: u64,
+}
+
+impl Index {
+    fn touch(&mut self, id: &Uuid) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(id) {
+            self.lru.remove(&entry.last_used);
+            entry.last_used = self.clock;
+            self.lru.insert(self.clock, *id);
+        }
+    }
+
+    fn remove(&mut self, id: &Uuid) {
+        if let Some(entry) = self.entries.remove(id) {
+            self.lru.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+    }
+}
+
+impl BlobCache {
+    pub async fn open(dir: PathBuf, max_size: u64, max_entry_size: u64) -> std::io::Result<Self> {
+        if tokio::fs::try_exists(&dir).await? {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            max_size,
+            max_entry_size: max_entry_size.min(max_size),
+            index: Mutex::default(),
+        })
+    }
+
+    fn path(&self, id: &Uuid) -> PathBuf {
+        self.dir.join(id.to_string())
+    }
+
+    /// Whether the blob is small enough to be cached
+    fn accepts(&self, size: i64) -> bool {
+        size as u64 <= self.max_entry_size
+    }
+
+    /// Data of the blob if it is cached and up to date
+    pub async fn get(&self, blob: &Blob) -> Option<BlobStream> {
+        {
+            let mut index = self.index.lock().expect("unable to lock mutex");
+            let entry = index.entries.get(&blob.id)?;
+            if entry.etag != blob.etag || entry.size != blob.size as u64 {
+                tracing::debug!(blob = %blob.id, "cached blob is outdated");
+                index.remove(&blob.id);
+                drop(index);
+                self.remove_file(blob.id);
+                return None;
+            }
+            index.touch(&blob.id);
+        }
+
+        // an open file stays readable when the entry is evicted in the meantime
+        let file = tokio::fs::File::open(self.path(&blob.id)).await.ok()?;
+        let stream = futures::stream::try_unfold(file, |mut file| async move {
+            let mut buf = BytesMut::with_capacity(READ_CHUNK_SIZE);
+            let read = file
+                .read_buf(&mut buf)
+                .await
+                .map_err(|err| s3s::S3Error::with_source(s3s::S3ErrorCode::InternalError, Box::new(err)))?;
+            if read == 0 {
+                return Ok(None);
+            }
+            Ok(Some((buf.freeze(), file)))
+        });
+        Some(Box::pin(stream))
+    }
+
+    /// Copy the data read from the backend into the cache while it is passed on
+    pub fn read_through(self: &Arc<Self>, blob: &Blob, inner: BlobStream) -> BlobStream {
+        if !self.accepts(blob.size) {
+            return inner;
+        }
+        Box::pin(ReadThrough {
+            inner,
+            fill: Some(self.fill(blob.id)),
+            etag: blob.etag.clone(),
+            size: blob.size as u64,
+        })
+    }
+
+    /// Start writing a blob into the cache. It becomes visible after [`CacheFill::finish`], a fill which is
+    /// dropped before is discarded.
+    pub fn fill(self: &Arc<Self>, id: Uuid) -> CacheFill {
+        let (tx, rx) = mpsc::channel(FILL_QUEUE_DEPTH);
+        tokio::spawn(self.clone().write_file(id, rx));
+        CacheFill { tx: Some(tx) }
+    }
+
+    async fn write_file(self: Arc<Self>, id: Uuid, mut rx: mpsc::Receiver<FillMessage>) {
+        let temp = self.dir.join(format!("{id}.{}.tmp", Uuid::new_v4()));
+        let res: std::io::Result<Option<(u64, String)>> = async {
+            let mut file = tokio::fs::File::create(&temp).await?;
+            let mut written = 0;
+            while let Some(message) = rx.recv().await {
+                match message {
+                    FillMessage::Chunk(chunk) => {
+                        written += chunk.len() as u64;
+                        if written > self.max_entry_size {
+                            return Ok(None);
+                        }
+                        file.write_all(&chunk).await?;
+                    }
+                    FillMessage::Finish { size, etag } => {
+                        if written != size {
+                            return Ok(None);
+                        }
+                        file.flush().await?;
+                        tokio::fs::rename(&temp, self.path(&id)).await?;
+                        return Ok(Some((size, etag)));
+                    }
+                }
+            }
+            Ok(None)
+        }
+        .await;
+
+        match res {
+            Ok(Some((size, etag))) => self.insert(id, size, etag),
+            Ok(None) => {
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, blob = %id, "unable to write blob to the cache");
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+        }
+    }
+
+    fn insert(&self, id: Uuid, size: u64, etag: String) {
+        let mut evicted = Vec::new();
+        {
+            let mut index = self.index.lock().expect("unable to lock mutex");
+            index.remove(&id);
+            while index.size + size > self.max_size {
+                let Some((_, oldest)) = index.lru.pop_first() else {
+                    break;
+                };
+                if let Some(entry) = index.entries.remove(&oldest) {
+                    index.size -= entry.size;
+                }
+                evicted.push(oldest);
+            }
+            index.clock += 1;
+            let last_used = index.clock;
+            index.entries.insert(id, Entry { etag, size, last_used });
+            index.lru.insert(last_used, id);
+            index.size += size;
+        }
+        for id in evicted {
+            self.remove_file(id);
+        }
+    }
+
+    /// Drop the blob if it is cached, e.g. after it has been removed from the backend
+    pub fn evict(&self, id: &Uuid) {
+        let cached = {
+            let mut index = self.index.lock().expect("unable to lock mutex");
+            let cached = index.entries.contains_key(id);
+            index.remove(id);
+            cached
+        };
+        if cached {
+            self.remove_file(*id);
+        }
+    }
+
+    fn remove_file(&self, id: Uuid) {
+        let path = self.path(&id);
+        tokio::spawn(async move {
+            if let Err(err) = tokio::fs::remove_file(&path).await {
+                tracing::debug!(error = %err, path = %path.display(), "unable to remove cached blob");
+            }
+        });
+    }
+}
+
+enum FillMessage {
+    Chunk(Bytes),
+    Finish { size: u64, etag: String },
+}
+
+/// Data of a blob on its way into the cache
+pub struct CacheFill {
+    tx: Option<mpsc::Sender<FillMessage>>,
+}
+
+impl CacheFill {
+    /// Never waits for the disk, the fill is abandoned instead
+    pub fn push(&mut self, chunk: &Bytes) {
+        if let Some(tx) = &self.tx {
+            if tx.try_send(FillMessage::Chunk(chunk.clone())).is_err() {
+                self.tx = None;
+            }
+        }
+    }
+
+    pub fn finish(self, size: u64, etag: String) {
+        if let Some(tx) = self.tx {
+            let _ = tx.try_send(FillMessage::Finish { size, etag });
+        }
+    }
+}
+
+struct ReadThrough {
+    inner: BlobStream,
+    fill: Option<CacheFill>,
+    etag: String,
+    size: u64,
+}
+
+impl Stream for ReadThrough {
+    type Item = Result<Bytes, s3s::S3Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let res = self.inner.as_mut().poll_next(cx);
+        match &res {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(fill) = &mut self.fill {
+                    fill.push(chunk);
+                }
+            }
+            Poll::Ready(Some(Err(_))) => self.fill = None,
+            Poll::Ready(None) => {
+                if let Some(fill) = self.fill.take() {
+                    let etag = std::mem::take(&mut self.etag);
+                    fill.finish(self.size, etag);
+                }
+            }
+            Poll::Pending => {}
+        }
+        res
+    }
+}
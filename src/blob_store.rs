@@ -7,4 +7,19 @@ pub trait BlobStore: Send + Sync + std::fmt::Debug + 'static {
         offset: u64,
         length: u64,
     ) -> Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>, s3s::S3Error>;
+    /// Read a blob which is stored as several backend objects (key, size) one after another
+    async fn get_parts_reader(
+        &self,
+        parts: Vec<(String, u64)>,
+        offset: u64,
+        length: u64,
+    ) -> Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>, s3s::S3Error>;
+    /// Remove the backend object. Removing a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<(), s3s::S3Error>;
+    /// Keys of all objects in the store
+    async fn list(&self) -> Result<Vec<String>, s3s::S3Error>;
+    /// Size and modification time of the object
+    async fn stat(&self, key: &str) -> Result<(u64, std::time::SystemTime), s3s::S3Error>;
+    /// Check that the backend is reachable
+    async fn health_check(&self) -> Result<(), s3s::S3Error>;
 }
@@ -0,0 +1,140 @@
+//! Request metrics of buckets.
+//!
+//! Object requests which match a metrics configuration of the bucket are counted by bucket, configuration id and
+//! operation, like the request metrics of CloudWatch. Requests only read the cached configurations, a missing or
+//! expired entry is loaded in the background. So requests to a bucket are counted from its second request after a
+//! start, and changes made through another gateway take effect once the cache expires.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use opentelemetry::KeyValue;
+
+use crate::meta_store::{MetaStore, MetricsConfig};
+use crate::telemetry::metrics;
+
+const TTL: Duration = Duration::from_secs(60);
+const MAX_CACHED_BUCKETS: usize = 10_000;
+
+/// Object operations reported by the request metrics
+#[derive(Debug, Clone, Copy)]
+pub enum Operation {
+    Get,
+    Head,
+    Put,
+    Delete,
+}
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "GetObject",
+            Self::Head => "HeadObject",
+            Self::Put => "PutObject",
+            Self::Delete => "DeleteObject",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Cached {
+    expires_at: Instant,
+    configs: Arc<Vec<MetricsConfig>>,
+    /// a background load of the bucket is running
+    loading: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct BucketMetrics {
+    cache: Arc<Mutex<HashMap<String, Cached>>>,
+}
+
+impl BucketMetrics {
+    /// Load the configurations of the bucket again after they have been changed
+    pub fn invalidate(&self, bucket: &str) {
+        if let Some(cached) = self.cache.lock().expect("unable to lock mutex").get_mut(bucket) {
+            cached.expires_at = Instant::now();
+        }
+    }
+
+    /// Cached configurations of the bucket, starts a background load if they are missing or expired
+    fn configs(&self, db: &Arc<dyn MetaStore>, bucket: &str) -> Arc<Vec<MetricsConfig>> {
+        let mut cache = self.cache.lock().expect("unable to lock mutex");
+        if let Some(cached) = cache.get_mut(bucket) {
+            if cached.expires_at > Instant::now() || cached.loading {
+                return cached.configs.clone();
+            }
+            cached.loading = true;
+        } else {
+            if cache.len() >= MAX_CACHED_BUCKETS {
+                cache.clear();
+            }
+            let cached = Cached {
+                expires_at: Instant::now(),
+                configs: Arc::default(),
+                loading: true,
+            };
+            cache.insert(bucket.to_owned(), cached);
+        }
+        let configs = cache[bucket].configs.clone();
+        drop(cache);
+
+        let (cache, db, bucket) = (self.cache.clone(), db.clone(), bucket.to_owned());
+        tokio::spawn(async move {
+            let loaded = db.list_bucket_metrics(&bucket).await;
+            let mut cache = cache.lock().expect("unable to lock mutex");
+            let Some(cached) = cache.get_mut(&bucket) else {
+                return;
+            };
+            cached.loading = false;
+            match loaded {
+                Ok(configs) => {
+                    cached.configs = Arc::new(configs);
+                    cached.expires_at = Instant::now() + TTL;
+                }
+                // the stale configurations are used until the next attempt
+                Err(err) => tracing::debug!(error = %err, bucket, "unable to load metrics configurations"),
+            }
+        });
+        configs
+    }
+
+    /// `tagging` is the tag set of the object in the `x-amz-tagging` form, `bytes` the object data transferred
+    pub fn record(
+        &self,
+        db: &Arc<dyn MetaStore>,
+        bucket: &str,
+        key: &str,
+        tagging: Option<&str>,
+        operation: Operation,
+        bytes: u64,
+    ) {
+        let configs = self.configs(db, bucket);
+
+        for config in configs.iter().filter(|config| matches(config, key, tagging)) {
+            let attributes = [
+                KeyValue::new("bucket", bucket.to_owned()),
+                KeyValue::new("filter_id", config.id.clone()),
+                KeyValue::new("operation", operation.as_str()),
+            ];
+            metrics().bucket_requests.add(1, &attributes);
+            match operation {
+                Operation::Get => metrics().bucket_bytes_downloaded.add(bytes, &attributes),
+                Operation::Put => metrics().bucket_bytes_uploaded.add(bytes, &attributes),
+                Operation::Head | Operation::Delete => {}
+            }
+        }
+    }
+}
+
+fn matches(config: &MetricsConfig, key: &str, tagging: Option<&str>) -> bool {
+    if config.prefix.as_deref().is_some_and(|prefix| !key.starts_with(prefix)) {
+        return false;
+    }
+    let Some(required) = &config.tagging else {
+        return true;
+    };
+    let tags: Vec<_> = form_urlencoded::parse(tagging.unwrap_or_default().as_bytes()).collect();
+    form_urlencoded::parse(required.as_bytes()).all(|tag| tags.contains(&tag))
+}
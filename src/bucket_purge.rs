@@ -0,0 +1,60 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::meta_store::MetaStore;
+
+const IDLE_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 1000;
+
+/// Removes buckets which have been deleted through the admin API together with their contents.
+///
+/// The objects and unfinished multipart uploads are removed in batches and their blobs are handed over
+/// to the garbage collector without a trash retention. The bucket is dropped once it is empty.
+pub struct BucketPurger {
+    db: Arc<dyn MetaStore>,
+}
+
+impl BucketPurger {
+    pub fn new(db: Arc<dyn MetaStore>) -> Self {
+        Self { db }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let buckets = match self.db.list_deleting_buckets().await {
+                Ok(buckets) => buckets,
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to fetch buckets being deleted");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            for bucket in &buckets {
+                if let Err(err) = self.purge(bucket).await {
+                    tracing::error!(error = %err, bucket, "unable to purge bucket");
+                }
+            }
+            tokio::time::sleep(IDLE_INTERVAL).await;
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn purge(&self, bucket: &str) -> anyhow::Result<()> {
+        let mut removed = 0;
+        loop {
+            let batch = self.db.purge_bucket(bucket, BATCH_SIZE).await?;
+            removed += batch;
+            if batch == 0 {
+                break;
+            }
+        }
+
+        // a write which has passed the bucket check before the deletion may still add an object,
+        // it is removed by the next round
+        if self.db.drop_purged_bucket(bucket).await? {
+            tracing::info!(bucket, removed, "bucket has been deleted");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{Method, Request, Response, StatusCode};
+
+use crate::meta_store::MetaStore;
+use crate::post_policy::bucket_name;
+
+/// Adds `x-amz-bucket-region` to HeadBucket responses.
+///
+/// SDKs rely on the header to find the region of a bucket, including from 301 and 403 responses which
+/// s3s can not attach headers to, so it is added after the request has been handled.
+#[derive(Clone)]
+pub struct BucketRegion<S> {
+    inner: S,
+    db: Arc<dyn MetaStore>,
+    base_domain: Option<String>,
+    /// region of buckets created before regions were tracked
+    default_region: String,
+}
+
+impl<S> BucketRegion<S> {
+    pub fn new(inner: S, db: Arc<dyn MetaStore>, base_domain: Option<String>, default_region: String) -> Self {
+        Self {
+            inner,
+            db,
+            base_domain,
+            default_region,
+        }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for BucketRegion<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let bucket = match *req.method() {
+            Method::HEAD => bucket_name(&req, self.base_domain.as_deref()),
+            _ => None,
+        };
+        let fut = self.inner.call(req);
+        let Some(bucket) = bucket else {
+            return Box::pin(fut);
+        };
+
+        let db = self.db.clone();
+        let default_region = self.default_region.clone();
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if res.status() == StatusCode::NOT_FOUND {
+                return Ok(res);
+            }
+            match db.get_bucket_metadata(&bucket).await {
+                Ok(Some(bucket)) => {
+                    let region = bucket.region.unwrap_or(default_region);
+                    if let Ok(region) = region.parse() {
+                        res.headers_mut().insert("x-amz-bucket-region", region);
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => tracing::debug!(error = %err, bucket, "unable to get the bucket region"),
+            }
+            Ok(res)
+        })
+    }
+}
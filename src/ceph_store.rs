@@ -1,43 +1,154 @@
-use std::io::Write;
 use std::sync::{Arc, Mutex};
 use std::{fmt::Debug, os::raw::c_void};
 
-use bytes::BufMut;
+use futures::StreamExt;
 
 use {ceph::ceph as ceph_helpers, ceph::error::RadosError, std::str};
 
 use crate::blob_store;
+use crate::error::StartupError;
 
 pub struct RadosBlobStore {
     rados: Arc<RadosWrp>,
+    /// size of a range fetched by a single parallel read
+    read_chunk_size: u64,
+    /// number of ranges fetched concurrently, 1 disables parallel reads
+    read_concurrency: usize,
+    /// maximum size of a single sequential backend read
+    read_buffer_size: u64,
+    write_buffers: Arc<BufferPool>,
+    /// number of buffers a single writer may have in flight
+    write_queue_depth: usize,
 }
 
+/// Connection settings of a RADOS pool
+#[derive(Debug, Clone)]
+pub struct RadosConfig {
+    pub conf_path: String,
+    pub user: String,
+    pub pool: String,
+    /// objects are stored in this namespace of the pool if set
+    pub namespace: Option<String>,
+    /// maximum number of idle stripers kept for reuse
+    pub max_idle: usize,
+}
+
+/// How often the pool is checked for availability
+const HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
 impl RadosBlobStore {
-    pub async fn new() -> Self {
-        let user_id = "admin";
-        let config_file = "/tmp/ceph/ceph.conf".to_owned();
-        let pool_name = ".mgr";
+    pub async fn new(config: &RadosConfig) -> Result<Self, StartupError> {
+        tracing::info!("connecting to ceph");
+        let cluster = ceph_helpers::connect_to_ceph(&config.user, &config.conf_path)?;
+
+        let rados = Arc::new(RadosWrp::new(cluster, config));
+        tokio::spawn(health_check(Arc::downgrade(&rados)));
+
+        Ok(Self {
+            rados,
+            read_chunk_size: STRIPE_SIZE as u64,
+            read_concurrency: 1,
+            read_buffer_size: STRIPE_SIZE as u64,
+            write_buffers: Arc::new(BufferPool::new(STRIPE_SIZE, 0)),
+            write_queue_depth: 2,
+        })
+    }
 
-        println!("Connecting to ceph");
-        let cluster = ceph_helpers::connect_to_ceph(user_id, &config_file).expect("unable to to connect to the ceph");
-        // let ioctx = cluster.get_rados_ioctx(cluster, ".mgr").unwrap();
+    /// Size of the backend reads and writes and the number of idle write buffers kept for reuse
+    pub fn with_buffers(mut self, read_buffer_size: u64, write_buffer_size: usize, pooled_buffers: usize) -> Self {
+        self.read_buffer_size = read_buffer_size.max(1);
+        self.write_buffers = Arc::new(BufferPool::new(write_buffer_size.max(1), pooled_buffers));
+        self
+    }
 
-        // let ioctx = cluster.get_rados_ioctx(".mgr").unwrap();
-        // let rados_striper = ioctx.get_rados_striper().unwrap();
+    /// Number of buffers a single writer sends to the backend concurrently before it stops accepting data
+    pub fn with_write_queue_depth(mut self, depth: usize) -> Self {
+        self.write_queue_depth = depth.max(1);
+        self
+    }
 
-        // let mut written: u64 = 0;
-        // let dummy_data = vec![0; 4 * 1024 * 1024];
-        // while written < 40 * 1024 * 1024 {
-        //     rados_striper.rados_object_write("test", &dummy_data, written).unwrap();
-        //     written += dummy_data.len() as u64;
-        // }
+    /// Reads larger than `chunk_size` are split into ranges which are fetched `concurrency` at a time
+    pub fn with_parallel_reads(mut self, chunk_size: u64, concurrency: usize) -> Self {
+        self.read_chunk_size = chunk_size.max(1);
+        self.read_concurrency = concurrency.max(1);
+        self
+    }
 
-        Self {
-            rados: Arc::new(RadosWrp::new(cluster, pool_name)),
+    fn parallel_reader(
+        &self,
+        parts: Vec<(String, u64)>,
+        offset: u64,
+        length: u64,
+    ) -> core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>> {
+        // split the requested range into chunks which never cross object boundaries
+        let mut ranges = Vec::new();
+        let mut part_start = 0;
+        for (name, size) in parts {
+            let start = offset.max(part_start);
+            let end = (offset + length).min(part_start + size);
+            let mut cursor = start;
+            while cursor < end {
+                let chunk = self.read_chunk_size.min(end - cursor);
+                ranges.push((name.clone(), cursor - part_start, chunk));
+                cursor += chunk;
+            }
+            part_start += size;
         }
+
+        let rados = self.rados.clone();
+        let stream = futures::stream::iter(ranges)
+            .map(move |(name, offset, length)| {
+                let rados = rados.clone();
+                async move {
+                    let res = tokio::task::spawn_blocking(move || read_range(&rados, &name, offset, length)).await;
+                    let res = try_!(res);
+                    Ok(try_!(res))
+                }
+            })
+            .buffered(self.read_concurrency);
+        Box::pin(stream)
     }
 }
 
+/// Read exactly `length` bytes of the object
+fn read_range(rados: &Arc<RadosWrp>, name: &str, offset: u64, length: u64) -> Result<bytes::Bytes, RadosError> {
+    let rados_striper = rados.get_striper()?;
+    rados_striper.check(read_exact(rados_striper.inner(), name, offset, length))
+}
+
+/// Periodically check the pool until the store is dropped
+async fn health_check(rados: std::sync::Weak<RadosWrp>) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let Some(rados) = rados.upgrade() else {
+            return;
+        };
+        if let Err(err) = tokio::task::spawn_blocking(move || rados.check_health()).await {
+            tracing::error!(error = %err, "rados health check has failed");
+        }
+    }
+}
+
+fn read_exact(
+    rados_striper: &ceph::ceph::RadosStriper,
+    name: &str,
+    offset: u64,
+    length: u64,
+) -> Result<bytes::Bytes, RadosError> {
+    let mut data = bytes::BytesMut::with_capacity(length as usize);
+    while (data.len() as u64) < length {
+        let mut buf = Vec::with_capacity((length - data.len() as u64) as usize);
+        let read = rados_striper.rados_object_read(name, &mut buf, offset + data.len() as u64)?;
+        if read == 0 {
+            return Err(RadosError::Error(format!("object {name} is shorter than expected")));
+        }
+        data.extend_from_slice(&buf);
+    }
+    Ok(data.freeze())
+}
+
 // Rados is Sync then it should be ok to Send it. Some PullRequests add this functionality
 unsafe impl Sync for RadosBlobStore {}
 unsafe impl Send for RadosBlobStore {}
@@ -52,9 +163,9 @@ impl Debug for RadosBlobStore {
 impl blob_store::BlobStore for RadosBlobStore {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn get_writer(&self, key: &str) -> Result<core::pin::Pin<Box<dyn tokio::io::AsyncWrite + Send>>, s3s::S3Error> {
-        let ioctx = self.rados.get_rados_ioctx();
-        let ioctx = try_!(ioctx);
-        Ok(Box::pin(RadosWriter::new(ioctx, key)))
+        let rados_striper = try_!(self.rados.get_striper());
+        let writer = RadosWriter::new(rados_striper, key, self.write_buffers.clone(), self.write_queue_depth);
+        Ok(Box::pin(writer))
     }
 
     async fn get_reader(
@@ -64,74 +175,237 @@ impl blob_store::BlobStore for RadosBlobStore {
         length: u64,
     ) -> Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>, s3s::S3Error>
     {
-        let ioctx = self.rados.get_rados_ioctx();
-        let ioctx = try_!(ioctx);
-        Ok(Box::pin(RadosReader::new(ioctx, key, offset, length)))
+        if self.read_concurrency > 1 && length > self.read_chunk_size {
+            return Ok(self.parallel_reader(vec![(key.to_owned(), offset + length)], offset, length));
+        }
+
+        let rados_striper = try_!(self.rados.get_striper());
+        let reader = RadosReader::new(
+            rados_striper,
+            vec![(key.to_owned(), offset + length)],
+            offset,
+            length,
+            self.read_buffer_size,
+        );
+        Ok(Box::pin(reader))
+    }
+
+    async fn get_parts_reader(
+        &self,
+        parts: Vec<(String, u64)>,
+        offset: u64,
+        length: u64,
+    ) -> Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>, s3s::S3Error>
+    {
+        if self.read_concurrency > 1 && length > self.read_chunk_size {
+            return Ok(self.parallel_reader(parts, offset, length));
+        }
+
+        let rados_striper = try_!(self.rados.get_striper());
+        let reader = RadosReader::new(rados_striper, parts, offset, length, self.read_buffer_size);
+        Ok(Box::pin(reader))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn delete(&self, key: &str) -> Result<(), s3s::S3Error> {
+        let rados_striper = try_!(self.rados.get_striper());
+        match rados_striper.inner().rados_object_remove(key) {
+            Ok(()) => Ok(()),
+            // the object may have never been written or removed by a previous attempt
+            Err(RadosError::ApiError(errno))
+                if std::io::Error::from_raw_os_error(errno as i32).kind() == std::io::ErrorKind::NotFound =>
+            {
+                Ok(())
+            }
+            Err(err) => {
+                rados_striper.mark_broken();
+                crate::error::log(&err);
+                Err(crate::error::to_s3_error(err))
+            }
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<String>, s3s::S3Error> {
+        let rados = self.rados.clone();
+        let res = tokio::task::spawn_blocking(move || -> Result<Vec<String>, RadosError> {
+            let ioctx = rados.get_rados_ioctx()?;
+            let pool = ceph_helpers::Pool {
+                ctx: ioctx.rados_list_pool_objects()?,
+            };
+            // the striper stores `<key>.<16 hex digits>` objects, the first one is always present
+            Ok(pool
+                .filter_map(|object| object.name.strip_suffix(".0000000000000000").map(ToOwned::to_owned))
+                .collect())
+        })
+        .await;
+        Ok(try_!(try_!(res)))
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn stat(&self, key: &str) -> Result<(u64, std::time::SystemTime), s3s::S3Error> {
+        let rados_striper = try_!(self.rados.get_striper());
+        match rados_striper.inner().rados_object_stat(key) {
+            Ok(stat) => Ok(stat),
+            Err(RadosError::ApiError(errno))
+                if std::io::Error::from_raw_os_error(errno as i32).kind() == std::io::ErrorKind::NotFound =>
+            {
+                Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchKey))
+            }
+            Err(err) => {
+                rados_striper.mark_broken();
+                crate::error::log(&err);
+                Err(crate::error::to_s3_error(err))
+            }
+        }
+    }
+
+    async fn health_check(&self) -> Result<(), s3s::S3Error> {
+        let rados = self.rados.clone();
+        if try_!(tokio::task::spawn_blocking(move || rados.check_health()).await) {
+            Ok(())
+        } else {
+            Err(s3s::S3Error::with_message(
+                s3s::S3ErrorCode::ServiceUnavailable,
+                "rados pool is unavailable",
+            ))
+        }
     }
 }
 
 const STRIPE_SIZE: usize = 4 * 1024 * 1024;
 
+/// Write buffers are reused between uploads instead of allocating a new one for every request
+struct BufferPool {
+    buffers: Mutex<Vec<bytes::BytesMut>>,
+    /// data is sent to the backend once this much is buffered
+    buffer_size: usize,
+    /// maximum number of idle buffers kept in the pool
+    max_buffers: usize,
+}
+
+impl BufferPool {
+    fn new(buffer_size: usize, max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            buffer_size,
+            max_buffers,
+        }
+    }
+
+    fn get(&self) -> bytes::BytesMut {
+        let buf = self.buffers.lock().expect("unable to lock mutex").pop();
+        buf.unwrap_or_else(|| bytes::BytesMut::with_capacity(self.buffer_size))
+    }
+
+    fn put(&self, mut buf: bytes::BytesMut) {
+        buf.clear();
+        let mut buffers = self.buffers.lock().expect("unable to lock mutex");
+        if buf.capacity() >= self.buffer_size && buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+}
+
+type WriteTask = tokio::task::JoinHandle<Result<bytes::BytesMut, RadosError>>;
+
+/// Buffers the data and writes full buffers on the blocking thread pool.
+///
+/// At most `max_in_flight` writes are submitted at once, `poll_write` waits for the oldest of them
+/// when the queue is full. Errors of the background writes are returned by the next `poll_write`
+/// or `poll_flush`.
 struct RadosWriter {
-    rados_striper: StriperWrp,
-    name: String,
-    buf: bytes::buf::Writer<bytes::BytesMut>,
+    rados_striper: Arc<PooledStriper>,
+    name: Arc<str>,
+    pool: Arc<BufferPool>,
+    buf: bytes::BytesMut,
     offset: u64,
+    in_flight: std::collections::VecDeque<WriteTask>,
+    max_in_flight: usize,
 }
 
 impl RadosWriter {
-    fn new(ioctx: ceph::ceph::IoCtx, name: &str) -> Self {
-        let buf = bytes::BytesMut::with_capacity(2 * STRIPE_SIZE).writer();
-        let rados_striper = ioctx.get_rados_striper().unwrap();
-
+    fn new(rados_striper: PooledStriper, name: &str, pool: Arc<BufferPool>, max_in_flight: usize) -> Self {
         Self {
-            rados_striper: StriperWrp { inner: rados_striper },
-            name: name.to_owned(),
-            buf: buf,
+            rados_striper: Arc::new(rados_striper),
+            name: name.into(),
+            buf: pool.get(),
+            pool,
             offset: 0,
+            in_flight: std::collections::VecDeque::new(),
+            max_in_flight: max_in_flight.max(1),
+        }
+    }
+
+    /// Hand the buffered data over to the blocking thread pool
+    fn submit(&mut self) {
+        let buf = std::mem::replace(&mut self.buf, self.pool.get());
+        let rados_striper = self.rados_striper.clone();
+        let name = self.name.clone();
+        let offset = self.offset;
+        self.offset += buf.len() as u64;
+        self.in_flight.push_back(tokio::task::spawn_blocking(move || {
+            rados_striper.check(rados_striper.inner().rados_object_write(&name, &buf, offset))?;
+            Ok(buf)
+        }));
+    }
+
+    /// Wait for the oldest submitted write to complete
+    fn poll_oldest(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        let Some(task) = self.in_flight.front_mut() else {
+            return std::task::Poll::Ready(Ok(()));
+        };
+        let res = futures::ready!(futures::FutureExt::poll_unpin(task, cx));
+        self.in_flight.pop_front();
+        match res {
+            Ok(Ok(buf)) => {
+                self.pool.put(buf);
+                std::task::Poll::Ready(Ok(()))
+            }
+            Ok(Err(e)) => std::task::Poll::Ready(Err(std::io::Error::other(e))),
+            Err(e) => std::task::Poll::Ready(Err(std::io::Error::other(e))),
         }
     }
 }
 
+impl Drop for RadosWriter {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
 impl tokio::io::AsyncWrite for RadosWriter {
     fn poll_write(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
-        let writer = self.buf.by_ref();
-        (*writer).write_all(buf)?;
-        writer.flush()?;
-        if writer.get_ref().len() > STRIPE_SIZE {
-            // flush to the rados file
-            let data = (*writer).get_mut().split().freeze();
-            let offset = self.offset;
-            self.rados_striper
-                .inner
-                .rados_object_write(&self.name, &data, offset)
-                .unwrap();
-            self.offset = offset + data.len() as u64;
-        }
-        std::task::Poll::Ready(Ok(buf.len()))
+        // do not accept more data until there is room in the queue
+        while self.in_flight.len() >= self.max_in_flight {
+            futures::ready!(self.poll_oldest(cx))?;
+        }
+
+        let buffer_size = self.pool.buffer_size;
+        let len = buf.len().min(buffer_size - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() >= buffer_size {
+            self.submit();
+        }
+        std::task::Poll::Ready(Ok(len))
     }
 
     fn poll_flush(
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), std::io::Error>> {
-        let writer = self.buf.by_ref();
-        writer.flush()?;
-        if writer.get_ref().len() == 0 {
-            return std::task::Poll::Ready(Ok(()));
+        if !self.buf.is_empty() {
+            while self.in_flight.len() >= self.max_in_flight {
+                futures::ready!(self.poll_oldest(cx))?;
+            }
+            self.submit();
+        }
+        while !self.in_flight.is_empty() {
+            futures::ready!(self.poll_oldest(cx))?;
         }
-
-        let data = writer.get_mut().split().freeze();
-        let offset = self.offset;
-        self.rados_striper
-            .inner
-            .rados_object_write(&self.name, &data, offset)
-            .unwrap();
-        self.offset = offset + data.len() as u64;
         std::task::Poll::Ready(Ok(()))
     }
 
@@ -139,90 +413,228 @@ impl tokio::io::AsyncWrite for RadosWriter {
         self: std::pin::Pin<&mut Self>,
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Result<(), std::io::Error>> {
-        std::task::Poll::Ready(Ok(()))
+        self.poll_flush(cx)
     }
 }
 
+/// Number of reads submitted ahead of the one being consumed
+const READ_AHEAD: usize = 1;
+
+type ReadTask = tokio::task::JoinHandle<Result<bytes::Bytes, RadosError>>;
+
+/// Streams `length` bytes starting at `offset`. Reads run on the blocking thread pool and the next
+/// one is started while the current chunk is being sent to the client.
 struct RadosReader {
-    rados_striper: Arc<Mutex<StriperWrp>>,
-    name: String,
-    buf: bytes::BytesMut,
-    offset: u64,
-    length: u64,
-    cursur: u64,
+    rados_striper: Arc<PooledStriper>,
+    /// objects which form the blob and their sizes
+    parts: Vec<(String, u64)>,
+    /// maximum size of a single backend read
+    read_size: u64,
+    /// position of the next read to submit
+    cursor: u64,
+    end: u64,
+    pending: std::collections::VecDeque<ReadTask>,
 }
 
 impl RadosReader {
-    fn new(ioctx: ceph::ceph::IoCtx, name: &str, offset: u64, length: u64) -> Self {
-        let buf = bytes::BytesMut::with_capacity(2 * STRIPE_SIZE).writer();
-        let rados_striper = ioctx.get_rados_striper().unwrap();
-
+    fn new(rados_striper: PooledStriper, parts: Vec<(String, u64)>, offset: u64, length: u64, read_size: u64) -> Self {
         Self {
-            rados_striper: Arc::new(Mutex::new(StriperWrp { inner: rados_striper })),
-            name: name.to_owned(),
-            buf: bytes::BytesMut::with_capacity(STRIPE_SIZE),
-            offset: offset,
-            length: length,
-            cursur: offset,
-        }
-    }
-}
-
-// impl tokio::io::AsyncRead for RadosReader {
-//     fn poll_read(
-//         mut self: std::pin::Pin<&mut Self>,
-//         cx: &mut std::task::Context<'_>,
-//         buf: &mut tokio::io::ReadBuf<'_>,
-//     ) -> std::task::Poll<std::io::Result<()>> {
-//         let next_read = std::cmp::min(self.offset + self.length - self.cursur, STRIPE_SIZE as u64);
-//         if next_read == 0 {
-//             return std::task::Poll::Ready(Ok(()));
-//         }
-
-//         //self.buf.
-//         //buf.
-//         //self.rados_striper.inner.rados_object_read(&self.name, fill_buffer, self.cursur)
-//         todo!()
-//     }
-// }
+            rados_striper: Arc::new(rados_striper),
+            parts,
+            read_size: read_size.max(1),
+            cursor: offset,
+            end: offset + length,
+            pending: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Start the read at the cursor. Reads never cross object boundaries.
+    fn submit(&mut self) -> Result<(), RadosError> {
+        // find the object which holds the cursor
+        let mut part_offset = self.cursor;
+        let Some((name, part_size)) = self.parts.iter().find(|(_, size)| {
+            if part_offset < *size {
+                return true;
+            }
+            part_offset -= size;
+            false
+        }) else {
+            return Err(RadosError::Error(format!("offset {} is beyond the end of the blob", self.cursor)));
+        };
+        let length = (self.end - self.cursor).min(self.read_size).min(part_size - part_offset);
+
+        let rados_striper = self.rados_striper.clone();
+        let name = name.clone();
+        self.pending.push_back(tokio::task::spawn_blocking(move || {
+            rados_striper.check(read_exact(rados_striper.inner(), &name, part_offset, length))
+        }));
+        self.cursor += length;
+        Ok(())
+    }
+
+    fn fail(&mut self, err: impl std::error::Error + Send + Sync + 'static) -> s3s::S3Error {
+        // stop the stream, the remaining reads are not needed anymore
+        self.cursor = self.end;
+        for task in self.pending.drain(..) {
+            task.abort();
+        }
+        crate::error::log(&err);
+        crate::error::to_s3_error(err)
+    }
+}
+
+impl Drop for RadosReader {
+    fn drop(&mut self) {
+        for task in self.pending.drain(..) {
+            task.abort();
+        }
+    }
+}
 
 impl futures::Stream for RadosReader {
     type Item = Result<bytes::Bytes, s3s::S3Error>;
 
     fn poll_next(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
-        let next_read_size = std::cmp::min(self.offset + self.length - self.cursur, STRIPE_SIZE as u64);
-        if next_read_size == 0 {
-            return std::task::Poll::Ready(None);
+        while self.pending.len() <= READ_AHEAD && self.cursor < self.end {
+            if let Err(err) = self.submit() {
+                return std::task::Poll::Ready(Some(Err(self.fail(err))));
+            }
         }
 
-        let striper = self.rados_striper.lock().expect("unable to lock mutex");
-        let mut b = vec![0; next_read_size as usize];
-        striper.inner.rados_object_read(&self.name, &mut b, self.cursur).unwrap();
-        drop(striper);
-        let b = bytes::Bytes::from(b);
-        self.cursur = self.cursur + b.len() as u64;
-        std::task::Poll::Ready(Some(Ok(b)))
+        let Some(task) = self.pending.front_mut() else {
+            return std::task::Poll::Ready(None);
+        };
+        let res = futures::ready!(futures::FutureExt::poll_unpin(task, cx));
+        self.pending.pop_front();
+        let res = match res {
+            Ok(Ok(data)) => Ok(data),
+            Ok(Err(err)) => Err(self.fail(err)),
+            Err(err) => Err(self.fail(err)),
+        };
+
+        // keep the read-ahead going while the caller consumes this chunk
+        if res.is_ok() && self.cursor < self.end {
+            if let Err(err) = self.submit() {
+                return std::task::Poll::Ready(Some(Err(self.fail(err))));
+            }
+        }
+        std::task::Poll::Ready(Some(res))
     }
 }
 
 struct RadosWrp {
     cluster: ceph_helpers::Rados,
     pool_name: String,
+    namespace: Option<String>,
+    /// stripers which are not used by any reader or writer
+    idle: Mutex<Vec<StriperWrp>>,
+    max_idle: usize,
+    /// result of the last health check
+    healthy: std::sync::atomic::AtomicBool,
 }
 
 unsafe impl Sync for RadosWrp {}
 unsafe impl Send for RadosWrp {}
 
 impl RadosWrp {
-    fn new(rados: ceph_helpers::Rados, pool: &str) -> Self {
+    fn new(rados: ceph_helpers::Rados, config: &RadosConfig) -> Self {
         return Self {
             cluster: rados,
-            pool_name: pool.to_owned(),
+            pool_name: config.pool.clone(),
+            namespace: config.namespace.clone(),
+            idle: Mutex::new(Vec::new()),
+            max_idle: config.max_idle,
+            healthy: std::sync::atomic::AtomicBool::new(true),
         };
     }
 
     fn get_rados_ioctx(&self) -> Result<ceph_helpers::IoCtx, RadosError> {
-        self.cluster.get_rados_ioctx(&self.pool_name)
+        let ioctx = self.cluster.get_rados_ioctx(&self.pool_name)?;
+        if let Some(namespace) = &self.namespace {
+            ioctx.rados_set_namespace(namespace)?;
+        }
+        Ok(ioctx)
+    }
+
+    /// Take an idle striper or create a new one
+    fn get_striper(self: &Arc<Self>) -> Result<PooledStriper, RadosError> {
+        let idle = self.idle.lock().expect("unable to lock mutex").pop();
+        let striper = match idle {
+            Some(striper) => striper,
+            None => StriperWrp {
+                inner: self.get_rados_ioctx()?.get_rados_striper()?,
+            },
+        };
+        Ok(PooledStriper {
+            striper: Some(striper),
+            rados: self.clone(),
+            broken: std::sync::atomic::AtomicBool::new(false),
+        })
+    }
+
+    fn put_striper(&self, striper: StriperWrp) {
+        if !self.healthy.load(std::sync::atomic::Ordering::Relaxed) {
+            return;
+        }
+        let mut idle = self.idle.lock().expect("unable to lock mutex");
+        if idle.len() < self.max_idle {
+            idle.push(striper);
+        }
+    }
+
+    /// Check that the pool is reachable. Idle stripers are dropped if it is not.
+    /// Returns whether the pool is available
+    fn check_health(&self) -> bool {
+        let res = self.get_rados_ioctx().and_then(|ioctx| ioctx.rados_stat_pool());
+        match res {
+            Ok(_) => {
+                if !self.healthy.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                    tracing::info!(pool = %self.pool_name, "rados pool is available again");
+                }
+                true
+            }
+            Err(err) => {
+                tracing::error!(error = %err, pool = %self.pool_name, "rados pool is unavailable");
+                self.healthy.store(false, std::sync::atomic::Ordering::Relaxed);
+                self.idle.lock().expect("unable to lock mutex").clear();
+                false
+            }
+        }
+    }
+}
+
+/// Striper borrowed from the pool. It is returned on drop unless an operation on it has failed.
+struct PooledStriper {
+    striper: Option<StriperWrp>,
+    rados: Arc<RadosWrp>,
+    broken: std::sync::atomic::AtomicBool,
+}
+
+impl PooledStriper {
+    fn inner(&self) -> &ceph::ceph::RadosStriper {
+        &self.striper.as_ref().expect("striper is only taken on drop").inner
+    }
+
+    fn mark_broken(&self) {
+        self.broken.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Failed stripers are not reused
+    fn check<T>(&self, res: Result<T, RadosError>) -> Result<T, RadosError> {
+        if res.is_err() {
+            self.mark_broken();
+        }
+        res
+    }
+}
+
+impl Drop for PooledStriper {
+    fn drop(&mut self) {
+        if let Some(striper) = self.striper.take() {
+            if !self.broken.load(std::sync::atomic::Ordering::Relaxed) {
+                self.rados.put_striper(striper);
+            }
+        }
     }
 }
 
@@ -231,3 +643,5 @@ struct StriperWrp {
 }
 
 unsafe impl Send for StriperWrp {}
+// writes of a single writer go to different offsets and the striper handles them concurrently
+unsafe impl Sync for StriperWrp {}
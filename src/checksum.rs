@@ -0,0 +1,155 @@
+//! Additional checksums of the S3 API (`x-amz-checksum-*`).
+//!
+//! Values are base64 encoded digests. Multipart objects get a composite checksum: the digest of the
+//! concatenated part digests followed by the number of parts, e.g. `<base64>-3`.
+
+use md5::Digest;
+use s3s::dto::{ChecksumAlgorithm, ChecksumMode};
+use s3s::{s3_error, S3Result};
+
+use crate::meta_store::Checksum;
+
+static CRC32C: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+pub enum ChecksumHasher {
+    Crc32(crc32fast::Hasher),
+    Crc32c(crc::Digest<'static, u32>),
+    Sha1(sha1::Sha1),
+    Sha256(sha2::Sha256),
+}
+
+impl ChecksumHasher {
+    pub fn new(algorithm: &str) -> S3Result<Self> {
+        match algorithm {
+            ChecksumAlgorithm::CRC32 => Ok(Self::Crc32(crc32fast::Hasher::new())),
+            ChecksumAlgorithm::CRC32C => Ok(Self::Crc32c(CRC32C.digest())),
+            ChecksumAlgorithm::SHA1 => Ok(Self::Sha1(sha1::Sha1::new())),
+            ChecksumAlgorithm::SHA256 => Ok(Self::Sha256(sha2::Sha256::new())),
+            other => Err(s3_error!(InvalidArgument, "Unsupported checksum algorithm: {}", other)),
+        }
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Self::Crc32(_) => ChecksumAlgorithm::CRC32,
+            Self::Crc32c(_) => ChecksumAlgorithm::CRC32C,
+            Self::Sha1(_) => ChecksumAlgorithm::SHA1,
+            Self::Sha256(_) => ChecksumAlgorithm::SHA256,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Crc32(h) => h.update(data),
+            Self::Crc32c(h) => h.update(data),
+            Self::Sha1(h) => h.update(data),
+            Self::Sha256(h) => h.update(data),
+        }
+    }
+
+    /// Raw digest, CRCs are big-endian
+    fn finalize_raw(self) -> Vec<u8> {
+        match self {
+            Self::Crc32(h) => h.finalize().to_be_bytes().to_vec(),
+            Self::Crc32c(h) => h.finalize().to_be_bytes().to_vec(),
+            Self::Sha1(h) => h.finalize().to_vec(),
+            Self::Sha256(h) => h.finalize().to_vec(),
+        }
+    }
+
+    pub fn finalize(self) -> Checksum {
+        let algorithm = self.algorithm().to_owned();
+        Checksum {
+            algorithm,
+            value: base64_simd::STANDARD.encode_to_string(self.finalize_raw()),
+        }
+    }
+}
+
+/// Hasher for the checksum sent by the client. The SDKs announce the algorithm with
+/// `x-amz-sdk-checksum-algorithm` even if the checksum itself is sent in the trailer.
+pub fn hasher(expected: Option<&Checksum>, algorithm: Option<&str>) -> S3Result<Option<ChecksumHasher>> {
+    expected
+        .map(|c| c.algorithm.as_str())
+        .or(algorithm)
+        .map(ChecksumHasher::new)
+        .transpose()
+}
+
+/// Checksum of the written data, `BadDigest` if it differs from the one sent by the client
+pub fn verify(hasher: Option<ChecksumHasher>, expected: Option<&Checksum>) -> S3Result<Option<Checksum>> {
+    let checksum = hasher.map(ChecksumHasher::finalize);
+    if let (Some(expected), Some(checksum)) = (expected, &checksum) {
+        if expected != checksum {
+            return Err(s3_error!(
+                BadDigest,
+                "The {} you specified did not match the calculated checksum.",
+                expected.algorithm
+            ));
+        }
+    }
+    Ok(checksum)
+}
+
+/// Checksum of a multipart object from the checksums of its parts
+pub fn composite(algorithm: &str, parts: &[&str]) -> S3Result<Checksum> {
+    let mut hasher = ChecksumHasher::new(algorithm)?;
+    for part in parts {
+        let digest = base64_simd::STANDARD
+            .decode_to_vec(part)
+            .map_err(|_| s3_error!(InternalError, "Stored part checksum is not valid base64"))?;
+        hasher.update(&digest);
+    }
+    let checksum = hasher.finalize();
+    Ok(Checksum {
+        value: format!("{}-{}", checksum.value, parts.len()),
+        ..checksum
+    })
+}
+
+/// The checksum sent in one of the `x-amz-checksum-*` headers, at most one of them may be present
+pub fn from_headers(
+    crc32: Option<String>,
+    crc32c: Option<String>,
+    sha1: Option<String>,
+    sha256: Option<String>,
+) -> S3Result<Option<Checksum>> {
+    let mut checksums = [
+        (ChecksumAlgorithm::CRC32, crc32),
+        (ChecksumAlgorithm::CRC32C, crc32c),
+        (ChecksumAlgorithm::SHA1, sha1),
+        (ChecksumAlgorithm::SHA256, sha256),
+    ]
+    .into_iter()
+    .filter_map(|(algorithm, value)| {
+        value.map(|value| Checksum {
+            algorithm: algorithm.to_owned(),
+            value,
+        })
+    });
+    let checksum = checksums.next();
+    if checksums.next().is_some() {
+        return Err(s3_error!(InvalidRequest, "Expecting a single x-amz-checksum- header"));
+    }
+    Ok(checksum)
+}
+
+/// Stored checksums are only returned by GetObject and HeadObject with `x-amz-checksum-mode: ENABLED`
+pub fn requested(mode: Option<&ChecksumMode>) -> bool {
+    mode.is_some_and(|mode| mode.as_str() == ChecksumMode::ENABLED)
+}
+
+/// Spread the checksum over the fields of the output
+pub fn to_dto(checksum: Option<Checksum>) -> s3s::dto::Checksum {
+    let mut dto = s3s::dto::Checksum::default();
+    if let Some(checksum) = checksum {
+        let field = match checksum.algorithm.as_str() {
+            ChecksumAlgorithm::CRC32 => &mut dto.checksum_crc32,
+            ChecksumAlgorithm::CRC32C => &mut dto.checksum_crc32c,
+            ChecksumAlgorithm::SHA1 => &mut dto.checksum_sha1,
+            _ => &mut dto.checksum_sha256,
+        };
+        *field = Some(checksum.value);
+    }
+    dto
+}
@@ -0,0 +1,38 @@
+//! Sources of time and identifiers.
+//!
+//! The metadata store and the S3 service take both from here instead of the system, so that time-dependent
+//! behavior like the trash retention, leases and expiry can be driven by a simulated clock.
+
+use std::fmt::Debug;
+
+use uuid::Uuid;
+
+use crate::meta_store::Timestamp;
+
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+/// Identifiers of blobs and multipart uploads
+pub trait IdGenerator: Debug + Send + Sync {
+    fn new_id(&self) -> Uuid;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now_utc()
+    }
+}
+
+/// Random UUIDs, unique without any coordination between gateway instances
+#[derive(Debug, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn new_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
@@ -0,0 +1,119 @@
+//! Operation of several gateways on the same database.
+//!
+//! The GC shares its queue through leases on the rows and runs on every gateway. The other background workers
+//! run on a single gateway at a time which holds the lease of the worker in `worker_leases`: replication,
+//! tiering, restores, inventory, batch jobs, bucket purge, data migrations and the recovery of temporary
+//! blobs. A gateway which stops renewing the lease is replaced once it expires.
+//!
+//! In-process state is not shared. Gateways publish [`GatewayEvent`]s through Postgres LISTEN/NOTIFY so that
+//! the others drop cached blobs removed by the GC. The gateway-wide read-only mode stays per instance.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use uuid::Uuid;
+
+use crate::blob_cache::BlobCache;
+use crate::meta_store::{GatewayEvent, MetaStore};
+
+const LEASE: Duration = Duration::from_secs(30);
+const RENEW_INTERVAL: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Runs a worker only on the gateway which holds its lease
+pub struct Singleton {
+    db: Arc<dyn MetaStore>,
+    name: &'static str,
+    /// identifier of this gateway
+    holder: Uuid,
+}
+
+impl Singleton {
+    pub fn new(db: Arc<dyn MetaStore>, name: &'static str, holder: Uuid) -> Self {
+        Self { db, name, holder }
+    }
+
+    /// Start the worker once the lease is taken and stop it when the lease is lost. The lease is given up
+    /// before it expires if it can not be renewed, so two gateways never run the worker at the same time.
+    /// Returns when the worker finishes.
+    pub async fn run<F, Fut>(self, start: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        loop {
+            if !self.claim().await {
+                tokio::time::sleep(RENEW_INTERVAL).await;
+                continue;
+            }
+            tracing::info!(worker = self.name, "worker has been started on this gateway");
+            let mut worker = tokio::spawn(start());
+            let mut renewed = Instant::now();
+            loop {
+                tokio::select! {
+                    res = &mut worker => match res {
+                        // e.g. all data migrations are applied, the other gateways find nothing to do
+                        Ok(()) => {
+                            tracing::info!(worker = self.name, "worker has finished");
+                            return;
+                        }
+                        Err(err) => {
+                            tracing::error!(error = %err, worker = self.name, "worker has failed");
+                            break;
+                        }
+                    },
+                    _ = tokio::time::sleep(RENEW_INTERVAL) => {}
+                }
+                match self.db.claim_worker_lease(self.name, &self.holder, LEASE).await {
+                    Ok(true) => renewed = Instant::now(),
+                    Ok(false) => break,
+                    Err(err) => {
+                        tracing::warn!(error = %err, worker = self.name, "unable to renew the worker lease");
+                        if renewed.elapsed() + RENEW_INTERVAL >= LEASE {
+                            break;
+                        }
+                    }
+                }
+            }
+            worker.abort();
+            tracing::warn!(worker = self.name, "worker has been stopped on this gateway");
+        }
+    }
+
+    async fn claim(&self) -> bool {
+        match self.db.claim_worker_lease(self.name, &self.holder, LEASE).await {
+            Ok(claimed) => claimed,
+            Err(err) => {
+                tracing::error!(error = %err, worker = self.name, "unable to claim the worker lease");
+                false
+            }
+        }
+    }
+}
+
+/// Drop blobs removed by the GC of any gateway from the local cache. Blobs removed while the connection
+/// is lost stay cached until they are evicted, they are never served because their objects are gone.
+pub async fn evict_removed_blobs(db: Arc<dyn MetaStore>, cache: Arc<BlobCache>) {
+    loop {
+        let mut events = match db.listen_gateway_events().await {
+            Ok(events) => events,
+            Err(err) => {
+                tracing::error!(error = %err, "unable to listen to gateway events");
+                tokio::time::sleep(RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+        while let Some(event) = events.next().await {
+            match event {
+                Ok(GatewayEvent::BlobRemoved(id)) => cache.evict(&id),
+                Err(err) => {
+                    tracing::warn!(error = %err, "gateway events have been interrupted");
+                    break;
+                }
+            }
+        }
+        tokio::time::sleep(RETRY_INTERVAL).await;
+    }
+}
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::meta_store::{DataMigration, MetaStore, MigrationJob};
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Applies data migrations in the background while the gateway serves requests.
+///
+/// Every migration processes its rows in batches ordered by key. The last key of a batch is saved in
+/// `migration_jobs` so that the job continues from there after a restart, finished jobs are skipped.
+/// Batches are spaced by `batch_interval` to limit the load on the database.
+pub struct MigrationWorker {
+    db: Arc<dyn MetaStore>,
+    migrations: Vec<DataMigration>,
+    batch_size: i64,
+    batch_interval: Duration,
+}
+
+impl MigrationWorker {
+    pub fn new(db: Arc<dyn MetaStore>, migrations: Vec<DataMigration>, batch_size: i64, batch_interval: Duration) -> Self {
+        Self {
+            db,
+            migrations,
+            batch_size,
+            batch_interval,
+        }
+    }
+
+    pub async fn run(self) {
+        for migration in &self.migrations {
+            loop {
+                match self.migrate(migration).await {
+                    Ok(()) => break,
+                    Err(err) => {
+                        tracing::error!(error = %err, migration = migration.name(), "data migration has failed, retrying");
+                        tokio::time::sleep(RETRY_INTERVAL).await;
+                    }
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(migration = migration.name()))]
+    async fn migrate(&self, migration: &DataMigration) -> anyhow::Result<()> {
+        let mut job = match self.db.get_migration_job(migration.name()).await? {
+            Some(job) if job.finished => return Ok(()),
+            Some(job) => {
+                tracing::info!(processed = job.processed, "resuming data migration");
+                job
+            }
+            None => {
+                tracing::info!("starting data migration");
+                MigrationJob {
+                    name: migration.name().to_owned(),
+                    checkpoint: None,
+                    processed: 0,
+                    finished: false,
+                }
+            }
+        };
+
+        let mut interval = tokio::time::interval(self.batch_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let batch = self
+                .db
+                .run_migration_batch(migration, job.checkpoint.as_deref(), self.batch_size)
+                .await?;
+
+            job.processed += batch.processed;
+            if batch.checkpoint.is_some() {
+                job.checkpoint = batch.checkpoint;
+            }
+            job.finished = batch.processed < self.batch_size;
+            self.db.save_migration_job(&job).await?;
+
+            if job.finished {
+                tracing::info!(processed = job.processed, "data migration has finished");
+                return Ok(());
+            }
+        }
+    }
+}
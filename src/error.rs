@@ -1,5 +1,6 @@
 use std::panic::Location;
 
+use s3s::{S3Error, S3ErrorCode};
 use tracing::error;
 
 #[inline]
@@ -16,13 +17,126 @@ pub(crate) fn log(source: &dyn std::error::Error) {
     );
 }
 
+/// Failure to connect to a dependency of the gateway or an invalid configuration at startup
+#[derive(Debug, thiserror::Error)]
+pub enum StartupError {
+    #[error("invalid configuration: {0}")]
+    Config(&'static str),
+    #[error("unable to connect to the ceph cluster: {0}")]
+    Ceph(#[from] ceph::error::RadosError),
+    #[error("unable to connect to the database: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("unable to perform database migrations: {0}")]
+    Migration(#[from] sqlx::migrate::MigrateError),
+    #[error("unable to set up telemetry: {0}")]
+    Telemetry(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+    #[error("unable to open the blob cache: {0}")]
+    Cache(#[source] std::io::Error),
+}
+
+/// Convert an arbitrary error into the closest S3 error.
+///
+/// `S3Error` is passed through untouched, well known database and backend failures are
+/// translated into their S3 counterparts and everything else becomes `InternalError`.
+pub(crate) fn to_s3_error<E>(err: E) -> S3Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let err: Box<dyn std::error::Error + Send + Sync + 'static> = Box::new(err);
+    let err = match err.downcast::<S3Error>() {
+        Ok(err) => return *err,
+        Err(err) => err,
+    };
+
+    let Some((code, message)) = classify(err.as_ref()) else {
+        let mut res = S3Error::new(S3ErrorCode::InternalError);
+        res.set_source(err);
+        return res;
+    };
+
+    let mut res = S3Error::with_message(code, message);
+    res.set_source(err);
+    res
+}
+
+fn classify(err: &(dyn std::error::Error + 'static)) -> Option<(S3ErrorCode, &'static str)> {
+    if let Some(err) = err.downcast_ref::<sqlx::Error>() {
+        return classify_sqlx(err);
+    }
+    if let Some(err) = err.downcast_ref::<ceph::error::RadosError>() {
+        return classify_rados(err);
+    }
+    if let Some(err) = err.downcast_ref::<std::io::Error>() {
+        return classify_io(err.kind());
+    }
+    None
+}
+
+/// Whether the failed transaction may succeed when it is run again: a serialization failure or a deadlock.
+/// Constraint violations and other errors fail the same way again.
+pub(crate) fn is_retryable(err: &S3Error) -> bool {
+    let Some(sqlx::Error::Database(err)) = err.source().and_then(|e| e.downcast_ref::<sqlx::Error>()) else {
+        return false;
+    };
+    matches!(err.code().as_deref(), Some("40001") | Some("40P01"))
+}
+
+fn classify_sqlx(err: &sqlx::Error) -> Option<(S3ErrorCode, &'static str)> {
+    match err {
+        sqlx::Error::Database(err) => {
+            if err.is_unique_violation() {
+                return match err.constraint() {
+                    Some("buckets_pkey") => {
+                        Some((S3ErrorCode::BucketAlreadyExists, "The requested bucket name is not available"))
+                    }
+                    _ => Some((
+                        S3ErrorCode::OperationAborted,
+                        "A conflicting operation is currently in progress against this resource",
+                    )),
+                };
+            }
+
+            // serialization_failure and deadlock_detected are safe to retry
+            match err.code().as_deref() {
+                Some("40001") | Some("40P01") => Some((S3ErrorCode::ServiceUnavailable, "Please retry the request")),
+                _ => None,
+            }
+        }
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => {
+            Some((S3ErrorCode::ServiceUnavailable, "Metadata storage is unavailable"))
+        }
+        _ => None,
+    }
+}
+
+fn classify_rados(err: &ceph::error::RadosError) -> Option<(S3ErrorCode, &'static str)> {
+    match err {
+        ceph::error::RadosError::ApiError(errno) => classify_io(std::io::Error::from_raw_os_error(*errno as i32).kind()),
+        ceph::error::RadosError::IoError(err) => classify_io(err.kind()),
+        _ => None,
+    }
+}
+
+fn classify_io(kind: std::io::ErrorKind) -> Option<(S3ErrorCode, &'static str)> {
+    match kind {
+        std::io::ErrorKind::NotFound => Some((S3ErrorCode::NoSuchKey, "The specified key does not exist")),
+        // the gateway credentials were rejected by the backend
+        std::io::ErrorKind::PermissionDenied => Some((
+            S3ErrorCode::InvalidAccessKeyId,
+            "The gateway is not allowed to access the storage backend",
+        )),
+        std::io::ErrorKind::TimedOut => Some((S3ErrorCode::ServiceUnavailable, "Storage backend timed out")),
+        _ => None,
+    }
+}
+
 macro_rules! try_ {
     ($result:expr) => {
         match $result {
             Ok(val) => val,
             Err(err) => {
                 crate::error::log(&err);
-                return Err(::s3s::S3Error::internal_error(err));
+                return Err(crate::error::to_s3_error(err));
             }
         }
     };
@@ -0,0 +1,133 @@
+use std::panic::AssertUnwindSafe;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hyper::service::Service;
+use hyper::{header, Method, Request, Response, StatusCode};
+use s3s::{S3Error, S3ErrorCode};
+
+use crate::post_policy::{error_response, xml_escape};
+
+const REQUEST_ID_HEADER: &str = "x-amz-request-id";
+const INTERNAL_ERROR_MESSAGE: &str = "We encountered an internal error. Please try again.";
+
+/// Tags every response with a request id and completes error responses the way S3 does.
+///
+/// Error bodies get the `Resource` and `RequestId` elements, internal errors without a message get the
+/// generic one and errors without a body get an XML body built from the status. A panic of a handler is
+/// answered with `InternalError` instead of a dropped connection. The request id is logged with the panic
+/// so that the report of a client can be matched with the log.
+#[derive(Clone)]
+pub struct ErrorDetails<S> {
+    inner: S,
+}
+
+impl<S> ErrorDetails<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for ErrorDetails<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let request_id = format!("{:016X}", fastrand::u64(..));
+        let resource = req.uri().path().to_owned();
+        let head = req.method() == Method::HEAD;
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(res) => res?,
+                Err(panic) => {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .copied()
+                        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                        .unwrap_or_default();
+                    tracing::error!(request_id, resource, panic = message, "request handler has panicked");
+                    let mut err = S3Error::with_message(S3ErrorCode::InternalError, INTERNAL_ERROR_MESSAGE);
+                    err.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                    error_response(&err)
+                }
+            };
+
+            let mut res = if !head && (res.status().is_client_error() || res.status().is_server_error()) {
+                complete(res, &resource, &request_id).await
+            } else {
+                res
+            };
+            let value = header::HeaderValue::from_str(&request_id).expect("hex digits are a valid header value");
+            res.headers_mut().insert(REQUEST_ID_HEADER, value);
+            Ok(res)
+        })
+    }
+}
+
+/// Add the elements which are missing in the error body
+async fn complete(res: Response<s3s::Body>, resource: &str, request_id: &str) -> Response<s3s::Body> {
+    let (mut parts, body) = res.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => {
+            tracing::debug!(error = %err, "unable to read the error response");
+            return Response::from_parts(parts, s3s::Body::empty());
+        }
+    };
+
+    let mut text = if body.is_empty() {
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/xml"));
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code></Error>",
+            status_code(parts.status)
+        )
+    } else {
+        match String::from_utf8(body.to_vec()) {
+            Ok(text) if text.contains("</Error>") => text,
+            // not an S3 error, e.g. a plain text rejection of hyper
+            _ => return Response::from_parts(parts, s3s::Body::from(body)),
+        }
+    };
+
+    let mut details = String::new();
+    if !text.contains("<Message>") && text.contains("<Code>InternalError</Code>") {
+        details.push_str(&format!("<Message>{INTERNAL_ERROR_MESSAGE}</Message>"));
+    }
+    if !text.contains("<Resource>") {
+        details.push_str(&format!("<Resource>{}</Resource>", xml_escape(resource)));
+    }
+    if !text.contains("<RequestId>") {
+        details.push_str(&format!("<RequestId>{request_id}</RequestId>"));
+    }
+    details.push_str("</Error>");
+    text = text.replacen("</Error>", &details, 1);
+
+    parts.headers.insert(header::CONTENT_LENGTH, text.len().into());
+    Response::from_parts(parts, s3s::Body::from(text))
+}
+
+/// Error code of a response which has no body
+fn status_code(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::FORBIDDEN => "AccessDenied",
+        StatusCode::NOT_FOUND => "NotFound",
+        StatusCode::METHOD_NOT_ALLOWED => "MethodNotAllowed",
+        StatusCode::NOT_IMPLEMENTED => "NotImplemented",
+        StatusCode::SERVICE_UNAVAILABLE => "ServiceUnavailable",
+        status if status.is_client_error() => "InvalidRequest",
+        _ => "InternalError",
+    }
+}
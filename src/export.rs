@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::StreamExt;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{Blob, ListOptions, MetaStore, Object};
+use crate::tiering::{self, ColdTier};
+
+const PAGE_SIZE: u64 = 1000;
+
+/// Copies the objects of a bucket to an external store under their own keys, e.g. to move the data
+/// away from the gateway or to keep a copy which is readable without the metadata database.
+///
+/// Multipart objects are written as a single backend object. Existing keys in the target are replaced.
+pub struct Exporter {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    target: Arc<dyn BlobStore>,
+    cold: Option<ColdTier>,
+    concurrency: usize,
+}
+
+/// Progress of an export which is reported through the admin API while it runs
+#[derive(Debug, Default)]
+pub struct ExportProgress {
+    pub exported: AtomicU64,
+    pub exported_bytes: AtomicU64,
+    pub failed: AtomicU64,
+    pub finished: AtomicBool,
+}
+
+impl ExportProgress {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "exported": self.exported.load(Ordering::Relaxed),
+            "exported_bytes": self.exported_bytes.load(Ordering::Relaxed),
+            "failed": self.failed.load(Ordering::Relaxed),
+            "finished": self.finished.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Exporter {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>, target: Arc<dyn BlobStore>, concurrency: usize) -> Self {
+        Self {
+            db,
+            blob,
+            target,
+            cold: None,
+            concurrency,
+        }
+    }
+
+    pub fn with_cold_tier(mut self, cold: Option<ColdTier>) -> Self {
+        self.cold = cold;
+        self
+    }
+
+    #[tracing::instrument(level = "info", skip(self, progress))]
+    pub async fn run(self, bucket: String, prefix: Option<String>, progress: Arc<ExportProgress>) -> anyhow::Result<()> {
+        let res = self.export_all(&bucket, &prefix, &progress).await;
+        progress.finished.store(true, Ordering::Relaxed);
+        res
+    }
+
+    async fn export_all(&self, bucket: &str, prefix: &Option<String>, progress: &ExportProgress) -> anyhow::Result<()> {
+        let mut marker = None;
+        loop {
+            let page = self
+                .db
+                .list_objects(ListOptions {
+                    bucket,
+                    prefix,
+                    delim: "",
+                    marker: &marker,
+                    max_keys: PAGE_SIZE,
+                    with_versions: false,
+                    version_marker: None,
+                })
+                .await?;
+
+            futures::stream::iter(&page.objects)
+                .for_each_concurrent(self.concurrency, |(object, blob)| async move {
+                    let Some(blob) = blob else {
+                        return;
+                    };
+                    match self.export(object, blob).await {
+                        Ok(()) => {
+                            progress.exported.fetch_add(1, Ordering::Relaxed);
+                            progress.exported_bytes.fetch_add(blob.size as u64, Ordering::Relaxed);
+                        }
+                        Err(err) => {
+                            tracing::error!(error = %err, key = object.oid, "unable to export object");
+                            progress.failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                })
+                .await;
+
+            if page.marker.is_none() {
+                return Ok(());
+            }
+            marker = page.marker;
+        }
+    }
+
+    async fn export(&self, object: &Object, blob: &Blob) -> anyhow::Result<()> {
+        let size = blob.size as u64;
+        let store = tiering::blob_store(&self.blob, self.cold.as_ref(), blob.storage_class.as_deref())?;
+        let parts = self.db.get_blob_parts(&blob.id).await?;
+        let mut reader = if parts.is_empty() {
+            store.get_reader(&blob.id.to_string(), 0, size).await?
+        } else {
+            let parts = parts.iter().map(|p| (p.blob_id.to_string(), p.size as u64)).collect();
+            store.get_parts_reader(parts, 0, size).await?
+        };
+
+        // writers do not truncate, a longer previous object would leave its tail behind
+        self.target.delete(&object.oid).await?;
+        let mut writer = self.target.get_writer(&object.oid).await?;
+        while let Some(chunk) = reader.next().await {
+            writer.write_all(&chunk?).await?;
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,221 @@
+//! Fault injection for chaos tests of the compensation logic, built with the `fault-injection` feature only.
+//!
+//! Faults are configured per operation: database queries by their span name (e.g. `db_insert_bucket_info`)
+//! and blob store calls as `blob_<method>` (e.g. `blob_write`). The configuration is global to the process
+//! and changed through the admin API.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::io::AsyncWrite;
+use tokio::time::Sleep;
+
+use crate::blob_store::BlobStore;
+
+#[derive(Debug, Clone, Default)]
+pub struct Fault {
+    /// delay before the operation starts
+    pub latency: Duration,
+    /// share of the calls which fail, from 0 to 1
+    pub error_rate: f64,
+    /// failing writes accept this many bytes before the error instead of failing at once
+    pub fail_after_bytes: Option<u64>,
+}
+
+#[derive(Default)]
+pub struct FaultInjector {
+    faults: Mutex<HashMap<String, Fault>>,
+}
+
+impl FaultInjector {
+    pub fn set(&self, operation: &str, fault: Fault) {
+        self.faults
+            .lock()
+            .expect("unable to lock mutex")
+            .insert(operation.to_owned(), fault);
+    }
+
+    pub fn clear(&self) {
+        self.faults.lock().expect("unable to lock mutex").clear();
+    }
+
+    pub fn list(&self) -> Vec<(String, Fault)> {
+        let faults = self.faults.lock().expect("unable to lock mutex");
+        faults.iter().map(|(op, fault)| (op.clone(), fault.clone())).collect()
+    }
+
+    /// Delay of the call and whether it fails
+    fn decide(&self, operation: &str) -> Option<(Fault, bool)> {
+        let faults = self.faults.lock().expect("unable to lock mutex");
+        let fault = faults.get(operation)?;
+        let fail = fault.error_rate > 0.0 && fastrand::f64() < fault.error_rate;
+        Some((fault.clone(), fail))
+    }
+}
+
+pub fn faults() -> &'static FaultInjector {
+    static FAULTS: OnceLock<FaultInjector> = OnceLock::new();
+    FAULTS.get_or_init(FaultInjector::default)
+}
+
+fn injected(operation: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, format!("injected fault of {operation}"))
+}
+
+fn injected_s3(operation: &str) -> s3s::S3Error {
+    s3s::S3Error::with_source(s3s::S3ErrorCode::InternalError, Box::new(injected(operation)))
+}
+
+pin_project_lite::pin_project! {
+    /// Database query which is delayed or fails with an I/O error instead of being sent
+    pub struct FaultyQuery<F> {
+        #[pin]
+        inner: F,
+        #[pin]
+        delay: Option<Sleep>,
+        operation: &'static str,
+        fail: bool,
+    }
+}
+
+impl<F> FaultyQuery<F> {
+    pub fn new(inner: F, operation: &'static str) -> Self {
+        let (delay, fail) = match faults().decide(operation) {
+            Some((fault, fail)) => ((!fault.latency.is_zero()).then(|| tokio::time::sleep(fault.latency)), fail),
+            None => (None, false),
+        };
+        Self {
+            inner,
+            delay,
+            operation,
+            fail,
+        }
+    }
+}
+
+impl<F, T> Future for FaultyQuery<F>
+where
+    F: Future<Output = Result<T, sqlx::Error>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+            ready!(delay.poll(cx));
+            this.delay.set(None);
+        }
+        if *this.fail {
+            return Poll::Ready(Err(sqlx::Error::Io(injected(this.operation))));
+        }
+        this.inner.poll(cx)
+    }
+}
+
+type BlobReader = Pin<Box<dyn Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>;
+
+/// Blob store whose calls are delayed or fail according to the configured faults
+#[derive(Debug)]
+pub struct FaultyBlobStore {
+    inner: std::sync::Arc<dyn BlobStore>,
+}
+
+impl FaultyBlobStore {
+    pub fn new(inner: std::sync::Arc<dyn BlobStore>) -> Self {
+        Self { inner }
+    }
+
+    /// Err if the call fails, the fault is returned for partial failures of writes
+    async fn inject(&self, operation: &'static str) -> Result<Option<Fault>, s3s::S3Error> {
+        let Some((fault, fail)) = faults().decide(operation) else {
+            return Ok(None);
+        };
+        tokio::time::sleep(fault.latency).await;
+        match (fail, fault.fail_after_bytes) {
+            (false, _) => Ok(None),
+            (true, Some(_)) => Ok(Some(fault)),
+            (true, None) => Err(injected_s3(operation)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for FaultyBlobStore {
+    async fn get_writer(&self, key: &str) -> Result<Pin<Box<dyn AsyncWrite + Send>>, s3s::S3Error> {
+        self.inject("blob_get_writer").await?;
+        let writer = self.inner.get_writer(key).await?;
+        // the decision is taken per writer, partial failures cut the data at a fixed offset
+        match self.inject("blob_write").await {
+            Ok(None) => Ok(writer),
+            Ok(Some(fault)) => Ok(Box::pin(FaultyWriter {
+                inner: writer,
+                remaining: fault.fail_after_bytes.unwrap_or_default(),
+            })),
+            Err(_) => Ok(Box::pin(FaultyWriter {
+                inner: writer,
+                remaining: 0,
+            })),
+        }
+    }
+
+    async fn get_reader(&self, key: &str, offset: u64, length: u64) -> Result<BlobReader, s3s::S3Error> {
+        self.inject("blob_get_reader").await?;
+        self.inner.get_reader(key, offset, length).await
+    }
+
+    async fn get_parts_reader(&self, parts: Vec<(String, u64)>, offset: u64, length: u64) -> Result<BlobReader, s3s::S3Error> {
+        self.inject("blob_get_parts_reader").await?;
+        self.inner.get_parts_reader(parts, offset, length).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), s3s::S3Error> {
+        self.inject("blob_delete").await?;
+        self.inner.delete(key).await
+    }
+
+    async fn list(&self) -> Result<Vec<String>, s3s::S3Error> {
+        self.inject("blob_list").await?;
+        self.inner.list().await
+    }
+
+    async fn stat(&self, key: &str) -> Result<(u64, std::time::SystemTime), s3s::S3Error> {
+        self.inject("blob_stat").await?;
+        self.inner.stat(key).await
+    }
+
+    async fn health_check(&self) -> Result<(), s3s::S3Error> {
+        self.inject("blob_health_check").await?;
+        self.inner.health_check().await
+    }
+}
+
+/// Passes `remaining` bytes to the backend and fails afterwards
+struct FaultyWriter {
+    inner: Pin<Box<dyn AsyncWrite + Send>>,
+    remaining: u64,
+}
+
+impl AsyncWrite for FaultyWriter {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        if self.remaining == 0 {
+            return Poll::Ready(Err(injected("blob_write")));
+        }
+        let len = buf.len().min(self.remaining as usize);
+        let written = ready!(self.inner.as_mut().poll_write(cx, &buf[..len]))?;
+        self.remaining -= written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.inner.as_mut().poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{GcTask, MetaStore};
+use crate::tiering::{self, ColdTier};
+
+const IDLE_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 100;
+/// a batch must be finished before the lease expires, otherwise another worker removes the blobs again
+const LEASE: Duration = Duration::from_secs(300);
+/// delay before the first retry of a failed blob, it grows with every attempt
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Removes deleted and overwritten blobs from the backend.
+///
+/// Blobs stay in `blobs_gc` until their trash retention expires so that objects can be undeleted
+/// through the admin API. The metadata row is dropped only after the backend objects are gone.
+///
+/// Several workers, also of different gateways, share the queue: every worker leases a batch of blobs
+/// and the database skips the rows leased by the others. A blob which fails `max_attempts` times
+/// becomes a dead letter until it is requeued through the admin API.
+pub struct GarbageCollector {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    cold: Option<ColdTier>,
+    workers: usize,
+    max_attempts: i32,
+}
+
+impl GarbageCollector {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>, workers: usize, max_attempts: i32) -> Self {
+        Self {
+            db,
+            blob,
+            cold: None,
+            workers,
+            max_attempts,
+        }
+    }
+
+    pub fn with_cold_tier(mut self, cold: Option<ColdTier>) -> Self {
+        self.cold = cold;
+        self
+    }
+
+    pub async fn run(self) {
+        let this = Arc::new(self);
+        futures::future::join_all((0..this.workers).map(|worker| this.clone().work(worker))).await;
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn work(self: Arc<Self>, worker: usize) {
+        loop {
+            let tasks = match self.db.claim_blob_gc(BATCH_SIZE, LEASE).await {
+                Ok(tasks) => tasks,
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to claim expired blobs");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            if tasks.is_empty() {
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            for task in tasks {
+                match self.collect(&task).await {
+                    Ok(()) => crate::telemetry::metrics().gc_removed.add(1, &[]),
+                    Err(err) => {
+                        crate::telemetry::metrics().gc_failed.add(1, &[]);
+                        let dead = task.attempts >= self.max_attempts;
+                        if dead {
+                            tracing::error!(error = %err, blob = %task.blob_id, attempts = task.attempts, "unable to remove blob, giving up");
+                        } else {
+                            tracing::warn!(error = %err, blob = %task.blob_id, attempts = task.attempts, "unable to remove blob");
+                        }
+                        let retry_after = RETRY_INTERVAL * task.attempts.max(1) as u32;
+                        if let Err(err) = self.db.fail_blob_gc(&task.blob_id, dead, retry_after).await {
+                            tracing::error!(error = %err, blob = %task.blob_id, "unable to release the blob lease");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn collect(&self, task: &GcTask) -> anyhow::Result<()> {
+        let store = tiering::blob_store(&self.blob, self.cold.as_ref(), task.storage_class.as_deref())?;
+        let parts = self.db.get_blob_parts(&task.blob_id).await?;
+        if parts.is_empty() {
+            store.delete(&task.blob_id.to_string()).await?;
+        }
+
+        // multipart blobs are stored as separate backend objects
+        for part in parts {
+            store.delete(&part.blob_id.to_string()).await?;
+        }
+
+        self.db.remove_blob_gc(&task.blob_id).await
+    }
+}
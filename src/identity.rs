@@ -0,0 +1,275 @@
+//! Sources of access keys.
+//!
+//! A key is looked up in the configured providers in order, the first provider which knows the key wins. Keys of
+//! the runtime config are always checked first-hand, results of the database and of external providers, including
+//! unknown keys, are cached for a while so that a signed request does not cost a round trip to the identity service.
+//!
+//! Keys of an external provider are linked to an existing user in the `keys` table (without the secret), so that
+//! ownership, policies and requester-pays checks work for them like for keys of the database.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode, Uri};
+use s3s::auth::{S3Auth, SecretKey};
+use s3s::{s3_error, S3Error, S3Result};
+use serde_json::Value;
+
+use crate::auth::ConfigAuth;
+use crate::meta_store::MetaStore;
+
+const INTROSPECTION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bound of the cache, unknown keys are cached too and may be chosen by anyone
+const MAX_CACHED_KEYS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProviderKind {
+    /// access_key and secret_key of the runtime config or the command line
+    Config,
+    /// the `keys` table
+    Database,
+    /// OAuth 2.0 token introspection (RFC 7662) of an OIDC provider
+    Introspection,
+    /// EC2 credentials of OpenStack Keystone
+    Keystone,
+}
+
+pub struct Credential {
+    pub secret_key: String,
+    /// user the key belongs to, set by external providers only
+    pub user: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait CredentialProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether results may be cached, false for providers which are cheaper than the cache
+    fn cacheable(&self) -> bool {
+        true
+    }
+
+    /// None if the key is not known to the provider
+    async fn resolve(&self, access_key: &str) -> S3Result<Option<Credential>>;
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for ConfigAuth {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn cacheable(&self) -> bool {
+        false
+    }
+
+    async fn resolve(&self, access_key: &str) -> S3Result<Option<Credential>> {
+        match self.get_secret_key(access_key).await {
+            Ok(secret_key) => Ok(Some(Credential {
+                secret_key: secret_key.expose().to_owned(),
+                user: None,
+            })),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+/// Keys of the `keys` table
+pub struct DatabaseKeys {
+    db: Arc<dyn MetaStore>,
+}
+
+impl DatabaseKeys {
+    pub fn new(db: Arc<dyn MetaStore>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for DatabaseKeys {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn resolve(&self, access_key: &str) -> S3Result<Option<Credential>> {
+        let secret_key = self.db.get_secret_key(access_key).await?;
+        Ok(secret_key.map(|secret_key| Credential { secret_key, user: None }))
+    }
+}
+
+/// Service accounts of an OIDC provider.
+///
+/// The access key is sent as the token to the introspection endpoint, authenticated with the client credentials
+/// of the gateway. An active response must carry the user id in `sub` and the S3 secret in `secret_key`, e.g.
+/// `{"active": true, "sub": "alice", "secret_key": "..."}`. Only plain HTTP endpoints are supported, e.g. a
+/// relay next to the gateway.
+pub struct Introspection {
+    endpoint: Uri,
+    /// `Basic` authorization of the gateway as an OAuth client
+    authorization: Option<String>,
+    client: Client<HttpConnector>,
+}
+
+impl Introspection {
+    pub fn new(endpoint: Uri, client_id: Option<String>, client_secret: Option<String>) -> Self {
+        let authorization = client_id.map(|id| {
+            let credentials = format!("{id}:{}", client_secret.unwrap_or_default());
+            format!("Basic {}", base64_simd::STANDARD.encode_to_string(credentials))
+        });
+        Self {
+            endpoint,
+            authorization,
+            client: Client::new(),
+        }
+    }
+
+    async fn introspect(&self, access_key: &str) -> anyhow::Result<Value> {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("token", access_key)
+            .append_pair("token_type_hint", "s3_access_key")
+            .finish();
+        let mut req = Request::builder()
+            .method(Method::POST)
+            .uri(&self.endpoint)
+            .header(hyper::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(hyper::header::ACCEPT, "application/json");
+        if let Some(authorization) = &self.authorization {
+            req = req.header(hyper::header::AUTHORIZATION, authorization);
+        }
+        let req = req.body(Body::from(body))?;
+
+        let res = tokio::time::timeout(INTROSPECTION_TIMEOUT, self.client.request(req)).await??;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!("introspection endpoint has responded with {}", res.status());
+        }
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for Introspection {
+    fn name(&self) -> &'static str {
+        "introspection"
+    }
+
+    async fn resolve(&self, access_key: &str) -> S3Result<Option<Credential>> {
+        let res = match self.introspect(access_key).await {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::error!(error = %err, "unable to introspect an access key");
+                return Err(s3_error!(ServiceUnavailable, "Identity provider is unavailable"));
+            }
+        };
+        if res.get("active").and_then(Value::as_bool) != Some(true) {
+            return Ok(None);
+        }
+        let field = |name: &str| res.get(name).and_then(Value::as_str).filter(|v| !v.is_empty());
+        let (Some(user), Some(secret_key)) = (field("sub"), field("secret_key")) else {
+            tracing::warn!("active introspection response without sub or secret_key");
+            return Ok(None);
+        };
+        Ok(Some(Credential {
+            secret_key: secret_key.to_owned(),
+            user: Some(user.to_owned()),
+        }))
+    }
+}
+
+struct CachedKey {
+    secret_key: Option<String>,
+    expires_at: Instant,
+}
+
+/// Resolves keys through a chain of providers
+pub struct ProviderAuth {
+    providers: Vec<Arc<dyn CredentialProvider>>,
+    db: Arc<dyn MetaStore>,
+    ttl: Duration,
+    cache: Mutex<HashMap<String, CachedKey>>,
+}
+
+impl ProviderAuth {
+    pub fn new(providers: Vec<Arc<dyn CredentialProvider>>, db: Arc<dyn MetaStore>, ttl: Duration) -> Self {
+        Self {
+            providers,
+            db,
+            ttl,
+            cache: Mutex::default(),
+        }
+    }
+
+    fn cached(&self, access_key: &str) -> Option<Option<String>> {
+        let cache = self.cache.lock().expect("unable to lock mutex");
+        cache
+            .get(access_key)
+            .filter(|cached| cached.expires_at > Instant::now())
+            .map(|cached| cached.secret_key.clone())
+    }
+
+    fn store(&self, access_key: &str, secret_key: Option<String>) {
+        let mut cache = self.cache.lock().expect("unable to lock mutex");
+        if cache.len() >= MAX_CACHED_KEYS {
+            let now = Instant::now();
+            cache.retain(|_, cached| cached.expires_at > now);
+            if cache.len() >= MAX_CACHED_KEYS {
+                cache.clear();
+            }
+        }
+        let expires_at = Instant::now() + self.ttl;
+        cache.insert(access_key.to_owned(), CachedKey { secret_key, expires_at });
+    }
+
+    /// The first provider of the chain which knows the key
+    async fn resolve(&self, providers: &[Arc<dyn CredentialProvider>], access_key: &str) -> S3Result<Option<String>> {
+        for provider in providers {
+            let Some(credential) = provider.resolve(access_key).await? else {
+                continue;
+            };
+            if let Some(user) = &credential.user {
+                if !self.db.link_external_key(access_key, user, provider.name()).await? {
+                    tracing::warn!(
+                        provider = provider.name(),
+                        user,
+                        "key of an external provider can not be linked to a user"
+                    );
+                    return Ok(None);
+                }
+            }
+            return Ok(Some(credential.secret_key));
+        }
+        Ok(None)
+    }
+}
+
+#[async_trait::async_trait]
+impl S3Auth for ProviderAuth {
+    async fn get_secret_key(&self, access_key: &str) -> S3Result<SecretKey> {
+        for (i, provider) in self.providers.iter().enumerate() {
+            if !provider.cacheable() {
+                if let Some(credential) = provider.resolve(access_key).await? {
+                    return Ok(credential.secret_key.into());
+                }
+                continue;
+            }
+
+            // the cache holds the result of the rest of the chain, errors are not cached
+            let secret_key = match self.cached(access_key) {
+                Some(secret_key) => secret_key,
+                None => {
+                    let secret_key = self.resolve(&self.providers[i..], access_key).await?;
+                    self.store(access_key, secret_key.clone());
+                    secret_key
+                }
+            };
+            return secret_key.map(Into::into).ok_or_else(not_signed_up);
+        }
+        Err(not_signed_up())
+    }
+}
+
+fn not_signed_up() -> S3Error {
+    s3_error!(NotSignedUp, "Your account is not signed up")
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use md5::{Digest, Md5};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{Blob, Bucket, ContentHeaders, MetaStore, Object};
+
+/// Makes objects which have been written to a backend outside of the gateway available through it.
+///
+/// Every key of the source store becomes an object of the bucket with the same size and modification
+/// time. Blobs are named by the gateway, so the data is copied inside the backend instead of being
+/// uploaded again. Keys which already exist in the bucket are skipped, so an interrupted import can be
+/// started again.
+pub struct Importer {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    source: Arc<dyn BlobStore>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportStats {
+    pub imported: u64,
+    pub skipped: u64,
+    pub failed: u64,
+}
+
+impl Importer {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>, source: Arc<dyn BlobStore>) -> Self {
+        Self { db, blob, source }
+    }
+
+    #[tracing::instrument(level = "info", skip(self, bucket), fields(bucket = %bucket.name))]
+    pub async fn run(self, bucket: Bucket, prefix: String) -> anyhow::Result<ImportStats> {
+        let keys = self.source.list().await?;
+        let mut stats = ImportStats::default();
+        for key in keys.iter().filter(|k| k.starts_with(&prefix)) {
+            match self.import(&bucket, key).await {
+                Ok(true) => stats.imported += 1,
+                Ok(false) => stats.skipped += 1,
+                Err(err) => {
+                    tracing::error!(error = %err, key, "unable to import object");
+                    stats.failed += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Returns false if the object already exists
+    async fn import(&self, bucket: &Bucket, key: &str) -> anyhow::Result<bool> {
+        if self.db.load_object_metadata(&bucket.name, key, &None).await?.is_some() {
+            return Ok(false);
+        }
+
+        let (size, modified) = self.source.stat(key).await?;
+        let modified = time::OffsetDateTime::from(modified);
+        let mut blob = Blob {
+            id: Uuid::new_v4(),
+            size: size as i64,
+            parts: None,
+            part_size: None,
+            upload_timestamp: modified,
+            storage_class: None,
+            etag: String::default(),
+            checksum: None,
+        };
+        self.db.write_temp_blob(&blob).await?;
+
+        let res: anyhow::Result<bool> = async {
+            blob.etag = self.copy(key, size, &blob.id).await?;
+            let object = Object {
+                bucket_name: bucket.name.clone(),
+                oid: key.to_owned(),
+                version_id: None,
+                last_modified: blob.upload_timestamp,
+                blob_id: Some(blob.id),
+                metadata: None,
+                tagging: None,
+                replication_status: None,
+                last_accessed: None,
+                content_headers: ContentHeaders::default(),
+            };
+            Ok(self.db.import_object(&object, &blob).await?)
+        }
+        .await;
+
+        match res {
+            Ok(true) => Ok(true),
+            // created by a client in the meantime
+            Ok(false) => {
+                self.db.clean_temp_blob(&blob).await;
+                Ok(false)
+            }
+            Err(err) => {
+                self.db.clean_temp_blob(&blob).await;
+                Err(err)
+            }
+        }
+    }
+
+    /// Copy the data into the blob, returns the MD5 of the data which is the etag of a regular upload
+    async fn copy(&self, key: &str, size: u64, blob_id: &Uuid) -> anyhow::Result<String> {
+        let mut reader = self.source.get_reader(key, 0, size).await?;
+        let mut writer = self.blob.get_writer(&blob_id.to_string()).await?;
+        let mut md5_hash = <Md5 as Digest>::new();
+        let mut copied = 0;
+        while let Some(chunk) = reader.next().await {
+            let chunk = chunk?;
+            md5_hash.update(&chunk);
+            copied += chunk.len() as u64;
+            writer.write_all(&chunk).await?;
+        }
+        writer.flush().await?;
+        if copied != size {
+            anyhow::bail!("source object has {copied} bytes instead of {size}");
+        }
+        Ok(hex_simd::encode_to_string(md5_hash.finalize(), hex_simd::AsciiCase::Lower))
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use md5::{Digest, Md5};
+use serde_json::json;
+use time::format_description::well_known::Rfc3339;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::blob_store::BlobStore;
+use crate::listing::ObjectLister;
+use crate::meta_store::{Blob, Bucket, ContentHeaders, InventoryTask, MetaStore, Object, Timestamp};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+const PAGE_SIZE: u64 = 1000;
+
+/// Periodically dumps object listings of buckets with an inventory configuration
+/// into the destination bucket.
+///
+/// Each report consists of a CSV data file and a `manifest.json` which follow the layout used by AWS:
+/// `<prefix>/<source bucket>/<inventory id>/data/<uuid>.csv` and
+/// `<prefix>/<source bucket>/<inventory id>/<YYYY-MM-DDTHH-MMZ>/manifest.json`.
+pub struct InventoryWorker {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+}
+
+impl InventoryWorker {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>) -> Self {
+        Self { db, blob }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.db.claim_inventory_tasks().await {
+                Ok(tasks) => {
+                    for task in tasks {
+                        match self.generate(&task).await {
+                            Ok(()) => {
+                                tracing::info!(bucket = %task.bucket, id = %task.config.id, "inventory report has been generated")
+                            }
+                            Err(err) => {
+                                tracing::error!(error = %err, bucket = %task.bucket, id = %task.config.id, "unable to generate inventory report")
+                            }
+                        }
+                    }
+                }
+                Err(err) => tracing::error!(error = %err, "unable to fetch inventory tasks"),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn generate(&self, task: &InventoryTask) -> anyhow::Result<()> {
+        let Some(destination) = self.db.get_bucket_metadata(&task.config.destination_bucket).await? else {
+            anyhow::bail!("destination bucket {} does not exist", task.config.destination_bucket);
+        };
+
+        let root = match &task.config.destination_prefix {
+            Some(prefix) if !prefix.is_empty() => format!("{}/{}/{}", prefix.trim_end_matches('/'), task.bucket, task.config.id),
+            _ => format!("{}/{}", task.bucket, task.config.id),
+        };
+
+        let data_key = format!("{root}/data/{}.csv", Uuid::new_v4());
+        let data = self.write_report(task, &destination, &data_key).await?;
+
+        let started_at = task.started_at;
+        let manifest = json!({
+            "sourceBucket": task.bucket,
+            "destinationBucket": format!("arn:aws:s3:::{}", task.config.destination_bucket),
+            "version": "2016-11-30",
+            "creationTimestamp": (started_at.unix_timestamp() * 1000).to_string(),
+            "fileFormat": "CSV",
+            "fileSchema": "Bucket, Key, Size, LastModifiedDate, ETag, StorageClass",
+            "files": [{
+                "key": data_key,
+                "size": data.size,
+                "MD5checksum": data.etag,
+            }],
+        });
+        let manifest_key = format!(
+            "{root}/{:04}-{:02}-{:02}T{:02}-{:02}Z/manifest.json",
+            started_at.year(),
+            started_at.month() as u8,
+            started_at.day(),
+            started_at.hour(),
+            started_at.minute()
+        );
+        write_object(
+            self.db.as_ref(),
+            self.blob.as_ref(),
+            &destination,
+            &manifest_key,
+            manifest.to_string().as_bytes(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Stream the listing of the source bucket into a new object
+    async fn write_report(&self, task: &InventoryTask, destination: &Bucket, key: &str) -> anyhow::Result<Blob> {
+        let mut blob = new_blob(self.db.as_ref()).await?;
+        let res: anyhow::Result<()> = async {
+            let mut writer = self.blob.get_writer(&blob.id.to_string()).await?;
+            let mut md5_hash = <Md5 as Digest>::new();
+
+            let mut lister = ObjectLister::new(self.db.as_ref(), &task.bucket, &task.config.prefix, "");
+            while let Some(page) = lister.next_page(PAGE_SIZE).await? {
+                let mut chunk = String::new();
+                for (object, object_blob) in &page.objects {
+                    let Some(object_blob) = object_blob else {
+                        continue;
+                    };
+                    chunk.push_str(&csv_row(&task.bucket, object, object_blob)?);
+                }
+                md5_hash.update(chunk.as_bytes());
+                blob.size += chunk.len() as i64;
+                writer.write_all(chunk.as_bytes()).await?;
+            }
+
+            writer.flush().await?;
+            blob.etag = hex_simd::encode_to_string(md5_hash.finalize(), hex_simd::AsciiCase::Lower);
+            Ok(())
+        }
+        .await;
+
+        commit(self.db.as_ref(), destination, key, blob, res).await
+    }
+}
+
+/// Store `data` as an object of the destination bucket
+pub(crate) async fn write_object(
+    db: &dyn MetaStore,
+    blob_store: &dyn BlobStore,
+    destination: &Bucket,
+    key: &str,
+    data: &[u8],
+) -> anyhow::Result<Blob> {
+    let mut blob = new_blob(db).await?;
+    let res: anyhow::Result<()> = async {
+        let mut writer = blob_store.get_writer(&blob.id.to_string()).await?;
+        writer.write_all(data).await?;
+        writer.flush().await?;
+        blob.size = data.len() as i64;
+        blob.etag = hex_simd::encode_to_string(Md5::digest(data), hex_simd::AsciiCase::Lower);
+        Ok(())
+    }
+    .await;
+
+    commit(db, destination, key, blob, res).await
+}
+
+async fn new_blob(db: &dyn MetaStore) -> anyhow::Result<Blob> {
+    let blob = Blob {
+        id: Uuid::new_v4(),
+        size: 0,
+        parts: None,
+        part_size: None,
+        upload_timestamp: Timestamp::UNIX_EPOCH,
+        storage_class: None,
+        etag: String::default(),
+        checksum: None,
+    };
+    db.write_temp_blob(&blob).await?;
+    Ok(blob)
+}
+
+/// Attach the written blob to the object or clean it up if writing has failed
+async fn commit(
+    db: &dyn MetaStore,
+    destination: &Bucket,
+    key: &str,
+    blob: Blob,
+    res: anyhow::Result<()>,
+) -> anyhow::Result<Blob> {
+    let res = match res {
+        Ok(()) => {
+            let object = Object {
+                bucket_name: destination.name.clone(),
+                oid: key.to_owned(),
+                version_id: None,
+                last_modified: Timestamp::UNIX_EPOCH,
+                blob_id: Some(blob.id),
+                metadata: None,
+                tagging: None,
+                replication_status: None,
+                last_accessed: None,
+                content_headers: ContentHeaders::default(),
+            };
+            db.write_object_metadata_with_blob(destination, &object, &blob, None)
+                .await
+                .map_err(anyhow::Error::from)
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = res {
+        db.clean_temp_blob(&blob).await;
+        return Err(err);
+    }
+    Ok(blob)
+}
+
+fn csv_row(bucket: &str, object: &Object, blob: &Blob) -> anyhow::Result<String> {
+    let last_modified = object.last_modified.format(&Rfc3339)?;
+    // keys are URL encoded so they never contain quotes or line breaks
+    Ok(format!(
+        "\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\"\n",
+        bucket,
+        urlencoding::encode(&object.oid),
+        blob.size,
+        last_modified,
+        blob.etag,
+        blob.storage_class.as_deref().unwrap_or("STANDARD")
+    ))
+}
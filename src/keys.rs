@@ -0,0 +1,112 @@
+//! Validation of object keys.
+//!
+//! Keys are stored and compared byte for byte, they are never normalized: `a//b` and `a/b` are different
+//! objects as in S3. Keys are checked when they are written, so reads and deletes of keys written before a
+//! stricter policy was enabled keep working. Prefixes, markers and delimiters of listings are only checked for
+//! characters the database can not store.
+
+use s3s::{s3_error, S3Error, S3ErrorCode, S3Result};
+
+/// Limit of the UTF-8 encoded key, the same as in AWS
+pub const MAX_KEY_LENGTH: usize = 1024;
+
+/// Keys accepted by writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyPolicy {
+    /// every key S3 accepts
+    S3,
+    /// additionally reject keys which are unsafe as file system paths: `.` and `..` segments, empty segments
+    /// (`a//b`, a leading `/`) and keys consisting of delimiters only
+    Strict,
+}
+
+/// Check the key of a new object
+pub fn check_key(key: &str, policy: KeyPolicy) -> S3Result<()> {
+    if key.is_empty() {
+        return Err(s3_error!(InvalidArgument, "Object key must not be empty"));
+    }
+    if key.len() > MAX_KEY_LENGTH {
+        let mut err = S3Error::with_message(S3ErrorCode::Custom("KeyTooLongError".into()), "Your key is too long");
+        err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+        return Err(err);
+    }
+    check_chars(key, "Object key")?;
+
+    if policy == KeyPolicy::Strict {
+        if key.bytes().all(|b| b == b'/') {
+            return Err(s3_error!(InvalidArgument, "Object key must not consist of delimiters only"));
+        }
+        // a trailing `/` marks a folder object and is allowed, it is the only empty segment
+        let segments = key.strip_suffix('/').unwrap_or(key).split('/');
+        for segment in segments {
+            match segment {
+                "" => return Err(s3_error!(InvalidArgument, "Object key must not contain empty path segments")),
+                "." | ".." => return Err(s3_error!(InvalidArgument, "Object key must not contain . or .. path segments")),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Postgres text can not contain NUL, such values would fail as an internal error in the query
+pub fn check_chars(value: &str, name: &str) -> S3Result<()> {
+    if value.contains('\0') {
+        return Err(s3_error!(InvalidArgument, "{} must not contain NUL characters", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(key: &str, policy: KeyPolicy) -> Option<S3ErrorCode> {
+        check_key(key, policy).err().map(|err| err.code().clone())
+    }
+
+    #[test]
+    fn keys_accepted_by_both_policies() {
+        for key in ["a", "a/b", "a/b/", "dir/", "a.b", "a/..b/c.", "...", "ключ/🙂"] {
+            assert_eq!(code(key, KeyPolicy::S3), None, "{key}");
+            assert_eq!(code(key, KeyPolicy::Strict), None, "{key}");
+        }
+    }
+
+    #[test]
+    fn path_segments_only_rejected_by_strict() {
+        for key in ["a/../b", "../a", "a/..", "./a", "a/./b", "a//b", "/a", "a//", "/", "//", "."] {
+            assert_eq!(code(key, KeyPolicy::S3), None, "{key}");
+            assert_eq!(code(key, KeyPolicy::Strict), Some(S3ErrorCode::InvalidArgument), "{key}");
+        }
+    }
+
+    #[test]
+    fn empty_and_nul_keys() {
+        for policy in [KeyPolicy::S3, KeyPolicy::Strict] {
+            assert_eq!(code("", policy), Some(S3ErrorCode::InvalidArgument));
+            assert_eq!(code("a\0b", policy), Some(S3ErrorCode::InvalidArgument));
+        }
+    }
+
+    #[test]
+    fn key_length_is_counted_in_bytes() {
+        let longest = "a".repeat(MAX_KEY_LENGTH);
+        assert_eq!(code(&longest, KeyPolicy::S3), None);
+
+        let too_long = "a".repeat(MAX_KEY_LENGTH + 1);
+        let expected = Some(S3ErrorCode::Custom("KeyTooLongError".into()));
+        assert_eq!(code(&too_long, KeyPolicy::S3), expected);
+        // 512 characters of two bytes each
+        assert_eq!(code(&"ä".repeat(512), KeyPolicy::S3), None);
+        assert_eq!(code(&"ä".repeat(513), KeyPolicy::S3), expected);
+    }
+
+    #[test]
+    fn listing_parameters_only_reject_nul() {
+        assert!(check_chars("a/../b", "Prefix").is_ok());
+        assert!(check_chars("", "Prefix").is_ok());
+        let err = check_chars("a\0", "Prefix").unwrap_err();
+        assert_eq!(err.message(), Some("Prefix must not contain NUL characters"));
+    }
+}
@@ -0,0 +1,153 @@
+//! EC2 credentials of OpenStack Keystone.
+//!
+//! Keystone stores an EC2 credential under the SHA-256 of its access key, so the secret can be fetched with one
+//! request of the v3 credentials API. The gateway authenticates with a service user which must be allowed to
+//! read the credentials of all users (e.g. the `admin` role). Keys are owned by the Keystone project of the
+//! credential, a gateway user with the project id must exist.
+
+use std::time::Duration;
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use s3s::{s3_error, S3Result};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::identity::{Credential, CredentialProvider};
+
+const KEYSTONE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct KeystoneConfig {
+    /// identity endpoint without the version, e.g. `http://keystone:5000`, plain HTTP only
+    pub url: String,
+    pub user: String,
+    pub password: String,
+    pub project: String,
+    /// domain of the service user and its project
+    pub domain: String,
+}
+
+pub struct Keystone {
+    config: KeystoneConfig,
+    client: Client<HttpConnector>,
+    /// token of the service user, renewed when Keystone rejects it
+    token: Mutex<Option<String>>,
+}
+
+impl Keystone {
+    pub fn new(mut config: KeystoneConfig) -> Self {
+        config.url = config.url.trim_end_matches('/').to_owned();
+        Self {
+            config,
+            client: Client::new(),
+            token: Mutex::default(),
+        }
+    }
+
+    async fn issue_token(&self) -> anyhow::Result<String> {
+        let domain = json!({"name": self.config.domain});
+        let body = json!({
+            "auth": {
+                "identity": {
+                    "methods": ["password"],
+                    "password": {
+                        "user": {"name": self.config.user, "domain": domain, "password": self.config.password}
+                    }
+                },
+                "scope": {"project": {"name": self.config.project, "domain": domain}}
+            }
+        });
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}/v3/auth/tokens", self.config.url))
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))?;
+        let res = tokio::time::timeout(KEYSTONE_TIMEOUT, self.client.request(req)).await??;
+        if !res.status().is_success() {
+            anyhow::bail!("keystone has rejected the service user with {}", res.status());
+        }
+        let token = res
+            .headers()
+            .get("x-subject-token")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("keystone has not returned a token"))?;
+        Ok(token.to_owned())
+    }
+
+    async fn token(&self) -> anyhow::Result<String> {
+        let mut token = self.token.lock().await;
+        match token.as_ref() {
+            Some(token) => Ok(token.clone()),
+            None => Ok(token.insert(self.issue_token().await?).clone()),
+        }
+    }
+
+    /// The `credential` object, None if the key does not exist
+    async fn get_credential(&self, access_key: &str) -> anyhow::Result<Option<Value>> {
+        let id = hex_simd::encode_to_string(Sha256::digest(access_key), hex_simd::AsciiCase::Lower);
+        let uri = format!("{}/v3/credentials/{id}", self.config.url);
+
+        // one retry with a new token, the cached one may have expired
+        for _ in 0..2 {
+            let req = Request::builder()
+                .method(Method::GET)
+                .uri(&uri)
+                .header("x-auth-token", self.token().await?)
+                .header(hyper::header::ACCEPT, "application/json")
+                .body(Body::empty())?;
+            let res = tokio::time::timeout(KEYSTONE_TIMEOUT, self.client.request(req)).await??;
+            match res.status() {
+                StatusCode::OK => {
+                    let body = hyper::body::to_bytes(res.into_body()).await?;
+                    let mut body: Value = serde_json::from_slice(&body)?;
+                    return Ok(Some(body["credential"].take()));
+                }
+                StatusCode::NOT_FOUND => return Ok(None),
+                StatusCode::UNAUTHORIZED => *self.token.lock().await = None,
+                status => anyhow::bail!("keystone has responded with {status}"),
+            }
+        }
+        anyhow::bail!("keystone has rejected a new token of the service user")
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialProvider for Keystone {
+    fn name(&self) -> &'static str {
+        "keystone"
+    }
+
+    async fn resolve(&self, access_key: &str) -> S3Result<Option<Credential>> {
+        let credential = match self.get_credential(access_key).await {
+            Ok(Some(credential)) => credential,
+            Ok(None) => return Ok(None),
+            Err(err) => {
+                tracing::error!(error = %err, "unable to fetch an EC2 credential from keystone");
+                return Err(s3_error!(ServiceUnavailable, "Identity provider is unavailable"));
+            }
+        };
+        if credential["type"] != "ec2" {
+            return Ok(None);
+        }
+        // the blob is a JSON document serialized into a string
+        let blob: Value = credential["blob"]
+            .as_str()
+            .and_then(|blob| serde_json::from_str(blob).ok())
+            .unwrap_or_default();
+        let (Some(access), Some(secret), Some(project)) =
+            (blob["access"].as_str(), blob["secret"].as_str(), credential["project_id"].as_str())
+        else {
+            tracing::warn!("EC2 credential of keystone without access, secret or project");
+            return Ok(None);
+        };
+        if access != access_key {
+            return Ok(None);
+        }
+        Ok(Some(Credential {
+            secret_key: secret.to_owned(),
+            user: Some(project.to_owned()),
+        }))
+    }
+}
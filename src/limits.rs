@@ -0,0 +1,147 @@
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{Request, Response};
+use s3s::{S3Error, S3ErrorCode};
+use tokio::sync::watch;
+
+use crate::post_policy::error_response;
+use crate::reload::RuntimeConfig;
+
+#[derive(Debug, thiserror::Error)]
+#[error("too many open connections")]
+pub struct TooManyConnections;
+
+/// Make-service which refuses new connections when `max_connections` are already open.
+///
+/// Every connection holds a permit until hyper drops its service. The limit follows the runtime config,
+/// connections above a lowered limit are not closed.
+#[derive(Clone)]
+pub struct ConnectionLimit<S> {
+    inner: S,
+    open: Arc<AtomicUsize>,
+    config: watch::Receiver<RuntimeConfig>,
+}
+
+impl<S> ConnectionLimit<S> {
+    pub fn new(inner: S, config: watch::Receiver<RuntimeConfig>) -> Self {
+        Self {
+            inner,
+            open: Arc::new(AtomicUsize::new(0)),
+            config,
+        }
+    }
+}
+
+impl<T, S: Clone> Service<T> for ConnectionLimit<S> {
+    type Response = LimitedConnection<S>;
+    type Error = TooManyConnections;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // an error here would stop the whole server
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: T) -> Self::Future {
+        let max_connections = self.config.borrow().max_connections;
+        let acquired = self
+            .open
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| (open < max_connections).then_some(open + 1));
+        if acquired.is_err() {
+            tracing::warn!("connection limit is reached, dropping connection");
+            return ready(Err(TooManyConnections));
+        }
+
+        ready(Ok(LimitedConnection {
+            inner: self.inner.clone(),
+            _permit: Permit(self.open.clone()),
+        }))
+    }
+}
+
+pub struct LimitedConnection<S> {
+    inner: S,
+    _permit: Permit,
+}
+
+struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<R, S: Service<R>> Service<R> for LimitedConnection<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+/// Fails requests which have not produced a response within the deadline with `RequestTimeout`.
+///
+/// The request future is dropped at the deadline like it is when the client disconnects, so backend writes
+/// stop and the temporary blobs of the request are handed over to the GC. The body of a response which has
+/// already started is not limited.
+#[derive(Clone)]
+pub struct RequestDeadline<S> {
+    inner: S,
+    deadline: Option<Duration>,
+}
+
+impl<S> RequestDeadline<S> {
+    /// The deadline is disabled if `None`
+    pub fn new(inner: S, deadline: Option<Duration>) -> Self {
+        Self { inner, deadline }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for RequestDeadline<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let Some(deadline) = self.deadline else {
+            return Box::pin(self.inner.call(req));
+        };
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            match tokio::time::timeout(deadline, fut).await {
+                Ok(res) => res,
+                Err(_) => {
+                    tracing::warn!(%method, path, "request has been cancelled at the deadline");
+                    let err = S3Error::with_message(
+                        S3ErrorCode::RequestTimeout,
+                        "The request has not been completed within the deadline",
+                    );
+                    Ok(error_response(&err))
+                }
+            }
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use tokio::time::Instant;
+
+use crate::meta_store::{ListOptions, ListResult, MetaStore};
+
+/// Walks through the listing of a bucket page by page, every page starts after the marker of the previous one
+pub struct ObjectLister<'a> {
+    db: &'a dyn MetaStore,
+    bucket: &'a str,
+    prefix: &'a Option<String>,
+    delim: &'a str,
+    marker: Option<String>,
+    finished: bool,
+}
+
+impl<'a> ObjectLister<'a> {
+    pub fn new(db: &'a dyn MetaStore, bucket: &'a str, prefix: &'a Option<String>, delim: &'a str) -> Self {
+        Self {
+            db,
+            bucket,
+            prefix,
+            delim,
+            marker: None,
+            finished: false,
+        }
+    }
+
+    /// Start after the key or common prefix
+    pub fn with_marker(mut self, marker: Option<String>) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    /// Up to `max_keys` keys and common prefixes, None after the last page
+    pub async fn next_page(&mut self, max_keys: u64) -> Result<Option<ListResult>, s3s::S3Error> {
+        if self.finished {
+            return Ok(None);
+        }
+        let page = self
+            .db
+            .list_objects(ListOptions {
+                bucket: self.bucket,
+                prefix: self.prefix,
+                delim: self.delim,
+                marker: &self.marker,
+                max_keys,
+                with_versions: false,
+                version_marker: None,
+            })
+            .await?;
+        self.finished = page.marker.is_none();
+        if page.marker.is_some() {
+            self.marker = page.marker.clone();
+        }
+        Ok(Some(page))
+    }
+
+    /// Up to `max_keys` entries fetched in pages of `page_size`. The pages after the first one are abandoned
+    /// at the deadline, the result is truncated at the last complete page then.
+    pub async fn collect(&mut self, max_keys: u64, page_size: u64, deadline: Instant) -> Result<ListResult, s3s::S3Error> {
+        let mut res = ListResult {
+            objects: Vec::new(),
+            common_prefixes: Vec::new(),
+            marker: None,
+            version_marker: None,
+        };
+        loop {
+            let collected = (res.objects.len() + res.common_prefixes.len()) as u64;
+            let limit = page_size.min(max_keys - collected);
+            let page = if collected == 0 {
+                self.next_page(limit).await?
+            } else {
+                match tokio::time::timeout_at(deadline, self.next_page(limit)).await {
+                    Ok(page) => page?,
+                    Err(_) => {
+                        tracing::debug!(bucket = self.bucket, collected, "listing has reached the deadline");
+                        break;
+                    }
+                }
+            };
+            let Some(page) = page else {
+                break;
+            };
+            res.objects.extend(page.objects);
+            res.common_prefixes.extend(page.common_prefixes);
+            if self.finished || (res.objects.len() + res.common_prefixes.len()) as u64 >= max_keys {
+                break;
+            }
+        }
+        res.marker = if self.finished { None } else { self.marker.clone() };
+        Ok(res)
+    }
+}
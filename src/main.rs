@@ -1,38 +1,113 @@
-use std::io::IsTerminal;
-use std::net::TcpListener;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
+use admin::AdminApi;
+use alerts::{AlertThresholds, Backlog, BacklogMonitor};
+use auth::{ConfigAuth, PolicyAuth, RegionAuth, RequesterPaysAuth};
+use batch::BatchWorker;
+use blob_cache::BlobCache;
+use bucket_purge::BucketPurger;
+use bucket_region::BucketRegion;
+use ceph_store::{RadosBlobStore, RadosConfig};
 use clap::Parser;
+use clock::{RandomIds, SystemClock};
+use coordination::Singleton;
+use data_migration::MigrationWorker;
+use error::StartupError;
+use error_details::ErrorDetails;
+use gc::GarbageCollector;
 use hyper::server::Server;
-use s3s::auth::SimpleAuth;
+use identity::{DatabaseKeys, Introspection, ProviderAuth, ProviderKind};
+use inventory::InventoryWorker;
+use keys::KeyPolicy;
+use keystone::{Keystone, KeystoneConfig};
+use limits::{ConnectionLimit, RequestDeadline};
+use meta_store::DataMigration;
+use metadata_export::MetadataExporter;
+use pg_database::RetryPolicy;
+use post_policy::PostPolicy;
+use presign::Presigner;
+use read_only::ReadOnly;
+use reload::RuntimeConfig;
+use replication::ReplicationWorker;
+use restore::RestoreWorker;
+use router::EndpointRouter;
 use s3s::service::S3ServiceBuilder;
-use service::RadosStore;
-
-use opentelemetry::KeyValue;
-use opentelemetry_otlp::WithExportConfig;
-use opentelemetry_sdk::{
-    trace::{self, RandomIdGenerator, Sampler},
-    Resource,
-};
+use secrets::Secrets;
+use service::{RadosStore, StoreConfig};
+use sig_debug::SignatureDebug;
+use telemetry::{RequestMetrics, TelemetryConfig};
+use temp_recovery::TempBlobRecovery;
+use tiering::{ColdTier, TieringWorker};
+use virtual_host::{DottedBuckets, VirtualHost};
+
 use std::time::Duration;
 use tracing::info;
-use tracing_subscriber::prelude::*;
 
 #[macro_use]
 mod error;
 
+mod admin;
+mod admin_v1;
+mod alerts;
+mod auth;
+mod batch;
+mod blob_cache;
 mod blob_store;
+mod bucket_metrics;
+mod bucket_purge;
+mod bucket_region;
 mod ceph_store;
+mod checksum;
+mod clock;
+mod coordination;
+mod data_migration;
+mod error_details;
+mod export;
+#[cfg(feature = "fault-injection")]
+mod faults;
+mod gc;
+mod identity;
+mod import;
+mod inventory;
+mod keys;
+mod keystone;
+mod limits;
+mod listing;
 mod meta_store;
+mod metadata_export;
+mod owners;
+mod parquet;
 mod pg_database;
+mod policy;
+mod post_policy;
+mod presign;
+mod read_only;
+mod reload;
+mod replication;
+mod restore;
+mod router;
+mod secrets;
+mod select;
 mod service;
+mod sig_debug;
+mod telemetry;
+mod temp_recovery;
+mod tiering;
+mod virtual_host;
 
 #[derive(Debug, Parser)]
 #[command(version)]
 struct Opt {
-    /// Host name to listen on.
+    /// Path to the ceph configuration file.
     #[arg(long, short, default_value = "/etc/ceph/ceph.conf")]
     config: String,
 
+    /// Ceph user used to connect to the cluster.
+    #[arg(long, default_value = "admin")]
+    ceph_user: String,
+
     #[arg(long, default_value = "localhost")]
     host: String,
 
@@ -40,7 +115,8 @@ struct Opt {
     #[arg(long, default_value = "8014")] // The original design was finished on 2020-08-14.
     port: u16,
 
-    /// Access key used for authentication.
+    /// Access key used for authentication. Secret options accept `file:<path>` and `vault:<path>#<field>`
+    /// references besides plain values.
     #[arg(long, short)]
     access_key: Option<String>,
 
@@ -48,36 +124,642 @@ struct Opt {
     #[arg(long, short)]
     secret_key: Option<String>,
 
-    /// Domain name used for virtual-hosted-style requests.
+    /// Domain name used for virtual-hosted-style requests. Include the port if clients send it in the Host
+    /// header (e.g. s3.example.org:8014).
     #[arg(long)]
     domain_name: Option<String>,
 
-    /// Root directory of stored data.
+    /// Handling of virtual-hosted-style requests to buckets with dots in their names, which wildcard TLS
+    /// certificates do not cover. Path-style requests to such buckets are always served.
+    #[arg(long, value_enum, default_value = "allow")]
+    dotted_virtual_hosts: DottedBuckets,
+
+    /// Region served by the gateway. Requests signed for other regions are rejected.
+    #[arg(long, default_value = "us-east-1")]
+    region: String,
+
+    /// RADOS pool which stores object data.
     #[arg(long, short)]
     pool: String,
 
-    /// Opentelemetry endpoint (http://ip:port)
+    /// RADOS namespace inside the pool. The default namespace is used if not set.
+    #[arg(long)]
+    namespace: Option<String>,
+
+    /// Maximum number of idle RADOS stripers kept per pool for reuse.
+    #[arg(long, default_value = "32")]
+    rados_max_idle: usize,
+
+    /// Opentelemetry endpoint (http://ip:port). Traces, metrics and logs are exported there.
     #[arg(long)]
     otlp_endpoint: Option<String>,
+
+    /// Fraction of traces exported to the opentelemetry endpoint (0.0 - 1.0).
+    #[arg(long, default_value = "1.0")]
+    otlp_sample_ratio: f64,
+
+    /// Interval in seconds between metric exports.
+    #[arg(long, default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    otlp_metrics_interval: u64,
+
+    /// Secondary pool used as a replication target. Replication is disabled if not set.
+    #[arg(long)]
+    replication_pool: Option<String>,
+
+    /// RADOS namespace inside the replication pool.
+    #[arg(long)]
+    replication_namespace: Option<String>,
+
+    /// Destination bucket which replication rules must name, all rules are replicated into the replication pool.
+    /// Defaults to the name of the pool.
+    #[arg(long)]
+    replication_destination: Option<String>,
+
+    /// RADOS pool of the cold tier. Objects which have not been used for a while are moved there,
+    /// tiering is disabled if not set.
+    #[arg(long)]
+    cold_pool: Option<String>,
+
+    /// RADOS namespace inside the cold pool.
+    #[arg(long)]
+    cold_namespace: Option<String>,
+
+    /// Storage class reported for the objects in the cold tier.
+    #[arg(long, default_value = "STANDARD_IA")]
+    cold_storage_class: String,
+
+    /// Number of days without reads or writes after which an object is moved to the cold tier.
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    cold_after_days: u64,
+
+    /// Minimal interval in seconds between two recorded reads of an object.
+    #[arg(long, default_value = "86400", value_parser = clap::value_parser!(u64).range(1..))]
+    access_sample_interval: u64,
+
+    /// Largest number of days a copy restored from the cold tier with RestoreObject is kept.
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(i32).range(1..))]
+    restore_max_days: i32,
+
+    /// Directory of the local blob cache. Its content is removed on start, the cache is disabled if not set.
+    #[arg(long)]
+    cache_dir: Option<std::path::PathBuf>,
+
+    /// Maximum size of the local blob cache in bytes.
+    #[arg(long, default_value = "10737418240")]
+    cache_size: u64,
+
+    /// Objects larger than this are not cached, in bytes.
+    #[arg(long, default_value = "67108864")]
+    cache_max_object_size: u64,
+
+    /// Database statements slower than this many milliseconds are logged with their SQL, without bind parameters.
+    #[arg(long, default_value = "1000")]
+    slow_query_threshold: u64,
+
+    /// Number of runs of a database transaction which fails with a serialization failure or a deadlock.
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    db_retry_attempts: u32,
+
+    /// Delay in milliseconds before the first retry of a database transaction, it doubles with every retry.
+    #[arg(long, default_value = "20")]
+    db_retry_backoff: u64,
+
+    /// Time in milliseconds a PutObject waits for others to commit their metadata in the same transaction,
+    /// for ingestion of many small objects. 0 commits every object on its own.
+    #[arg(long, default_value = "0")]
+    commit_batch_window: u64,
+
+    /// Largest number of objects whose metadata is committed in a single transaction.
+    #[arg(long, default_value = "64", value_parser = clap::value_parser!(u64).range(1..=1024))]
+    commit_batch_size: u64,
+
+    /// Largest ListObjects page served to service accounts, requests of other clients are limited to 1000 keys.
+    #[arg(long, default_value = "10000", value_parser = clap::value_parser!(i32).range(1..))]
+    extended_max_keys: i32,
+
+    /// Time in milliseconds after which a ListObjects page of more than 1000 keys is returned truncated.
+    #[arg(long, default_value = "10000")]
+    extended_list_timeout: u64,
+
+    /// Keys accepted for new objects: `s3` accepts every key S3 does, `strict` also rejects keys with `.`, `..`
+    /// or empty path segments, for buckets which are synced to file systems.
+    #[arg(long, value_enum, default_value = "s3")]
+    key_policy: KeyPolicy,
+
+    /// Return the object count and the size of the bucket in x-amz-bucket-object-count and x-amz-bucket-bytes-used
+    /// headers of HeadBucket.
+    #[arg(long)]
+    bucket_stats_headers: bool,
+
+    /// Maximum number of simultaneously open client connections.
+    #[arg(long, default_value = "1024")]
+    max_connections: usize,
+
+    /// Time in seconds a request may take until the response starts, e.g. to upload the whole body. Slower
+    /// requests are cancelled and fail with RequestTimeout. 0 disables the deadline.
+    #[arg(long, default_value = "0")]
+    request_deadline: u64,
+
+    /// Maximum size of the request head (request line and headers) in bytes.
+    #[arg(long, default_value = "65536", value_parser = clap::value_parser!(u64).range(8192..))]
+    max_header_size: u64,
+
+    /// Maximum object size accepted by a single PutObject or UploadPart in bytes.
+    #[arg(long, default_value = "5368709120")]
+    max_object_size: i64,
+
+    /// Maximum number of parts in a multipart upload.
+    #[arg(long, default_value = "10000", value_parser = clap::value_parser!(i32).range(1..=10000))]
+    max_parts: i32,
+
+    /// Time in seconds deleted and overwritten objects are kept before their data is removed.
+    #[arg(long, default_value = "86400")]
+    trash_retention: u64,
+
+    /// Size of a byte range fetched by a single backend read when serving large objects.
+    #[arg(long, default_value = "8388608")]
+    read_chunk_size: u64,
+
+    /// Number of byte ranges of a single GET fetched from the backend concurrently (1 disables parallel reads).
+    #[arg(long, default_value = "4", value_parser = clap::value_parser!(u64).range(1..=64))]
+    read_concurrency: u64,
+
+    /// PutObject bodies larger than this (in bytes) are split into backend objects written concurrently. 0 disables splitting.
+    #[arg(long, default_value = "67108864")]
+    offload_threshold: i64,
+
+    /// Size of a backend object of a split PutObject in bytes.
+    #[arg(long, default_value = "16777216", value_parser = clap::value_parser!(i64).range(1048576..))]
+    offload_part_size: i64,
+
+    /// Number of backend objects of a single PutObject written concurrently.
+    #[arg(long, default_value = "4", value_parser = clap::value_parser!(u64).range(1..=64))]
+    offload_concurrency: u64,
+
+    /// Size of a single sequential backend read in bytes.
+    #[arg(long, default_value = "4194304", value_parser = clap::value_parser!(u64).range(4096..))]
+    read_buffer_size: u64,
+
+    /// Amount of upload data (in bytes) buffered before it is written to the backend.
+    #[arg(long, default_value = "4194304", value_parser = clap::value_parser!(u64).range(4096..))]
+    write_buffer_size: u64,
+
+    /// Number of idle write buffers kept for reuse between uploads.
+    #[arg(long, default_value = "64")]
+    buffer_pool_size: usize,
+
+    /// Number of write buffers of a single upload sent to the backend concurrently.
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u64).range(1..=64))]
+    write_queue_depth: u64,
+
+    /// Include the canonical request and the string to sign in SignatureDoesNotMatch errors and logs.
+    #[arg(long)]
+    debug_signatures: bool,
+
+    /// Separate listener of the admin API (e.g. 127.0.0.1:8015). The admin API is disabled if neither this nor
+    /// the admin host is set.
+    #[arg(long)]
+    admin_listen: Option<SocketAddr>,
+
+    /// Host name which serves the admin API on the S3 listener (e.g. admin.s3.example.org), requests with
+    /// this Host header never reach the S3 service. Can be combined with a separate admin listener.
+    #[arg(long)]
+    admin_host: Option<String>,
+
+    /// Bearer token required by the admin API.
+    #[arg(long)]
+    admin_token: Option<String>,
+
+    /// Public URL of the gateway (e.g. https://s3.example.org) used in presigned URLs generated by the admin API.
+    /// The generation is disabled if not set.
+    #[arg(long)]
+    presign_endpoint: Option<String>,
+
+    /// Number of rows changed by a single batch of a background data migration.
+    #[arg(long, default_value = "1000", value_parser = clap::value_parser!(i64).range(1..))]
+    migration_batch_size: i64,
+
+    /// RADOS pool with objects written outside of the gateway which can be imported through the admin API.
+    #[arg(long)]
+    import_pool: Option<String>,
+
+    /// RADOS namespace inside the import pool.
+    #[arg(long)]
+    import_namespace: Option<String>,
+
+    /// RADOS pool which receives buckets exported through the admin API under their object keys.
+    #[arg(long)]
+    export_pool: Option<String>,
+
+    /// RADOS namespace inside the export pool.
+    #[arg(long)]
+    export_namespace: Option<String>,
+
+    /// Number of objects copied concurrently by an export.
+    #[arg(long, default_value = "8", value_parser = clap::value_parser!(u64).range(1..=256))]
+    export_concurrency: u64,
+
+    /// Minimal interval in milliseconds between batches of a background data migration.
+    #[arg(long, default_value = "100", value_parser = clap::value_parser!(u64).range(1..))]
+    migration_batch_interval: u64,
+
+    /// Number of concurrent GC workers removing blobs from the backend.
+    #[arg(long, default_value = "4", value_parser = clap::value_parser!(u64).range(1..=256))]
+    gc_workers: u64,
+
+    /// Number of failed attempts to remove a blob before the GC gives up on it until it is requeued
+    /// through the admin API.
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(i32).range(1..))]
+    gc_max_attempts: i32,
+
+    /// Age in seconds after which a temporary blob is considered left behind by an interrupted upload and is
+    /// recovered. Must be longer than the slowest upload.
+    #[arg(long, default_value = "86400", value_parser = clap::value_parser!(u64).range(60..))]
+    temp_blob_max_age: u64,
+
+    /// Warn when more blobs than this wait for the GC past their trash retention.
+    #[arg(long)]
+    alert_gc_backlog: Option<i64>,
+
+    /// Warn when the oldest temporary blob is older than this many seconds.
+    #[arg(long)]
+    alert_temp_blob_age: Option<u64>,
+
+    /// Warn when more objects than this have failed to replicate.
+    #[arg(long)]
+    alert_replication_failed: Option<i64>,
+
+    /// HTTP endpoint which receives a JSON POST when a backlog alert fires or resolves.
+    #[arg(long)]
+    alert_webhook: Option<hyper::Uri>,
+
+    /// Bucket which receives daily Parquet dumps of the object metadata of all other buckets. The export is
+    /// disabled if not set.
+    #[arg(long)]
+    metadata_export_bucket: Option<String>,
+
+    /// Key prefix of the metadata exports.
+    #[arg(long, default_value = "metadata")]
+    metadata_export_prefix: String,
+
+    /// Interval in seconds between two metadata exports.
+    #[arg(long, default_value = "86400", value_parser = clap::value_parser!(u64).range(60..))]
+    metadata_export_interval: u64,
+
+    /// Sources of access keys, asked in this order. The first one which knows a key wins.
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "config")]
+    auth_providers: Vec<ProviderKind>,
+
+    /// Seconds keys of the database and of external providers are cached for, unknown keys included.
+    #[arg(long, default_value = "300")]
+    auth_cache_ttl: u64,
+
+    /// OAuth 2.0 token introspection endpoint of the `introspection` auth provider, plain HTTP only.
+    #[arg(long)]
+    auth_introspection_url: Option<hyper::Uri>,
+
+    /// Client id the gateway authenticates with at the introspection endpoint.
+    #[arg(long)]
+    auth_introspection_client_id: Option<String>,
+
+    /// Client secret the gateway authenticates with at the introspection endpoint.
+    #[arg(long)]
+    auth_introspection_client_secret: Option<String>,
+
+    /// Keystone identity endpoint of the `keystone` auth provider without the API version, e.g.
+    /// http://keystone:5000. Plain HTTP only.
+    #[arg(long)]
+    keystone_url: Option<String>,
+
+    /// Service user the gateway reads EC2 credentials with, it needs access to the credentials of all users.
+    #[arg(long, default_value = "s3s-rados")]
+    keystone_user: String,
+
+    /// Password of the Keystone service user.
+    #[arg(long, default_value = "")]
+    keystone_password: String,
+
+    /// Project the Keystone service user is scoped to.
+    #[arg(long, default_value = "service")]
+    keystone_project: String,
+
+    /// Domain of the Keystone service user and its project.
+    #[arg(long, default_value = "Default")]
+    keystone_domain: String,
+
+    /// JSON file with settings reloaded on SIGHUP: log_level, max_connections, access_key, secret_key and
+    /// admin_tokens. Values from the file override the command line. Authentication can't be enabled by a reload.
+    #[arg(long)]
+    runtime_config: Option<std::path::PathBuf>,
+
+    /// HashiCorp Vault server which resolves `vault:` secret references, plain HTTP only (e.g. a Vault agent).
+    #[arg(long)]
+    vault_addr: Option<String>,
+
+    /// Token used with Vault, a `file:` reference is read again for every request (e.g. the sink of a Vault agent).
+    #[arg(long, default_value = "")]
+    vault_token: String,
+
+    /// Interval in seconds the runtime config and its secrets are reloaded at, in addition to SIGHUP.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    secret_refresh_interval: Option<u64>,
 }
 
 #[tokio::main]
 async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let opt = Opt::parse();
-    setup_tracing(&opt).unwrap();
-    let store = RadosStore::new().await;
+    let defaults = RuntimeConfig {
+        log_level: None,
+        max_connections: opt.max_connections,
+        credentials: opt.access_key.clone().zip(opt.secret_key.clone()),
+        admin_tokens: Vec::new(),
+    };
+    let mut secrets = Secrets::default();
+    if let Some(addr) = opt.vault_addr.clone() {
+        secrets = secrets.with_vault(addr, opt.vault_token.clone());
+    }
+    let secrets = Arc::new(secrets);
+    let runtime_config = RuntimeConfig::load(opt.runtime_config.as_ref(), &defaults, &secrets).await?;
+    let admin_token = secrets.resolve_opt(opt.admin_token.as_deref()).await?;
+    let telemetry = TelemetryConfig {
+        otlp_endpoint: opt.otlp_endpoint.clone(),
+        sample_ratio: opt.otlp_sample_ratio,
+        metrics_interval: Duration::from_secs(opt.otlp_metrics_interval),
+    };
+    let log_handle = telemetry::setup(&telemetry, runtime_config.log_level.as_deref()).map_err(StartupError::Telemetry)?;
+    let (config_tx, config_rx) = tokio::sync::watch::channel(runtime_config);
+    tokio::spawn(reload::apply_log_level(config_rx.clone(), log_handle));
+    let refresh = opt.secret_refresh_interval.map(Duration::from_secs);
+    if opt.runtime_config.is_some() || refresh.is_some() {
+        tokio::spawn(reload::reload_on_sighup(
+            opt.runtime_config.clone(),
+            defaults,
+            secrets.clone(),
+            refresh,
+            config_tx,
+        ));
+        info!("runtime config is reloaded on SIGHUP");
+    }
+    let rados = RadosConfig {
+        conf_path: opt.config.clone(),
+        user: opt.ceph_user.clone(),
+        pool: opt.pool.clone(),
+        namespace: opt.namespace.clone(),
+        max_idle: opt.rados_max_idle,
+    };
+    let config = StoreConfig {
+        rados: rados.clone(),
+        clock: Arc::new(SystemClock),
+        ids: Arc::new(RandomIds),
+        region: opt.region.clone(),
+        max_object_size: opt.max_object_size,
+        max_parts: opt.max_parts,
+        trash_retention: Duration::from_secs(opt.trash_retention),
+        read_chunk_size: opt.read_chunk_size,
+        read_concurrency: opt.read_concurrency as usize,
+        offload_threshold: opt.offload_threshold,
+        offload_part_size: opt.offload_part_size,
+        offload_concurrency: opt.offload_concurrency as usize,
+        read_buffer_size: opt.read_buffer_size,
+        write_buffer_size: opt.write_buffer_size as usize,
+        buffer_pool_size: opt.buffer_pool_size,
+        write_queue_depth: opt.write_queue_depth as usize,
+        slow_query_threshold: Duration::from_millis(opt.slow_query_threshold),
+        db_retry: RetryPolicy {
+            attempts: opt.db_retry_attempts,
+            backoff: Duration::from_millis(opt.db_retry_backoff),
+        },
+        extended_max_keys: opt.extended_max_keys,
+        extended_list_timeout: Duration::from_millis(opt.extended_list_timeout),
+        bucket_stats_headers: opt.bucket_stats_headers,
+        commit_batch_window: Duration::from_millis(opt.commit_batch_window),
+        commit_batch_size: opt.commit_batch_size as usize,
+        key_policy: opt.key_policy,
+        base_domain: opt.domain_name.clone(),
+        dotted_buckets: opt.dotted_virtual_hosts,
+        restore_max_days: opt.restore_max_days,
+        replication_destination: opt
+            .replication_pool
+            .as_ref()
+            .map(|pool| opt.replication_destination.clone().unwrap_or_else(|| pool.clone())),
+    };
+    let mut store = RadosStore::new(config).await?;
+    if let Some(dir) = &opt.cache_dir {
+        let cache = BlobCache::open(dir.clone(), opt.cache_size, opt.cache_max_object_size)
+            .await
+            .map_err(StartupError::Cache)?;
+        store = store.with_cache(cache);
+        info!("blob cache at {} is enabled", dir.display());
+    }
+    // holder of the leases of singleton workers
+    let gateway_id = uuid::Uuid::new_v4();
+    if let Some(cache) = store.cache() {
+        tokio::spawn(coordination::evict_removed_blobs(store.meta_store(), cache));
+    }
+    if let Some(pool) = &opt.cold_pool {
+        let cold = RadosConfig {
+            pool: pool.clone(),
+            namespace: opt.cold_namespace.clone(),
+            ..rados.clone()
+        };
+        let cold = ColdTier {
+            storage_class: opt.cold_storage_class.clone(),
+            store: Arc::new(RadosBlobStore::new(&cold).await?),
+        };
+        store = store.with_cold_tier(cold.clone(), Duration::from_secs(opt.access_sample_interval));
+        let (db, blob, restore_cold) = (store.meta_store(), store.blob_store(), cold.clone());
+        tokio::spawn(
+            Singleton::new(store.meta_store(), "restore", gateway_id)
+                .run(move || RestoreWorker::new(db.clone(), blob.clone(), restore_cold.clone()).run()),
+        );
+        let cold_after = Duration::from_secs(opt.cold_after_days * 24 * 60 * 60);
+        let (db, blob) = (store.meta_store(), store.blob_store());
+        tokio::spawn(
+            Singleton::new(store.meta_store(), "tiering", gateway_id)
+                .run(move || TieringWorker::new(db.clone(), blob.clone(), cold.clone(), cold_after).run()),
+        );
+        info!("tiering to pool {pool} is enabled");
+    }
+
+    tokio::spawn(
+        GarbageCollector::new(store.meta_store(), store.blob_store(), opt.gc_workers as usize, opt.gc_max_attempts)
+            .with_cold_tier(store.cold_tier())
+            .run(),
+    );
+    let (db, blob, max_age) = (store.meta_store(), store.blob_store(), Duration::from_secs(opt.temp_blob_max_age));
+    tokio::spawn(
+        Singleton::new(store.meta_store(), "temp_blob_recovery", gateway_id)
+            .run(move || TempBlobRecovery::new(db.clone(), blob.clone(), max_age).run()),
+    );
+    let (db, blob) = (store.meta_store(), store.blob_store());
+    tokio::spawn(
+        Singleton::new(store.meta_store(), "inventory", gateway_id)
+            .run(move || InventoryWorker::new(db.clone(), blob.clone()).run()),
+    );
+    let db = store.meta_store();
+    tokio::spawn(Singleton::new(store.meta_store(), "bucket_purge", gateway_id).run(move || BucketPurger::new(db.clone()).run()));
+    let (db, blob, cold) = (store.meta_store(), store.blob_store(), store.cold_tier());
+    tokio::spawn(
+        Singleton::new(store.meta_store(), "batch", gateway_id)
+            .run(move || BatchWorker::new(db.clone(), blob.clone()).with_cold_tier(cold.clone()).run()),
+    );
+    let migrations = vec![DataMigration::BucketRegion {
+        region: opt.region.clone(),
+    }];
+    let (db, batch_size, batch_interval) = (
+        store.meta_store(),
+        opt.migration_batch_size,
+        Duration::from_millis(opt.migration_batch_interval),
+    );
+    tokio::spawn(
+        Singleton::new(store.meta_store(), "data_migration", gateway_id)
+            .run(move || MigrationWorker::new(db.clone(), migrations.clone(), batch_size, batch_interval).run()),
+    );
+
+    let read_only = Arc::new(AtomicBool::new(false));
+    let mut admin_endpoint = None;
+    if opt.admin_listen.is_some() || opt.admin_host.is_some() {
+        let mut admin = AdminApi::new(store.meta_store(), store.blob_store(), read_only.clone(), admin_token.clone())
+            .with_cold_tier(store.cold_tier())
+            .with_runtime_config(config_rx.clone());
+        if let Some(pool) = &opt.import_pool {
+            let source = RadosConfig {
+                pool: pool.clone(),
+                namespace: opt.import_namespace.clone(),
+                ..rados.clone()
+            };
+            admin = admin.with_import_source(Arc::new(RadosBlobStore::new(&source).await?));
+        }
+        if let Some(pool) = &opt.export_pool {
+            let target = RadosConfig {
+                pool: pool.clone(),
+                namespace: opt.export_namespace.clone(),
+                ..rados.clone()
+            };
+            let target = Arc::new(RadosBlobStore::new(&target).await?);
+            admin = admin.with_export_target(target, opt.export_concurrency as usize);
+        }
+        if let Some(endpoint) = &opt.presign_endpoint {
+            let auth = Arc::new(ConfigAuth::new(config_rx.clone()));
+            admin = admin.with_presigner(Presigner::new(auth, opt.region.clone(), endpoint.clone()));
+        }
+        if let Some(addr) = opt.admin_listen {
+            let admin = admin.clone();
+            tokio::spawn(async move {
+                if let Err(err) = admin.serve(addr).await {
+                    tracing::error!(error = %err, "admin API has failed");
+                }
+            });
+            info!("admin API is running at http://{addr}");
+        }
+        if let Some(host) = opt.admin_host.clone() {
+            // S3 clients reach the admin host, the operator API must not be open to them
+            if !admin.has_token() {
+                return Err(StartupError::Config("--admin-host requires --admin-token or admin_tokens").into());
+            }
+            info!("admin API is served for host {host}");
+            admin_endpoint = Some((host, admin.with_required_token()));
+        }
+    }
 
+    if let Some(pool) = &opt.replication_pool {
+        let target = RadosConfig {
+            pool: pool.clone(),
+            namespace: opt.replication_namespace.clone(),
+            ..rados.clone()
+        };
+        let target = Arc::new(RadosBlobStore::new(&target).await?);
+        let (db, blob) = (store.meta_store(), store.blob_store());
+        tokio::spawn(
+            Singleton::new(store.meta_store(), "replication", gateway_id)
+                .run(move || ReplicationWorker::new(db.clone(), blob.clone(), target.clone()).run()),
+        );
+        info!("replication to pool {pool} is enabled");
+    }
+
+    if let Some(bucket) = &opt.metadata_export_bucket {
+        let (db, blob, destination, prefix, interval) = (
+            store.meta_store(),
+            store.blob_store(),
+            bucket.clone(),
+            opt.metadata_export_prefix.clone(),
+            Duration::from_secs(opt.metadata_export_interval),
+        );
+        tokio::spawn(
+            Singleton::new(store.meta_store(), "metadata_export", gateway_id).run(move || {
+                MetadataExporter::new(db.clone(), blob.clone(), destination.clone(), prefix.clone(), interval).run()
+            }),
+        );
+        info!("metadata export to bucket {bucket} is enabled");
+    }
+
+    let thresholds = AlertThresholds {
+        gc_backlog: opt.alert_gc_backlog,
+        temp_blob_age: opt.alert_temp_blob_age.map(Duration::from_secs),
+        replication_failed: opt.alert_replication_failed,
+    };
+    if thresholds.gc_backlog.is_some() || thresholds.temp_blob_age.is_some() || thresholds.replication_failed.is_some() {
+        let backlog = Arc::new(Backlog::default());
+        telemetry::observe_backlog(backlog.clone());
+        let (db, webhook) = (store.meta_store(), opt.alert_webhook.clone());
+        tokio::spawn(Singleton::new(store.meta_store(), "backlog_alerts", gateway_id).run(move || {
+            let mut monitor = BacklogMonitor::new(db.clone(), thresholds.clone(), backlog.clone());
+            if let Some(webhook) = webhook.clone() {
+                monitor = monitor.with_webhook(webhook);
+            }
+            monitor.run()
+        }));
+        info!("backlog alerts are enabled");
+    }
+
+    let db = store.meta_store();
     let service = {
         let mut b = S3ServiceBuilder::new(store);
 
         // Enable authentication
-        if let (Some(ak), Some(sk)) = (opt.access_key, opt.secret_key) {
-            b.set_auth(SimpleAuth::from_single(ak, sk));
-            info!("authentication is enabled");
+        let external = opt.auth_providers.iter().any(|p| *p != ProviderKind::Config);
+        if config_rx.borrow().credentials.is_some() || external {
+            let mut providers: Vec<Arc<dyn identity::CredentialProvider>> = Vec::new();
+            for kind in &opt.auth_providers {
+                providers.push(match kind {
+                    ProviderKind::Config => Arc::new(ConfigAuth::new(config_rx.clone())),
+                    ProviderKind::Database => Arc::new(DatabaseKeys::new(db.clone())),
+                    ProviderKind::Introspection => {
+                        let Some(url) = opt.auth_introspection_url.clone() else {
+                            return Err(StartupError::Config(
+                                "--auth-introspection-url is required by the introspection provider",
+                            )
+                            .into());
+                        };
+                        Arc::new(Introspection::new(
+                            url,
+                            opt.auth_introspection_client_id.clone(),
+                            secrets.resolve_opt(opt.auth_introspection_client_secret.as_deref()).await?,
+                        ))
+                    }
+                    ProviderKind::Keystone => {
+                        let Some(url) = opt.keystone_url.clone() else {
+                            return Err(StartupError::Config("--keystone-url is required by the keystone provider").into());
+                        };
+                        Arc::new(Keystone::new(KeystoneConfig {
+                            url,
+                            user: opt.keystone_user.clone(),
+                            password: secrets.resolve(&opt.keystone_password).await?,
+                            project: opt.keystone_project.clone(),
+                            domain: opt.keystone_domain.clone(),
+                        }))
+                    }
+                });
+            }
+            let auth = ProviderAuth::new(providers, db.clone(), Duration::from_secs(opt.auth_cache_ttl));
+            let auth = RequesterPaysAuth::new(auth, db.clone());
+            let auth = PolicyAuth::new(auth, db.clone());
+            b.set_auth(RegionAuth::new(auth, opt.region.clone()));
+            info!(providers = ?opt.auth_providers, "authentication is enabled");
         }
 
         // Enable parsing virtual-hosted-style requests
-        if let Some(domain_name) = opt.domain_name {
+        if let Some(domain_name) = opt.domain_name.clone() {
             b.set_base_domain(domain_name);
             info!("virtual-hosted-style requests are enabled");
         }
@@ -88,57 +770,42 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let listener = TcpListener::bind((opt.host.as_str(), opt.port))?;
     let local_addr = listener.local_addr()?;
 
-    let server = Server::from_tcp(listener)?.serve(service.into_shared().into_make_service());
+    let request_deadline = (opt.request_deadline > 0).then(|| Duration::from_secs(opt.request_deadline));
+    let mut router = EndpointRouter::new(RequestMetrics::new(ErrorDetails::new(RequestDeadline::new(
+        VirtualHost::new(
+            ReadOnly::new(
+                PostPolicy::new(
+                    BucketRegion::new(
+                        SignatureDebug::new(service.into_shared(), opt.debug_signatures),
+                        db.clone(),
+                        opt.domain_name.clone(),
+                        opt.region.clone(),
+                    ),
+                    opt.domain_name.clone(),
+                    opt.max_object_size as u64,
+                ),
+                db,
+                opt.domain_name.clone(),
+                read_only,
+            ),
+            opt.domain_name,
+            opt.dotted_virtual_hosts,
+        ),
+        request_deadline,
+    ))));
+    if let Some((host, admin)) = admin_endpoint {
+        router = router.with_admin(host, admin);
+    }
+
+    let server = Server::from_tcp(listener)?
+        .http1_max_buf_size(opt.max_header_size as usize)
+        .serve(ConnectionLimit::new(router, config_rx));
 
     info!("server is running at http://{local_addr}");
     server.with_graceful_shutdown(shutdown_signal()).await?;
 
     info!("server is stopped");
-    Ok(())
-}
-
-fn setup_tracing(args: &Opt) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    if args.otlp_endpoint.is_none() {
-        use tracing_subscriber::EnvFilter;
-
-        let env_filter = EnvFilter::from_default_env();
-        let enable_color = std::io::stdout().is_terminal();
-
-        tracing_subscriber::fmt()
-            .pretty()
-            .with_env_filter(env_filter)
-            .with_ansi(enable_color)
-            .init();
-        return Ok(());
-    }
-
-    let tracer = opentelemetry_otlp::new_pipeline()
-        .tracing()
-        .with_exporter(
-            opentelemetry_otlp::new_exporter()
-                .tonic()
-                .with_endpoint(&args.otlp_endpoint.clone().unwrap())
-                .with_timeout(Duration::from_secs(3)),
-        )
-        .with_trace_config(
-            trace::config()
-                .with_sampler(Sampler::AlwaysOn)
-                .with_id_generator(RandomIdGenerator::default())
-                .with_max_events_per_span(64)
-                .with_max_attributes_per_span(16)
-                .with_max_events_per_span(16)
-                .with_resource(Resource::new(vec![KeyValue::new("service.name", "s3s_rados")])),
-        )
-        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
-
-    let fmt_layer = tracing_subscriber::fmt::layer();
-    let opentelemetry = tracing_opentelemetry::layer().with_tracer(tracer);
-    let registry = tracing_subscriber::Registry::default()
-        .with(tracing_subscriber::filter::LevelFilter::DEBUG)
-        .with(fmt_layer)
-        .with(opentelemetry);
-    registry.try_init()?;
-
+    telemetry::shutdown();
     Ok(())
 }
 
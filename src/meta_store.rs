@@ -1,3 +1,4 @@
+use futures::stream::BoxStream;
 use s3s::{
     self,
     dto::{ChecksumSHA1, ChecksumSHA256},
@@ -33,10 +34,28 @@ pub enum MetaStoreError {
 
 #[async_trait::async_trait]
 pub trait MetaStore: Send + Sync + std::fmt::Debug + 'static {
-    /// Write complete object metadata and commit temporary blob
+    /// Write complete object metadata and commit temporary blob, returns the modification time of the object.
+    /// Returns `PreconditionFailed` if the current object does not satisfy the condition.
     ///
     /// TODO: Handle versioned
-    async fn write_object_metadata_with_blob(&self, bucket: &Bucket, object: &Object, blob: &Blob) -> Result<(), s3s::S3Error>;
+    async fn write_object_metadata_with_blob(
+        &self,
+        bucket: &Bucket,
+        object: &Object,
+        blob: &Blob,
+        condition: Option<&WriteCondition>,
+    ) -> Result<Timestamp, s3s::S3Error>;
+
+    /// Same as `write_object_metadata_with_blob` for a blob which was written as several backend objects.
+    /// Temporary blobs of all the parts are committed in the same transaction.
+    async fn write_object_metadata_with_parts(
+        &self,
+        bucket: &Bucket,
+        object: &Object,
+        blob: &Blob,
+        parts: &[BlobPart],
+        condition: Option<&WriteCondition>,
+    ) -> Result<Timestamp, s3s::S3Error>;
 
     async fn write_object_metadata(
         &self,
@@ -55,7 +74,22 @@ pub trait MetaStore: Send + Sync + std::fmt::Debug + 'static {
         version: &Option<s3s::dto::ObjectVersionId>,
     ) -> Result<Option<(Object, Option<Blob>)>, s3s::S3Error>;
 
-    /// This function does not delete object from the store. It is done by GC
+    /// Remove all objects whose keys start with the prefix in a single statement, their blobs are moved to the
+    /// trash. Returns the number of removed objects.
+    async fn delete_objects_by_prefix(&self, bucket: &str, prefix: &str) -> Result<u64, S3Error>;
+    /// Attach the blob of the source object to the target key of the same bucket and remove the source,
+    /// the data is not copied. Replacing the target works like a regular write. Returns `NoSuchKey` if the
+    /// source does not point to the blob anymore, returns the modification time of the target.
+    async fn move_object(
+        &self,
+        bucket: &str,
+        source: &str,
+        target: &str,
+        blob_id: &Uuid,
+        attributes: WriteAttributes<'_>,
+    ) -> Result<Timestamp, S3Error>;
+
+    /// This function does not delete object from the store. It is done by GC. Missing keys are not an error.
     ///
     /// TODO: Handle versioned
     async fn delete_object_metadata(
@@ -67,37 +101,392 @@ pub trait MetaStore: Send + Sync + std::fmt::Debug + 'static {
 
     /// Writes blob metadata to the temp storage. This is a first stage of the two-phase-commit
     /// 2PC allow to clean data from the storage if an error occures.
+    /// Attach a blob with data written outside of the gateway. Unlike regular writes the modification
+    /// time of the object is kept and existing objects are not replaced, returns false in this case.
+    async fn import_object(&self, object: &Object, blob: &Blob) -> Result<bool, s3s::S3Error>;
     async fn write_temp_blob(&self, blob: &Blob) -> Result<(), s3s::S3Error>;
 
-    /// Does not return any error because GC should handle failures
+    /// Queue the data of an uncommitted blob for the GC. Does not return any error because GC should handle failures
     async fn clean_temp_blob(&self, blob: &Blob);
     /// Remove commited blob asynchronously
     async fn add_blob_gc(&self, blob: &Blob) -> Result<User, s3s::S3Error>;
 
     // list objects (with prefix)
+    /// One page of keys and common prefixes after the marker in binary key order. A listing which is
+    /// paginated with the returned markers includes every object committed before it started and not
+    /// deleted until its page is read, objects written in the meantime may or may not be included.
+    /// Pages are read from the primary so a listing always sees the writes which have been acknowledged.
     async fn list_objects<'a>(&self, options: ListOptions<'a>) -> Result<ListResult, S3Error>;
 
-    async fn create_bucket(&self, owner: &str, bucket: &str) -> Result<Bucket, S3Error>;
+    /// Returns the existing bucket if the owner creates it again in the same region. A bucket of another owner
+    /// fails with `BucketAlreadyExists`, an own bucket of another region with `BucketAlreadyOwnedByYou`.
+    async fn create_bucket(&self, owner: &str, bucket: &str, options: &CreateBucketOptions) -> Result<Bucket, S3Error>;
     async fn delete_bucket(&self, bucket: &str) -> Result<(), S3Error>;
     /// Should be cached
     async fn get_bucket_metadata(&self, bucket: &str) -> Result<Option<Bucket>, s3s::S3Error>;
     async fn list_buckets_by_user(&self, user: &str) -> Result<Vec<Bucket>, s3s::S3Error>;
+    /// Usage of the bucket, kept up to date by the writes. None if the bucket does not exist
+    async fn get_bucket_stats(&self, bucket: &str) -> Result<Option<BucketStats>, s3s::S3Error>;
+    /// Buckets of every user, or of the owner if given
+    async fn list_all_buckets(&self, owner: Option<&str>) -> Result<Vec<Bucket>, s3s::S3Error>;
 
     // May be cached
     // user metadata
     async fn get_user_by_access_key(&self, key: &str) -> Result<User, s3s::S3Error>;
+    async fn get_user(&self, id: &str) -> Result<Option<User>, s3s::S3Error>;
+    /// Whether the key may see the buckets of all users, false for unknown keys
+    async fn is_service_account_key(&self, key: &str) -> Result<bool, s3s::S3Error>;
+    /// Returns `InvalidAccessKeyId` if the key does not exist
+    async fn set_service_account_key(&self, key: &str, enabled: bool) -> Result<(), s3s::S3Error>;
+    /// Secret of a key managed in the database, None for unknown keys and keys of an external provider
+    async fn get_secret_key(&self, key: &str) -> Result<Option<String>, s3s::S3Error>;
+    /// Link a key resolved by an external provider to an existing user, so that it is known to the rest of the
+    /// gateway. Returns false if the user does not exist or the key belongs to another provider or the database.
+    async fn link_external_key(&self, key: &str, user: &str, provider: &str) -> Result<bool, s3s::S3Error>;
 
     // config log
-    async fn get_blob_gc(&self) -> anyhow::Result<Vec<Uuid>>;
+    /// Lease up to `limit` blobs whose trash retention has expired. A blob is held by one worker at a time,
+    /// the lease expires if the worker stops before the blob is removed.
+    async fn claim_blob_gc(&self, limit: i64, lease: std::time::Duration) -> anyhow::Result<Vec<GcTask>>;
+    /// Forget the blob after it has been removed from the blob store
+    async fn remove_blob_gc(&self, blob_id: &Uuid) -> anyhow::Result<()>;
+    /// Release the lease of a blob which could not be removed. It is claimed again after `retry_after`,
+    /// a dead blob is not claimed until it is requeued.
+    async fn fail_blob_gc(&self, blob_id: &Uuid, dead: bool, retry_after: std::time::Duration) -> anyhow::Result<()>;
+    /// Requeue the dead blobs, returns their number
+    async fn requeue_failed_blob_gc(&self) -> anyhow::Result<u64>;
+    /// Up to `limit` temporary blobs registered longer than `older_than` ago
+    async fn list_stale_temp_blobs(&self, older_than: std::time::Duration, limit: i64) -> anyhow::Result<Vec<Uuid>>;
+    /// Forget a stale temporary blob. Its data is handed over to the GC unless a blob, a blob part or a
+    /// multipart part refers to it.
+    async fn recover_temp_blob(&self, blob_id: &Uuid) -> anyhow::Result<TempBlobState>;
+    /// Restore the most recently deleted or overwritten blob of the object from the trash
+    async fn undelete_object(&self, bucket: &str, object: &str) -> Result<Uuid, S3Error>;
+    /// Most recently deleted or overwritten blob of every key under the prefix which is still in the trash, in
+    /// key order after `marker`. Only blobs deleted at or after `deleted_after` are listed if it is given.
+    async fn list_trash(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        deleted_after: Option<Timestamp>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TrashEntry>, S3Error>;
+    /// Full records of the objects of the bucket in binary key order after `marker`
+    async fn export_objects(&self, bucket: &str, marker: &str, limit: i64) -> Result<Vec<(Object, Option<Blob>)>, S3Error>;
+    /// Keys of the objects which have the tag, with the given value if it is set, in binary key order after
+    /// `marker`
+    async fn find_objects_by_tag(
+        &self,
+        bucket: &str,
+        key: &str,
+        value: Option<&str>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<String>, S3Error>;
+    /// Restore up to `limit` keys listed by [`MetaStore::list_trash`] as new objects. Keys which exist again are
+    /// skipped, so that newer data is never replaced.
+    async fn restore_trash(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        deleted_after: Option<Timestamp>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<TrashRestoreBatch, S3Error>;
+
+    // replication
+    async fn put_bucket_replication(&self, bucket: &str, config: &ReplicationConfig) -> Result<(), S3Error>;
+    async fn get_bucket_replication(&self, bucket: &str) -> Result<Option<ReplicationConfig>, S3Error>;
+    async fn delete_bucket_replication(&self, bucket: &str) -> Result<(), S3Error>;
+    /// Fetch the oldest blobs waiting to be copied to the secondary backend
+    async fn get_replication_tasks(&self, limit: i64) -> anyhow::Result<Vec<ReplicationTask>>;
+    /// Remove the task from the queue and record the final replication status on the object
+    async fn finish_replication_task(&self, task: &ReplicationTask, status: &str) -> anyhow::Result<()>;
+
+    // inventory
+    async fn put_bucket_inventory(&self, bucket: &str, config: &InventoryConfig) -> Result<(), S3Error>;
+    async fn get_bucket_inventory(&self, bucket: &str, id: &str) -> Result<Option<InventoryConfig>, S3Error>;
+    async fn list_bucket_inventory(&self, bucket: &str) -> Result<Vec<InventoryConfig>, S3Error>;
+    async fn delete_bucket_inventory(&self, bucket: &str, id: &str) -> Result<(), S3Error>;
+    /// Mark enabled inventories whose schedule has elapsed as started and return them.
+    ///
+    /// A configuration is returned only once per period even if several gateways poll concurrently.
+    // request metrics
+    async fn put_bucket_metrics(&self, bucket: &str, config: &MetricsConfig) -> Result<(), S3Error>;
+    async fn get_bucket_metrics(&self, bucket: &str, id: &str) -> Result<Option<MetricsConfig>, S3Error>;
+    async fn list_bucket_metrics(&self, bucket: &str) -> Result<Vec<MetricsConfig>, S3Error>;
+    async fn delete_bucket_metrics(&self, bucket: &str, id: &str) -> Result<(), S3Error>;
+
+    async fn claim_inventory_tasks(&self) -> anyhow::Result<Vec<InventoryTask>>;
+
+    // multipart uploads
+    /// The content headers are kept with the upload and applied to the object on completion. With a checksum
+    /// algorithm every part must carry a checksum of it.
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        object: &str,
+        initiator: &str,
+        headers: &ContentHeaders,
+        checksum_algorithm: Option<&str>,
+    ) -> Result<Uuid, S3Error>;
+    /// None if the upload does not exist or has been completed
+    async fn get_multipart_upload(&self, upload_id: &Uuid) -> Result<Option<MultipartUpload>, S3Error>;
+    /// Attach an uploaded part and commit its temporary blob. A previous part with the same number is sent to GC.
+    ///
+    /// Returns `NoSuchUpload` if the upload does not exist for the object (anymore) and `InvalidRequest` if the
+    /// checksum of the part does not use the algorithm of the upload.
+    async fn write_multipart_part(
+        &self,
+        bucket: &str,
+        object: &str,
+        upload_id: &Uuid,
+        part: &MultipartPart,
+    ) -> Result<(), S3Error>;
+    /// Assemble the blob from the selected parts and attach it to the object. The upload timestamp of the
+    /// returned blob is the modification time of the object.
+    ///
+    /// MUST be idempotent: completing an already completed upload returns the committed blob.
+    ///
+    /// The composite checksum is calculated from the part checksums if the upload has a checksum algorithm.
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &Bucket,
+        object: &str,
+        upload_id: &Uuid,
+        parts: &[CompletedPart],
+    ) -> Result<Blob, S3Error>;
+    async fn abort_multipart_upload(&self, upload_id: &Uuid) -> Result<(), S3Error>;
+    /// Backend objects of a multipart blob in order. Empty for regular blobs.
+    async fn get_blob_parts(&self, blob_id: &Uuid) -> Result<Vec<BlobPart>, S3Error>;
+    /// Checksums recorded when the blob was committed
+    async fn get_blob_checksums(&self, blob_id: &Uuid) -> Result<BlobChecksums, S3Error>;
+
+    // tiering
+    /// Remember that the object has been read
+    async fn record_object_access(&self, bucket: &str, object: &str) -> Result<(), S3Error>;
+    /// Up to `limit` blobs of the standard backend whose objects have not been read or written for `cold_after`,
+    /// least recently used first
+    async fn list_cold_blobs(&self, cold_after: std::time::Duration, limit: i64) -> anyhow::Result<Vec<Blob>>;
+    /// Attach the copy of a blob which has been written to the cold tier to the objects of the original blob
+    /// and pass the original one to the GC. Returns false if the blob is not used anymore.
+    ///
+    /// Only the id and the storage class are taken from `cold`, the etag, upload time and checksum stay those
+    /// of the original.
+    async fn transition_blob(&self, blob_id: &Uuid, cold: &Blob) -> anyhow::Result<bool>;
+    async fn get_object_restore(&self, bucket: &str, object: &str) -> Result<Option<ObjectRestore>, S3Error>;
+    /// Queue a restore of the cold blob, replaces a previous restore of the object
+    async fn create_object_restore(&self, bucket: &str, object: &str, blob_id: &Uuid, days: i32) -> Result<(), S3Error>;
+    /// Record the restored copy or extend the expiry of a completed restore
+    async fn set_object_restore(
+        &self,
+        bucket: &str,
+        object: &str,
+        blob_id: &Uuid,
+        days: i32,
+        expires_at: Timestamp,
+    ) -> Result<(), S3Error>;
+    async fn delete_object_restore(&self, bucket: &str, object: &str, blob_id: &Uuid) -> anyhow::Result<()>;
+    /// Up to `limit` pending restores, oldest first
+    async fn list_pending_restores(&self, limit: i64) -> anyhow::Result<Vec<ObjectRestore>>;
+    /// Up to `limit` completed restores whose copy has expired
+    async fn list_expired_restores(&self, limit: i64) -> anyhow::Result<Vec<ObjectRestore>>;
+
+    // user policies
+    async fn put_user_policy(&self, user: &str, policy: &UserPolicy) -> Result<(), S3Error>;
+    async fn list_user_policies(&self, user: &str) -> Result<Vec<UserPolicy>, S3Error>;
+    async fn delete_user_policy(&self, user: &str, name: &str) -> Result<(), S3Error>;
+    /// Returns `InvalidArgument` if the user does not exist
+    async fn set_user_default_region(&self, user: &str, region: Option<&str>) -> Result<(), S3Error>;
+    /// Policies of the user who owns the access key
+    async fn get_policies_by_access_key(&self, access_key: &str) -> Result<Vec<UserPolicy>, S3Error>;
+
+    /// BucketOwnerEnforced, BucketOwnerPreferred, ObjectWriter or None to remove the ownership controls.
+    /// Returns `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_object_ownership(&self, bucket: &str, ownership: Option<&str>) -> Result<(), S3Error>;
+
+    // bucket settings, apart from the request payer they do not change the behaviour of the gateway
+    /// Returns `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_accelerate(&self, bucket: &str, status: &str) -> Result<(), S3Error>;
+    /// Requester-pays buckets only accept requests which acknowledge the charge, returns `NoSuchBucket` if
+    /// the bucket does not exist
+    async fn set_bucket_request_payer(&self, bucket: &str, payer: &str) -> Result<(), S3Error>;
+    /// Tag set in the `x-amz-tagging` form or None to remove it, returns `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_tagging(&self, bucket: &str, tagging: Option<&str>) -> Result<(), S3Error>;
+    /// Policy document or None to remove it, returns `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_policy(&self, bucket: &str, policy: Option<&str>) -> Result<(), S3Error>;
+
+    // public access block
+    async fn put_public_access_block(&self, bucket: &str, config: &PublicAccessBlock) -> Result<(), S3Error>;
+    async fn get_public_access_block(&self, bucket: &str) -> Result<Option<PublicAccessBlock>, S3Error>;
+    async fn delete_public_access_block(&self, bucket: &str) -> Result<(), S3Error>;
+
+    // maintenance
+    /// Returns `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_read_only(&self, bucket: &str, read_only: bool) -> Result<(), S3Error>;
+    async fn list_read_only_buckets(&self) -> Result<Vec<String>, S3Error>;
+    /// Spread the keys of the bucket over `shards` hash shards of the key index and move the existing keys.
+    /// Returns the number of moved keys or `NoSuchBucket` if the bucket does not exist
+    async fn set_bucket_key_shards(&self, bucket: &str, shards: i16) -> Result<u64, S3Error>;
+    /// Hide the bucket from clients and remove it with everything it contains in the background.
+    /// Returns `NoSuchBucket` if the bucket does not exist
+    async fn schedule_bucket_deletion(&self, bucket: &str) -> Result<(), S3Error>;
+    /// Give the bucket to another user. Returns `NoSuchBucket` if the bucket does not exist
+    async fn transfer_bucket(&self, bucket: &str, owner: &str) -> Result<(), S3Error>;
+    /// Rename the bucket with everything it contains, blobs are not touched. Returns `NoSuchBucket`
+    /// if the bucket does not exist and `BucketAlreadyExists` if the name is taken
+    async fn rename_bucket(&self, bucket: &str, new_name: &str) -> Result<(), S3Error>;
+    async fn list_deleting_buckets(&self) -> anyhow::Result<Vec<String>>;
+    /// Remove up to `limit` objects and multipart uploads of a deleted bucket and send their blobs to the GC,
+    /// returns the number of removed entries
+    async fn purge_bucket(&self, bucket: &str, limit: i64) -> anyhow::Result<u64>;
+    /// Drop a deleted bucket, returns false if it is not empty yet
+    async fn drop_purged_bucket(&self, bucket: &str) -> anyhow::Result<bool>;
+
+    // online data migrations
+    async fn get_migration_job(&self, name: &str) -> anyhow::Result<Option<MigrationJob>>;
+    async fn save_migration_job(&self, job: &MigrationJob) -> anyhow::Result<()>;
+    /// Migrate up to `limit` rows after the checkpoint. Batches must be idempotent, a batch is repeated
+    /// if the gateway stops before the checkpoint is saved.
+    async fn run_migration_batch(
+        &self,
+        migration: &DataMigration,
+        checkpoint: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<MigrationBatch>;
+
+    // batch jobs
+    /// Store the job together with the keys of the manifest, returns the number of keys
+    async fn create_batch_job(&self, job: &BatchJob, manifest: &BatchManifest) -> Result<i64, S3Error>;
+    async fn get_batch_job(&self, id: &Uuid) -> Result<Option<BatchJob>, S3Error>;
+    /// Lease the oldest active job which is not held by another worker
+    async fn claim_batch_job(&self, lease: std::time::Duration) -> anyhow::Result<Option<BatchJob>>;
+    /// Up to `limit` keys of the job which have not been processed yet, in key order
+    async fn list_pending_batch_tasks(&self, job_id: &Uuid, limit: i64) -> anyhow::Result<Vec<String>>;
+    /// Record the outcome of processed keys and extend the lease of the job
+    async fn complete_batch_tasks(
+        &self,
+        job_id: &Uuid,
+        results: &[BatchTaskResult],
+        lease: std::time::Duration,
+    ) -> anyhow::Result<()>;
+    /// Up to `limit` processed keys after `after` in key order
+    async fn list_batch_task_results(
+        &self,
+        job_id: &Uuid,
+        after: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<BatchTaskResult>>;
+    /// Complete or Failed
+    async fn finish_batch_job(&self, job_id: &Uuid, status: &str, report_key: Option<&str>) -> anyhow::Result<()>;
+    /// Replace the tag set of the object, returns `NoSuchKey` if the object does not exist
+    async fn set_object_tagging(&self, bucket: &str, object: &str, tagging: Option<&str>) -> Result<(), S3Error>;
+
+    // diagnostics
+    /// Check that the database responds and collect the backlog of the background workers
+    async fn get_health(&self) -> anyhow::Result<DbHealth>;
+
+    // coordination of gateways
+    /// Take or renew the lease of a worker which must run on a single gateway at a time. Returns false while
+    /// the lease is held by another holder.
+    async fn claim_worker_lease(&self, name: &str, holder: &Uuid, lease: std::time::Duration) -> anyhow::Result<bool>;
+    /// Events published by all gateways. The stream reconnects after a lost connection, events published in
+    /// the meantime are missed.
+    async fn listen_gateway_events(&self) -> anyhow::Result<BoxStream<'static, anyhow::Result<GatewayEvent>>>;
+}
+
+/// Change which other gateways have to apply to their in-process state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GatewayEvent {
+    /// the blob has been removed by the GC
+    BlobRemoved(Uuid),
 }
 
 pub type AccountId = s3s::dto::AccountId;
-pub type Timestamp = time::PrimitiveDateTime;
+/// Stored as `timestamptz`, values read from the database are in UTC
+pub type Timestamp = time::OffsetDateTime;
+
+/// Long-running data changes which are applied in the background after the schema migration
+#[derive(Debug, Clone)]
+pub enum DataMigration {
+    /// Store the region of buckets created before regions were tracked
+    BucketRegion { region: String },
+}
+
+impl DataMigration {
+    /// Unique name of the job in `migration_jobs`
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataMigration::BucketRegion { .. } => "bucket_region",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MigrationJob {
+    pub name: String,
+    pub checkpoint: Option<String>,
+    /// rows processed so far
+    pub processed: i64,
+    pub finished: bool,
+}
+
+#[derive(Debug)]
+pub struct MigrationBatch {
+    /// last row of the batch, None if there was nothing left
+    pub checkpoint: Option<String>,
+    pub processed: i64,
+}
+
+/// Outcome of the recovery of a temporary blob left behind by an interrupted upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TempBlobState {
+    /// the upload has been committed, only the registration was left
+    Committed,
+    /// the data has been handed over to the GC
+    Collected,
+    /// recovered by another instance in the meantime
+    Gone,
+}
+
+/// Database state reported by the admin API
+pub struct DbHealth {
+    /// round trip of a trivial query
+    pub latency: std::time::Duration,
+    pub pool_size: u32,
+    pub pool_idle: usize,
+    pub pool_max_size: u32,
+    /// blobs waiting for removal from the backend including the ones kept in the trash
+    pub blobs_gc: i64,
+    /// blobs which are kept in the trash until their retention expires
+    pub blobs_trash: i64,
+    /// uploads which have not been committed or cleaned up
+    pub temp_blobs: i64,
+    /// registration time of the oldest temp blob
+    pub oldest_temp_blob: Option<Timestamp>,
+    /// blobs the GC has given up on
+    pub blobs_gc_failed: i64,
+    /// objects whose replication has failed
+    pub replication_failed: i64,
+}
+
+/// Blob leased by a GC worker
+#[derive(Debug, Clone)]
+pub struct GcTask {
+    pub blob_id: Uuid,
+    /// number of claims including the current one
+    pub attempts: i32,
+    /// the blob is removed from the cold tier if set
+    pub storage_class: Option<String>,
+}
 
 pub struct User {
     pub id: AccountId,
     pub name: String,
     pub email: String,
+    /// region of new buckets without a location constraint, overrides the region of the gateway
+    pub default_region: Option<String>,
 }
 
 pub struct Key {
@@ -111,12 +500,38 @@ pub struct Bucket {
     pub name: String,
     pub owner: AccountId,
     pub creation_date: Timestamp,
+    /// BucketOwnerEnforced, BucketOwnerPreferred or ObjectWriter
+    pub object_ownership: Option<String>,
+    /// canned ACL
+    pub acl: String,
+    pub object_lock_enabled: bool,
+    /// region the bucket has been created in
+    pub region: Option<String>,
+    /// mutating requests are rejected, set through the admin API
+    pub read_only: bool,
+    /// Enabled or Suspended, None if it has never been set
+    pub accelerate_status: Option<String>,
+    /// BucketOwner or Requester
+    pub request_payer: String,
+    /// tag set in the `x-amz-tagging` form
+    pub tagging: Option<String>,
+    /// policy document, only evaluated by the public access block
+    pub policy: Option<String>,
     //versioning: bool,
     // lc policy
     // notification policy
     // retention_policy
 }
 
+/// Bucket settings which can only be provided on creation
+#[derive(Debug)]
+pub struct CreateBucketOptions {
+    pub object_ownership: Option<String>,
+    pub acl: String,
+    pub object_lock_enabled: bool,
+    pub region: String,
+}
+
 // users
 //  -> id: String (Unique)
 //  -> email: String (Unique)
@@ -163,6 +578,7 @@ pub struct Bucket {
 //  -> part_size: u32,
 //  -> storage_class
 
+#[derive(Clone)]
 pub struct Object {
     pub bucket_name: String,
     pub oid: String,
@@ -173,10 +589,80 @@ pub struct Object {
     pub blob_id: Option<Uuid>,
 
     pub metadata: Option<s3s::dto::Metadata>,
+    /// tag set in the `x-amz-tagging` form
+    pub tagging: Option<String>,
+    /// PENDING, COMPLETE or FAILED if the object is covered by a replication rule
+    pub replication_status: Option<String>,
+    /// last read of the object, only recorded while tiering is enabled and at most once per sampling interval
+    pub last_accessed: Option<Timestamp>,
+    pub content_headers: ContentHeaders,
     // retain_untill
     // legal_hold
 }
 
+/// Expected current object of a conditional write, checked in the transaction which replaces the object.
+/// Conditional writes of the same key are serialized, unconditional ones are not.
+#[derive(Debug, Clone, Default)]
+pub struct WriteCondition {
+    /// `If-Match`, the current object has one of the etags, `*` for any
+    pub if_match: Option<String>,
+    /// `If-None-Match`, `*` if the key must not exist
+    pub if_none_match: Option<String>,
+    /// `x-amz-if-version-id`, the version token of the current object, which is the id of its blob
+    pub version: Option<Uuid>,
+}
+
+impl WriteCondition {
+    /// `current` is the blob and the etag of the current object
+    pub fn is_met(&self, current: Option<(&Uuid, &str)>) -> bool {
+        let etag = current.map(|(_, etag)| etag);
+        let if_match = match (&self.if_match, etag) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(condition), Some(etag)) => crate::service::etag_matches(condition, etag),
+        };
+        let if_none_match = match (&self.if_none_match, etag) {
+            (Some(condition), Some(etag)) => !crate::service::etag_matches(condition, etag),
+            _ => true,
+        };
+        let version = match (&self.version, current) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(version), Some((blob, _))) => version == blob,
+        };
+        if_match && if_none_match && version
+    }
+}
+
+/// Representation headers provided at upload and returned on reads
+#[derive(Debug, Clone, Default)]
+pub struct ContentHeaders {
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub content_language: Option<String>,
+    pub content_disposition: Option<String>,
+    pub cache_control: Option<String>,
+    pub expires: Option<Timestamp>,
+}
+
+/// Attributes of an object set by its writer, they are replaced together with the blob
+#[derive(Debug, Clone, Copy)]
+pub struct WriteAttributes<'a> {
+    pub metadata: Option<&'a s3s::dto::Metadata>,
+    pub tagging: Option<&'a str>,
+    pub headers: &'a ContentHeaders,
+}
+
+impl<'a> From<&'a Object> for WriteAttributes<'a> {
+    fn from(object: &'a Object) -> Self {
+        Self {
+            metadata: object.metadata.as_ref(),
+            tagging: object.tagging.as_deref(),
+            headers: &object.content_headers,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Blob {
     pub id: Uuid,
@@ -187,13 +673,30 @@ pub struct Blob {
     /// size of a single part (excluding the last one)
     pub part_size: Option<i64>,
     pub upload_timestamp: Timestamp,
-    //storage_class: String,
+    /// storage class of a blob moved to the cold tier, blobs in the standard backend have none
+    pub storage_class: Option<String>,
     /// MD5 or MD5 of all the parts
     ///
     /// TODO: select better database type
     pub etag: String,
-    // pub checksum_algorithm: Option<String>,
-    // pub checksum: Option<String>,
+    /// additional checksum of the whole data, composite for multipart blobs
+    pub checksum: Option<Checksum>,
+}
+
+impl Blob {
+    /// Blob which has not been written yet. Only the id is used until it is committed.
+    pub fn temporary(id: Uuid) -> Self {
+        Self {
+            id,
+            size: 0,
+            parts: None,
+            part_size: None,
+            upload_timestamp: Timestamp::UNIX_EPOCH,
+            storage_class: None,
+            etag: String::default(),
+            checksum: None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -213,3 +716,232 @@ pub struct ListResult {
     pub marker: Option<String>,
     pub version_marker: Option<String>,
 }
+
+#[derive(Debug, Clone, Default)]
+pub struct BucketStats {
+    pub objects: i64,
+    /// size of the current objects
+    pub bytes: i64,
+    /// size of the uploaded parts of unfinished multipart uploads
+    pub multipart_bytes: i64,
+    /// deleted and overwritten objects waiting for the GC
+    pub gc_objects: i64,
+    pub gc_bytes: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicationConfig {
+    pub role: String,
+    pub rules: Vec<ReplicationRule>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicationRule {
+    pub id: String,
+    /// rules with higher priority win when several prefixes match
+    pub priority: i32,
+    pub prefix: String,
+    pub enabled: bool,
+    pub destination_bucket: String,
+    pub storage_class: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplicationTask {
+    pub blob_id: Uuid,
+    pub bucket: String,
+    pub oid: String,
+    pub destination_bucket: String,
+    pub size: i64,
+}
+
+/// Temporary copy of a cold object in the standard backend
+#[derive(Debug, Clone)]
+pub struct ObjectRestore {
+    pub bucket: String,
+    pub oid: String,
+    /// cold blob while the restore is pending, the copy afterwards
+    pub blob: Uuid,
+    pub days: i32,
+    /// None while the restore is pending
+    pub expires_at: Option<Timestamp>,
+}
+
+/// Unfinished multipart upload
+#[derive(Debug, Clone)]
+pub struct MultipartUpload {
+    pub bucket: String,
+    pub oid: String,
+    /// user who has created the upload, None for uploads created before it was recorded
+    pub initiator: Option<AccountId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultipartPart {
+    pub part_number: i32,
+    /// backend object holding the part data
+    pub blob_id: Uuid,
+    pub size: i64,
+    pub etag: String,
+    pub checksum: Option<Checksum>,
+}
+
+/// Part selected by the client in CompleteMultipartUpload
+#[derive(Debug, Clone)]
+pub struct CompletedPart {
+    pub part_number: i32,
+    pub etag: Option<String>,
+    pub checksum: Option<Checksum>,
+}
+
+/// Additional checksum of the data, the value is base64 encoded as in the `x-amz-checksum-*` headers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// CRC32, CRC32C, SHA1 or SHA256
+    pub algorithm: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlobChecksums {
+    /// composite checksum of a multipart blob
+    pub checksum: Option<Checksum>,
+    /// sizes and checksums of the backend parts in order, empty for single part blobs
+    pub parts: Vec<(i64, Option<String>)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlobPart {
+    pub blob_id: Uuid,
+    pub size: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct InventoryConfig {
+    pub id: String,
+    pub enabled: bool,
+    /// only objects with this prefix are listed
+    pub prefix: Option<String>,
+    pub destination_bucket: String,
+    pub destination_prefix: Option<String>,
+    /// CSV, ORC or Parquet
+    pub format: String,
+    /// Daily or Weekly
+    pub frequency: String,
+    /// All or Current
+    pub included_versions: String,
+}
+
+/// Filter of the request metrics of a bucket, an object must match all of its conditions
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub id: String,
+    pub prefix: Option<String>,
+    /// tag set in the `x-amz-tagging` form
+    pub tagging: Option<String>,
+}
+
+/// Every setting is off for a bucket without a configuration
+#[derive(Debug, Clone, Default)]
+pub struct PublicAccessBlock {
+    pub block_public_acls: bool,
+    pub ignore_public_acls: bool,
+    pub block_public_policy: bool,
+    pub restrict_public_buckets: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct UserPolicy {
+    pub name: String,
+    /// JSON policy document
+    pub document: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct InventoryTask {
+    pub bucket: String,
+    pub config: InventoryConfig,
+    pub started_at: Timestamp,
+}
+
+/// Action applied by a batch job to every key of its manifest
+#[derive(Debug, Clone)]
+pub enum BatchOperation {
+    /// Copy the objects to `<destination prefix><key>` of the destination bucket
+    Copy {
+        destination_bucket: String,
+        destination_prefix: String,
+    },
+    /// Replace the tag set of the objects, None removes it
+    Tag {
+        tagging: Option<String>,
+    },
+    Delete,
+    /// Move the objects from the cold tier back to the standard backend
+    RestoreTier,
+}
+
+impl BatchOperation {
+    /// Name stored in `batch_jobs`
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Copy { .. } => "copy",
+            BatchOperation::Tag { .. } => "tag",
+            BatchOperation::Delete => "delete",
+            BatchOperation::RestoreTier => "restore_tier",
+        }
+    }
+}
+
+/// Keys a batch job is applied to, they are resolved when the job is submitted
+#[derive(Debug, Clone)]
+pub enum BatchManifest {
+    Keys(Vec<String>),
+    /// every object under the prefix
+    Prefix(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    pub id: Uuid,
+    pub bucket: String,
+    pub operation: BatchOperation,
+    /// the report is written to `<report prefix>job-<id>/report.csv` of this bucket, no report if not set
+    pub report_bucket: Option<String>,
+    pub report_prefix: String,
+    /// Active, Complete or Failed
+    pub status: String,
+    pub total: i64,
+    pub succeeded: i64,
+    pub failed: i64,
+    pub report_key: Option<String>,
+    pub created_at: Timestamp,
+    pub finished_at: Option<Timestamp>,
+}
+
+/// Outcome of a single key of a batch job, the error is None on success
+#[derive(Debug, Clone)]
+pub struct BatchTaskResult {
+    pub oid: String,
+    pub error: Option<String>,
+}
+
+/// Blob of a deleted or overwritten object which can be restored until the trash retention expires
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    pub oid: String,
+    pub blob_id: Uuid,
+    pub size: i64,
+    pub etag: String,
+    /// derived from the current trash retention, it is off if the retention has been changed since
+    pub deleted_at: Timestamp,
+}
+
+#[derive(Debug)]
+pub struct TrashRestoreBatch {
+    pub restored: u64,
+    /// keys which exist again
+    pub skipped: u64,
+    /// last key of the batch, None if there are no more keys
+    pub marker: Option<String>,
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blob_store::BlobStore;
+use crate::inventory;
+use crate::meta_store::{Blob, Bucket, MetaStore, Object, Timestamp};
+use crate::parquet::{ParquetFile, Values};
+
+const ROWS_PER_FILE: i64 = 100_000;
+
+/// Periodically dumps the metadata of every bucket into Parquet files in the destination bucket, so that
+/// the namespace can be analyzed with DuckDB or Trino without queries against the metadata database.
+///
+/// Files of a run are written to `<prefix>/<bucket>/<YYYY-MM-DDTHH-MMZ>/part-NNNNN.parquet`, one row per object.
+/// The destination bucket itself is not exported.
+pub struct MetadataExporter {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    destination: String,
+    prefix: String,
+    interval: Duration,
+}
+
+impl MetadataExporter {
+    pub fn new(
+        db: Arc<dyn MetaStore>,
+        blob: Arc<dyn BlobStore>,
+        destination: String,
+        prefix: String,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            db,
+            blob,
+            destination,
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            interval,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            match self.export_all().await {
+                Ok(()) => tracing::info!(destination = %self.destination, "metadata has been exported"),
+                Err(err) => tracing::error!(error = %err, destination = %self.destination, "unable to export metadata"),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn export_all(&self) -> anyhow::Result<()> {
+        let Some(destination) = self.db.get_bucket_metadata(&self.destination).await? else {
+            anyhow::bail!("destination bucket {} does not exist", self.destination);
+        };
+
+        let started_at = Timestamp::now_utc();
+        let run = format!(
+            "{:04}-{:02}-{:02}T{:02}-{:02}Z",
+            started_at.year(),
+            started_at.month() as u8,
+            started_at.day(),
+            started_at.hour(),
+            started_at.minute()
+        );
+        for bucket in self.db.list_all_buckets(None).await? {
+            if bucket.name == destination.name {
+                continue;
+            }
+            // a failed bucket must not stop the export of the others
+            if let Err(err) = self.export_bucket(&destination, &bucket.name, &run).await {
+                tracing::error!(error = %err, bucket = %bucket.name, "unable to export bucket metadata");
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, destination))]
+    async fn export_bucket(&self, destination: &Bucket, bucket: &str, run: &str) -> anyhow::Result<()> {
+        let root = match self.prefix.as_str() {
+            "" => format!("{bucket}/{run}"),
+            prefix => format!("{prefix}/{bucket}/{run}"),
+        };
+
+        let mut marker = String::new();
+        let mut part = 0;
+        loop {
+            let rows = self.db.export_objects(bucket, &marker, ROWS_PER_FILE).await?;
+            let Some((last, _)) = rows.last() else {
+                break;
+            };
+            marker = last.oid.clone();
+            let key = format!("{root}/part-{part:05}.parquet");
+            inventory::write_object(self.db.as_ref(), self.blob.as_ref(), destination, &key, &to_parquet(&rows)).await?;
+            part += 1;
+            if (rows.len() as i64) < ROWS_PER_FILE {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn to_parquet(rows: &[(Object, Option<Blob>)]) -> Vec<u8> {
+    fn micros(timestamp: &Timestamp) -> i64 {
+        (timestamp.unix_timestamp_nanos() / 1000) as i64
+    }
+    let string = |f: fn(&Object, Option<&Blob>) -> Option<String>| {
+        Values::Utf8(rows.iter().map(|(object, blob)| f(object, blob.as_ref())).collect())
+    };
+
+    ParquetFile::default()
+        .column("key", string(|object, _| Some(object.oid.clone())))
+        .column(
+            "size",
+            Values::Int64(rows.iter().map(|(_, blob)| blob.as_ref().map(|b| b.size)).collect()),
+        )
+        .column(
+            "last_modified",
+            Values::Timestamp(rows.iter().map(|(object, _)| Some(micros(&object.last_modified))).collect()),
+        )
+        .column("etag", string(|_, blob| blob.map(|b| b.etag.clone())))
+        .column(
+            "storage_class",
+            string(|_, blob| blob.map(|b| b.storage_class.clone().unwrap_or_else(|| "STANDARD".to_owned()))),
+        )
+        .column("blob_id", string(|object, _| object.blob_id.map(|id| id.to_string())))
+        .column(
+            "parts",
+            Values::Int64(
+                rows.iter()
+                    .map(|(_, blob)| blob.as_ref().and_then(|b| b.parts).map(i64::from))
+                    .collect(),
+            ),
+        )
+        .column(
+            "uploaded_at",
+            Values::Timestamp(
+                rows.iter()
+                    .map(|(_, blob)| blob.as_ref().map(|b| micros(&b.upload_timestamp)))
+                    .collect(),
+            ),
+        )
+        .column("content_type", string(|object, _| object.content_headers.content_type.clone()))
+        .column("tagging", string(|object, _| object.tagging.clone()))
+        .column("replication_status", string(|object, _| object.replication_status.clone()))
+        .column(
+            "last_accessed",
+            Values::Timestamp(
+                rows.iter()
+                    .map(|(object, _)| object.last_accessed.as_ref().map(micros))
+                    .collect(),
+            ),
+        )
+        .to_bytes()
+}
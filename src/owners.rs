@@ -0,0 +1,44 @@
+//! Owner blocks of responses.
+//!
+//! Responses name owners by their canonical id and the name of the user as the display name. Names are cached for
+//! a while, listings with `fetch-owner` would otherwise cost a query per page.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use s3s::dto::Owner;
+use s3s::S3Result;
+
+use crate::meta_store::MetaStore;
+
+const TTL: Duration = Duration::from_secs(60);
+const MAX_CACHED_OWNERS: usize = 10_000;
+
+#[derive(Debug, Default)]
+pub struct Owners {
+    /// display names by user id, None for users which do not exist (anymore)
+    cache: Mutex<HashMap<String, (Instant, Option<String>)>>,
+}
+
+impl Owners {
+    pub async fn resolve(&self, db: &dyn MetaStore, id: &str) -> S3Result<Owner> {
+        let cached = self.cache.lock().expect("unable to lock mutex").get(id).cloned();
+        let display_name = match cached {
+            Some((expires_at, display_name)) if expires_at > Instant::now() => display_name,
+            _ => {
+                let display_name = db.get_user(id).await?.map(|user| user.name);
+                let mut cache = self.cache.lock().expect("unable to lock mutex");
+                if cache.len() >= MAX_CACHED_OWNERS {
+                    cache.clear();
+                }
+                cache.insert(id.to_owned(), (Instant::now() + TTL, display_name.clone()));
+                display_name
+            }
+        };
+        Ok(Owner {
+            display_name,
+            id: Some(id.to_owned()),
+        })
+    }
+}
@@ -0,0 +1,281 @@
+//! Minimal Parquet writer for metadata exports.
+//!
+//! Files have a single row group with one uncompressed PLAIN encoded data page per column. All columns are
+//! optional so that the definition levels are always present. The footer is encoded with the Thrift compact
+//! protocol, only the fields required by readers such as DuckDB, Trino and pyarrow are written.
+
+const MAGIC: &[u8] = b"PAR1";
+
+// parquet.thrift constants
+const TYPE_INT64: i32 = 2;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MICROS: i32 = 10;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+// Thrift compact protocol types
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+pub enum Values {
+    Int64(Vec<Option<i64>>),
+    /// microseconds since the unix epoch, UTC
+    Timestamp(Vec<Option<i64>>),
+    Utf8(Vec<Option<String>>),
+}
+
+impl Values {
+    fn len(&self) -> usize {
+        match self {
+            Self::Int64(v) | Self::Timestamp(v) => v.len(),
+            Self::Utf8(v) => v.len(),
+        }
+    }
+
+    fn physical_type(&self) -> i32 {
+        match self {
+            Self::Int64(_) | Self::Timestamp(_) => TYPE_INT64,
+            Self::Utf8(_) => TYPE_BYTE_ARRAY,
+        }
+    }
+
+    fn converted_type(&self) -> Option<i32> {
+        match self {
+            Self::Int64(_) => None,
+            Self::Timestamp(_) => Some(CONVERTED_TIMESTAMP_MICROS),
+            Self::Utf8(_) => Some(CONVERTED_UTF8),
+        }
+    }
+
+    /// Definition levels followed by the present values
+    fn encode_page(&self) -> Vec<u8> {
+        let defined: Vec<bool> = match self {
+            Self::Int64(v) | Self::Timestamp(v) => v.iter().map(Option::is_some).collect(),
+            Self::Utf8(v) => v.iter().map(Option::is_some).collect(),
+        };
+        let levels = encode_levels(&defined);
+        let mut page = Vec::with_capacity(4 + levels.len());
+        page.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+        page.extend_from_slice(&levels);
+        match self {
+            Self::Int64(v) | Self::Timestamp(v) => {
+                for value in v.iter().flatten() {
+                    page.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Self::Utf8(v) => {
+                for value in v.iter().flatten() {
+                    page.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    page.extend_from_slice(value.as_bytes());
+                }
+            }
+        }
+        page
+    }
+}
+
+/// Columns of a file, all of them must have the same number of values
+#[derive(Default)]
+pub struct ParquetFile {
+    columns: Vec<(&'static str, Values)>,
+}
+
+impl ParquetFile {
+    pub fn column(mut self, name: &'static str, values: Values) -> Self {
+        self.columns.push((name, values));
+        self
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let rows = self.columns.first().map_or(0, |(_, values)| values.len());
+        let mut out = MAGIC.to_vec();
+        // offset and size of every column chunk
+        let mut chunks = Vec::with_capacity(self.columns.len());
+        for (_, values) in &self.columns {
+            debug_assert_eq!(values.len(), rows);
+            let page = values.encode_page();
+            let mut header = Thrift::default();
+            header.i32(1, PAGE_DATA);
+            header.i32(2, page.len() as i32);
+            header.i32(3, page.len() as i32);
+            header.begin_struct(5);
+            header.i32(1, rows as i32);
+            header.i32(2, ENCODING_PLAIN);
+            header.i32(3, ENCODING_RLE);
+            header.i32(4, ENCODING_RLE);
+            header.end_struct();
+            let header = header.finish();
+
+            let offset = out.len() as i64;
+            out.extend_from_slice(&header);
+            out.extend_from_slice(&page);
+            chunks.push((offset, (header.len() + page.len()) as i64));
+        }
+
+        let mut meta = Thrift::default();
+        meta.i32(1, 1);
+        meta.begin_list(2, T_STRUCT, self.columns.len() + 1);
+        meta.begin_element();
+        meta.binary(4, b"schema");
+        meta.i32(5, self.columns.len() as i32);
+        meta.end_struct();
+        for (name, values) in &self.columns {
+            meta.begin_element();
+            meta.i32(1, values.physical_type());
+            meta.i32(3, REPETITION_OPTIONAL);
+            meta.binary(4, name.as_bytes());
+            if let Some(converted) = values.converted_type() {
+                meta.i32(6, converted);
+            }
+            meta.end_struct();
+        }
+        meta.i64(3, rows as i64);
+        meta.begin_list(4, T_STRUCT, 1);
+        meta.begin_element();
+        meta.begin_list(1, T_STRUCT, self.columns.len());
+        for ((name, values), (offset, size)) in self.columns.iter().zip(&chunks) {
+            meta.begin_element();
+            meta.i64(2, *offset);
+            meta.begin_struct(3);
+            meta.i32(1, values.physical_type());
+            meta.begin_list(2, T_I32, 2);
+            meta.list_i32(ENCODING_PLAIN);
+            meta.list_i32(ENCODING_RLE);
+            meta.begin_list(3, T_BINARY, 1);
+            meta.list_binary(name.as_bytes());
+            meta.i32(4, CODEC_UNCOMPRESSED);
+            meta.i64(5, rows as i64);
+            meta.i64(6, *size);
+            meta.i64(7, *size);
+            meta.i64(9, *offset);
+            meta.end_struct();
+            meta.end_struct();
+        }
+        meta.i64(2, chunks.iter().map(|(_, size)| size).sum());
+        meta.i64(3, rows as i64);
+        meta.end_struct();
+        meta.binary(6, concat!(env!("CARGO_PKG_NAME"), " version ", env!("CARGO_PKG_VERSION")).as_bytes());
+        let meta = meta.finish();
+
+        out.extend_from_slice(&meta);
+        out.extend_from_slice(&(meta.len() as u32).to_le_bytes());
+        out.extend_from_slice(MAGIC);
+        out
+    }
+}
+
+/// RLE runs of the RLE/bit-packing hybrid encoding with a bit width of 1
+fn encode_levels(defined: &[bool]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut rest = defined;
+    while let Some(&first) = rest.first() {
+        let run = rest.iter().take_while(|&&d| d == first).count();
+        varint(&mut out, (run as u64) << 1);
+        out.push(first as u8);
+        rest = &rest[run..];
+    }
+    out
+}
+
+fn varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Thrift compact protocol encoder of a single top-level struct
+struct Thrift {
+    buf: Vec<u8>,
+    /// id of the last field of every open struct, field ids are encoded as deltas
+    last_ids: Vec<i16>,
+}
+
+impl Default for Thrift {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            last_ids: vec![0],
+        }
+    }
+}
+
+impl Thrift {
+    fn field(&mut self, id: i16, ty: u8) {
+        let last = self.last_ids.last_mut().expect("an open struct");
+        let delta = id - *last;
+        if (1..=15).contains(&delta) {
+            self.buf.push(((delta as u8) << 4) | ty);
+        } else {
+            self.buf.push(ty);
+            varint(&mut self.buf, zigzag(id as i64));
+        }
+        *last = id;
+    }
+
+    fn i32(&mut self, id: i16, value: i32) {
+        self.field(id, T_I32);
+        varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn i64(&mut self, id: i16, value: i64) {
+        self.field(id, T_I64);
+        varint(&mut self.buf, zigzag(value));
+    }
+
+    fn binary(&mut self, id: i16, value: &[u8]) {
+        self.field(id, T_BINARY);
+        self.list_binary(value);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.last_ids.push(0);
+    }
+
+    fn end_struct(&mut self) {
+        self.buf.push(0);
+        self.last_ids.pop();
+    }
+
+    fn begin_list(&mut self, id: i16, element: u8, len: usize) {
+        self.field(id, T_LIST);
+        if len < 15 {
+            self.buf.push(((len as u8) << 4) | element);
+        } else {
+            self.buf.push(0xf0 | element);
+            varint(&mut self.buf, len as u64);
+        }
+    }
+
+    /// Struct element of a list, closed with `end_struct`
+    fn begin_element(&mut self) {
+        self.last_ids.push(0);
+    }
+
+    fn list_i32(&mut self, value: i32) {
+        varint(&mut self.buf, zigzag(value as i64));
+    }
+
+    fn list_binary(&mut self, value: &[u8]) {
+        varint(&mut self.buf, value.len() as u64);
+        self.buf.extend_from_slice(value);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.buf.push(0);
+        self.buf
+    }
+}
@@ -1,32 +1,95 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use md5::{Digest, Md5};
+use opentelemetry::KeyValue;
+use s3s::s3_error;
 use sqlx::pool::PoolConnection;
+use sqlx::ConnectOptions;
+use tokio::sync::{mpsc, oneshot};
+use tracing::instrument::Instrumented;
 use tracing::{debug_span, Instrument};
 use uuid::Uuid;
 
-use crate::meta_store::{Blob, Bucket, MetaStore, MetaStoreError, Object, Transaction, TransactionError};
-use crate::meta_store::{ListOptions, ListResult, User};
-use sqlx::postgres::PgPool;
+use crate::clock::{Clock, IdGenerator, RandomIds, SystemClock};
+use crate::error::StartupError;
+use crate::meta_store::{
+    BatchJob, BatchManifest, BatchOperation, BatchTaskResult, BucketStats, TempBlobState, TrashEntry, TrashRestoreBatch,
+};
+use crate::meta_store::{
+    Blob, BlobChecksums, Bucket, Checksum, ContentHeaders, MetaStore, MetaStoreError, Object, Transaction, TransactionError,
+};
+use crate::meta_store::{
+    BlobPart, CompletedPart, CreateBucketOptions, DataMigration, DbHealth, GatewayEvent, GcTask, InventoryConfig, InventoryTask,
+    MultipartPart, MultipartUpload, ObjectRestore, WriteAttributes,
+};
+use crate::meta_store::{
+    ListOptions, ListResult, MetricsConfig, MigrationBatch, MigrationJob, PublicAccessBlock, ReplicationConfig, ReplicationRule,
+    ReplicationTask, User, UserPolicy, WriteCondition,
+};
+use sqlx::postgres::{PgConnectOptions, PgListener, PgPool, PgRow};
 use sqlx::Row;
 use sqlx::{Connection, PgConnection, Postgres};
 
+/// Debug span of a query which also names the query in the duration metric
+macro_rules! query_span {
+    ($name:literal) => {
+        ($name, debug_span!($name))
+    };
+}
+
+#[derive(Clone)]
 pub struct PostgresDatabase {
     db_conn: PgPool,
+    /// how long deleted blobs are kept in the backend
+    trash_retention: Duration,
+    retry: RetryPolicy,
+    clock: Arc<dyn Clock>,
+    ids: Arc<dyn IdGenerator>,
+    /// commits of single blob objects are grouped into shared transactions if set
+    commit_batches: Option<mpsc::Sender<PendingCommit>>,
+}
+
+/// Commit of an uploaded object waiting for its batch. The sender is dropped without a result if the batch
+/// can not be committed, the object is committed on its own then.
+struct PendingCommit {
+    object: Object,
+    blob: Blob,
+    condition: Option<WriteCondition>,
+    done: oneshot::Sender<Result<crate::meta_store::Timestamp, s3s::S3Error>>,
+}
+
+/// Transactions which fail with a serialization failure or a deadlock are run again
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// total number of runs, 1 disables retries
+    pub attempts: u32,
+    /// delay before the first retry, it doubles with every retry and is randomized by up to a half
+    pub backoff: Duration,
 }
 
 impl PostgresDatabase {
-    pub async fn new() -> Self {
+    /// Statements slower than `slow_query_threshold` are logged with their SQL, bind parameters are never logged
+    pub async fn new(
+        trash_retention: Duration,
+        slow_query_threshold: Duration,
+        retry: RetryPolicy,
+    ) -> Result<Self, StartupError> {
         let url = "postgresql://localhost:5433/?user=yugabyte&password=yugabyte";
-        let mut conn = sqlx::PgConnection::connect(url)
-            .await
-            .expect("unable to connect to the database");
+        let mut conn = sqlx::PgConnection::connect(url).await?;
 
         // TODO: replace with config values
         let res = sqlx::query("SELECT * FROM pg_catalog.pg_database WHERE datname = $1")
             .bind("s3srados")
             .fetch_optional(&mut conn)
-            .await
-            .expect("");
+            .await?;
         if res.is_none() {
             tracing::info!("database not found... creating one");
             sqlx::query(
@@ -42,97 +105,481 @@ impl PostgresDatabase {
             "#,
             )
             .execute(&mut conn)
-            .await
-            .expect("unable to create database");
+            .await?;
             tracing::info!("database was created successfully");
         }
 
         let url = "postgresql://localhost:5433/s3srados?user=yugabyte&password=yugabyte";
-        let pool = PgPool::connect(url).await.expect("Unable to establish database connection");
+        let options = url
+            .parse::<PgConnectOptions>()?
+            .log_slow_statements(log::LevelFilter::Warn, slow_query_threshold);
+        let pool = PgPool::connect_with(options).await?;
+        crate::telemetry::observe_db_pool(&pool);
 
         tracing::info!("starting database migration");
-        sqlx::migrate!("./migrations")
-            .run(&pool)
-            .await
-            .expect("unable to perform migrations");
+        sqlx::migrate!("./migrations").run(&pool).await?;
         tracing::info!("finished database migration");
 
-        Self { db_conn: pool }
+        Ok(Self {
+            db_conn: pool,
+            trash_retention,
+            retry,
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+            commit_batches: None,
+        })
+    }
+
+    /// Take the time and new identifiers from the given sources instead of the system
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>, ids: Arc<dyn IdGenerator>) -> Self {
+        self.clock = clock;
+        self.ids = ids;
+        self
     }
-}
 
-impl Debug for PostgresDatabase {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("PgDatabase").finish()
+    /// Group the commits of objects uploaded with a single PutObject into one transaction per batch. A batch
+    /// is committed once it has `max_batch` objects or `window` after its first object, whichever comes first.
+    /// Objects of a batch are isolated by savepoints, a failed object does not fail the others.
+    pub fn with_commit_batching(mut self, window: Duration, max_batch: usize) -> Self {
+        let (sender, receiver) = mpsc::channel(max_batch * 4);
+        tokio::spawn(self.clone().collect_commit_batches(receiver, window, max_batch));
+        self.commit_batches = Some(sender);
+        self
     }
-}
 
-#[async_trait::async_trait]
-impl MetaStore for PostgresDatabase {
-    async fn write_object_metadata_with_blob(&self, bucket: &Bucket, object: &Object, blob: &Blob) -> Result<(), s3s::S3Error> {
-        let mut tx = try_!(self.db_conn.begin().instrument(debug_span!("db_begin_transaction")).await);
+    async fn collect_commit_batches(self, mut receiver: mpsc::Receiver<PendingCommit>, window: Duration, max_batch: usize) {
+        while let Some(first) = receiver.recv().await {
+            let deadline = tokio::time::Instant::now() + window;
+            let mut batch = vec![first];
+            while batch.len() < max_batch {
+                match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                    Ok(Some(commit)) => batch.push(commit),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+            // the next batch is collected while this one is committed
+            let db = self.clone();
+            tokio::spawn(async move { db.commit_batch(batch).await });
+        }
+    }
+
+    async fn commit_batch(&self, batch: Vec<PendingCommit>) {
+        let commits = &batch;
+        let res = self
+            .retry("commit_object_batch", || async move {
+                let mut tx = try_!(
+                    self.db_conn
+                        .begin()
+                        .instrument_query(query_span!("db_begin_transaction"))
+                        .await
+                );
+                let mut results = Vec::with_capacity(commits.len());
+                for commit in commits {
+                    let mut savepoint = try_!(tx.begin().await);
+                    let res = self
+                        .insert_object_with_blob(&mut savepoint, &commit.object, &commit.blob, commit.condition.as_ref())
+                        .await;
+                    if res.is_ok() {
+                        try_!(savepoint.commit().await);
+                    } else {
+                        try_!(savepoint.rollback().await);
+                    }
+                    results.push(res);
+                }
+                try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+                Ok(results)
+            })
+            .await;
+        match res {
+            Ok(results) => {
+                tracing::debug!(objects = batch.len(), "object batch has been committed");
+                for (commit, res) in batch.into_iter().zip(results) {
+                    let _ = commit.done.send(res);
+                }
+            }
+            Err(err) => {
+                let source = err.source().map(ToString::to_string).unwrap_or_default();
+                tracing::warn!(objects = batch.len(), error = source, "object batch has failed, committing one by one");
+            }
+        }
+    }
+
+    /// Make the uploaded blob permanent and point the object to it
+    async fn insert_object_with_blob(
+        &self,
+        conn: &mut PgConnection,
+        object: &Object,
+        blob: &Blob,
+        condition: Option<&WriteCondition>,
+    ) -> Result<crate::meta_store::Timestamp, s3s::S3Error> {
+        if let Some(condition) = condition {
+            self.check_write_condition(&mut *conn, &object.bucket_name, &object.oid, condition)
+                .await?;
+        }
         try_!(
             sqlx::query("DELETE FROM temp_blobs WHERE blob_id = $1;")
-                .bind(&blob.id)
-                .execute(&mut *tx)
-                .instrument(debug_span!("db_remove_temp_blob"))
+                .bind(blob.id)
+                .execute(&mut *conn)
+                .instrument_query(query_span!("db_remove_temp_blob"))
                 .await
         );
 
         // check etag not empty
         try_!(
-            sqlx::query("INSERT INTO blobs (id, size, uploaded_at, etag) VALUES ($1, $2, CURRENT_TIMESTAMP, $3);")
-                .bind(&blob.id)
-                .bind(blob.size)
-                .bind(&blob.etag)
-                .execute(&mut *tx)
-                .instrument(debug_span!("db_insert_permanent_blob"))
+            sqlx::query(
+                "INSERT INTO blobs (id, size, uploaded_at, etag, checksum_algorithm, checksum) VALUES ($1, $2, $4, $3, $5, $6);"
+            )
+            .bind(blob.id)
+            .bind(blob.size)
+            .bind(&blob.etag)
+            .bind(self.clock.now())
+            .bind(blob.checksum.as_ref().map(|c| &c.algorithm))
+            .bind(blob.checksum.as_ref().map(|c| &c.value))
+            .execute(&mut *conn)
+            .instrument_query(query_span!("db_insert_permanent_blob"))
+            .await
+        );
+
+        self.replace_object(&mut *conn, &object.bucket_name, &object.oid, &blob.id, object.into())
+            .await
+    }
+
+    /// Compare the current object with the condition. The key stays locked until the end of the transaction,
+    /// so the object can not be replaced by another conditional write in the meantime.
+    async fn check_write_condition(
+        &self,
+        conn: &mut PgConnection,
+        bucket: &str,
+        oid: &str,
+        condition: &WriteCondition,
+    ) -> Result<(), s3s::S3Error> {
+        // a row lock would not cover a key which does not exist yet
+        try_!(
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1 || '/' || $2, 0))")
+                .bind(bucket)
+                .bind(oid)
+                .execute(&mut *conn)
+                .instrument_query(query_span!("db_lock_object_key"))
                 .await
         );
+        let row = try_!(
+            sqlx::query(
+                r#"SELECT blobs.id, blobs.etag FROM objects JOIN blobs ON objects.blob = blobs.id
+                    WHERE objects.bucket = $1 AND objects.shard = object_shard($1, $2) AND objects.oid = $2"#
+            )
+            .bind(bucket)
+            .bind(oid)
+            .fetch_optional(&mut *conn)
+            .instrument_query(query_span!("db_select_current_object"))
+            .await
+        );
+        let current: Option<(Uuid, String)> = match row {
+            Some(row) => Some((try_!(row.try_get("id")), try_!(row.try_get("etag")))),
+            None => None,
+        };
+        if !condition.is_met(current.as_ref().map(|(id, etag)| (id, etag.as_str()))) {
+            return Err(s3_error!(PreconditionFailed, "The current object does not match the condition"));
+        }
+        Ok(())
+    }
+
+    /// Result of an already completed multipart upload
+    async fn load_completed_upload(&self, bucket: &str, object: &str, upload_id: &Uuid) -> Result<Blob, s3s::S3Error> {
+        let res = try_!(
+            sqlx::query(
+                r#"SELECT blobs.* FROM completed_multipart_uploads
+                    JOIN blobs ON completed_multipart_uploads.blob_id = blobs.id
+                    WHERE completed_multipart_uploads.upload_id = $1
+                        AND completed_multipart_uploads.bucket = $2
+                        AND completed_multipart_uploads.oid = $3"#
+            )
+            .bind(upload_id)
+            .bind(bucket)
+            .bind(object)
+            .fetch_optional(&self.db_conn)
+            .instrument_query(query_span!("db_select_completed_upload"))
+            .await
+        );
+        let Some(res) = res else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchUpload));
+        };
+
+        Ok(Blob {
+            id: try_!(res.try_get("id")),
+            size: try_!(res.try_get("size")),
+            parts: try_!(res.try_get("parts")),
+            part_size: try_!(res.try_get("part_size")),
+            upload_timestamp: try_!(res.try_get("uploaded_at")),
+            storage_class: try_!(res.try_get("storage_class")),
+            etag: try_!(res.try_get("etag")),
+            checksum: checksum_from_row(&res)?,
+        })
+    }
+
+    /// Schedule removal of a blob which belonged to the object. It can be undeleted until the retention expires.
+    async fn move_to_trash(&self, conn: &mut PgConnection, bucket: &str, oid: &str, blob_id: &Uuid) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO blobs_gc (id, bucket, oid, not_before)
+                    VALUES ($1, $2, $3, $5 + $4 * INTERVAL '1 second')"#
+            )
+            .bind(blob_id)
+            .bind(bucket)
+            .bind(oid)
+            .bind(self.trash_retention.as_secs() as i64)
+            .bind(self.clock.now())
+            .execute(&mut *conn)
+            .instrument_query(query_span!("db_insert_blob_gc"))
+            .await
+        );
+        Ok(())
+    }
 
+    /// Point the object to the new blob. The previous blob is sent to GC and replication is scheduled if configured.
+    /// Returns the modification time of the object.
+    async fn replace_object(
+        &self,
+        conn: &mut PgConnection,
+        bucket: &str,
+        oid: &str,
+        blob_id: &Uuid,
+        attributes: WriteAttributes<'_>,
+    ) -> Result<crate::meta_store::Timestamp, s3s::S3Error> {
         // TODO: handle versioned
         let old = try_!(
-            sqlx::query("SELECT (blob) FROM objects WHERE objects.bucket = $1 AND objects.oid = $2")
-                .bind(&object.bucket_name)
-                .bind(&object.oid)
-                .fetch_optional(&mut *tx)
-                .instrument(debug_span!("db_fetch_previous_version"))
+            sqlx::query("SELECT (blob) FROM objects WHERE objects.bucket = $1 AND objects.shard = object_shard($1, $2) AND objects.oid = $2")
+                .bind(bucket)
+                .bind(oid)
+                .fetch_optional(&mut *conn)
+                .instrument_query(query_span!("db_fetch_previous_version"))
                 .await
         );
         if let Some(old) = old {
             let old_blob_id: Uuid = old.get("blob");
+            self.move_to_trash(&mut *conn, bucket, oid, &old_blob_id).await?;
             try_!(
-                sqlx::query("INSERT INTO blobs_gc (id) VALUES ($1);")
-                    .bind(&old_blob_id)
-                    .execute(&mut *tx)
-                    .instrument(debug_span!("db_put_old_blob_gc"))
+                sqlx::query(
+                    "DELETE FROM objects WHERE objects.bucket = $1 AND objects.shard = object_shard($1, $2) AND objects.oid = $2"
+                )
+                .bind(bucket)
+                .bind(oid)
+                .execute(&mut *conn)
+                .instrument_query(query_span!("db_delete_old_object"))
+                .await
+            );
+        }
+
+        // pick the replication rule with the highest priority
+        let replication = try_!(
+            sqlx::query(
+                r#"SELECT destination_bucket FROM bucket_replication
+                    WHERE bucket = $1 AND enabled AND LEFT($2, LENGTH(prefix)) = prefix
+                    ORDER BY priority DESC
+                    LIMIT 1"#
+            )
+            .bind(bucket)
+            .bind(oid)
+            .fetch_optional(&mut *conn)
+            .instrument_query(query_span!("db_select_replication_rule"))
+            .await
+        );
+        let replication_status = replication.as_ref().map(|_| s3s::dto::ReplicationStatus::PENDING);
+
+        // TODO: manage object raplacement
+        let metadata = match attributes.metadata {
+            Some(metadata) => Some(try_!(serde_json::to_string(metadata))),
+            None => None,
+        };
+        let headers = attributes.headers;
+        let row = try_!(
+            sqlx::query(
+                r#"INSERT INTO objects (bucket, oid, last_modified, blob, replication_status, metadata, tagging,
+                        content_type, content_encoding, content_language, content_disposition, cache_control, expires)
+                    VALUES ($1, $2, $7, $3, $4, $5, $6, $8, $9, $10, $11, $12, $13)
+                    RETURNING last_modified"#
+            )
+            .bind(bucket)
+            .bind(oid)
+            .bind(blob_id)
+            .bind(replication_status)
+            .bind(metadata)
+            .bind(attributes.tagging)
+            .bind(self.clock.now())
+            .bind(&headers.content_type)
+            .bind(&headers.content_encoding)
+            .bind(&headers.content_language)
+            .bind(&headers.content_disposition)
+            .bind(&headers.cache_control)
+            .bind(headers.expires)
+            .fetch_one(&mut *conn)
+            .instrument_query(query_span!("db_insert_object_info"))
+            .await
+        );
+        let last_modified = try_!(row.try_get("last_modified"));
+
+        if let Some(replication) = replication {
+            let destination_bucket: String = try_!(replication.try_get("destination_bucket"));
+            try_!(
+                sqlx::query(
+                    "INSERT INTO replication_queue (blob_id, bucket, oid, destination_bucket, created_at) VALUES ($1, $2, $3, $4, $5)"
+                )
+                .bind(blob_id)
+                .bind(bucket)
+                .bind(oid)
+                .bind(destination_bucket)
+                .bind(self.clock.now())
+                .execute(&mut *conn)
+                .instrument_query(query_span!("db_enqueue_replication"))
+                .await
+            );
+        }
+
+        Ok(last_modified)
+    }
+
+    /// Run the transaction again while it fails with a retryable error, any other error is returned right away
+    async fn retry<T, F, Fut>(&self, operation: &'static str, mut transaction: F) -> Result<T, s3s::S3Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, s3s::S3Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let err = match transaction().await {
+                Ok(res) => return Ok(res),
+                Err(err) => err,
+            };
+            if !crate::error::is_retryable(&err) {
+                return Err(err);
+            }
+            let source = err.source().map(ToString::to_string).unwrap_or_default();
+            if attempt >= self.retry.attempts {
+                tracing::error!(operation, attempt, error = source, "transaction has failed, giving up");
+                return Err(err);
+            }
+
+            let backoff = self.retry.backoff * 2u32.saturating_pow(attempt - 1);
+            let delay = backoff.mul_f64(0.5 + fastrand::f64() / 2.0);
+            tracing::warn!(operation, attempt, error = source, ?delay, "transaction has failed, retrying");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl Debug for PostgresDatabase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PgDatabase").finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl MetaStore for PostgresDatabase {
+    async fn write_object_metadata_with_blob(
+        &self,
+        bucket: &Bucket,
+        object: &Object,
+        blob: &Blob,
+        condition: Option<&WriteCondition>,
+    ) -> Result<crate::meta_store::Timestamp, s3s::S3Error> {
+        if let Some(batches) = &self.commit_batches {
+            let (done, result) = oneshot::channel();
+            let commit = PendingCommit {
+                object: object.clone(),
+                blob: blob.clone(),
+                condition: condition.cloned(),
+                done,
+            };
+            if batches.send(commit).await.is_ok() {
+                match result.await {
+                    // e.g. a deadlock with another batch, the retries below apply
+                    Ok(Err(err)) if crate::error::is_retryable(&err) => {}
+                    Ok(res) => return res,
+                    Err(_) => {}
+                }
+            }
+        }
+
+        self.retry("write_object_metadata_with_blob", || async move {
+            let mut tx = try_!(
+                self.db_conn
+                    .begin()
+                    .instrument_query(query_span!("db_begin_transaction"))
                     .await
             );
+            let last_modified = self.insert_object_with_blob(&mut tx, object, blob, condition).await?;
+            try_!(tx.commit().await);
+            Ok(last_modified)
+        })
+        .await
+    }
+
+    async fn write_object_metadata_with_parts(
+        &self,
+        _bucket: &Bucket,
+        object: &Object,
+        blob: &Blob,
+        parts: &[BlobPart],
+        condition: Option<&WriteCondition>,
+    ) -> Result<crate::meta_store::Timestamp, s3s::S3Error> {
+        self.retry("write_object_metadata_with_parts", || async move {
+            let mut tx = try_!(
+                self.db_conn
+                    .begin()
+                    .instrument_query(query_span!("db_begin_transaction"))
+                    .await
+            );
+            if let Some(condition) = condition {
+                self.check_write_condition(&mut tx, &object.bucket_name, &object.oid, condition)
+                    .await?;
+            }
+            let part_ids: Vec<Uuid> = parts.iter().map(|p| p.blob_id).collect();
             try_!(
-                sqlx::query("DELETE FROM objects WHERE objects.bucket = $1 AND objects.oid = $2")
-                    .bind(&object.bucket_name)
-                    .bind(&object.oid)
+                sqlx::query("DELETE FROM temp_blobs WHERE blob_id = ANY($1);")
+                    .bind(&part_ids)
                     .execute(&mut *tx)
-                    .instrument(debug_span!("db_delete_old_object"))
+                    .instrument_query(query_span!("db_remove_temp_blobs"))
                     .await
             );
-        }
 
-        // TODO: manage object raplacement
-        try_!(
-            sqlx::query("INSERT INTO objects (bucket, oid, last_modified, blob) VALUES ($1, $2, CURRENT_TIMESTAMP, $3)")
-                .bind(&object.bucket_name)
-                .bind(&object.oid)
-                .bind(&blob.id)
+            try_!(
+                sqlx::query(
+                    r#"INSERT INTO blobs (id, size, parts, part_size, uploaded_at, etag, checksum_algorithm, checksum)
+                    VALUES ($1, $2, $3, $4, $6, $5, $7, $8)"#
+                )
+                .bind(blob.id)
+                .bind(blob.size)
+                .bind(blob.parts)
+                .bind(blob.part_size)
+                .bind(&blob.etag)
+                .bind(self.clock.now())
+                .bind(blob.checksum.as_ref().map(|c| &c.algorithm))
+                .bind(blob.checksum.as_ref().map(|c| &c.value))
                 .execute(&mut *tx)
-                .instrument(debug_span!("db_insert_object_info"))
+                .instrument_query(query_span!("db_insert_permanent_blob"))
                 .await
-        );
-        // create object or object version
-        // put blob metadata and remove temp_blob
-        //
-        try_!(tx.commit().await);
-        Ok(())
+            );
+            for (index, part) in parts.iter().enumerate() {
+                try_!(
+                    sqlx::query("INSERT INTO blob_parts (blob_id, part_index, part_blob_id, size) VALUES ($1, $2, $3, $4)")
+                        .bind(blob.id)
+                        .bind(index as i32)
+                        .bind(part.blob_id)
+                        .bind(part.size)
+                        .execute(&mut *tx)
+                        .instrument_query(query_span!("db_insert_blob_part"))
+                        .await
+                );
+            }
+
+            let last_modified = self
+                .replace_object(&mut tx, &object.bucket_name, &object.oid, &blob.id, object.into())
+                .await?;
+
+            try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+            Ok(last_modified)
+        })
+        .await
     }
 
     async fn write_object_metadata(
@@ -161,6 +608,7 @@ impl MetaStore for PostgresDatabase {
                         LEFT OUTER JOIN BLOBS ON OBJECTS.BLOB = BLOBS.ID
                     WHERE
                         OBJECTS.BUCKET = $1
+                        AND OBJECTS.SHARD = OBJECT_SHARD($1, $2)
                         AND OBJECTS.OID = $2
                     ORDER BY
                         OBJECTS.LAST_MODIFIED DESC
@@ -176,30 +624,7 @@ impl MetaStore for PostgresDatabase {
         let Some(row) = row else {
             return Ok(None);
         };
-
-        let object = Object {
-            bucket_name: bucket.to_owned(),
-            oid: object.to_owned(),
-            version_id: None, // TODO: handle version
-            last_modified: try_!(row.try_get("last_modified")),
-            blob_id: try_!(row.try_get("blob")),
-            metadata: None, // TODO: handle metadata
-        };
-
-        let blob = if object.blob_id.is_some() {
-            Some(Blob {
-                id: try_!(row.try_get("id")),
-                size: try_!(row.try_get("size")),
-                parts: try_!(row.try_get("parts")),
-                part_size: try_!(row.try_get("part_size")),
-                upload_timestamp: try_!(row.try_get("uploaded_at")),
-                etag: try_!(row.try_get("etag")),
-            })
-        } else {
-            None
-        };
-
-        Ok(Some((object, blob)))
+        Ok(Some(object_from_row(&row)?))
     }
 
     async fn delete_object_metadata(
@@ -208,49 +633,162 @@ impl MetaStore for PostgresDatabase {
         object: &str,
         _version: &Option<s3s::dto::ObjectVersionId>,
     ) -> Result<(), s3s::S3Error> {
-        let mut tx = try_!(self.db_conn.begin().instrument(debug_span!("db_begin_transaction")).await);
+        self.retry("delete_object_metadata", || async move {
+            let mut tx = try_!(
+                self.db_conn
+                    .begin()
+                    .instrument_query(query_span!("db_begin_transaction"))
+                    .await
+            );
+            let row = try_!(
+                sqlx::query("SELECT (blob) FROM objects WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2")
+                    .bind(bucket)
+                    .bind(object)
+                    .fetch_optional(&mut *tx)
+                    .instrument_query(query_span!("db_check_object_exists"))
+                    .await
+            );
+
+            // TODO: Handle versioned
+            // deleting a missing key succeeds like in S3
+            let Some(row) = row else {
+                return Ok(());
+            };
+            let blob: Option<Uuid> = try_!(row.try_get("blob"));
+            if let Some(blob) = blob {
+                self.move_to_trash(&mut tx, bucket, object, &blob).await?;
+            }
+
+            try_!(
+                sqlx::query("DELETE FROM objects WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2")
+                    .bind(bucket)
+                    .bind(object)
+                    .execute(&mut *tx)
+                    .instrument_query(query_span!("db_delete_object"))
+                    .await
+            );
+
+            try_!(tx.commit().await);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_objects_by_prefix(&self, bucket: &str, prefix: &str) -> Result<u64, s3s::S3Error> {
         let row = try_!(
-            sqlx::query("SELECT (blob) FROM objects WHERE bucket = $1 AND oid = $2")
-                .bind(bucket)
-                .bind(object)
-                .fetch_optional(&mut *tx)
-                .instrument(debug_span!("db_check_object_exists"))
-                .await
+            sqlx::query(
+                r#"WITH removed AS (
+                        DELETE FROM objects WHERE bucket = $1 AND shard = ANY(object_shards($1))
+                            AND oid COLLATE "C" >= $2 AND STARTS_WITH(oid, $2)
+                        RETURNING blob, oid
+                    ),
+                    gc AS (INSERT INTO blobs_gc (id, bucket, oid, not_before)
+                        SELECT blob, $1, oid, $4 + $3 * INTERVAL '1 second' FROM removed WHERE blob IS NOT NULL
+                        ON CONFLICT DO NOTHING)
+                    SELECT count(*) AS count FROM removed"#
+            )
+            .bind(bucket)
+            .bind(prefix)
+            .bind(self.trash_retention.as_secs() as i64)
+            .bind(self.clock.now())
+            .fetch_one(&self.db_conn)
+            .instrument_query(query_span!("db_delete_objects_by_prefix"))
+            .await
         );
+        let count: i64 = try_!(row.try_get("count"));
+        Ok(count as u64)
+    }
 
-        // TODO: Handle versioned
-        let Some(row) = row else {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchKey));
-        };
-        let blob: Option<Uuid> = try_!(row.try_get("blob"));
-        if let Some(blob) = blob {
-            try_!(
-                sqlx::query("INSERT INTO blobs_gc (id) VALUES ($1)")
-                    .bind(blob)
+    async fn move_object(
+        &self,
+        bucket: &str,
+        source: &str,
+        target: &str,
+        blob_id: &Uuid,
+        attributes: WriteAttributes<'_>,
+    ) -> Result<crate::meta_store::Timestamp, s3s::S3Error> {
+        self.retry("move_object", || async move {
+            let mut tx = try_!(
+                self.db_conn
+                    .begin()
+                    .instrument_query(query_span!("db_begin_transaction"))
+                    .await
+            );
+            // the blob is not sent to GC, it stays referenced by the target
+            let res = try_!(
+                sqlx::query("DELETE FROM objects WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2 AND blob = $3")
+                    .bind(bucket)
+                    .bind(source)
+                    .bind(blob_id)
                     .execute(&mut *tx)
-                    .instrument(debug_span!("db_insert_blob_gc"))
+                    .instrument_query(query_span!("db_delete_moved_object"))
                     .await
             );
-        }
+            if res.rows_affected() == 0 {
+                return Err(s3_error!(NoSuchKey, "Source object has been changed during the move"));
+            }
+
+            let last_modified = self.replace_object(&mut tx, bucket, target, blob_id, attributes).await?;
 
+            try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+            Ok(last_modified)
+        })
+        .await
+    }
+
+    async fn import_object(&self, object: &Object, blob: &Blob) -> Result<bool, s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
         try_!(
-            sqlx::query("DELETE FROM objects WHERE bucket = $1 AND oid = $2")
-                .bind(bucket)
-                .bind(object)
+            sqlx::query("DELETE FROM temp_blobs WHERE blob_id = $1;")
+                .bind(blob.id)
                 .execute(&mut *tx)
-                .instrument(debug_span!("db_delete_object"))
+                .instrument_query(query_span!("db_remove_temp_blob"))
                 .await
         );
+        try_!(
+            sqlx::query("INSERT INTO blobs (id, size, uploaded_at, etag) VALUES ($1, $2, $3, $4);")
+                .bind(blob.id)
+                .bind(blob.size)
+                .bind(object.last_modified)
+                .bind(&blob.etag)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_insert_permanent_blob"))
+                .await
+        );
+        let res = try_!(
+            sqlx::query(
+                r#"INSERT INTO objects (bucket, oid, last_modified, blob)
+                    SELECT $1, $2, $3, $4
+                    WHERE NOT EXISTS (SELECT 1 FROM objects WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2)"#
+            )
+            .bind(&object.bucket_name)
+            .bind(&object.oid)
+            .bind(object.last_modified)
+            .bind(blob.id)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_import_object"))
+            .await
+        );
+        if res.rows_affected() == 0 {
+            try_!(tx.rollback().await);
+            return Ok(false);
+        }
 
         try_!(tx.commit().await);
-        Ok(())
+        Ok(true)
     }
 
     #[tracing::instrument(level = "debug")]
     async fn write_temp_blob(&self, blob: &Blob) -> Result<(), s3s::S3Error> {
         try_!(
-            sqlx::query("INSERT INTO temp_blobs (blob_id, uploaded_at) VALUES ($1, CURRENT_TIMESTAMP)")
+            sqlx::query("INSERT INTO temp_blobs (blob_id, uploaded_at) VALUES ($1, $2)")
                 .bind(&blob.id)
+                .bind(self.clock.now())
                 .execute(&self.db_conn)
                 .await
         );
@@ -260,11 +798,16 @@ impl MetaStore for PostgresDatabase {
 
     #[tracing::instrument(level = "debug")]
     async fn clean_temp_blob(&self, blob: &Blob) {
-        sqlx::query("DELETE FROM temp_blobs WHERE blob_id = $1;")
-            .bind(&blob.id)
-            .execute(&self.db_conn)
-            .await
-            .ok();
+        // nothing is queued if the blob has been committed in the meantime
+        sqlx::query(
+            r#"WITH removed AS (DELETE FROM temp_blobs WHERE blob_id = $1 RETURNING blob_id)
+                INSERT INTO blobs_gc (id) SELECT blob_id FROM removed ON CONFLICT DO NOTHING"#,
+        )
+        .bind(blob.id)
+        .execute(&self.db_conn)
+        .instrument_query(query_span!("db_clean_temp_blob"))
+        .await
+        .ok();
     }
 
     async fn add_blob_gc(&self, blob: &Blob) -> Result<User, s3s::S3Error> {
@@ -272,28 +815,55 @@ impl MetaStore for PostgresDatabase {
     }
 
     #[tracing::instrument(level = "debug")]
-    async fn create_bucket(&self, owner: &str, bucket: &str) -> Result<Bucket, s3s::S3Error> {
-        let mut tx = try_!(self.db_conn.begin().instrument(debug_span!("db_begin_transaction")).await);
-        // check if already exist
-        let res = sqlx::query("SELECT name, user_id, creation_date FROM buckets WHERE name = $1;")
-            .bind(bucket)
-            .fetch_optional(&mut *tx)
-            .instrument(debug_span!("db_select_bucket_info"))
-            .await;
-        let res = try_!(res);
-        if res.is_some() {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::BucketAlreadyExists));
+    async fn create_bucket(&self, owner: &str, bucket: &str, options: &CreateBucketOptions) -> Result<Bucket, s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        // a concurrent creation of the same bucket is waited for instead of failing with a unique violation
+        let res = sqlx::query(
+            r#"INSERT INTO buckets (name, user_id, creation_date, object_ownership, acl, object_lock_enabled, region)
+                VALUES ($1, $2, $7, $3, $4, $5, $6)
+                ON CONFLICT (name) DO NOTHING"#,
+        )
+        .bind(bucket)
+        .bind(owner)
+        .bind(&options.object_ownership)
+        .bind(&options.acl)
+        .bind(options.object_lock_enabled)
+        .bind(&options.region)
+        .bind(self.clock.now())
+        .execute(&mut *tx)
+        .instrument_query(query_span!("db_insert_bucket_info"))
+        .await;
+        if try_!(res).rows_affected() == 0 {
+            let row = try_!(
+                sqlx::query("SELECT * FROM buckets WHERE name = $1")
+                    .bind(bucket)
+                    .fetch_one(&mut *tx)
+                    .instrument_query(query_span!("db_select_bucket_info"))
+                    .await
+            );
+            let deleting: bool = try_!(row.try_get("deleting"));
+            let existing = bucket_from_row(&row)?;
+            if deleting {
+                return Err(s3_error!(
+                    OperationAborted,
+                    "The bucket is being deleted, please try again after the deletion has finished"
+                ));
+            }
+            if existing.owner != owner {
+                return Err(s3s::S3Error::new(s3s::S3ErrorCode::BucketAlreadyExists));
+            }
+            // creating an own bucket again in the same region succeeds, the settings of the request are ignored
+            if existing.region.as_deref().is_none_or(|region| region == options.region) {
+                return Ok(existing);
+            }
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::BucketAlreadyOwnedByYou));
         }
 
-        // insert new bucket info
-        let res = sqlx::query("INSERT INTO buckets (name, user_id, creation_date) VALUES ($1, $2, CURRENT_TIMESTAMP);")
-            .bind(bucket)
-            .bind(owner)
-            .execute(&mut *tx)
-            .instrument(debug_span!("db_insert_bucket_info"))
-            .await;
-        try_!(res);
-
         // TODO: create partition
         // try_!(
         //     // does not want to bind table name for some reason
@@ -302,25 +872,21 @@ impl MetaStore for PostgresDatabase {
         //         format!("objects_bucket_{}", bucket.replace("-", "_")), bucket
         //     ))
         //     .execute(&mut *tx)
-        //     .instrument(debug_span!("db_create_table_partition"))
+        //     .instrument_query(query_span!("db_create_table_partition"))
         //     .await
         // );
 
         // fetch the result
-        let res = sqlx::query("SELECT name, user_id, creation_date FROM buckets WHERE name = $1;")
+        let res = sqlx::query("SELECT * FROM buckets WHERE name = $1;")
             .bind(bucket)
             .fetch_one(&mut *tx)
-            .instrument(debug_span!("db_select_bucket_info"))
+            .instrument_query(query_span!("db_select_bucket_info"))
             .await;
         let res = try_!(res);
 
-        let bucket = Bucket {
-            name: try_!(res.try_get("name")),
-            owner: try_!(res.try_get("user_id")),
-            creation_date: try_!(res.try_get("creation_date")),
-        };
+        let bucket = bucket_from_row(&res)?;
 
-        try_!(tx.commit().instrument(debug_span!("db_commit_transaction")).await);
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
 
         Ok(bucket)
     }
@@ -331,6 +897,12 @@ impl MetaStore for PostgresDatabase {
             .bind(bucket)
             .execute(&self.db_conn)
             .await;
+        // objects still reference the bucket
+        if let Err(sqlx::Error::Database(err)) = &res {
+            if err.is_foreign_key_violation() {
+                return Err(s3s::S3Error::new(s3s::S3ErrorCode::BucketNotEmpty));
+            }
+        }
         let _res = try_!(res);
 
         Ok(())
@@ -338,7 +910,7 @@ impl MetaStore for PostgresDatabase {
 
     #[tracing::instrument(level = "debug")]
     async fn get_bucket_metadata(&self, bucket: &str) -> Result<Option<Bucket>, s3s::S3Error> {
-        let res = sqlx::query("SELECT name, user_id, creation_date FROM buckets WHERE name = $1;")
+        let res = sqlx::query("SELECT * FROM buckets WHERE name = $1 AND NOT deleting;")
             .bind(bucket)
             .fetch_optional(&self.db_conn)
             .await;
@@ -346,141 +918,2409 @@ impl MetaStore for PostgresDatabase {
             return Ok(None);
         };
 
-        Ok(Some(Bucket {
-            name: try_!(res.try_get("name")),
-            owner: try_!(res.try_get("user_id")),
-            creation_date: try_!(res.try_get("creation_date")),
-        }))
+        Ok(Some(bucket_from_row(&res)?))
     }
 
     #[tracing::instrument(level = "debug")]
     async fn list_buckets_by_user(&self, user_id: &str) -> Result<Vec<Bucket>, s3s::S3Error> {
-        let res = sqlx::query("SELECT * FROM buckets WHERE user_id = $1 ORDER BY NAME ASC")
+        let res = sqlx::query("SELECT * FROM buckets WHERE user_id = $1 AND NOT deleting ORDER BY NAME ASC")
             .bind(user_id)
             .fetch_all(&self.db_conn)
             .await;
         let res = try_!(res);
 
-        res.into_iter()
-            .map(|r| {
-                Ok(Bucket {
-                    name: try_!(r.try_get("name")),
-                    owner: try_!(r.try_get("user_id")),
-                    creation_date: try_!(r.try_get("creation_date")),
-                })
-            })
-            .collect()
+        res.iter().map(bucket_from_row).collect()
     }
 
-    #[tracing::instrument(level = "debug")]
+    async fn get_bucket_stats(&self, bucket: &str) -> Result<Option<BucketStats>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM bucket_stats WHERE bucket = $1")
+                .bind(bucket)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_bucket_stats"))
+                .await
+        );
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(BucketStats {
+            objects: try_!(row.try_get("objects")),
+            bytes: try_!(row.try_get("bytes")),
+            multipart_bytes: try_!(row.try_get("multipart_bytes")),
+            gc_objects: try_!(row.try_get("gc_objects")),
+            gc_bytes: try_!(row.try_get("gc_bytes")),
+        }))
+    }
+
+    async fn list_all_buckets(&self, owner: Option<&str>) -> Result<Vec<Bucket>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT * FROM buckets WHERE ($1::varchar IS NULL OR user_id = $1) AND NOT deleting ORDER BY name")
+                .bind(owner)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_all_buckets"))
+                .await
+        );
+        rows.iter().map(bucket_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug")]
     async fn get_user_by_access_key(&self, key: &str) -> Result<User, s3s::S3Error> {
         let res = sqlx::query("SELECT users.* from keys JOIN users ON keys.user_id = users.id WHERE keys.access_key = $1")
             .bind(key)
             .fetch_optional(&self.db_conn)
             .await;
         let Some(res) = try_!(res) else {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchKey));
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::InvalidAccessKeyId));
+        };
+        user_from_row(&res)
+    }
+
+    async fn get_user(&self, id: &str) -> Result<Option<User>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM users WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_user"))
+                .await
+        );
+        row.as_ref().map(user_from_row).transpose()
+    }
+
+    async fn is_service_account_key(&self, key: &str) -> Result<bool, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT service_account FROM keys WHERE access_key = $1")
+                .bind(key)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_is_service_account_key"))
+                .await
+        );
+        match row {
+            Some(row) => Ok(try_!(row.try_get("service_account"))),
+            None => Ok(false),
+        }
+    }
+
+    async fn get_secret_key(&self, key: &str) -> Result<Option<String>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT secret_key FROM keys WHERE access_key = $1 AND provider IS NULL")
+                .bind(key)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_secret_key"))
+                .await
+        );
+        match row {
+            Some(row) => Ok(Some(try_!(row.try_get("secret_key")))),
+            None => Ok(None),
+        }
+    }
+
+    async fn link_external_key(&self, key: &str, user: &str, provider: &str) -> Result<bool, s3s::S3Error> {
+        // an external key must not take over a key of the database or of another provider
+        let res = try_!(
+            sqlx::query(
+                r#"INSERT INTO keys (access_key, secret_key, user_id, provider)
+                SELECT $1, '', id, $3 FROM users WHERE id = $2
+                ON CONFLICT (access_key) DO UPDATE SET user_id = EXCLUDED.user_id
+                WHERE keys.provider = EXCLUDED.provider"#
+            )
+            .bind(key)
+            .bind(user)
+            .bind(provider)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_link_external_key"))
+            .await
+        );
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn set_service_account_key(&self, key: &str, enabled: bool) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE keys SET service_account = $2 WHERE access_key = $1")
+                .bind(key)
+                .bind(enabled)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_service_account_key"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::InvalidAccessKeyId));
+        }
+        Ok(())
+    }
+
+    async fn claim_blob_gc(&self, limit: i64, lease: Duration) -> anyhow::Result<Vec<GcTask>> {
+        // rows leased by other workers are skipped instead of waited for
+        let rows = sqlx::query(
+            r#"UPDATE blobs_gc SET leased_until = $3 + $2 * INTERVAL '1 millisecond', attempts = attempts + 1
+                WHERE id IN (
+                    SELECT id FROM blobs_gc
+                    WHERE not_before <= $3 AND NOT failed
+                        AND (leased_until IS NULL OR leased_until <= $3)
+                    ORDER BY not_before LIMIT $1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, attempts, (SELECT storage_class FROM blobs WHERE blobs.id = blobs_gc.id) AS storage_class"#,
+        )
+        .bind(limit)
+        .bind(lease.as_millis() as i64)
+        .bind(self.clock.now())
+        .fetch_all(&self.db_conn)
+        .instrument_query(query_span!("db_claim_blob_gc"))
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(GcTask {
+                    blob_id: r.try_get("id")?,
+                    attempts: r.try_get("attempts")?,
+                    storage_class: r.try_get("storage_class")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove_blob_gc(&self, blob_id: &Uuid) -> anyhow::Result<()> {
+        let mut tx = self.db_conn.begin().await?;
+        sqlx::query("DELETE FROM blobs_gc WHERE id = $1")
+            .bind(blob_id)
+            .execute(&mut *tx)
+            .await?;
+        // multipart parts are removed by cascade
+        sqlx::query("DELETE FROM blobs WHERE id = $1")
+            .bind(blob_id)
+            .execute(&mut *tx)
+            .await?;
+        // delivered on commit, other gateways drop the blob from their caches
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(GATEWAY_EVENTS_CHANNEL)
+            .bind(event_payload(&GatewayEvent::BlobRemoved(*blob_id)))
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_notify_blob_removed"))
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn fail_blob_gc(&self, blob_id: &Uuid, dead: bool, retry_after: Duration) -> anyhow::Result<()> {
+        sqlx::query("UPDATE blobs_gc SET failed = $2, leased_until = $4 + $3 * INTERVAL '1 millisecond' WHERE id = $1")
+            .bind(blob_id)
+            .bind(dead)
+            .bind(retry_after.as_millis() as i64)
+            .bind(self.clock.now())
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_fail_blob_gc"))
+            .await?;
+        Ok(())
+    }
+
+    async fn requeue_failed_blob_gc(&self) -> anyhow::Result<u64> {
+        let res = sqlx::query("UPDATE blobs_gc SET failed = FALSE, attempts = 0, leased_until = NULL WHERE failed")
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_requeue_failed_blob_gc"))
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn list_stale_temp_blobs(&self, older_than: Duration, limit: i64) -> anyhow::Result<Vec<Uuid>> {
+        let rows = sqlx::query(
+            r#"SELECT blob_id FROM temp_blobs
+                WHERE uploaded_at < $3 - $1 * INTERVAL '1 second'
+                ORDER BY uploaded_at LIMIT $2"#,
+        )
+        .bind(older_than.as_secs() as i64)
+        .bind(limit)
+        .bind(self.clock.now())
+        .fetch_all(&self.db_conn)
+        .instrument_query(query_span!("db_list_stale_temp_blobs"))
+        .await?;
+        Ok(rows.iter().map(|r| r.try_get("blob_id")).collect::<Result<_, _>>()?)
+    }
+
+    async fn recover_temp_blob(&self, blob_id: &Uuid) -> anyhow::Result<TempBlobState> {
+        // a single statement, so a concurrent recovery by another instance sees the row removed
+        let row = sqlx::query(
+            r#"WITH removed AS (DELETE FROM temp_blobs WHERE blob_id = $1 RETURNING blob_id),
+                committed AS (SELECT blob_id FROM removed WHERE
+                    EXISTS (SELECT 1 FROM blobs WHERE id = $1)
+                    OR EXISTS (SELECT 1 FROM blob_parts WHERE part_blob_id = $1)
+                    OR EXISTS (SELECT 1 FROM multipart_parts WHERE blob_id = $1)),
+                gc AS (INSERT INTO blobs_gc (id)
+                    SELECT blob_id FROM removed WHERE NOT EXISTS (SELECT 1 FROM committed)
+                    ON CONFLICT DO NOTHING)
+                SELECT (SELECT count(*) FROM removed) AS removed, (SELECT count(*) FROM committed) AS committed"#,
+        )
+        .bind(blob_id)
+        .fetch_one(&self.db_conn)
+        .instrument_query(query_span!("db_recover_temp_blob"))
+        .await?;
+        let removed: i64 = row.try_get("removed")?;
+        let committed: i64 = row.try_get("committed")?;
+        Ok(match (removed, committed) {
+            (0, _) => TempBlobState::Gone,
+            (_, 0) => TempBlobState::Collected,
+            _ => TempBlobState::Committed,
+        })
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn undelete_object(&self, bucket: &str, object: &str) -> Result<Uuid, s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        let row = try_!(
+            sqlx::query(
+                r#"SELECT id FROM blobs_gc
+                    WHERE bucket = $1 AND oid = $2 AND not_before > $3
+                    ORDER BY not_before DESC
+                    LIMIT 1
+                    FOR UPDATE"#
+            )
+            .bind(bucket)
+            .bind(object)
+            .bind(self.clock.now())
+            .fetch_optional(&mut *tx)
+            .instrument_query(query_span!("db_select_trash"))
+            .await
+        );
+        let Some(row) = row else {
+            return Err(s3_error!(NoSuchKey, "Object is not in the trash"));
+        };
+        let blob_id: Uuid = try_!(row.try_get("id"));
+
+        try_!(
+            sqlx::query("DELETE FROM blobs_gc WHERE id = $1")
+                .bind(blob_id)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_delete_blob_gc"))
+                .await
+        );
+        // the current version (if any) goes to the trash instead
+        let attributes = WriteAttributes {
+            metadata: None,
+            tagging: None,
+            headers: &ContentHeaders::default(),
+        };
+        self.replace_object(&mut tx, bucket, object, &blob_id, attributes).await?;
+
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(blob_id)
+    }
+
+    async fn list_trash(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        deleted_after: Option<crate::meta_store::Timestamp>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TrashEntry>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query(
+                r#"SELECT DISTINCT ON (blobs_gc.oid) blobs_gc.oid, blobs_gc.id, blobs.size, blobs.etag,
+                        blobs_gc.not_before - $5 * INTERVAL '1 second' AS deleted_at
+                    FROM blobs_gc JOIN blobs ON blobs_gc.id = blobs.id
+                    WHERE blobs_gc.bucket = $1 AND STARTS_WITH(blobs_gc.oid, $2) AND blobs_gc.not_before > $7
+                        AND ($3::timestamptz IS NULL OR blobs_gc.not_before - $5 * INTERVAL '1 second' >= $3)
+                        AND ($4::varchar IS NULL OR blobs_gc.oid > $4)
+                    ORDER BY blobs_gc.oid, blobs_gc.not_before DESC
+                    LIMIT $6"#
+            )
+            .bind(bucket)
+            .bind(prefix)
+            .bind(deleted_after)
+            .bind(marker)
+            .bind(self.trash_retention.as_secs() as i64)
+            .bind(limit)
+            .bind(self.clock.now())
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_list_trash"))
+            .await
+        );
+        rows.iter()
+            .map(|row| {
+                Ok(TrashEntry {
+                    oid: try_!(row.try_get("oid")),
+                    blob_id: try_!(row.try_get("id")),
+                    size: try_!(row.try_get("size")),
+                    etag: try_!(row.try_get("etag")),
+                    deleted_at: try_!(row.try_get("deleted_at")),
+                })
+            })
+            .collect()
+    }
+
+    async fn export_objects(&self, bucket: &str, marker: &str, limit: i64) -> Result<Vec<(Object, Option<Blob>)>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query(
+                r#"SELECT * FROM UNNEST(object_shards($1)) AS shards(shard),
+                    LATERAL (SELECT * FROM objects
+                        WHERE bucket = $1 AND objects.shard = shards.shard AND oid COLLATE "C" > $2
+                        ORDER BY oid COLLATE "C"
+                        LIMIT $3) AS keys
+                    LEFT OUTER JOIN blobs ON keys.blob = blobs.id
+                    ORDER BY keys.oid COLLATE "C"
+                    LIMIT $3"#
+            )
+            .bind(bucket)
+            .bind(marker)
+            .bind(limit)
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_export_objects"))
+            .await
+        );
+        rows.iter().map(object_from_row).collect()
+    }
+
+    async fn find_objects_by_tag(
+        &self,
+        bucket: &str,
+        key: &str,
+        value: Option<&str>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<String>, s3s::S3Error> {
+        // tags are stored in the canonical x-amz-tagging encoding
+        let encode = |s: &str| form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+        let rows = try_!(
+            sqlx::query(
+                r#"SELECT oid FROM object_tags
+                    WHERE bucket = $1 AND key = $2 AND ($3::varchar IS NULL OR value = $3)
+                        AND oid COLLATE "C" > $4
+                    ORDER BY oid COLLATE "C"
+                    LIMIT $5"#
+            )
+            .bind(bucket)
+            .bind(encode(key))
+            .bind(value.map(encode))
+            .bind(marker.unwrap_or_default())
+            .bind(limit)
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_find_objects_by_tag"))
+            .await
+        );
+        rows.iter().map(|row| Ok(try_!(row.try_get("oid")))).collect()
+    }
+
+    async fn restore_trash(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        deleted_after: Option<crate::meta_store::Timestamp>,
+        marker: Option<&str>,
+        limit: i64,
+    ) -> Result<TrashRestoreBatch, s3s::S3Error> {
+        // a blob restored or collected concurrently is not returned by the DELETE and skipped
+        let row = try_!(
+            sqlx::query(
+                r#"WITH candidates AS (
+                        SELECT DISTINCT ON (oid) id, oid FROM blobs_gc
+                        WHERE bucket = $1 AND STARTS_WITH(oid, $2) AND not_before > $7
+                            AND ($3::timestamptz IS NULL OR not_before - $5 * INTERVAL '1 second' >= $3)
+                            AND ($4::varchar IS NULL OR oid > $4)
+                        ORDER BY oid, not_before DESC
+                        LIMIT $6
+                    ),
+                    removed AS (
+                        DELETE FROM blobs_gc WHERE id IN (
+                            SELECT id FROM candidates
+                            WHERE NOT EXISTS (SELECT 1 FROM objects WHERE objects.bucket = $1
+                                AND objects.shard = object_shard($1, candidates.oid) AND objects.oid = candidates.oid)
+                        )
+                        RETURNING id, oid
+                    ),
+                    restored AS (
+                        INSERT INTO objects (bucket, oid, last_modified, blob)
+                            SELECT $1, oid, $7, id FROM removed
+                        RETURNING oid
+                    )
+                    SELECT (SELECT count(*) FROM candidates) AS candidates,
+                        (SELECT count(*) FROM restored) AS restored,
+                        (SELECT oid FROM candidates ORDER BY oid DESC LIMIT 1) AS marker"#
+            )
+            .bind(bucket)
+            .bind(prefix)
+            .bind(deleted_after)
+            .bind(marker)
+            .bind(self.trash_retention.as_secs() as i64)
+            .bind(limit)
+            .bind(self.clock.now())
+            .fetch_one(&self.db_conn)
+            .instrument_query(query_span!("db_restore_trash"))
+            .await
+        );
+        let candidates: i64 = try_!(row.try_get("candidates"));
+        let restored: i64 = try_!(row.try_get("restored"));
+        Ok(TrashRestoreBatch {
+            restored: restored as u64,
+            skipped: (candidates - restored) as u64,
+            marker: try_!(row.try_get("marker")),
+        })
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn list_objects<'a>(&self, options: ListOptions<'a>) -> Result<ListResult, s3s::S3Error> {
+        // TODO: Handle versions
+        if options.max_keys == 0 {
+            return Ok(ListResult {
+                objects: Vec::default(),
+                common_prefixes: Vec::default(),
+                marker: None,
+                version_marker: None,
+            });
+        }
+
+        // Keys are scanned in binary key order (COLLATE "C", there is an index for it) and grouped into common
+        // prefixes here. Once a common prefix has been found the scan continues after all of its keys instead of
+        // reading them. The database collation could put keys of the next page before the marker, so it is not
+        // used for the comparison. The prefix is compared as a plain string, LIKE would treat `%` and `_` in keys
+        // as wildcards. A common prefix equal to the marker has been returned by the previous page already.
+        // Buckets with several key shards are scanned shard by shard and merged in key order.
+        let prefix = options.prefix.as_deref().unwrap_or_default();
+        let max_keys = options.max_keys as usize;
+        let mut last_dir = options.marker.clone();
+        let mut after = match &options.marker {
+            Some(marker) if common_prefix(marker, prefix, options.delim) == Some(marker.as_str()) => past_prefix(marker),
+            Some(marker) => marker.clone(),
+            None => String::new(),
+        };
+        let mut keys: Vec<Object> = Vec::default();
+        let mut common_prefixes: Vec<String> = Vec::default();
+        // the last key or common prefix of the page, the next page starts after it
+        let mut last = None;
+        loop {
+            let batch = max_keys - keys.len() - common_prefixes.len();
+            let rows = try_!(
+                sqlx::query(LIST_OBJECTS_QUERY)
+                    .bind(options.bucket)
+                    .bind(&after)
+                    .bind(prefix)
+                    .bind(batch as i64)
+                    .fetch_all(&self.db_conn)
+                    .instrument_query(query_span!("db_list_objects"))
+                    .await
+            );
+            let fetched = rows.len();
+
+            for r in rows {
+                let oid: String = try_!(r.try_get("oid"));
+                after = oid.clone();
+                match common_prefix(&oid, prefix, options.delim) {
+                    Some(dir) if last_dir.as_deref() == Some(dir) => continue,
+                    Some(dir) => {
+                        last_dir = Some(dir.to_owned());
+                        common_prefixes.push(dir.to_owned());
+                        last = last_dir.clone();
+                    }
+                    None => {
+                        last = Some(oid.clone());
+                        keys.push(Object {
+                            bucket_name: options.bucket.to_owned(),
+                            oid,
+                            version_id: None, // TODO handle version
+                            last_modified: try_!(r.try_get("last_modified")),
+                            blob_id: try_!(r.try_get("blob")),
+                            metadata: None, // TODO: handle metadata
+                            tagging: None,
+                            replication_status: None,
+                            last_accessed: None,
+                            content_headers: ContentHeaders::default(),
+                        });
+                    }
+                }
+                if keys.len() + common_prefixes.len() == max_keys {
+                    break;
+                }
+            }
+
+            if keys.len() + common_prefixes.len() == max_keys || fetched < batch {
+                break;
+            }
+            if let Some(dir) = last_dir.as_deref().filter(|dir| after.starts_with(dir)) {
+                after = past_prefix(dir);
+            }
+        }
+
+        // blobs are read for the keys only, not for the ones grouped into common prefixes
+        let blob_ids: Vec<Uuid> = keys.iter().filter_map(|o| o.blob_id).collect();
+        let rows = try_!(
+            sqlx::query("SELECT * FROM blobs WHERE id = ANY($1)")
+                .bind(&blob_ids)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_objects_blobs"))
+                .await
+        );
+        let mut blobs = HashMap::with_capacity(rows.len());
+        for r in rows {
+            let blob = Blob {
+                id: try_!(r.try_get("id")),
+                size: try_!(r.try_get("size")),
+                parts: try_!(r.try_get("parts")),
+                part_size: try_!(r.try_get("part_size")),
+                upload_timestamp: try_!(r.try_get("uploaded_at")),
+                storage_class: try_!(r.try_get("storage_class")),
+                etag: try_!(r.try_get("etag")),
+                checksum: checksum_from_row(&r)?,
+            };
+            blobs.insert(blob.id, blob);
+        }
+
+        let count = keys.len() + common_prefixes.len();
+        let objects = keys
+            .into_iter()
+            .map(|o| {
+                let blob = o.blob_id.and_then(|id| blobs.get(&id).cloned());
+                (o, blob)
+            })
+            .collect();
+        Ok(ListResult {
+            objects,
+            common_prefixes,
+            marker: if count >= max_keys { last } else { None },
+            version_marker: None,
+        })
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn put_bucket_replication(&self, bucket: &str, config: &ReplicationConfig) -> Result<(), s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        try_!(
+            sqlx::query("UPDATE buckets SET replication_role = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(&config.role)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_update_replication_role"))
+                .await
+        );
+        try_!(
+            sqlx::query("DELETE FROM bucket_replication WHERE bucket = $1")
+                .bind(bucket)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_delete_replication_rules"))
+                .await
+        );
+
+        for rule in &config.rules {
+            try_!(
+                sqlx::query(
+                    r#"INSERT INTO bucket_replication (bucket, rule_id, priority, prefix, enabled, destination_bucket, storage_class)
+                        VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+                )
+                .bind(bucket)
+                .bind(&rule.id)
+                .bind(rule.priority)
+                .bind(&rule.prefix)
+                .bind(rule.enabled)
+                .bind(&rule.destination_bucket)
+                .bind(&rule.storage_class)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_insert_replication_rule"))
+                .await
+            );
+        }
+
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn get_bucket_replication(&self, bucket: &str) -> Result<Option<ReplicationConfig>, s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("SELECT replication_role FROM buckets WHERE name = $1")
+                .bind(bucket)
+                .fetch_optional(&self.db_conn)
+                .await
+        );
+        let Some(res) = res else {
+            return Ok(None);
+        };
+        let role: Option<String> = try_!(res.try_get("replication_role"));
+        let Some(role) = role else {
+            return Ok(None);
+        };
+
+        let rows = try_!(
+            sqlx::query("SELECT * FROM bucket_replication WHERE bucket = $1 ORDER BY priority DESC, rule_id ASC")
+                .bind(bucket)
+                .fetch_all(&self.db_conn)
+                .await
+        );
+        let rules = rows
+            .into_iter()
+            .map(|r| {
+                Ok(ReplicationRule {
+                    id: try_!(r.try_get("rule_id")),
+                    priority: try_!(r.try_get("priority")),
+                    prefix: try_!(r.try_get("prefix")),
+                    enabled: try_!(r.try_get("enabled")),
+                    destination_bucket: try_!(r.try_get("destination_bucket")),
+                    storage_class: try_!(r.try_get("storage_class")),
+                })
+            })
+            .collect::<Result<Vec<_>, s3s::S3Error>>()?;
+
+        Ok(Some(ReplicationConfig { role, rules }))
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn delete_bucket_replication(&self, bucket: &str) -> Result<(), s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        try_!(
+            sqlx::query("UPDATE buckets SET replication_role = NULL WHERE name = $1")
+                .bind(bucket)
+                .execute(&mut *tx)
+                .await
+        );
+        try_!(
+            sqlx::query("DELETE FROM bucket_replication WHERE bucket = $1")
+                .bind(bucket)
+                .execute(&mut *tx)
+                .await
+        );
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(())
+    }
+
+    async fn get_replication_tasks(&self, limit: i64) -> anyhow::Result<Vec<ReplicationTask>> {
+        let rows = sqlx::query(
+            r#"SELECT replication_queue.*, blobs.size FROM replication_queue
+                JOIN blobs ON replication_queue.blob_id = blobs.id
+                ORDER BY replication_queue.created_at ASC
+                LIMIT $1"#,
+        )
+        .bind(limit)
+        .fetch_all(&self.db_conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|r| {
+                Ok(ReplicationTask {
+                    blob_id: r.try_get("blob_id")?,
+                    bucket: r.try_get("bucket")?,
+                    oid: r.try_get("oid")?,
+                    destination_bucket: r.try_get("destination_bucket")?,
+                    size: r.try_get("size")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn finish_replication_task(&self, task: &ReplicationTask, status: &str) -> anyhow::Result<()> {
+        let mut tx = self.db_conn.begin().await?;
+        // the object may have been overwritten in the meantime
+        sqlx::query(
+            r#"UPDATE objects SET replication_status = $4
+            WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2 AND blob = $3"#,
+        )
+        .bind(&task.bucket)
+        .bind(&task.oid)
+        .bind(task.blob_id)
+        .bind(status)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM replication_queue WHERE blob_id = $1")
+            .bind(task.blob_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn put_bucket_inventory(&self, bucket: &str, config: &InventoryConfig) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO bucket_inventory
+                    (bucket, inventory_id, enabled, prefix, destination_bucket, destination_prefix, format, frequency, included_versions)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                    ON CONFLICT (bucket, inventory_id) DO UPDATE SET
+                        enabled = EXCLUDED.enabled,
+                        prefix = EXCLUDED.prefix,
+                        destination_bucket = EXCLUDED.destination_bucket,
+                        destination_prefix = EXCLUDED.destination_prefix,
+                        format = EXCLUDED.format,
+                        frequency = EXCLUDED.frequency,
+                        included_versions = EXCLUDED.included_versions"#
+            )
+            .bind(bucket)
+            .bind(&config.id)
+            .bind(config.enabled)
+            .bind(&config.prefix)
+            .bind(&config.destination_bucket)
+            .bind(&config.destination_prefix)
+            .bind(&config.format)
+            .bind(&config.frequency)
+            .bind(&config.included_versions)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_put_inventory"))
+            .await
+        );
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn get_bucket_inventory(&self, bucket: &str, id: &str) -> Result<Option<InventoryConfig>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM bucket_inventory WHERE bucket = $1 AND inventory_id = $2")
+                .bind(bucket)
+                .bind(id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_inventory"))
+                .await
+        );
+        row.as_ref().map(inventory_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn list_bucket_inventory(&self, bucket: &str) -> Result<Vec<InventoryConfig>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT * FROM bucket_inventory WHERE bucket = $1 ORDER BY inventory_id ASC")
+                .bind(bucket)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_inventory"))
+                .await
+        );
+        rows.iter().map(inventory_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn delete_bucket_inventory(&self, bucket: &str, id: &str) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("DELETE FROM bucket_inventory WHERE bucket = $1 AND inventory_id = $2")
+                .bind(bucket)
+                .bind(id)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_delete_inventory"))
+                .await
+        );
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn put_bucket_metrics(&self, bucket: &str, config: &MetricsConfig) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO bucket_metrics (bucket, metrics_id, prefix, tagging) VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (bucket, metrics_id) DO UPDATE SET prefix = EXCLUDED.prefix, tagging = EXCLUDED.tagging"#
+            )
+            .bind(bucket)
+            .bind(&config.id)
+            .bind(&config.prefix)
+            .bind(&config.tagging)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_put_metrics"))
+            .await
+        );
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn get_bucket_metrics(&self, bucket: &str, id: &str) -> Result<Option<MetricsConfig>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM bucket_metrics WHERE bucket = $1 AND metrics_id = $2")
+                .bind(bucket)
+                .bind(id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_metrics"))
+                .await
+        );
+        row.as_ref().map(metrics_from_row).transpose()
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn list_bucket_metrics(&self, bucket: &str) -> Result<Vec<MetricsConfig>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT * FROM bucket_metrics WHERE bucket = $1 ORDER BY metrics_id ASC")
+                .bind(bucket)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_metrics"))
+                .await
+        );
+        rows.iter().map(metrics_from_row).collect()
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn delete_bucket_metrics(&self, bucket: &str, id: &str) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("DELETE FROM bucket_metrics WHERE bucket = $1 AND metrics_id = $2")
+                .bind(bucket)
+                .bind(id)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_delete_metrics"))
+                .await
+        );
+        Ok(())
+    }
+
+    async fn claim_inventory_tasks(&self) -> anyhow::Result<Vec<InventoryTask>> {
+        // the row is locked by UPDATE so concurrent gateways re-check the condition and skip it
+        let rows = sqlx::query(
+            r#"UPDATE bucket_inventory SET last_run = $1
+                WHERE enabled AND (
+                    last_run IS NULL
+                    OR last_run + CASE frequency WHEN 'Weekly' THEN INTERVAL '7 days' ELSE INTERVAL '1 day' END <= $1
+                )
+                RETURNING *"#,
+        )
+        .bind(self.clock.now())
+        .fetch_all(&self.db_conn)
+        .await?;
+
+        rows.iter()
+            .map(|r| {
+                Ok(InventoryTask {
+                    bucket: r.try_get("bucket")?,
+                    config: inventory_from_row(r)?,
+                    started_at: r.try_get("last_run")?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        object: &str,
+        initiator: &str,
+        headers: &ContentHeaders,
+        checksum_algorithm: Option<&str>,
+    ) -> Result<Uuid, s3s::S3Error> {
+        let upload_id = self.ids.new_id();
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO multipart_uploads (upload_id, bucket, oid, created_at,
+                        content_type, content_encoding, content_language, content_disposition, cache_control, expires,
+                        checksum_algorithm, initiator)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"#
+            )
+            .bind(upload_id)
+            .bind(bucket)
+            .bind(object)
+            .bind(self.clock.now())
+            .bind(&headers.content_type)
+            .bind(&headers.content_encoding)
+            .bind(&headers.content_language)
+            .bind(&headers.content_disposition)
+            .bind(&headers.cache_control)
+            .bind(headers.expires)
+            .bind(checksum_algorithm)
+            .bind(initiator)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_insert_multipart_upload"))
+            .await
+        );
+
+        Ok(upload_id)
+    }
+
+    async fn get_multipart_upload(&self, upload_id: &Uuid) -> Result<Option<MultipartUpload>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT bucket, oid, initiator FROM multipart_uploads WHERE upload_id = $1")
+                .bind(upload_id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_multipart_upload"))
+                .await
+        );
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(MultipartUpload {
+            bucket: try_!(row.try_get("bucket")),
+            oid: try_!(row.try_get("oid")),
+            initiator: try_!(row.try_get("initiator")),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn write_multipart_part(
+        &self,
+        bucket: &str,
+        object: &str,
+        upload_id: &Uuid,
+        part: &MultipartPart,
+    ) -> Result<(), s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        // prevents the upload from being completed or aborted concurrently, the bucket may have been renamed
+        // while the part was written
+        let upload = try_!(
+            sqlx::query(
+                "SELECT checksum_algorithm FROM multipart_uploads WHERE upload_id = $1 AND bucket = $2 AND oid = $3 FOR SHARE"
+            )
+            .bind(upload_id)
+            .bind(bucket)
+            .bind(object)
+            .fetch_optional(&mut *tx)
+            .instrument_query(query_span!("db_lock_multipart_upload"))
+            .await
+        );
+        let Some(upload) = upload else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchUpload));
+        };
+        // checksums of other algorithms are not kept, they can not be combined
+        let algorithm: Option<String> = try_!(upload.try_get("checksum_algorithm"));
+        let checksum = match (&algorithm, &part.checksum) {
+            (None, _) => None,
+            (Some(expected), Some(checksum)) if *expected == checksum.algorithm => Some(&checksum.value),
+            (Some(expected), checksum) => {
+                return Err(s3_error!(
+                    InvalidRequest,
+                    "Checksum Type mismatch occurred, expected checksum Type: {}, actual checksum Type: {}",
+                    expected.to_lowercase(),
+                    checksum.as_ref().map_or("null".to_owned(), |c| c.algorithm.to_lowercase())
+                ));
+            }
+        };
+
+        try_!(
+            sqlx::query("DELETE FROM temp_blobs WHERE blob_id = $1;")
+                .bind(part.blob_id)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_remove_temp_blob"))
+                .await
+        );
+
+        let old = try_!(
+            sqlx::query("DELETE FROM multipart_parts WHERE upload_id = $1 AND part_number = $2 RETURNING blob_id")
+                .bind(upload_id)
+                .bind(part.part_number)
+                .fetch_optional(&mut *tx)
+                .instrument_query(query_span!("db_delete_previous_part"))
+                .await
+        );
+        if let Some(old) = old {
+            let old_blob_id: Uuid = try_!(old.try_get("blob_id"));
+            try_!(
+                sqlx::query("INSERT INTO blobs_gc (id) VALUES ($1);")
+                    .bind(old_blob_id)
+                    .execute(&mut *tx)
+                    .instrument_query(query_span!("db_put_old_blob_gc"))
+                    .await
+            );
+        }
+
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO multipart_parts (upload_id, part_number, blob_id, size, etag, uploaded_at, checksum)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)"#
+            )
+            .bind(upload_id)
+            .bind(part.part_number)
+            .bind(part.blob_id)
+            .bind(part.size)
+            .bind(&part.etag)
+            .bind(self.clock.now())
+            .bind(checksum)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_insert_multipart_part"))
+            .await
+        );
+
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(bucket))]
+    async fn complete_multipart_upload(
+        &self,
+        bucket: &Bucket,
+        object: &str,
+        upload_id: &Uuid,
+        parts: &[CompletedPart],
+    ) -> Result<Blob, s3s::S3Error> {
+        self.retry("complete_multipart_upload", || async move {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        // concurrent completions wait here and replay the committed result afterwards
+        let upload = try_!(
+            sqlx::query("SELECT * FROM multipart_uploads WHERE upload_id = $1 AND bucket = $2 AND oid = $3 FOR UPDATE")
+                .bind(upload_id)
+                .bind(&bucket.name)
+                .bind(object)
+                .fetch_optional(&mut *tx)
+                .instrument_query(query_span!("db_lock_multipart_upload"))
+                .await
+        );
+        let Some(upload) = upload else {
+            try_!(tx.rollback().await);
+            return self.load_completed_upload(&bucket.name, object, upload_id).await;
+        };
+        let headers = content_headers_from_row(&upload)?;
+        let algorithm: Option<String> = try_!(upload.try_get("checksum_algorithm"));
+
+        let rows = try_!(
+            sqlx::query("SELECT * FROM multipart_parts WHERE upload_id = $1 ORDER BY part_number ASC")
+                .bind(upload_id)
+                .fetch_all(&mut *tx)
+                .instrument_query(query_span!("db_select_multipart_parts"))
+                .await
+        );
+        let uploaded = rows
+            .into_iter()
+            .map(|r| {
+                let checksum: Option<String> = try_!(r.try_get("checksum"));
+                Ok(MultipartPart {
+                    part_number: try_!(r.try_get("part_number")),
+                    blob_id: try_!(r.try_get("blob_id")),
+                    size: try_!(r.try_get("size")),
+                    etag: try_!(r.try_get("etag")),
+                    checksum: algorithm.clone().zip(checksum).map(|(algorithm, value)| Checksum { algorithm, value }),
+                })
+            })
+            .collect::<Result<Vec<_>, s3s::S3Error>>()?;
+
+        let selected = select_completed_parts(&uploaded, parts)?;
+        let blob = Blob {
+            id: *upload_id,
+            size: selected.iter().map(|p| p.size).sum(),
+            parts: Some(selected.len() as i32),
+            part_size: selected.first().map(|p| p.size),
+            upload_timestamp: crate::meta_store::Timestamp::UNIX_EPOCH,
+            storage_class: None,
+            etag: multipart_etag(&selected),
+            checksum: None,
+        };
+        // parts uploaded before checksums were tracked have none
+        let part_checksums: Option<Vec<&str>> =
+            selected.iter().map(|p| p.checksum.as_ref().map(|c| c.value.as_str())).collect();
+        let checksum = match (&algorithm, part_checksums) {
+            (Some(algorithm), Some(part_checksums)) => Some(crate::checksum::composite(algorithm, &part_checksums)?),
+            _ => None,
+        };
+
+        let res = try_!(
+            sqlx::query(
+                r#"INSERT INTO blobs (id, size, parts, part_size, uploaded_at, etag, checksum_algorithm, checksum)
+                    VALUES ($1, $2, $3, $4, $6, $5, $7, $8)
+                    RETURNING uploaded_at"#
+            )
+            .bind(blob.id)
+            .bind(blob.size)
+            .bind(blob.parts)
+            .bind(blob.part_size)
+            .bind(&blob.etag)
+            .bind(self.clock.now())
+            .bind(checksum.as_ref().map(|c| &c.algorithm))
+            .bind(checksum.as_ref().map(|c| &c.value))
+            .fetch_one(&mut *tx)
+            .instrument_query(query_span!("db_insert_permanent_blob"))
+            .await
+        );
+        let blob = Blob {
+            upload_timestamp: try_!(res.try_get("uploaded_at")),
+            checksum,
+            ..blob
+        };
+
+        for (index, part) in selected.iter().enumerate() {
+            try_!(
+                sqlx::query("INSERT INTO blob_parts (blob_id, part_index, part_blob_id, size, checksum) VALUES ($1, $2, $3, $4, $5)")
+                    .bind(blob.id)
+                    .bind(index as i32)
+                    .bind(part.blob_id)
+                    .bind(part.size)
+                    .bind(part.checksum.as_ref().map(|c| &c.value))
+                    .execute(&mut *tx)
+                    .instrument_query(query_span!("db_insert_blob_part"))
+                    .await
+            );
+        }
+
+        // parts which were uploaded but not selected by the client
+        let selected_numbers: Vec<i32> = selected.iter().map(|p| p.part_number).collect();
+        try_!(
+            sqlx::query("INSERT INTO blobs_gc (id) SELECT blob_id FROM multipart_parts WHERE upload_id = $1 AND NOT (part_number = ANY($2))")
+                .bind(upload_id)
+                .bind(&selected_numbers)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_put_unused_parts_gc"))
+                .await
+        );
+        try_!(
+            sqlx::query("DELETE FROM multipart_uploads WHERE upload_id = $1")
+                .bind(upload_id)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_delete_multipart_upload"))
+                .await
+        );
+
+        let attributes = WriteAttributes {
+            metadata: None,
+            tagging: None,
+            headers: &headers,
+        };
+        self.replace_object(&mut tx, &bucket.name, object, &blob.id, attributes).await?;
+
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO completed_multipart_uploads (upload_id, bucket, oid, blob_id, completed_at)
+                    VALUES ($1, $2, $3, $4, $5)"#
+            )
+            .bind(upload_id)
+            .bind(&bucket.name)
+            .bind(object)
+            .bind(blob.id)
+            .bind(self.clock.now())
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_insert_completed_upload"))
+            .await
+        );
+
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(blob)
+        })
+        .await
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn abort_multipart_upload(&self, upload_id: &Uuid) -> Result<(), s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        let upload = try_!(
+            sqlx::query("SELECT upload_id FROM multipart_uploads WHERE upload_id = $1 FOR UPDATE")
+                .bind(upload_id)
+                .fetch_optional(&mut *tx)
+                .instrument_query(query_span!("db_lock_multipart_upload"))
+                .await
+        );
+        if upload.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchUpload));
+        }
+
+        try_!(
+            sqlx::query("INSERT INTO blobs_gc (id) SELECT blob_id FROM multipart_parts WHERE upload_id = $1")
+                .bind(upload_id)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_put_parts_gc"))
+                .await
+        );
+        try_!(
+            sqlx::query("DELETE FROM multipart_uploads WHERE upload_id = $1")
+                .bind(upload_id)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_delete_multipart_upload"))
+                .await
+        );
+
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn get_blob_checksums(&self, blob_id: &Uuid) -> Result<BlobChecksums, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT checksum_algorithm, checksum FROM blobs WHERE id = $1")
+                .bind(blob_id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_select_blob_checksum"))
+                .await
+        );
+        let Some(row) = row else {
+            return Ok(BlobChecksums::default());
+        };
+        let algorithm: Option<String> = try_!(row.try_get("checksum_algorithm"));
+        let checksum: Option<String> = try_!(row.try_get("checksum"));
+
+        let rows = try_!(
+            sqlx::query("SELECT size, checksum FROM blob_parts WHERE blob_id = $1 ORDER BY part_index ASC")
+                .bind(blob_id)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_select_blob_part_checksums"))
+                .await
+        );
+        let parts = rows
+            .into_iter()
+            .map(|r| Ok((try_!(r.try_get("size")), try_!(r.try_get("checksum")))))
+            .collect::<Result<Vec<_>, s3s::S3Error>>()?;
+
+        Ok(BlobChecksums {
+            checksum: algorithm
+                .zip(checksum)
+                .map(|(algorithm, value)| Checksum { algorithm, value }),
+            parts,
+        })
+    }
+
+    async fn get_blob_parts(&self, blob_id: &Uuid) -> Result<Vec<BlobPart>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT part_blob_id, size FROM blob_parts WHERE blob_id = $1 ORDER BY part_index ASC")
+                .bind(blob_id)
+                .fetch_all(&self.db_conn)
+                .await
+        );
+
+        rows.into_iter()
+            .map(|r| {
+                Ok(BlobPart {
+                    blob_id: try_!(r.try_get("part_blob_id")),
+                    size: try_!(r.try_get("size")),
+                })
+            })
+            .collect()
+    }
+
+    async fn record_object_access(&self, bucket: &str, object: &str) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("UPDATE objects SET last_accessed = $3 WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2")
+                .bind(bucket)
+                .bind(object)
+                .bind(self.clock.now())
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_record_object_access"))
+                .await
+        );
+        Ok(())
+    }
+
+    async fn list_cold_blobs(&self, cold_after: Duration, limit: i64) -> anyhow::Result<Vec<Blob>> {
+        // pending replication reads the blob from the standard backend
+        let rows = sqlx::query(
+            r#"SELECT blobs.* FROM objects
+                JOIN blobs ON objects.blob = blobs.id
+                WHERE blobs.storage_class IS NULL
+                    AND COALESCE(objects.last_accessed, objects.last_modified) < $3 - $1 * INTERVAL '1 second'
+                    AND objects.replication_status IS DISTINCT FROM 'PENDING'
+                    AND NOT EXISTS (SELECT 1 FROM object_restores r WHERE r.blob = blobs.id)
+                ORDER BY COALESCE(objects.last_accessed, objects.last_modified)
+                LIMIT $2"#,
+        )
+        .bind(cold_after.as_secs() as i64)
+        .bind(limit)
+        .bind(self.clock.now())
+        .fetch_all(&self.db_conn)
+        .instrument_query(query_span!("db_list_cold_blobs"))
+        .await?;
+        rows.iter()
+            .map(|r| {
+                Ok(Blob {
+                    id: r.try_get("id")?,
+                    size: r.try_get("size")?,
+                    parts: r.try_get("parts")?,
+                    part_size: r.try_get("part_size")?,
+                    upload_timestamp: r.try_get("uploaded_at")?,
+                    storage_class: r.try_get("storage_class")?,
+                    etag: r.try_get("etag")?,
+                    checksum: checksum_from_row(r)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn transition_blob(&self, blob_id: &Uuid, cold: &Blob) -> anyhow::Result<bool> {
+        let mut tx = self.db_conn.begin().await?;
+        sqlx::query("DELETE FROM temp_blobs WHERE blob_id = $1")
+            .bind(cold.id)
+            .execute(&mut *tx)
+            .await?;
+        // the etag and the checksums are taken from the original, clients and sync tools must not notice the move
+        let res = sqlx::query(
+            r#"INSERT INTO blobs (id, size, uploaded_at, etag, storage_class, checksum_algorithm, checksum)
+                SELECT $1, size, uploaded_at, etag, $3, checksum_algorithm, checksum FROM blobs WHERE id = $2"#,
+        )
+        .bind(cold.id)
+        .bind(blob_id)
+        .bind(&cold.storage_class)
+        .execute(&mut *tx)
+        .instrument_query(query_span!("db_insert_permanent_blob"))
+        .await?;
+        // already removed by the GC
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+        let res = sqlx::query("UPDATE objects SET blob = $2 WHERE blob = $1")
+            .bind(blob_id)
+            .bind(cold.id)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_transition_objects"))
+            .await?;
+        // overwritten or deleted while the data was copied
+        if res.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Ok(false);
+        }
+        // the data is still available in the cold tier, so the original skips the trash
+        sqlx::query("INSERT INTO blobs_gc (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(blob_id)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_insert_blob_gc"))
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn get_object_restore(&self, bucket: &str, object: &str) -> Result<Option<ObjectRestore>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM object_restores WHERE bucket = $1 AND oid = $2")
+                .bind(bucket)
+                .bind(object)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_object_restore"))
+                .await
+        );
+        row.as_ref().map(restore_from_row).transpose()
+    }
+
+    async fn create_object_restore(&self, bucket: &str, object: &str, blob_id: &Uuid, days: i32) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO object_restores (bucket, oid, blob, days, requested_at) VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (bucket, oid) DO UPDATE SET blob = EXCLUDED.blob, days = EXCLUDED.days,
+                        requested_at = EXCLUDED.requested_at, expires_at = NULL"#
+            )
+            .bind(bucket)
+            .bind(object)
+            .bind(blob_id)
+            .bind(days)
+            .bind(self.clock.now())
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_create_object_restore"))
+            .await
+        );
+        Ok(())
+    }
+
+    async fn set_object_restore(
+        &self,
+        bucket: &str,
+        object: &str,
+        blob_id: &Uuid,
+        days: i32,
+        expires_at: crate::meta_store::Timestamp,
+    ) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("UPDATE object_restores SET blob = $3, days = $4, expires_at = $5 WHERE bucket = $1 AND oid = $2")
+                .bind(bucket)
+                .bind(object)
+                .bind(blob_id)
+                .bind(days)
+                .bind(expires_at)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_object_restore"))
+                .await
+        );
+        Ok(())
+    }
+
+    async fn delete_object_restore(&self, bucket: &str, object: &str, blob_id: &Uuid) -> anyhow::Result<()> {
+        // a new restore of the object may have replaced the row
+        sqlx::query("DELETE FROM object_restores WHERE bucket = $1 AND oid = $2 AND blob = $3")
+            .bind(bucket)
+            .bind(object)
+            .bind(blob_id)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_delete_object_restore"))
+            .await?;
+        Ok(())
+    }
+
+    async fn list_pending_restores(&self, limit: i64) -> anyhow::Result<Vec<ObjectRestore>> {
+        let rows = sqlx::query("SELECT * FROM object_restores WHERE expires_at IS NULL ORDER BY requested_at LIMIT $1")
+            .bind(limit)
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_list_pending_restores"))
+            .await?;
+        Ok(rows.iter().map(restore_from_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn list_expired_restores(&self, limit: i64) -> anyhow::Result<Vec<ObjectRestore>> {
+        let rows = sqlx::query("SELECT * FROM object_restores WHERE expires_at < $2 ORDER BY expires_at LIMIT $1")
+            .bind(limit)
+            .bind(self.clock.now())
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_list_expired_restores"))
+            .await?;
+        Ok(rows.iter().map(restore_from_row).collect::<Result<_, _>>()?)
+    }
+
+    async fn put_user_policy(&self, user: &str, policy: &UserPolicy) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO user_policies (user_id, name, document) VALUES ($1, $2, $3)
+                    ON CONFLICT (user_id, name) DO UPDATE SET document = EXCLUDED.document"#
+            )
+            .bind(user)
+            .bind(&policy.name)
+            .bind(&policy.document)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_put_user_policy"))
+            .await
+        );
+        Ok(())
+    }
+
+    async fn set_user_default_region(&self, user: &str, region: Option<&str>) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE users SET default_region = $2 WHERE id = $1")
+                .bind(user)
+                .bind(region)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_user_default_region"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3_error!(InvalidArgument, "User {} does not exist", user));
+        }
+        Ok(())
+    }
+
+    async fn list_user_policies(&self, user: &str) -> Result<Vec<UserPolicy>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT name, document FROM user_policies WHERE user_id = $1 ORDER BY name")
+                .bind(user)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_user_policies"))
+                .await
+        );
+        rows.iter().map(user_policy_from_row).collect()
+    }
+
+    async fn delete_user_policy(&self, user: &str, name: &str) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("DELETE FROM user_policies WHERE user_id = $1 AND name = $2")
+                .bind(user)
+                .bind(name)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_delete_user_policy"))
+                .await
+        );
+        Ok(())
+    }
+
+    async fn get_policies_by_access_key(&self, access_key: &str) -> Result<Vec<UserPolicy>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query(
+                r#"SELECT user_policies.name, user_policies.document FROM keys
+                    JOIN user_policies ON keys.user_id = user_policies.user_id
+                    WHERE keys.access_key = $1"#
+            )
+            .bind(access_key)
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_get_policies_by_access_key"))
+            .await
+        );
+        rows.iter().map(user_policy_from_row).collect()
+    }
+
+    async fn set_bucket_object_ownership(&self, bucket: &str, ownership: Option<&str>) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET object_ownership = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(ownership)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_object_ownership"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_accelerate(&self, bucket: &str, status: &str) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET accelerate_status = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(status)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_accelerate"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_tagging(&self, bucket: &str, tagging: Option<&str>) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET tagging = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(tagging)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_tagging"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_policy(&self, bucket: &str, policy: Option<&str>) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET policy = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(policy)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_policy"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_request_payer(&self, bucket: &str, payer: &str) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET request_payer = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(payer)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_request_payer"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn put_public_access_block(&self, bucket: &str, config: &PublicAccessBlock) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO bucket_public_access_block
+                    (bucket, block_public_acls, ignore_public_acls, block_public_policy, restrict_public_buckets)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (bucket) DO UPDATE SET
+                        block_public_acls = EXCLUDED.block_public_acls,
+                        ignore_public_acls = EXCLUDED.ignore_public_acls,
+                        block_public_policy = EXCLUDED.block_public_policy,
+                        restrict_public_buckets = EXCLUDED.restrict_public_buckets"#
+            )
+            .bind(bucket)
+            .bind(config.block_public_acls)
+            .bind(config.ignore_public_acls)
+            .bind(config.block_public_policy)
+            .bind(config.restrict_public_buckets)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_put_public_access_block"))
+            .await
+        );
+        Ok(())
+    }
+
+    async fn get_public_access_block(&self, bucket: &str) -> Result<Option<PublicAccessBlock>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM bucket_public_access_block WHERE bucket = $1")
+                .bind(bucket)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_public_access_block"))
+                .await
+        );
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(PublicAccessBlock {
+            block_public_acls: try_!(row.try_get("block_public_acls")),
+            ignore_public_acls: try_!(row.try_get("ignore_public_acls")),
+            block_public_policy: try_!(row.try_get("block_public_policy")),
+            restrict_public_buckets: try_!(row.try_get("restrict_public_buckets")),
+        }))
+    }
+
+    async fn delete_public_access_block(&self, bucket: &str) -> Result<(), s3s::S3Error> {
+        try_!(
+            sqlx::query("DELETE FROM bucket_public_access_block WHERE bucket = $1")
+                .bind(bucket)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_delete_public_access_block"))
+                .await
+        );
+        Ok(())
+    }
+
+    async fn set_bucket_read_only(&self, bucket: &str, read_only: bool) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET read_only = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(read_only)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_bucket_read_only"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn set_bucket_key_shards(&self, bucket: &str, shards: i16) -> Result<u64, s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        // inserts into the bucket wait for the transaction, see the objects_shard trigger
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET key_shards = $2 WHERE name = $1")
+                .bind(bucket)
+                .bind(shards)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_set_bucket_key_shards"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        let res = try_!(
+            sqlx::query("UPDATE objects SET shard = key_shard(oid, $2) WHERE bucket = $1 AND shard <> key_shard(oid, $2)")
+                .bind(bucket)
+                .bind(shards)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_reshard_objects"))
+                .await
+        );
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(res.rows_affected())
+    }
+
+    async fn list_read_only_buckets(&self) -> Result<Vec<String>, s3s::S3Error> {
+        let rows = try_!(
+            sqlx::query("SELECT name FROM buckets WHERE read_only ORDER BY name")
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_list_read_only_buckets"))
+                .await
+        );
+        rows.iter().map(|row| Ok(try_!(row.try_get("name")))).collect()
+    }
+
+    async fn schedule_bucket_deletion(&self, bucket: &str) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE buckets SET deleting = TRUE WHERE name = $1 AND NOT deleting")
+                .bind(bucket)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_schedule_bucket_deletion"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn transfer_bucket(&self, bucket: &str, owner: &str) -> Result<(), s3s::S3Error> {
+        let res = sqlx::query("UPDATE buckets SET user_id = $2 WHERE name = $1 AND NOT deleting")
+            .bind(bucket)
+            .bind(owner)
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_transfer_bucket"))
+            .await;
+        if let Err(sqlx::Error::Database(err)) = &res {
+            if err.is_foreign_key_violation() {
+                return Err(s3_error!(InvalidArgument, "User {} does not exist", owner));
+            }
+        }
+        if try_!(res).rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+        Ok(())
+    }
+
+    async fn rename_bucket(&self, bucket: &str, new_name: &str) -> Result<(), s3s::S3Error> {
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        // objects, uploads and bucket settings follow through ON UPDATE CASCADE
+        let res = sqlx::query("UPDATE buckets SET name = $2 WHERE name = $1 AND NOT deleting")
+            .bind(bucket)
+            .bind(new_name)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_rename_bucket"))
+            .await;
+        if let Err(sqlx::Error::Database(err)) = &res {
+            if err.is_unique_violation() {
+                return Err(s3s::S3Error::new(s3s::S3ErrorCode::BucketAlreadyExists));
+            }
+        }
+        if try_!(res).rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        }
+
+        // tables which keep the name without a foreign key
+        for query in [
+            "UPDATE completed_multipart_uploads SET bucket = $2 WHERE bucket = $1",
+            "UPDATE replication_queue SET bucket = $2 WHERE bucket = $1",
+            "UPDATE blobs_gc SET bucket = $2 WHERE bucket = $1",
+            "UPDATE bucket_inventory SET destination_bucket = $2 WHERE destination_bucket = $1",
+        ] {
+            try_!(
+                sqlx::query(query)
+                    .bind(bucket)
+                    .bind(new_name)
+                    .execute(&mut *tx)
+                    .instrument_query(query_span!("db_rename_bucket_references"))
+                    .await
+            );
+        }
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(())
+    }
+
+    async fn list_deleting_buckets(&self) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query("SELECT name FROM buckets WHERE deleting ORDER BY name")
+            .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_list_deleting_buckets"))
+            .await?;
+        Ok(rows.iter().map(|row| row.try_get("name")).collect::<Result<_, _>>()?)
+    }
+
+    async fn purge_bucket(&self, bucket: &str, limit: i64) -> anyhow::Result<u64> {
+        let mut tx = self
+            .db_conn
+            .begin()
+            .instrument_query(query_span!("db_begin_transaction"))
+            .await?;
+        // the bucket is gone, so its blobs skip the trash
+        let objects: i64 = sqlx::query(
+            r#"WITH removed AS (
+                    DELETE FROM objects WHERE (bucket, oid, last_modified) IN
+                        (SELECT bucket, oid, last_modified FROM objects WHERE bucket = $1 LIMIT $2)
+                    RETURNING blob
+                ),
+                gc AS (INSERT INTO blobs_gc (id) SELECT blob FROM removed WHERE blob IS NOT NULL ON CONFLICT DO NOTHING)
+                SELECT count(*) AS count FROM removed"#,
+        )
+        .bind(bucket)
+        .bind(limit)
+        .fetch_one(&mut *tx)
+        .instrument_query(query_span!("db_purge_objects"))
+        .await?
+        .try_get("count")?;
+        // parts are removed by cascade, the statement still sees them
+        let uploads: i64 = sqlx::query(
+            r#"WITH removed AS (
+                    DELETE FROM multipart_uploads WHERE upload_id IN
+                        (SELECT upload_id FROM multipart_uploads WHERE bucket = $1 LIMIT $2)
+                    RETURNING upload_id
+                ),
+                gc AS (INSERT INTO blobs_gc (id) SELECT blob_id FROM multipart_parts WHERE upload_id IN (SELECT upload_id FROM removed)
+                    ON CONFLICT DO NOTHING)
+                SELECT count(*) AS count FROM removed"#,
+        )
+        .bind(bucket)
+        .bind(limit)
+        .fetch_one(&mut *tx)
+        .instrument_query(query_span!("db_purge_multipart_uploads"))
+        .await?
+        .try_get("count")?;
+        tx.commit().instrument_query(query_span!("db_commit_transaction")).await?;
+        Ok((objects + uploads) as u64)
+    }
+
+    async fn drop_purged_bucket(&self, bucket: &str) -> anyhow::Result<bool> {
+        let mut tx = self
+            .db_conn
+            .begin()
+            .instrument_query(query_span!("db_begin_transaction"))
+            .await?;
+        // pending copies of the removed objects are not needed anymore
+        sqlx::query("DELETE FROM replication_queue WHERE bucket = $1")
+            .bind(bucket)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_delete_replication_tasks"))
+            .await?;
+        let res = sqlx::query("DELETE FROM buckets WHERE name = $1 AND deleting")
+            .bind(bucket)
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_drop_bucket"))
+            .await;
+        if let Err(sqlx::Error::Database(err)) = &res {
+            if err.is_foreign_key_violation() {
+                return Ok(false);
+            }
+        }
+        let res = res?;
+        tx.commit().instrument_query(query_span!("db_commit_transaction")).await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn get_migration_job(&self, name: &str) -> anyhow::Result<Option<MigrationJob>> {
+        let row = sqlx::query("SELECT * FROM migration_jobs WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.db_conn)
+            .instrument_query(query_span!("db_get_migration_job"))
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let finished_at: Option<crate::meta_store::Timestamp> = row.try_get("finished_at")?;
+        Ok(Some(MigrationJob {
+            name: row.try_get("name")?,
+            checkpoint: row.try_get("checkpoint")?,
+            processed: row.try_get("processed")?,
+            finished: finished_at.is_some(),
+        }))
+    }
+
+    async fn save_migration_job(&self, job: &MigrationJob) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"INSERT INTO migration_jobs (name, checkpoint, processed, finished_at)
+                VALUES ($1, $2, $3, CASE WHEN $4 THEN $5 END)
+                ON CONFLICT (name) DO UPDATE
+                SET checkpoint = $2, processed = $3, updated_at = $5,
+                    finished_at = CASE WHEN $4 THEN $5 END"#,
+        )
+        .bind(&job.name)
+        .bind(&job.checkpoint)
+        .bind(job.processed)
+        .bind(job.finished)
+        .bind(self.clock.now())
+        .execute(&self.db_conn)
+        .instrument_query(query_span!("db_save_migration_job"))
+        .await?;
+        Ok(())
+    }
+
+    async fn run_migration_batch(
+        &self,
+        migration: &DataMigration,
+        checkpoint: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<MigrationBatch> {
+        let rows = match migration {
+            DataMigration::BucketRegion { region } => {
+                sqlx::query(
+                    r#"WITH batch AS (SELECT name FROM buckets WHERE name > $1 ORDER BY name LIMIT $2),
+                        updated AS (UPDATE buckets SET region = $3 WHERE name IN (SELECT name FROM batch) AND region IS NULL)
+                        SELECT name AS checkpoint FROM batch ORDER BY name"#,
+                )
+                .bind(checkpoint.unwrap_or_default())
+                .bind(limit)
+                .bind(region)
+                .fetch_all(&self.db_conn)
+                .instrument_query(query_span!("db_migrate_bucket_region"))
+                .await?
+            }
         };
 
-        Ok(User {
-            id: try_!(res.try_get("id")),
-            name: try_!(res.try_get("name")),
-            email: try_!(res.try_get("email")),
+        Ok(MigrationBatch {
+            checkpoint: match rows.last() {
+                Some(row) => Some(row.try_get("checkpoint")?),
+                None => None,
+            },
+            processed: rows.len() as i64,
         })
     }
 
-    async fn get_blob_gc(&self) -> anyhow::Result<Vec<Uuid>> {
-        let res = sqlx::query("SELECT (id) FROM blobs_gc LIMIT 1000")
+    async fn create_batch_job(&self, job: &BatchJob, manifest: &BatchManifest) -> Result<i64, s3s::S3Error> {
+        let (destination_bucket, destination_prefix, tagging) = match &job.operation {
+            BatchOperation::Copy {
+                destination_bucket,
+                destination_prefix,
+            } => (Some(destination_bucket.as_str()), Some(destination_prefix.as_str()), None),
+            BatchOperation::Tag { tagging } => (None, None, tagging.as_deref()),
+            BatchOperation::Delete | BatchOperation::RestoreTier => (None, None, None),
+        };
+
+        let mut tx = try_!(
+            self.db_conn
+                .begin()
+                .instrument_query(query_span!("db_begin_transaction"))
+                .await
+        );
+        try_!(
+            sqlx::query(
+                r#"INSERT INTO batch_jobs (id, bucket, operation, destination_bucket, destination_prefix, tagging,
+                        report_bucket, report_prefix, created_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"#
+            )
+            .bind(job.id)
+            .bind(&job.bucket)
+            .bind(job.operation.name())
+            .bind(destination_bucket)
+            .bind(destination_prefix)
+            .bind(tagging)
+            .bind(&job.report_bucket)
+            .bind(&job.report_prefix)
+            .bind(self.clock.now())
+            .execute(&mut *tx)
+            .instrument_query(query_span!("db_insert_batch_job"))
+            .await
+        );
+        let res = match manifest {
+            BatchManifest::Keys(keys) => {
+                sqlx::query("INSERT INTO batch_job_tasks (job_id, oid) SELECT $1, UNNEST($2::varchar[]) ON CONFLICT DO NOTHING")
+                    .bind(job.id)
+                    .bind(keys)
+                    .execute(&mut *tx)
+                    .instrument_query(query_span!("db_insert_batch_job_tasks"))
+                    .await
+            }
+            BatchManifest::Prefix(prefix) => {
+                sqlx::query(
+                    r#"INSERT INTO batch_job_tasks (job_id, oid)
+                        SELECT $1, oid FROM objects
+                        WHERE bucket = $2 AND shard = ANY(object_shards($2))
+                            AND oid COLLATE "C" >= $3 AND STARTS_WITH(oid, $3)
+                        ON CONFLICT DO NOTHING"#,
+                )
+                .bind(job.id)
+                .bind(&job.bucket)
+                .bind(prefix)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_insert_batch_job_tasks"))
+                .await
+            }
+        };
+        let total = try_!(res).rows_affected() as i64;
+        try_!(
+            sqlx::query("UPDATE batch_jobs SET total = $2 WHERE id = $1")
+                .bind(job.id)
+                .bind(total)
+                .execute(&mut *tx)
+                .instrument_query(query_span!("db_update_batch_job_total"))
+                .await
+        );
+        try_!(tx.commit().instrument_query(query_span!("db_commit_transaction")).await);
+        Ok(total)
+    }
+
+    async fn get_batch_job(&self, id: &Uuid) -> Result<Option<BatchJob>, s3s::S3Error> {
+        let row = try_!(
+            sqlx::query("SELECT * FROM batch_jobs WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.db_conn)
+                .instrument_query(query_span!("db_get_batch_job"))
+                .await
+        );
+        row.as_ref().map(batch_job_from_row).transpose()
+    }
+
+    async fn claim_batch_job(&self, lease: Duration) -> anyhow::Result<Option<BatchJob>> {
+        let row = sqlx::query(
+            r#"UPDATE batch_jobs SET leased_until = $2 + $1 * INTERVAL '1 millisecond'
+                WHERE id IN (
+                    SELECT id FROM batch_jobs
+                    WHERE status = 'Active' AND (leased_until IS NULL OR leased_until <= $2)
+                    ORDER BY created_at LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *"#,
+        )
+        .bind(lease.as_millis() as i64)
+        .bind(self.clock.now())
+        .fetch_optional(&self.db_conn)
+        .instrument_query(query_span!("db_claim_batch_job"))
+        .await?;
+        Ok(row.as_ref().map(batch_job_from_row).transpose()?)
+    }
+
+    async fn list_pending_batch_tasks(&self, job_id: &Uuid, limit: i64) -> anyhow::Result<Vec<String>> {
+        let rows = sqlx::query("SELECT oid FROM batch_job_tasks WHERE job_id = $1 AND NOT done ORDER BY oid LIMIT $2")
+            .bind(job_id)
+            .bind(limit)
             .fetch_all(&self.db_conn)
+            .instrument_query(query_span!("db_list_pending_batch_tasks"))
             .await?;
-        Ok(res
-            .into_iter()
-            .map(|r| {
-                let id: Uuid = r.try_get("id").expect("Unable to convert table");
-                id
+        Ok(rows.iter().map(|row| row.try_get("oid")).collect::<Result<_, _>>()?)
+    }
+
+    async fn complete_batch_tasks(&self, job_id: &Uuid, results: &[BatchTaskResult], lease: Duration) -> anyhow::Result<()> {
+        let oids: Vec<&str> = results.iter().map(|r| r.oid.as_str()).collect();
+        let errors: Vec<Option<&str>> = results.iter().map(|r| r.error.as_deref()).collect();
+        let failed = errors.iter().filter(|e| e.is_some()).count() as i64;
+
+        let mut tx = self
+            .db_conn
+            .begin()
+            .instrument_query(query_span!("db_begin_transaction"))
+            .await?;
+        sqlx::query(
+            r#"UPDATE batch_job_tasks SET done = TRUE, error = results.error
+                FROM UNNEST($2::varchar[], $3::varchar[]) AS results(oid, error)
+                WHERE batch_job_tasks.job_id = $1 AND batch_job_tasks.oid = results.oid"#,
+        )
+        .bind(job_id)
+        .bind(&oids)
+        .bind(&errors)
+        .execute(&mut *tx)
+        .instrument_query(query_span!("db_complete_batch_tasks"))
+        .await?;
+        sqlx::query(
+            r#"UPDATE batch_jobs SET succeeded = succeeded + $2, failed = failed + $3,
+                    leased_until = $5 + $4 * INTERVAL '1 millisecond'
+                WHERE id = $1"#,
+        )
+        .bind(job_id)
+        .bind(results.len() as i64 - failed)
+        .bind(failed)
+        .bind(lease.as_millis() as i64)
+        .bind(self.clock.now())
+        .execute(&mut *tx)
+        .instrument_query(query_span!("db_update_batch_job_progress"))
+        .await?;
+        tx.commit().instrument_query(query_span!("db_commit_transaction")).await?;
+        Ok(())
+    }
+
+    async fn list_batch_task_results(
+        &self,
+        job_id: &Uuid,
+        after: Option<&str>,
+        limit: i64,
+    ) -> anyhow::Result<Vec<BatchTaskResult>> {
+        let rows = sqlx::query(
+            r#"SELECT oid, error FROM batch_job_tasks
+                WHERE job_id = $1 AND done AND ($2::varchar IS NULL OR oid > $2)
+                ORDER BY oid LIMIT $3"#,
+        )
+        .bind(job_id)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(&self.db_conn)
+        .instrument_query(query_span!("db_list_batch_task_results"))
+        .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(BatchTaskResult {
+                    oid: row.try_get("oid")?,
+                    error: row.try_get("error")?,
+                })
             })
-            .collect())
+            .collect()
     }
 
-    #[tracing::instrument(level = "debug")]
-    async fn list_objects<'a>(&self, options: ListOptions<'a>) -> Result<ListResult, s3s::S3Error> {
-        // TODO: Handle versions
-        // TODO: sanitize input
-        let substr_regex = format!("#\"{}%#\"{}%", options.prefix.as_ref().map_or("", |v| &v), options.delim);
-        let like_regex = format!("{}%", options.prefix.as_ref().map_or("", |v| &v));
-        // if no offset
-        let rows = try_!(sqlx::query(r#"
-            WITH all_oids AS (SELECT *, SUBSTRING(oid FROM $1 FOR '#') AS dir FROM objects WHERE bucket = $3 AND oid > $5 AND oid LIKE $2),
-                 dirs AS (SELECT DISTINCT dir AS oid, CAST(NULL AS UUID) AS blob, TRUE AS is_dir FROM all_oids WHERE dir IS NOT NULL),
-                 OIDS_WITH_DIR AS (SELECT *, CASE WHEN DIR IS NULL THEN FALSE WHEN DIR IS NOT NULL THEN TRUE END AS IS_DIR FROM ALL_OIDS),
-                 JOINED_OIDS AS (SELECT OID, BLOB, IS_DIR FROM OIDS_WITH_DIR WHERE IS_DIR = FALSE UNION ALL SELECT OID, BLOB, IS_DIR FROM DIRS ORDER BY OID ASC LIMIT $4)
-
-                 SELECT JOINED_OIDS.oid, JOINED_OIDS.is_dir, JOINED_OIDS.blob, ALL_OIDS.last_modified, blobs.size, blobs.parts, blobs.part_size, blobs.uploaded_at, blobs.etag FROM JOINED_OIDS 
-	                LEFT JOIN ALL_OIDS ON JOINED_OIDS.oid = ALL_OIDS.oid
-	                LEFT JOIN blobs ON JOINED_OIDS.blob = blobs.id
-            "#)
-            .bind(substr_regex)
-            .bind(like_regex)
-            .bind(options.bucket)
-            .bind(options.max_keys as i64)
-            .bind(options.marker.as_ref().map_or("", |v| &v))
-            .fetch_all(&self.db_conn)
-            .instrument(debug_span!("db_list_objects"))
-            .await);
+    async fn finish_batch_job(&self, job_id: &Uuid, status: &str, report_key: Option<&str>) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"UPDATE batch_jobs SET status = $2, report_key = $3, finished_at = $4, leased_until = NULL
+                WHERE id = $1"#,
+        )
+        .bind(job_id)
+        .bind(status)
+        .bind(report_key)
+        .bind(self.clock.now())
+        .execute(&self.db_conn)
+        .instrument_query(query_span!("db_finish_batch_job"))
+        .await?;
+        Ok(())
+    }
 
-        // hanle offset
+    async fn set_object_tagging(&self, bucket: &str, object: &str, tagging: Option<&str>) -> Result<(), s3s::S3Error> {
+        let res = try_!(
+            sqlx::query("UPDATE objects SET tagging = $3 WHERE bucket = $1 AND shard = object_shard($1, $2) AND oid = $2")
+                .bind(bucket)
+                .bind(object)
+                .bind(tagging)
+                .execute(&self.db_conn)
+                .instrument_query(query_span!("db_set_object_tagging"))
+                .await
+        );
+        if res.rows_affected() == 0 {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchKey));
+        }
+        Ok(())
+    }
 
-        let mut count = 0;
-        let mut common_prefixes: Vec<String> = Vec::default();
-        let mut objetcs: Vec<(Object, Option<Blob>)> = Vec::default();
-        rows.into_iter().try_for_each(|r| {
-            count = count + 1;
-            let name: String = try_!(r.try_get("oid"));
-            let is_dir: bool = try_!(r.try_get("is_dir"));
-            if is_dir {
-                common_prefixes.push(name);
-                return Ok(());
+    async fn get_health(&self) -> anyhow::Result<DbHealth> {
+        let started = std::time::Instant::now();
+        sqlx::query("SELECT 1")
+            .execute(&self.db_conn)
+            .instrument_query(query_span!("db_ping"))
+            .await?;
+        let latency = started.elapsed();
+
+        let row = sqlx::query(
+            r#"SELECT
+                (SELECT count(*) FROM blobs_gc) AS blobs_gc,
+                (SELECT count(*) FROM blobs_gc WHERE not_before > $1) AS blobs_trash,
+                (SELECT count(*) FROM temp_blobs) AS temp_blobs,
+                (SELECT min(uploaded_at) FROM temp_blobs) AS oldest_temp_blob,
+                (SELECT count(*) FROM blobs_gc WHERE failed) AS blobs_gc_failed,
+                (SELECT count(*) FROM objects WHERE replication_status = 'FAILED') AS replication_failed"#,
+        )
+        .bind(self.clock.now())
+        .fetch_one(&self.db_conn)
+        .instrument_query(query_span!("db_get_health"))
+        .await?;
+
+        Ok(DbHealth {
+            latency,
+            pool_size: self.db_conn.size(),
+            pool_idle: self.db_conn.num_idle(),
+            pool_max_size: self.db_conn.options().get_max_connections(),
+            blobs_gc: row.try_get("blobs_gc")?,
+            blobs_trash: row.try_get("blobs_trash")?,
+            temp_blobs: row.try_get("temp_blobs")?,
+            oldest_temp_blob: row.try_get("oldest_temp_blob")?,
+            blobs_gc_failed: row.try_get("blobs_gc_failed")?,
+            replication_failed: row.try_get("replication_failed")?,
+        })
+    }
+
+    async fn claim_worker_lease(&self, name: &str, holder: &Uuid, lease: Duration) -> anyhow::Result<bool> {
+        // the holder renews its own lease, an expired lease is taken over
+        let row = sqlx::query(
+            r#"INSERT INTO worker_leases (name, holder, leased_until)
+                VALUES ($1, $2, $4 + $3 * INTERVAL '1 millisecond')
+                ON CONFLICT (name) DO UPDATE SET holder = EXCLUDED.holder, leased_until = EXCLUDED.leased_until
+                    WHERE worker_leases.holder = EXCLUDED.holder OR worker_leases.leased_until <= $4
+                RETURNING holder"#,
+        )
+        .bind(name)
+        .bind(holder)
+        .bind(lease.as_millis() as i64)
+        .bind(self.clock.now())
+        .fetch_optional(&self.db_conn)
+        .instrument_query(query_span!("db_claim_worker_lease"))
+        .await?;
+        Ok(row.is_some())
+    }
+
+    async fn listen_gateway_events(&self) -> anyhow::Result<BoxStream<'static, anyhow::Result<GatewayEvent>>> {
+        let mut listener = PgListener::connect_with(&self.db_conn).await?;
+        listener.listen(GATEWAY_EVENTS_CHANNEL).await?;
+        let events = listener.into_stream().filter_map(|notification| async move {
+            match notification {
+                // events of newer gateways are skipped during a rolling upgrade
+                Ok(notification) => parse_event(notification.payload()).map(Ok),
+                Err(err) => Some(Err(err.into())),
             }
+        });
+        Ok(events.boxed())
+    }
+}
 
-            let obj = Object {
-                bucket_name: options.bucket.to_owned(),
-                oid: name.to_owned(),
-                version_id: None, // TODO handle version
-                last_modified: try_!(r.try_get("last_modified")),
-                blob_id: try_!(r.try_get("blob")),
-                metadata: None, // TODO: handle metadata
-            };
+/// One batch of a listing: up to `$4` keys of bucket `$1` after `$2` starting with `$3`, in binary key order
+const LIST_OBJECTS_QUERY: &str = r#"SELECT oid, blob, last_modified FROM UNNEST(object_shards($1)) AS shards(shard),
+    LATERAL (SELECT oid, blob, last_modified FROM objects
+        WHERE bucket = $1 AND objects.shard = shards.shard
+            AND oid COLLATE "C" > $2 AND oid COLLATE "C" >= $3 AND STARTS_WITH(oid, $3)
+        ORDER BY oid COLLATE "C" ASC
+        LIMIT $4) AS keys
+    ORDER BY oid COLLATE "C" ASC
+    LIMIT $4"#;
 
-            let blob = if obj.blob_id.is_some() {
-                Some(Blob {
-                    id: obj.blob_id.clone().unwrap(),
-                    size: try_!(r.try_get("size")),
-                    parts: try_!(r.try_get("parts")),
-                    part_size: try_!(r.try_get("part_size")),
-                    upload_timestamp: try_!(r.try_get("uploaded_at")),
-                    etag: try_!(r.try_get("etag")),
-                })
-            } else {
-                None
-            };
+/// NOTIFY channel of [`GatewayEvent`]s
+const GATEWAY_EVENTS_CHANNEL: &str = "gateway_events";
+
+fn event_payload(event: &GatewayEvent) -> String {
+    match event {
+        GatewayEvent::BlobRemoved(id) => format!("blob_removed:{id}"),
+    }
+}
 
-            objetcs.push((obj, blob));
-            return Ok(());
-        })?;
+fn parse_event(payload: &str) -> Option<GatewayEvent> {
+    let (kind, value) = payload.split_once(':')?;
+    match kind {
+        "blob_removed" => Some(GatewayEvent::BlobRemoved(value.parse().ok()?)),
+        _ => None,
+    }
+}
 
-        let marker = if let Some(l) = objetcs.last() {
-            Some(l.0.oid.clone())
-        } else {
-            None
-        };
+/// Object joined with its blob
+fn object_from_row(row: &PgRow) -> Result<(Object, Option<Blob>), s3s::S3Error> {
+    let object = Object {
+        bucket_name: try_!(row.try_get("bucket")),
+        oid: try_!(row.try_get("oid")),
+        version_id: None, // TODO: handle version
+        last_modified: try_!(row.try_get("last_modified")),
+        blob_id: try_!(row.try_get("blob")),
+        metadata: metadata_from_row(row)?,
+        tagging: try_!(row.try_get("tagging")),
+        replication_status: try_!(row.try_get("replication_status")),
+        last_accessed: try_!(row.try_get("last_accessed")),
+        content_headers: content_headers_from_row(row)?,
+    };
 
-        Ok(ListResult {
-            objects: objetcs,
-            common_prefixes: common_prefixes,
-            marker: if count >= options.max_keys { marker } else { None },
-            version_marker: None,
+    let blob = if object.blob_id.is_some() {
+        Some(Blob {
+            id: try_!(row.try_get("id")),
+            size: try_!(row.try_get("size")),
+            parts: try_!(row.try_get("parts")),
+            part_size: try_!(row.try_get("part_size")),
+            upload_timestamp: try_!(row.try_get("uploaded_at")),
+            storage_class: try_!(row.try_get("storage_class")),
+            etag: try_!(row.try_get("etag")),
+            checksum: checksum_from_row(row)?,
         })
+    } else {
+        None
+    };
+    Ok((object, blob))
+}
+
+fn restore_from_row(row: &PgRow) -> Result<ObjectRestore, s3s::S3Error> {
+    Ok(ObjectRestore {
+        bucket: try_!(row.try_get("bucket")),
+        oid: try_!(row.try_get("oid")),
+        blob: try_!(row.try_get("blob")),
+        days: try_!(row.try_get("days")),
+        expires_at: try_!(row.try_get("expires_at")),
+    })
+}
+
+fn checksum_from_row(row: &PgRow) -> Result<Option<Checksum>, s3s::S3Error> {
+    let algorithm: Option<String> = try_!(row.try_get("checksum_algorithm"));
+    let value: Option<String> = try_!(row.try_get("checksum"));
+    Ok(algorithm.zip(value).map(|(algorithm, value)| Checksum { algorithm, value }))
+}
+
+fn metadata_from_row(row: &PgRow) -> Result<Option<s3s::dto::Metadata>, s3s::S3Error> {
+    let metadata: Option<String> = try_!(row.try_get("metadata"));
+    match metadata {
+        Some(metadata) => Ok(Some(try_!(serde_json::from_str(&metadata)))),
+        None => Ok(None),
+    }
+}
+
+fn content_headers_from_row(row: &PgRow) -> Result<ContentHeaders, s3s::S3Error> {
+    Ok(ContentHeaders {
+        content_type: try_!(row.try_get("content_type")),
+        content_encoding: try_!(row.try_get("content_encoding")),
+        content_language: try_!(row.try_get("content_language")),
+        content_disposition: try_!(row.try_get("content_disposition")),
+        cache_control: try_!(row.try_get("cache_control")),
+        expires: try_!(row.try_get("expires")),
+    })
+}
+
+fn user_from_row(row: &PgRow) -> Result<User, s3s::S3Error> {
+    Ok(User {
+        id: try_!(row.try_get("id")),
+        name: try_!(row.try_get("name")),
+        email: try_!(row.try_get("email")),
+        default_region: try_!(row.try_get("default_region")),
+    })
+}
+
+fn user_policy_from_row(row: &PgRow) -> Result<UserPolicy, s3s::S3Error> {
+    Ok(UserPolicy {
+        name: try_!(row.try_get("name")),
+        document: try_!(row.try_get("document")),
+    })
+}
+
+fn bucket_from_row(row: &PgRow) -> Result<Bucket, s3s::S3Error> {
+    Ok(Bucket {
+        name: try_!(row.try_get("name")),
+        owner: try_!(row.try_get("user_id")),
+        creation_date: try_!(row.try_get("creation_date")),
+        object_ownership: try_!(row.try_get("object_ownership")),
+        acl: try_!(row.try_get("acl")),
+        object_lock_enabled: try_!(row.try_get("object_lock_enabled")),
+        region: try_!(row.try_get("region")),
+        read_only: try_!(row.try_get("read_only")),
+        accelerate_status: try_!(row.try_get("accelerate_status")),
+        request_payer: try_!(row.try_get("request_payer")),
+        tagging: try_!(row.try_get("tagging")),
+        policy: try_!(row.try_get("policy")),
+    })
+}
+
+/// Minimal size of every part except the last one
+const MIN_PART_SIZE: i64 = 5 * 1024 * 1024;
+
+/// Validate the part list sent by the client against the uploaded parts
+fn batch_job_from_row(row: &PgRow) -> Result<BatchJob, s3s::S3Error> {
+    let operation: String = try_!(row.try_get("operation"));
+    let operation = match operation.as_str() {
+        "copy" => BatchOperation::Copy {
+            destination_bucket: try_!(row.try_get("destination_bucket")),
+            destination_prefix: try_!(row.try_get("destination_prefix")),
+        },
+        "tag" => BatchOperation::Tag {
+            tagging: try_!(row.try_get("tagging")),
+        },
+        "delete" => BatchOperation::Delete,
+        "restore_tier" => BatchOperation::RestoreTier,
+        other => return Err(s3_error!(InternalError, "Unknown batch operation: {}", other)),
+    };
+    Ok(BatchJob {
+        id: try_!(row.try_get("id")),
+        bucket: try_!(row.try_get("bucket")),
+        operation,
+        report_bucket: try_!(row.try_get("report_bucket")),
+        report_prefix: try_!(row.try_get("report_prefix")),
+        status: try_!(row.try_get("status")),
+        total: try_!(row.try_get("total")),
+        succeeded: try_!(row.try_get("succeeded")),
+        failed: try_!(row.try_get("failed")),
+        report_key: try_!(row.try_get("report_key")),
+        created_at: try_!(row.try_get("created_at")),
+        finished_at: try_!(row.try_get("finished_at")),
+    })
+}
+
+fn select_completed_parts<'a>(
+    uploaded: &'a [MultipartPart],
+    parts: &[CompletedPart],
+) -> Result<Vec<&'a MultipartPart>, s3s::S3Error> {
+    if parts.is_empty() {
+        return Err(s3_error!(MalformedXML, "You must specify at least one part"));
+    }
+
+    let mut selected = Vec::with_capacity(parts.len());
+    let mut prev_number = 0;
+    for part in parts {
+        if part.part_number <= prev_number {
+            return Err(s3_error!(InvalidPartOrder));
+        }
+        prev_number = part.part_number;
+
+        let Some(uploaded) = uploaded.iter().find(|p| p.part_number == part.part_number) else {
+            return Err(s3_error!(InvalidPart, "Part {} has not been uploaded", part.part_number));
+        };
+        if let Some(etag) = &part.etag {
+            if etag.trim_matches('"') != uploaded.etag {
+                return Err(s3_error!(InvalidPart, "ETag of part {} does not match", part.part_number));
+            }
+        }
+        if let Some(checksum) = &part.checksum {
+            if uploaded.checksum.as_ref().is_some_and(|c| c != checksum) {
+                return Err(s3_error!(InvalidPart, "Checksum of part {} does not match", part.part_number));
+            }
+        }
+        selected.push(uploaded);
+    }
+
+    if let Some((_last, rest)) = selected.split_last() {
+        if rest.iter().any(|p| p.size < MIN_PART_SIZE) {
+            return Err(s3_error!(EntityTooSmall));
+        }
+    }
+
+    Ok(selected)
+}
+
+/// MD5 of concatenated part digests followed by the number of parts, as AWS does
+fn multipart_etag(parts: &[&MultipartPart]) -> String {
+    let mut md5_hash = <Md5 as Digest>::new();
+    for part in parts {
+        md5_hash.update(hex_simd::decode_to_vec(&part.etag).unwrap_or_default());
     }
+    let digest = hex_simd::encode_to_string(md5_hash.finalize(), hex_simd::AsciiCase::Lower);
+    format!("{digest}-{}", parts.len())
 }
 
 struct BlobTransaction {
@@ -502,3 +3342,556 @@ impl Transaction for BlobTransaction {
         todo!()
     }
 }
+
+fn metrics_from_row(row: &PgRow) -> Result<MetricsConfig, s3s::S3Error> {
+    Ok(MetricsConfig {
+        id: try_!(row.try_get("metrics_id")),
+        prefix: try_!(row.try_get("prefix")),
+        tagging: try_!(row.try_get("tagging")),
+    })
+}
+
+fn inventory_from_row(row: &PgRow) -> Result<InventoryConfig, s3s::S3Error> {
+    Ok(InventoryConfig {
+        id: try_!(row.try_get("inventory_id")),
+        enabled: try_!(row.try_get("enabled")),
+        prefix: try_!(row.try_get("prefix")),
+        destination_bucket: try_!(row.try_get("destination_bucket")),
+        destination_prefix: try_!(row.try_get("destination_prefix")),
+        format: try_!(row.try_get("format")),
+        frequency: try_!(row.try_get("frequency")),
+        included_versions: try_!(row.try_get("included_versions")),
+    })
+}
+
+/// The common prefix of a key ends with the first delimiter after the prefix, a delimiter inside the
+/// prefix itself does not count
+fn common_prefix<'a>(key: &'a str, prefix: &str, delim: &str) -> Option<&'a str> {
+    if delim.is_empty() {
+        return None;
+    }
+    let rest = key.get(prefix.len()..)?;
+    rest.find(delim).map(|pos| &key[..prefix.len() + pos + delim.len()])
+}
+
+/// Lower bound of the keys after all the keys which start with `prefix` in binary order
+fn past_prefix(prefix: &str) -> String {
+    format!("{prefix}{}", char::MAX)
+}
+
+#[cfg(feature = "fault-injection")]
+type Query<F> = crate::faults::FaultyQuery<F>;
+#[cfg(not(feature = "fault-injection"))]
+type Query<F> = F;
+
+trait InstrumentQuery: Future + Sized {
+    fn instrument_query(self, (query, span): (&'static str, tracing::Span)) -> TimedQuery<Self> {
+        #[cfg(feature = "fault-injection")]
+        let inner = crate::faults::FaultyQuery::new(self, query);
+        #[cfg(not(feature = "fault-injection"))]
+        let inner = self;
+        TimedQuery {
+            inner: inner.instrument(span),
+            query,
+            started: None,
+        }
+    }
+}
+
+impl<F: Future> InstrumentQuery for F {}
+
+pin_project_lite::pin_project! {
+    /// Records the duration of a query including the wait for a connection of the pool
+    struct TimedQuery<F> {
+        #[pin]
+        inner: Instrumented<Query<F>>,
+        query: &'static str,
+        started: Option<Instant>,
+    }
+}
+
+impl<F> Future for TimedQuery<F>
+where
+    Query<F>: Future,
+{
+    type Output = <Query<F> as Future>::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let started = *this.started.get_or_insert_with(Instant::now);
+        let res = this.inner.poll(cx);
+        if res.is_ready() {
+            crate::telemetry::metrics()
+                .db_query_duration
+                .record(started.elapsed().as_secs_f64(), &[KeyValue::new("db.query", *this.query)]);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(part_number: i32, data: &[u8]) -> MultipartPart {
+        MultipartPart {
+            part_number,
+            blob_id: Uuid::nil(),
+            size: data.len() as i64,
+            etag: hex_simd::encode_to_string(Md5::digest(data), hex_simd::AsciiCase::Lower),
+            checksum: None,
+        }
+    }
+
+    #[test]
+    fn multipart_etag_is_stable() {
+        let (a, b) = (part(1, b"aaaaa"), part(2, b"bbb"));
+        assert_eq!(a.etag, "594f803b380a41396ed63dca39503542");
+        assert_eq!(b.etag, "08f8e0260c64418510cefb2b06eee5cd");
+
+        assert_eq!(multipart_etag(&[&a, &b]), "72d27afac8e2fbd3662861b9d607a02c-2");
+        assert_eq!(multipart_etag(&[&a]), "3f39a134e77e08c106b9726a8ae7cc0c-1");
+        // the order of the parts matters
+        assert_ne!(multipart_etag(&[&b, &a]), multipart_etag(&[&a, &b]));
+    }
+
+    #[test]
+    fn common_prefix_ends_at_the_first_delimiter_after_the_prefix() {
+        assert_eq!(common_prefix("a/b/c", "", "/"), Some("a/"));
+        assert_eq!(common_prefix("a/b/c", "a/", "/"), Some("a/b/"));
+        assert_eq!(common_prefix("a/b/c", "a", "/"), Some("a/"));
+        assert_eq!(common_prefix("a/b/c", "a/b/", "/"), None);
+        assert_eq!(common_prefix("a/", "a/", "/"), None);
+        assert_eq!(common_prefix("abc", "", "/"), None);
+        assert_eq!(common_prefix("a/b", "", ""), None);
+        // delimiters of several characters
+        assert_eq!(common_prefix("a--b--c", "a--", "--"), Some("a--b--"));
+        assert_eq!(common_prefix("a-b", "", "--"), None);
+        // keys shorter than the prefix do not belong to it
+        assert_eq!(common_prefix("a", "a/b", "/"), None);
+    }
+
+    #[test]
+    fn past_prefix_follows_every_key_with_the_prefix() {
+        let bound = past_prefix("a/");
+        for key in ["a/", "a/b", "a/\u{ffff}", "a/\u{10fff0}zzz"] {
+            assert!(key.as_bytes() < bound.as_bytes(), "{key}");
+        }
+        for key in ["a0", "b"] {
+            assert!(key.as_bytes() >= bound.as_bytes(), "{key}");
+        }
+        assert!(past_prefix("").as_bytes() > "\u{ffff}".as_bytes());
+    }
+
+    /// Tests below need a database, they run with `cargo test -- --ignored` against `TEST_DATABASE_URL`
+    async fn test_db(clock: Arc<dyn Clock>) -> PostgresDatabase {
+        let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+        let pool = PgPool::connect(&url).await.expect("unable to connect to the test database");
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .expect("unable to migrate the test database");
+        PostgresDatabase {
+            db_conn: pool,
+            trash_retention: Duration::from_secs(60),
+            retry: RetryPolicy {
+                attempts: 1,
+                backoff: Duration::ZERO,
+            },
+            clock,
+            ids: Arc::new(RandomIds),
+            commit_batches: None,
+        }
+    }
+
+    /// Clock which only moves when the test advances it, it starts at a fixed time far in the past
+    #[derive(Debug)]
+    struct TestClock(std::sync::Mutex<crate::meta_store::Timestamp>);
+
+    impl TestClock {
+        fn new() -> Arc<Self> {
+            let start = crate::meta_store::Timestamp::from_unix_timestamp(946_684_800).unwrap();
+            Arc::new(Self(std::sync::Mutex::new(start)))
+        }
+
+        fn advance(&self, by: Duration) {
+            *self.0.lock().expect("unable to lock mutex") += by;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> crate::meta_store::Timestamp {
+            *self.0.lock().expect("unable to lock mutex")
+        }
+    }
+
+    /// GC claims are not limited to a bucket, tests which claim blobs take turns
+    static GC_TESTS: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    /// Bucket of the root user with a name unique to the test run
+    async fn test_bucket(db: &PostgresDatabase, name: &str) -> Bucket {
+        let name = format!("{name}-{}", Uuid::new_v4().simple());
+        let options = CreateBucketOptions {
+            object_ownership: None,
+            acl: "private".to_owned(),
+            object_lock_enabled: false,
+            region: "us-east-1".to_owned(),
+        };
+        db.create_bucket("root", &name, &options).await.unwrap()
+    }
+
+    /// `count` objects without blobs named `dir<n>/key<n>`, spread over 100 directories
+    async fn seed_bucket(db: &PostgresDatabase, bucket: &str, count: i32) {
+        sqlx::query(
+            r#"INSERT INTO objects (bucket, oid, last_modified)
+                SELECT $1, 'dir' || lpad((i % 100)::text, 2, '0') || '/key' || lpad(i::text, 6, '0'), now()
+                FROM generate_series(1, $2) AS i"#,
+        )
+        .bind(bucket)
+        .bind(count)
+        .execute(&db.db_conn)
+        .await
+        .unwrap();
+        sqlx::query("ANALYZE objects").execute(&db.db_conn).await.unwrap();
+    }
+
+    async fn explain(db: &PostgresDatabase, bucket: &str, after: &str, prefix: &str, limit: i64) -> String {
+        let rows = sqlx::query(&format!("EXPLAIN {LIST_OBJECTS_QUERY}"))
+            .bind(bucket)
+            .bind(after)
+            .bind(prefix)
+            .bind(limit)
+            .fetch_all(&db.db_conn)
+            .await
+            .unwrap();
+        rows.iter().map(|r| r.get::<String, _>(0)).collect::<Vec<_>>().join("\n")
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn listing_reads_the_binary_key_index() {
+        let db = test_db(Arc::new(SystemClock)).await;
+        let bucket = test_bucket(&db, "explain").await;
+        seed_bucket(&db, &bucket.name, 20_000).await;
+
+        for (after, prefix) in [("", ""), ("dir42/key004242", ""), ("", "dir07/"), ("dir08\u{10ffff}", "")] {
+            let plan = explain(&db, &bucket.name, after, prefix, 1000).await;
+            assert!(plan.contains("objects_bucket_shard_oid_binary"), "{plan}");
+            assert!(!plan.contains("Seq Scan on objects"), "{plan}");
+            // blobs are read for the keys of the page only, not joined to every scanned row
+            assert!(!plan.contains("blobs"), "{plan}");
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn listing_skips_the_keys_of_common_prefixes() {
+        let db = test_db(Arc::new(SystemClock)).await;
+        let bucket = test_bucket(&db, "prefixes").await;
+        seed_bucket(&db, &bucket.name, 20_000).await;
+
+        let list = |marker: Option<String>, max_keys| {
+            let (bucket, prefix) = (bucket.name.clone(), None);
+            let db = db.clone();
+            async move {
+                db.list_objects(ListOptions {
+                    bucket: &bucket,
+                    prefix: &prefix,
+                    delim: "/",
+                    marker: &marker,
+                    max_keys,
+                    with_versions: false,
+                    version_marker: None,
+                })
+                .await
+                .unwrap()
+            }
+        };
+
+        let res = list(None, 1000).await;
+        assert!(res.objects.is_empty());
+        let expected: Vec<String> = (0..100).map(|i| format!("dir{i:02}/")).collect();
+        assert_eq!(res.common_prefixes, expected);
+        assert_eq!(res.marker, None);
+
+        let res = list(None, 10).await;
+        assert_eq!(res.common_prefixes, expected[..10]);
+        assert_eq!(res.marker.as_deref(), Some("dir09/"));
+        let res = list(res.marker, 10).await;
+        assert_eq!(res.common_prefixes, expected[10..20]);
+    }
+
+    async fn put_object(db: &PostgresDatabase, bucket: &Bucket, key: &str) -> Uuid {
+        put_object_with_etag(db, bucket, key, "d41d8cd98f00b204e9800998ecf8427e").await
+    }
+
+    async fn put_object_with_etag(db: &PostgresDatabase, bucket: &Bucket, key: &str, etag: &str) -> Uuid {
+        let now = db.clock.now();
+        let blob = Blob {
+            id: Uuid::new_v4(),
+            size: 0,
+            parts: None,
+            part_size: None,
+            upload_timestamp: now,
+            storage_class: None,
+            etag: etag.to_owned(),
+            checksum: None,
+        };
+        let object = Object {
+            bucket_name: bucket.name.clone(),
+            oid: key.to_owned(),
+            version_id: None,
+            last_modified: now,
+            blob_id: Some(blob.id),
+            metadata: None,
+            tagging: None,
+            replication_status: None,
+            last_accessed: None,
+            content_headers: ContentHeaders::default(),
+        };
+        db.write_temp_blob(&blob).await.unwrap();
+        db.write_object_metadata_with_blob(bucket, &object, &blob, None)
+            .await
+            .unwrap();
+        blob.id
+    }
+
+    /// Claimed blobs of the test, blobs left behind by other tests are ignored
+    async fn claim(db: &PostgresDatabase, blobs: &[Uuid]) -> Vec<(Uuid, i32)> {
+        let tasks = db.claim_blob_gc(1000, Duration::from_secs(30)).await.unwrap();
+        let mut claimed: Vec<_> = tasks
+            .into_iter()
+            .filter(|t| blobs.contains(&t.blob_id))
+            .map(|t| (t.blob_id, t.attempts))
+            .collect();
+        claimed.sort();
+        claimed
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn trash_is_collected_after_the_retention() {
+        let _turn = GC_TESTS.lock().await;
+        let clock = TestClock::new();
+        let db = test_db(clock.clone()).await;
+        let bucket = test_bucket(&db, "trash").await;
+
+        let overwritten = put_object(&db, &bucket, "a").await;
+        let deleted = put_object(&db, &bucket, "a").await;
+        clock.advance(Duration::from_secs(10));
+        db.delete_object_metadata(&bucket.name, "a", &None).await.unwrap();
+        let blobs = [overwritten, deleted];
+
+        let trash = db.list_trash(&bucket.name, "", None, None, 10).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].blob_id, deleted);
+        assert_eq!(trash[0].deleted_at, clock.now());
+        assert!(claim(&db, &blobs).await.is_empty());
+
+        // the overwritten blob expires first
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(claim(&db, &blobs).await, [(overwritten, 1)]);
+        assert_eq!(db.list_trash(&bucket.name, "", None, None, 10).await.unwrap().len(), 1);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(claim(&db, &blobs).await, [(deleted, 1)]);
+        assert!(db.list_trash(&bucket.name, "", None, None, 10).await.unwrap().is_empty());
+
+        for blob in blobs {
+            db.remove_blob_gc(&blob).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn gc_leases_and_retries_follow_the_clock() {
+        let _turn = GC_TESTS.lock().await;
+        let clock = TestClock::new();
+        let db = test_db(clock.clone()).await;
+        let bucket = test_bucket(&db, "gc").await;
+
+        let blob = put_object(&db, &bucket, "a").await;
+        db.delete_object_metadata(&bucket.name, "a", &None).await.unwrap();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(claim(&db, &[blob]).await, [(blob, 1)]);
+
+        // leased until the worker gives up
+        clock.advance(Duration::from_secs(29));
+        assert!(claim(&db, &[blob]).await.is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(claim(&db, &[blob]).await, [(blob, 2)]);
+
+        db.fail_blob_gc(&blob, false, Duration::from_secs(300)).await.unwrap();
+        clock.advance(Duration::from_secs(299));
+        assert!(claim(&db, &[blob]).await.is_empty());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(claim(&db, &[blob]).await, [(blob, 3)]);
+
+        // dead blobs wait for a requeue however much time passes
+        db.fail_blob_gc(&blob, true, Duration::ZERO).await.unwrap();
+        clock.advance(Duration::from_secs(86400 * 365));
+        assert!(claim(&db, &[blob]).await.is_empty());
+        assert!(db.requeue_failed_blob_gc().await.unwrap() >= 1);
+        assert_eq!(claim(&db, &[blob]).await, [(blob, 1)]);
+
+        db.remove_blob_gc(&blob).await.unwrap();
+    }
+
+    /// Keys and common prefixes of every page of the listing
+    async fn list_all(
+        db: &PostgresDatabase,
+        bucket: &str,
+        prefix: &str,
+        delim: &str,
+        max_keys: u64,
+    ) -> (Vec<String>, Vec<String>) {
+        let prefix = Some(prefix.to_owned());
+        let (mut keys, mut common_prefixes, mut marker) = (Vec::new(), Vec::new(), None);
+        loop {
+            let res = db
+                .list_objects(ListOptions {
+                    bucket,
+                    prefix: &prefix,
+                    delim,
+                    marker: &marker,
+                    max_keys,
+                    with_versions: false,
+                    version_marker: None,
+                })
+                .await
+                .unwrap();
+            keys.extend(res.objects.into_iter().map(|(o, _)| o.oid));
+            common_prefixes.extend(res.common_prefixes);
+            if res.marker.is_none() {
+                return (keys, common_prefixes);
+            }
+            marker = res.marker;
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn like_wildcards_in_prefixes_are_literal() {
+        let db = test_db(Arc::new(SystemClock)).await;
+        let bucket = test_bucket(&db, "wildcards").await;
+        for key in ["a%b", "axb", "a_b"] {
+            put_object(&db, &bucket, key).await;
+        }
+
+        assert_eq!(list_all(&db, &bucket.name, "a%", "", 1000).await.0, ["a%b"]);
+        assert_eq!(list_all(&db, &bucket.name, "a_", "", 1000).await.0, ["a_b"]);
+        assert_eq!(list_all(&db, &bucket.name, "a", "", 1000).await.0, ["a%b", "a_b", "axb"]);
+        // wildcards as delimiters
+        let (keys, prefixes) = list_all(&db, &bucket.name, "", "%", 1000).await;
+        assert_eq!((keys, prefixes), (vec!["a_b".to_owned(), "axb".to_owned()], vec!["a%".to_owned()]));
+        let (keys, prefixes) = list_all(&db, &bucket.name, "a", "_", 1).await;
+        assert_eq!((keys, prefixes), (vec!["a%b".to_owned(), "axb".to_owned()], vec!["a_".to_owned()]));
+
+        assert_eq!(db.delete_objects_by_prefix(&bucket.name, "a_").await.unwrap(), 1);
+        assert_eq!(db.delete_objects_by_prefix(&bucket.name, "a%").await.unwrap(), 1);
+        assert_eq!(list_all(&db, &bucket.name, "", "", 1000).await.0, ["axb"]);
+        let trash = db.list_trash(&bucket.name, "a_", None, None, 10).await.unwrap();
+        assert_eq!(trash.iter().map(|e| e.oid.as_str()).collect::<Vec<_>>(), ["a_b"]);
+    }
+
+    /// Listing of `keys` with the prefix and delimiter, computed without the database
+    fn model_listing(keys: &[String], prefix: &str, delim: &str) -> (Vec<String>, Vec<String>) {
+        let mut sorted: Vec<&String> = keys.iter().filter(|k| k.starts_with(prefix)).collect();
+        sorted.sort();
+        sorted.dedup();
+        let (mut listed, mut common_prefixes) = (Vec::new(), Vec::<String>::new());
+        for key in sorted {
+            let rest = &key[prefix.len()..];
+            match rest.find(delim).filter(|_| !delim.is_empty()) {
+                Some(end) => {
+                    let dir = &key[..prefix.len() + end + delim.len()];
+                    if common_prefixes.last().map(String::as_str) != Some(dir) {
+                        common_prefixes.push(dir.to_owned());
+                    }
+                }
+                None => listed.push(key.clone()),
+            }
+        }
+        (listed, common_prefixes)
+    }
+
+    /// Keys of up to 6 characters from a small alphabet, so that prefixes and delimiters repeat often
+    fn generate_keys(seed: u64, count: usize) -> Vec<String> {
+        const ALPHABET: &[&str] = &["a", "b", "/", "-", "%", "\u{e9}", "\u{10000}"];
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let mut next = |n: usize| {
+            // xorshift64*
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            (state.wrapping_mul(0x2545f4914f6cdd1d) >> 33) as usize % n
+        };
+        (0..count)
+            .map(|_| (0..1 + next(6)).map(|_| ALPHABET[next(ALPHABET.len())]).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn listing_matches_the_model_on_generated_keys() {
+        let db = test_db(Arc::new(SystemClock)).await;
+        for seed in 0..8 {
+            let keys = generate_keys(seed, 60);
+            let bucket = test_bucket(&db, "model").await;
+            sqlx::query("INSERT INTO objects (bucket, oid, last_modified) SELECT $1, k, now() FROM UNNEST($2::varchar[]) AS k")
+                .bind(&bucket.name)
+                .bind(model_listing(&keys, "", "").0)
+                .execute(&db.db_conn)
+                .await
+                .unwrap();
+
+            for prefix in ["", "a", "a/", "b-", "%", "\u{e9}"] {
+                for delim in ["", "/", "-", "\u{e9}", "ab"] {
+                    let expected = model_listing(&keys, prefix, delim);
+                    for max_keys in [1, 7, 1000] {
+                        let listed = list_all(&db, &bucket.name, prefix, delim, max_keys).await;
+                        assert_eq!(
+                            listed, expected,
+                            "seed {seed}, prefix {prefix:?}, delimiter {delim:?}, max keys {max_keys}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs TEST_DATABASE_URL"]
+    async fn etags_survive_moves_between_backends() {
+        let db = test_db(Arc::new(SystemClock)).await;
+        let bucket = test_bucket(&db, "etags").await;
+
+        for (key, etag) in [
+            ("simple", "5eb63bbbe01eeed093cb22bb8f5acdc3"),
+            ("multipart", "72d27afac8e2fbd3662861b9d607a02c-2"),
+        ] {
+            let mut blob_id = put_object_with_etag(&db, &bucket, key, etag).await;
+            // to the cold tier and back, the copies get whatever etag their backend computes
+            for storage_class in [Some("GLACIER"), None] {
+                let copy = Blob {
+                    id: Uuid::new_v4(),
+                    size: 0,
+                    parts: None,
+                    part_size: None,
+                    upload_timestamp: db.clock.now(),
+                    storage_class: storage_class.map(str::to_owned),
+                    etag: "backend etag".to_owned(),
+                    checksum: None,
+                };
+                assert!(db.transition_blob(&blob_id, &copy).await.unwrap());
+                blob_id = copy.id;
+
+                let (object, blob) = db.load_object_metadata(&bucket.name, key, &None).await.unwrap().unwrap();
+                let blob = blob.unwrap();
+                assert_eq!(object.blob_id, Some(copy.id));
+                assert_eq!(blob.storage_class.as_deref(), storage_class);
+                assert_eq!(blob.etag, etag, "{key} in {storage_class:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Subset of IAM user policies: statements with `Effect`, `Action` and `Resource`.
+//!
+//! A request is allowed if every action it performs is allowed by some statement and denied by none.
+//! Users without policies are not restricted.
+//!
+//! Bucket policies use the same subset with a `Principal` in every statement. They are stored so the public
+//! access block can act on them, their statements do not change who may access the bucket.
+
+use hyper::{HeaderMap, Method};
+use s3s::path::S3Path;
+use serde_json::Value;
+
+pub const ARN_PREFIX: &str = "arn:aws:s3:::";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug)]
+struct Statement {
+    effect: Effect,
+    /// `*` for everyone, empty in user policies
+    principals: Vec<String>,
+    actions: Vec<String>,
+    resources: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct PolicyDocument {
+    statements: Vec<Statement>,
+}
+
+impl PolicyDocument {
+    /// Parse the document. Elements outside of the supported subset are rejected, ignoring them could grant
+    /// more than the operator intended.
+    pub fn parse(document: &str) -> Result<Self, String> {
+        Self::parse_document(document, false)
+    }
+
+    /// Parse a bucket policy, its statements must name a principal
+    pub fn parse_bucket_policy(document: &str) -> Result<Self, String> {
+        Self::parse_document(document, true)
+    }
+
+    /// A bucket policy is public if it allows anything to everyone
+    pub fn is_public(&self) -> bool {
+        self.statements
+            .iter()
+            .any(|s| s.effect == Effect::Allow && s.principals.iter().any(|p| p == "*"))
+    }
+
+    fn parse_document(document: &str, bucket_policy: bool) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(document).map_err(|e| format!("invalid JSON: {e}"))?;
+        let Value::Object(value) = value else {
+            return Err("policy must be a JSON object".to_owned());
+        };
+
+        let mut statements = Vec::new();
+        for (key, value) in value {
+            match key.as_str() {
+                "Version" | "Id" => {}
+                "Statement" => match value {
+                    Value::Array(items) => {
+                        for item in items {
+                            statements.push(Statement::parse(item, bucket_policy)?);
+                        }
+                    }
+                    item @ Value::Object(_) => statements.push(Statement::parse(item, bucket_policy)?),
+                    _ => return Err("Statement must be an object or an array".to_owned()),
+                },
+                _ => return Err(format!("unsupported policy element {key}")),
+            }
+        }
+        if statements.is_empty() {
+            return Err("policy has no statements".to_owned());
+        }
+        Ok(Self { statements })
+    }
+
+    fn effect(&self, action: &str, resource: &str) -> Option<Effect> {
+        let mut res = None;
+        for statement in &self.statements {
+            if !statement.matches(action, resource) {
+                continue;
+            }
+            if statement.effect == Effect::Deny {
+                return Some(Effect::Deny);
+            }
+            res = Some(Effect::Allow);
+        }
+        res
+    }
+}
+
+impl Statement {
+    fn parse(value: Value, bucket_policy: bool) -> Result<Self, String> {
+        let Value::Object(value) = value else {
+            return Err("Statement must be a JSON object".to_owned());
+        };
+
+        let mut effect = None;
+        let mut principals = None;
+        let mut actions = None;
+        let mut resources = None;
+        for (key, value) in value {
+            match key.as_str() {
+                "Sid" => {}
+                "Effect" => {
+                    effect = match value.as_str() {
+                        Some("Allow") => Some(Effect::Allow),
+                        Some("Deny") => Some(Effect::Deny),
+                        _ => return Err("Effect must be either Allow or Deny".to_owned()),
+                    }
+                }
+                "Action" => actions = Some(string_or_array(&key, value)?),
+                "Resource" => resources = Some(string_or_array(&key, value)?),
+                "Principal" if bucket_policy => principals = Some(parse_principal(value)?),
+                _ => return Err(format!("unsupported statement element {key}")),
+            }
+        }
+
+        let principals = match principals {
+            Some(principals) => principals,
+            None if bucket_policy => return Err("Principal is required".to_owned()),
+            None => Vec::new(),
+        };
+        Ok(Self {
+            effect: effect.ok_or("Effect is required")?,
+            principals,
+            actions: actions.ok_or("Action is required")?,
+            resources: resources.ok_or("Resource is required")?,
+        })
+    }
+
+    fn matches(&self, action: &str, resource: &str) -> bool {
+        // action names are case insensitive
+        let action = action.to_ascii_lowercase();
+        self.actions.iter().any(|p| wildcard_match(&p.to_ascii_lowercase(), &action))
+            && self.resources.iter().any(|p| wildcard_match(p, resource))
+    }
+}
+
+fn string_or_array(key: &str, value: Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::String(s) => Ok(vec![s]),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|v| match v {
+                Value::String(s) => Ok(s),
+                _ => Err(format!("{key} must contain only strings")),
+            })
+            .collect(),
+        _ => Err(format!("{key} must be a string or an array of strings")),
+    }
+}
+
+/// `*` or an object with `AWS` or `CanonicalUser` ids, where `{"AWS": "*"}` is everyone as well
+fn parse_principal(value: Value) -> Result<Vec<String>, String> {
+    let value = match value {
+        Value::String(s) if s == "*" => return Ok(vec![s]),
+        Value::Object(value) => value,
+        _ => return Err("Principal must be * or an object".to_owned()),
+    };
+    let mut principals = Vec::new();
+    for (key, value) in value {
+        match key.as_str() {
+            "AWS" | "CanonicalUser" => principals.extend(string_or_array(&key, value)?),
+            _ => return Err(format!("unsupported principal type {key}")),
+        }
+    }
+    Ok(principals)
+}
+
+/// Match `text` against a pattern where `*` matches any sequence and `?` matches a single character
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it was tried at
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Every action must be allowed by some policy and denied by none
+pub fn is_allowed(policies: &[PolicyDocument], actions: &[(String, String)]) -> bool {
+    actions.iter().all(|(action, resource)| {
+        let mut allowed = false;
+        for policy in policies {
+            match policy.effect(action, resource) {
+                Some(Effect::Deny) => return false,
+                Some(Effect::Allow) => allowed = true,
+                None => {}
+            }
+        }
+        allowed
+    })
+}
+
+/// IAM actions and resources the request performs
+pub fn request_actions(method: &Method, path: &S3Path, query: Option<&str>, headers: &HeaderMap) -> Vec<(String, String)> {
+    let query: Vec<String> = query
+        .map(|q| form_urlencoded::parse(q.as_bytes()).map(|(k, _)| k.into_owned()).collect())
+        .unwrap_or_default();
+    let has = |name: &str| query.iter().any(|k| k == name);
+
+    let (action, resource) = match path {
+        S3Path::Root => ("ListAllMyBuckets", ARN_PREFIX.to_owned()),
+        // DeleteObjects, the keys are only known after the body is parsed so it needs access to the whole bucket
+        S3Path::Bucket { bucket } if *method == Method::POST && has("delete") => {
+            ("DeleteObject", format!("{ARN_PREFIX}{bucket}/*"))
+        }
+        S3Path::Bucket { bucket } => (bucket_action(method, &has), format!("{ARN_PREFIX}{bucket}")),
+        S3Path::Object { bucket, key } => (object_action(method, &has), format!("{ARN_PREFIX}{bucket}/{key}")),
+    };
+
+    let mut res = vec![(format!("s3:{action}"), resource)];
+    // CopyObject and UploadPartCopy read the source object
+    if let Some(source) = headers.get("x-amz-copy-source").and_then(|v| v.to_str().ok()) {
+        let source = source.split('?').next().unwrap_or_default();
+        let source = urlencoding::decode(source)
+            .map(|s| s.into_owned())
+            .unwrap_or_else(|_| source.to_owned());
+        let source = format!("{ARN_PREFIX}{}", source.trim_start_matches('/'));
+        // moves remove the source object
+        if headers.get(crate::service::MOVE_HEADER).is_some_and(|v| v == "true") {
+            res.push(("s3:DeleteObject".to_owned(), source.clone()));
+        }
+        res.push(("s3:GetObject".to_owned(), source));
+    }
+    res
+}
+
+fn bucket_action(method: &Method, has: &dyn Fn(&str) -> bool) -> &'static str {
+    let sub = |get: &'static str, put: &'static str, delete: &'static str| match *method {
+        Method::PUT => put,
+        Method::DELETE => delete,
+        _ => get,
+    };
+
+    if has("policy") {
+        sub("GetBucketPolicy", "PutBucketPolicy", "DeleteBucketPolicy")
+    } else if has("acl") {
+        sub("GetBucketAcl", "PutBucketAcl", "PutBucketAcl")
+    } else if has("versioning") {
+        sub("GetBucketVersioning", "PutBucketVersioning", "PutBucketVersioning")
+    } else if has("replication") {
+        sub(
+            "GetReplicationConfiguration",
+            "PutReplicationConfiguration",
+            "PutReplicationConfiguration",
+        )
+    } else if has("inventory") {
+        sub("GetInventoryConfiguration", "PutInventoryConfiguration", "PutInventoryConfiguration")
+    } else if has("metrics") {
+        sub("GetMetricsConfiguration", "PutMetricsConfiguration", "PutMetricsConfiguration")
+    } else if has("tagging") {
+        sub("GetBucketTagging", "PutBucketTagging", "PutBucketTagging")
+    } else if has("cors") {
+        sub("GetBucketCORS", "PutBucketCORS", "PutBucketCORS")
+    } else if has("lifecycle") {
+        sub("GetLifecycleConfiguration", "PutLifecycleConfiguration", "PutLifecycleConfiguration")
+    } else if has("encryption") {
+        sub("GetEncryptionConfiguration", "PutEncryptionConfiguration", "PutEncryptionConfiguration")
+    } else if has("object-lock") {
+        sub(
+            "GetBucketObjectLockConfiguration",
+            "PutBucketObjectLockConfiguration",
+            "PutBucketObjectLockConfiguration",
+        )
+    } else if has("accelerate") {
+        sub("GetAccelerateConfiguration", "PutAccelerateConfiguration", "PutAccelerateConfiguration")
+    } else if has("requestPayment") {
+        sub("GetBucketRequestPayment", "PutBucketRequestPayment", "PutBucketRequestPayment")
+    } else if has("publicAccessBlock") {
+        sub("GetBucketPublicAccessBlock", "PutBucketPublicAccessBlock", "PutBucketPublicAccessBlock")
+    } else if has("ownershipControls") {
+        sub("GetBucketOwnershipControls", "PutBucketOwnershipControls", "PutBucketOwnershipControls")
+    } else if has("policyStatus") {
+        "GetBucketPolicyStatus"
+    } else if has("location") {
+        "GetBucketLocation"
+    } else if has("uploads") {
+        "ListBucketMultipartUploads"
+    } else if has("versions") {
+        "ListBucketVersions"
+    } else {
+        match *method {
+            Method::PUT => "CreateBucket",
+            Method::DELETE => "DeleteBucket",
+            _ => "ListBucket",
+        }
+    }
+}
+
+fn object_action(method: &Method, has: &dyn Fn(&str) -> bool) -> &'static str {
+    if has("tagging") {
+        return match *method {
+            Method::PUT => "PutObjectTagging",
+            Method::DELETE => "DeleteObjectTagging",
+            _ => "GetObjectTagging",
+        };
+    }
+    if has("acl") {
+        return match *method {
+            Method::PUT => "PutObjectAcl",
+            _ => "GetObjectAcl",
+        };
+    }
+    match *method {
+        Method::PUT | Method::POST if has("restore") => "RestoreObject",
+        Method::GET if has("uploadId") => "ListMultipartUploadParts",
+        Method::DELETE if has("uploadId") => "AbortMultipartUpload",
+        Method::PUT => "PutObject",
+        Method::POST if has("select") => "GetObject",
+        Method::POST => "PutObject",
+        Method::DELETE => "DeleteObject",
+        _ => "GetObject",
+    }
+}
@@ -0,0 +1,455 @@
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use hyper::body::HttpBody;
+use hyper::service::Service;
+use hyper::{header, Method, Request, Response, StatusCode};
+use s3s::{s3_error, S3Error, S3Result};
+use time::format_description::well_known::Rfc3339;
+
+use crate::virtual_host::host_bucket;
+
+/// Form fields which are not covered by the policy
+const UNSIGNED_FIELDS: &[&str] = &["policy", "x-amz-signature", "file"];
+/// Form fields besides the file are small
+const MAX_FIELDS_SIZE: u64 = 1024 * 1024;
+
+/// Validates the policy document of browser form uploads (`POST /bucket` with `multipart/form-data`).
+///
+/// s3s checks the form signature and turns the request into PutObject but ignores the policy itself,
+/// so the form is parsed here first and the request is rejected unless it satisfies the policy.
+/// The response is adjusted according to `success_action_status` and `success_action_redirect`.
+#[derive(Clone)]
+pub struct PostPolicy<S> {
+    inner: S,
+    base_domain: Option<String>,
+    max_object_size: u64,
+}
+
+impl<S> PostPolicy<S> {
+    pub fn new(inner: S, base_domain: Option<String>, max_object_size: u64) -> Self {
+        Self {
+            inner,
+            base_domain,
+            max_object_size,
+        }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for PostPolicy<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let Some(boundary) = form_boundary(&req) else {
+            return Box::pin(self.inner.call(req));
+        };
+        let Some(bucket) = self.bucket_name(&req) else {
+            // POST to an object or to the root is rejected by s3s
+            return Box::pin(self.inner.call(req));
+        };
+
+        // the ready service must be used for the call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let max_size = self.max_object_size + MAX_FIELDS_SIZE;
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let form = match read_form(body, max_size, &boundary)
+                .await
+                .and_then(|body| Form::parse(body, &boundary))
+            {
+                Ok(form) => form,
+                Err(err) => return Ok(error_response(&err)),
+            };
+            if let Err(err) = form.check_policy(&bucket) {
+                tracing::info!(error = %err, bucket, "form upload is rejected by the policy");
+                return Ok(error_response(&err));
+            }
+
+            let body = form.to_bytes(&boundary);
+            parts.headers.insert(header::CONTENT_LENGTH, body.len().into());
+            let host = parts
+                .headers
+                .get(header::HOST)
+                .and_then(|h| h.to_str().ok())
+                .map(ToOwned::to_owned);
+            let res = inner.call(Request::from_parts(parts, hyper::Body::from(body))).await?;
+            Ok(form.success_response(res, &bucket, host.as_deref()))
+        })
+    }
+}
+
+impl<S> PostPolicy<S> {
+    fn bucket_name(&self, req: &Request<hyper::Body>) -> Option<String> {
+        bucket_name(req, self.base_domain.as_deref())
+    }
+}
+
+/// Bucket of a request addressed to the bucket itself rather than to an object
+pub(crate) fn bucket_name<B>(req: &Request<B>, base_domain: Option<&str>) -> Option<String> {
+    let host = req.headers().get(header::HOST).and_then(|h| h.to_str().ok());
+    if let (Some(host), Some(domain)) = (host, base_domain) {
+        if let Some(bucket) = host_bucket(host, domain) {
+            return (req.uri().path() == "/").then(|| bucket.to_owned());
+        }
+    }
+
+    let path = req.uri().path().trim_start_matches('/');
+    let bucket = path.strip_suffix('/').unwrap_or(path);
+    (!bucket.is_empty() && !bucket.contains('/')).then(|| bucket.to_owned())
+}
+
+/// Bucket of any request, None for requests to the root
+pub(crate) fn request_bucket<B>(req: &Request<B>, base_domain: Option<&str>) -> Option<String> {
+    let host = req.headers().get(header::HOST).and_then(|h| h.to_str().ok());
+    if let (Some(host), Some(domain)) = (host, base_domain) {
+        if let Some(bucket) = host_bucket(host, domain) {
+            return Some(bucket.to_owned());
+        }
+    }
+
+    let bucket = req.uri().path().trim_start_matches('/').split('/').next().unwrap_or_default();
+    (!bucket.is_empty()).then(|| bucket.to_owned())
+}
+
+fn form_boundary(req: &Request<hyper::Body>) -> Option<String> {
+    if req.method() != Method::POST {
+        return None;
+    }
+    let content_type = req.headers().get(header::CONTENT_TYPE)?.to_str().ok()?;
+    let (mime, params) = content_type.split_once(';')?;
+    if !mime.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.split(';').find_map(|p| {
+        let (name, value) = p.trim().split_once('=')?;
+        name.eq_ignore_ascii_case("boundary")
+            .then(|| value.trim_matches('"').to_owned())
+    })
+}
+
+async fn read_form(mut body: hyper::Body, max_size: u64, boundary: &str) -> S3Result<Bytes> {
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = try_!(chunk);
+        buf.extend_from_slice(&chunk);
+        if buf.len() as u64 > max_size + boundary.len() as u64 {
+            return Err(s3_error!(EntityTooLarge));
+        }
+    }
+    Ok(buf.freeze())
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| p + from)
+}
+
+struct FormPart {
+    /// raw part headers
+    headers: String,
+    /// field names are case insensitive, they are lowercased while parsing
+    name: String,
+    filename: Option<String>,
+    data: Bytes,
+}
+
+struct Form {
+    parts: Vec<FormPart>,
+}
+
+impl Form {
+    fn parse(body: Bytes, boundary: &str) -> S3Result<Self> {
+        let malformed = || {
+            s3_error!(
+                MalformedPOSTRequest,
+                "The body of your POST request is not well-formed multipart/form-data"
+            )
+        };
+        let delimiter = format!("--{boundary}");
+        let separator = format!("\r\n--{boundary}");
+
+        let mut pos = find(&body, delimiter.as_bytes(), 0).ok_or_else(malformed)? + delimiter.len();
+        let mut parts = Vec::new();
+        loop {
+            match body.get(pos..pos + 2) {
+                Some(b"--") => break,
+                Some(b"\r\n") => pos += 2,
+                _ => return Err(malformed()),
+            }
+
+            let headers_end = find(&body, b"\r\n\r\n", pos).ok_or_else(malformed)?;
+            let headers = std::str::from_utf8(&body[pos..headers_end])
+                .map_err(|_| malformed())?
+                .to_owned();
+            let data_start = headers_end + 4;
+            let data_end = find(&body, separator.as_bytes(), data_start).ok_or_else(malformed)?;
+
+            let disposition = headers
+                .lines()
+                .find_map(|l| {
+                    let (name, value) = l.split_once(':')?;
+                    name.trim().eq_ignore_ascii_case("content-disposition").then_some(value)
+                })
+                .ok_or_else(malformed)?;
+            let param = |key: &str| {
+                disposition.split(';').find_map(|p| {
+                    let (name, value) = p.trim().split_once('=')?;
+                    name.eq_ignore_ascii_case(key).then(|| value.trim_matches('"').to_owned())
+                })
+            };
+
+            parts.push(FormPart {
+                name: param("name").ok_or_else(malformed)?.to_ascii_lowercase(),
+                filename: param("filename"),
+                headers,
+                data: body.slice(data_start..data_end),
+            });
+            pos = data_end + separator.len();
+        }
+
+        let mut form = Self { parts };
+        // the key may refer to the name of the uploaded file
+        let filename = form.file().and_then(|f| f.filename.clone()).unwrap_or_default();
+        if let Some(key) = form.parts.iter_mut().find(|p| p.name == "key") {
+            let value = String::from_utf8_lossy(&key.data).replace("${filename}", &filename);
+            key.data = Bytes::from(value);
+        }
+        Ok(form)
+    }
+
+    fn file(&self) -> Option<&FormPart> {
+        self.parts.iter().find(|p| p.name == "file")
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        self.parts
+            .iter()
+            .find(|p| p.name == name && p.name != "file")
+            .map(|p| String::from_utf8_lossy(&p.data).into_owned())
+    }
+
+    fn to_bytes(&self, boundary: &str) -> Bytes {
+        let mut buf = BytesMut::new();
+        for part in &self.parts {
+            buf.extend_from_slice(format!("--{boundary}\r\n{}\r\n\r\n", part.headers).as_bytes());
+            buf.extend_from_slice(&part.data);
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+        buf.freeze()
+    }
+
+    fn check_policy(&self, bucket: &str) -> S3Result<()> {
+        let denied = |reason: &str| s3_error!(AccessDenied, "Invalid according to Policy: {}", reason);
+        let invalid = || s3_error!(InvalidPolicyDocument, "Invalid Policy: Invalid JSON.");
+
+        let Some(policy) = self.field("policy") else {
+            return Err(s3_error!(AccessDenied, "Anonymous form uploads are not supported"));
+        };
+        let policy = base64_simd::STANDARD.decode_to_vec(policy.trim()).map_err(|_| invalid())?;
+        let policy: serde_json::Value = serde_json::from_slice(&policy).map_err(|_| invalid())?;
+
+        let expiration = policy.get("expiration").and_then(|e| e.as_str()).ok_or_else(invalid)?;
+        let expiration = time::OffsetDateTime::parse(expiration, &Rfc3339)
+            .map_err(|_| s3_error!(InvalidPolicyDocument, "Invalid Policy: Invalid 'expiration' value"))?;
+        if expiration < time::OffsetDateTime::now_utc() {
+            return Err(denied("Policy expired."));
+        }
+
+        let value = |name: &str| match name {
+            "bucket" => Some(bucket.to_owned()),
+            name => self.field(name),
+        };
+        let file_size = self
+            .file()
+            .ok_or_else(|| s3_error!(InvalidArgument, "POST requires exactly one file upload per request."))?
+            .data
+            .len() as i64;
+
+        let mut covered = vec!["bucket".to_owned()];
+        let conditions = policy.get("conditions").and_then(|c| c.as_array()).ok_or_else(invalid)?;
+        for condition in conditions {
+            match condition {
+                // {"name": "value"} is an exact match
+                serde_json::Value::Object(object) => {
+                    for (name, expected) in object {
+                        let name = name.to_ascii_lowercase();
+                        let expected = expected.as_str().ok_or_else(invalid)?;
+                        if value(&name).as_deref() != Some(expected) {
+                            return Err(denied(&format!("Policy Condition failed: [\"eq\", \"${name}\", \"{expected}\"]")));
+                        }
+                        covered.push(name);
+                    }
+                }
+                serde_json::Value::Array(items) => match items.as_slice() {
+                    [op, min, max] if op.as_str() == Some("content-length-range") => {
+                        let (Some(min), Some(max)) = (min.as_i64(), max.as_i64()) else {
+                            return Err(invalid());
+                        };
+                        if file_size < min {
+                            return Err(s3_error!(
+                                EntityTooSmall,
+                                "Your proposed upload is smaller than the minimum allowed size"
+                            ));
+                        }
+                        if file_size > max {
+                            return Err(s3_error!(EntityTooLarge, "Your proposed upload exceeds the maximum allowed size"));
+                        }
+                    }
+                    [op, name, expected] => {
+                        let (Some(op), Some(name), Some(expected)) = (op.as_str(), name.as_str(), expected.as_str()) else {
+                            return Err(invalid());
+                        };
+                        let name = name.strip_prefix('$').ok_or_else(invalid)?.to_ascii_lowercase();
+                        let actual = value(&name).unwrap_or_default();
+                        let matches = match op {
+                            "eq" => actual == expected,
+                            // Content-Type may hold several comma separated values
+                            "starts-with" if name == "content-type" => actual.split(',').all(|v| v.trim().starts_with(expected)),
+                            "starts-with" => actual.starts_with(expected),
+                            _ => return Err(invalid()),
+                        };
+                        if !matches {
+                            return Err(denied(&format!("Policy Condition failed: [\"{op}\", \"${name}\", \"{expected}\"]")));
+                        }
+                        covered.push(name);
+                    }
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(invalid()),
+            }
+        }
+
+        for part in &self.parts {
+            let is_unsigned = UNSIGNED_FIELDS.contains(&part.name.as_str()) || part.name.starts_with("x-ignore-");
+            if !is_unsigned && !covered.contains(&part.name) {
+                return Err(denied(&format!("Extra input fields: {}", part.name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn success_response(&self, mut res: Response<s3s::Body>, bucket: &str, host: Option<&str>) -> Response<s3s::Body> {
+        if !res.status().is_success() {
+            return res;
+        }
+        let key = self.field("key").unwrap_or_default();
+        let etag = res
+            .headers()
+            .get(header::ETAG)
+            .and_then(|e| e.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        if let Some(redirect) = self.field("success_action_redirect").filter(|r| !r.is_empty()) {
+            let separator = if redirect.contains('?') { '&' } else { '?' };
+            let location = format!(
+                "{redirect}{separator}bucket={}&key={}&etag={}",
+                urlencoding::encode(bucket),
+                urlencoding::encode(&key),
+                urlencoding::encode(&etag)
+            );
+            *res.status_mut() = StatusCode::SEE_OTHER;
+            if let Ok(location) = location.parse() {
+                res.headers_mut().insert(header::LOCATION, location);
+            }
+            return res;
+        }
+
+        match self.field("success_action_status").as_deref() {
+            Some("200") => res,
+            Some("201") => {
+                let location = format!("http://{}/{bucket}/{}", host.unwrap_or_default(), urlencoding::encode(&key));
+                let body = format!(
+                    "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<PostResponse><Location>{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></PostResponse>",
+                    xml_escape(&location),
+                    xml_escape(bucket),
+                    xml_escape(&key),
+                    xml_escape(&etag)
+                );
+                *res.status_mut() = StatusCode::CREATED;
+                res.headers_mut()
+                    .insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/xml"));
+                res.headers_mut().insert(header::CONTENT_LENGTH, body.len().into());
+                *res.body_mut() = s3s::Body::from(body);
+                res
+            }
+            _ => {
+                *res.status_mut() = StatusCode::NO_CONTENT;
+                res
+            }
+        }
+    }
+}
+
+pub(crate) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub(crate) fn error_response(err: &S3Error) -> Response<s3s::Body> {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>",
+        xml_escape(err.code().as_str()),
+        xml_escape(err.message().unwrap_or_default())
+    );
+    let mut res = Response::new(s3s::Body::from(body));
+    *res.status_mut() = err.status_code().unwrap_or(StatusCode::BAD_REQUEST);
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, header::HeaderValue::from_static("application/xml"));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(fields: &[(&str, &str)], conditions: &str) -> Form {
+        let policy = format!(r#"{{"expiration": "2100-01-01T00:00:00Z", "conditions": [{conditions}]}}"#);
+        let policy = base64_simd::STANDARD.encode_to_string(policy);
+        let mut body = String::new();
+        for (name, value) in [("Policy", policy.as_str())].iter().chain(fields) {
+            body.push_str(&format!("--b\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"));
+        }
+        body.push_str("--b\r\nContent-Disposition: form-data; name=\"File\"; filename=\"a.txt\"\r\n\r\ndata\r\n--b--\r\n");
+        Form::parse(Bytes::from(body), "b").unwrap()
+    }
+
+    #[test]
+    fn field_names_are_case_insensitive() {
+        let form = form(
+            &[("Key", "${filename}"), ("X-Amz-Meta-Tag", "v")],
+            r#"{"Bucket": "bucket"}, ["starts-with", "$KEY", ""], {"x-amz-meta-tag": "v"}"#,
+        );
+        assert_eq!(form.field("key").as_deref(), Some("a.txt"));
+        assert!(form.file().is_some());
+        form.check_policy("bucket").unwrap();
+    }
+
+    #[test]
+    fn fields_without_conditions_are_rejected() {
+        let form = form(&[("Key", "a"), ("X-Amz-Meta-Other", "v")], r#"["starts-with", "$key", ""]"#);
+        let err = form.check_policy("bucket").unwrap_err();
+        assert_eq!(err.code(), &s3s::S3ErrorCode::AccessDenied);
+        assert_eq!(err.message(), Some("Invalid according to Policy: Extra input fields: x-amz-meta-other"));
+    }
+}
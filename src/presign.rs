@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use hyper::Method;
+use s3s::auth::S3Auth;
+use s3s::S3Result;
+use sha2::Sha256;
+
+use crate::meta_store::Timestamp;
+use crate::sig_debug::{hex_sha256, uri_encode};
+
+/// SigV4 does not accept presigned URLs valid for longer than 7 days
+pub const MAX_EXPIRES: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Generates SigV4 presigned URLs for path-style requests to the public endpoint of the gateway
+#[derive(Clone)]
+pub struct Presigner {
+    auth: Arc<dyn S3Auth>,
+    region: String,
+    /// scheme and host the clients use to reach the gateway, e.g. `https://s3.example.org`
+    endpoint: String,
+}
+
+impl Presigner {
+    pub fn new(auth: Arc<dyn S3Auth>, region: String, endpoint: String) -> Self {
+        let endpoint = endpoint.trim_end_matches('/').to_owned();
+        Self { auth, region, endpoint }
+    }
+
+    /// URL which allows the request `method` on the object for `expires` from `now` without credentials
+    pub async fn presign(
+        &self,
+        method: &Method,
+        bucket: &str,
+        key: &str,
+        access_key: &str,
+        now: Timestamp,
+        expires: Duration,
+    ) -> S3Result<String> {
+        let secret_key = self.auth.get_secret_key(access_key).await?;
+        let host = self
+            .endpoint
+            .split_once("://")
+            .map_or(self.endpoint.as_str(), |(_, host)| host);
+
+        let date = format!("{:04}{:02}{:02}", now.year(), now.month() as u8, now.day());
+        let amz_date = format!("{date}T{:02}{:02}{:02}Z", now.hour(), now.minute(), now.second());
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+
+        let mut path = String::new();
+        uri_encode(&mut path, &format!("/{bucket}/{key}"), false);
+
+        // the parameters are already in the canonical order
+        let params = [
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_owned()),
+            ("X-Amz-Credential", format!("{access_key}/{scope}")),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", expires.as_secs().to_string()),
+            ("X-Amz-SignedHeaders", "host".to_owned()),
+        ];
+        let mut query = String::new();
+        for (name, value) in &params {
+            if !query.is_empty() {
+                query.push('&');
+            }
+            query.push_str(name);
+            query.push('=');
+            uri_encode(&mut query, value, true);
+        }
+
+        let canonical_request = format!("{method}\n{path}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD");
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", hex_sha256(canonical_request.as_bytes()));
+
+        let mut signing_key = hmac_sha256(format!("AWS4{}", secret_key.expose()).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            signing_key = hmac_sha256(&signing_key, part.as_bytes());
+        }
+        let signature =
+            hex_simd::encode_to_string(hmac_sha256(&signing_key, string_to_sign.as_bytes()), hex_simd::AsciiCase::Lower);
+
+        Ok(format!("{}{path}?{query}&X-Amz-Signature={signature}", self.endpoint))
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
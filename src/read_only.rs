@@ -0,0 +1,92 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{Method, Request, Response, StatusCode};
+use s3s::{S3Error, S3ErrorCode};
+
+use crate::meta_store::MetaStore;
+use crate::post_policy::{error_response, request_bucket};
+
+/// Rejects mutating requests while the gateway or the bucket is read-only, reads are served as usual.
+///
+/// The gateway-wide mode is kept in memory of this instance and answers with `503 SlowDown` so that
+/// clients retry later. Read-only buckets are stored in the database and answer with `AccessDenied`.
+#[derive(Clone)]
+pub struct ReadOnly<S> {
+    inner: S,
+    db: Arc<dyn MetaStore>,
+    base_domain: Option<String>,
+    gateway: Arc<AtomicBool>,
+}
+
+impl<S> ReadOnly<S> {
+    pub fn new(inner: S, db: Arc<dyn MetaStore>, base_domain: Option<String>, gateway: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            db,
+            base_domain,
+            gateway,
+        }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for ReadOnly<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        if !is_mutating(&req) {
+            return Box::pin(self.inner.call(req));
+        }
+        if self.gateway.load(Ordering::Relaxed) {
+            let mut err = S3Error::with_message(S3ErrorCode::SlowDown, "The service is in read-only maintenance mode");
+            err.set_status_code(StatusCode::SERVICE_UNAVAILABLE);
+            return Box::pin(async move { Ok(error_response(&err)) });
+        }
+        let Some(bucket) = request_bucket(&req, self.base_domain.as_deref()) else {
+            return Box::pin(self.inner.call(req));
+        };
+
+        // the ready service must be used for the call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let db = self.db.clone();
+        Box::pin(async move {
+            match db.get_bucket_metadata(&bucket).await {
+                Ok(Some(md)) if md.read_only => {
+                    let mut err = S3Error::with_message(S3ErrorCode::AccessDenied, "The bucket is in read-only mode");
+                    err.set_status_code(StatusCode::FORBIDDEN);
+                    return Ok(error_response(&err));
+                }
+                Ok(_) => {}
+                // the request fails later on if the database is unavailable
+                Err(err) => tracing::debug!(error = %err, bucket, "unable to check whether the bucket is read-only"),
+            }
+            inner.call(req).await
+        })
+    }
+}
+
+/// SelectObjectContent is the only read sent as POST
+fn is_mutating<B>(req: &Request<B>) -> bool {
+    match *req.method() {
+        Method::GET | Method::HEAD | Method::OPTIONS => false,
+        Method::POST => !req
+            .uri()
+            .query()
+            .is_some_and(|q| form_urlencoded::parse(q.as_bytes()).any(|(k, _)| k == "select")),
+        _ => true,
+    }
+}
@@ -0,0 +1,172 @@
+//! Settings which can be changed without a restart.
+//!
+//! They are read from a JSON file on start and again on SIGHUP, components subscribe to the watch channel.
+//! A file which fails to load is reported and the previous settings are kept. Credentials and admin tokens may be
+//! secret references, they are resolved again on every reload.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::secrets::Secrets;
+
+pub type LogHandle = reload::Handle<EnvFilter, Registry>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeConfig {
+    /// tracing filter directives (e.g. `info,s3s_rados=debug`), `RUST_LOG` is used if not set
+    pub log_level: Option<String>,
+    pub max_connections: usize,
+    /// access key and secret key of the gateway user
+    pub credentials: Option<(String, String)>,
+    /// bearer tokens accepted by the admin API in addition to the one from the command line
+    pub admin_tokens: Vec<String>,
+}
+
+impl RuntimeConfig {
+    /// Values missing in the file are taken from `defaults` (the command line), without a file only the secrets of
+    /// the defaults are resolved
+    pub async fn load(path: Option<&PathBuf>, defaults: &Self, secrets: &Secrets) -> anyhow::Result<Self> {
+        let config = match path {
+            Some(path) => Self::parse(path, defaults).await?,
+            None => defaults.clone(),
+        };
+        config.resolve(secrets).await
+    }
+
+    async fn resolve(mut self, secrets: &Secrets) -> anyhow::Result<Self> {
+        if let Some((access_key, secret_key)) = &self.credentials {
+            self.credentials = Some((secrets.resolve(access_key).await?, secrets.resolve(secret_key).await?));
+        }
+        for token in &mut self.admin_tokens {
+            *token = secrets.resolve(token).await?;
+        }
+        Ok(self)
+    }
+
+    async fn parse(path: &PathBuf, defaults: &Self) -> anyhow::Result<Self> {
+        let data = tokio::fs::read(path).await?;
+        let Value::Object(value) = serde_json::from_slice(&data)? else {
+            anyhow::bail!("runtime config must be a JSON object");
+        };
+
+        let string = |key: &str| -> anyhow::Result<Option<String>> {
+            match value.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => anyhow::bail!("{key} must be a string"),
+            }
+        };
+
+        let mut config = defaults.clone();
+        if let Some(log_level) = string("log_level")? {
+            EnvFilter::try_new(&log_level)?;
+            config.log_level = Some(log_level);
+        }
+        match value.get("max_connections") {
+            None | Some(Value::Null) => {}
+            Some(v) => {
+                config.max_connections = v
+                    .as_u64()
+                    .filter(|v| *v > 0)
+                    .ok_or_else(|| anyhow::anyhow!("max_connections must be a positive integer"))?
+                    as usize
+            }
+        }
+        match (string("access_key")?, string("secret_key")?) {
+            (Some(access_key), Some(secret_key)) => config.credentials = Some((access_key, secret_key)),
+            (None, None) => {}
+            _ => anyhow::bail!("access_key and secret_key must be set together"),
+        }
+        match value.get("admin_tokens") {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(tokens)) => {
+                config.admin_tokens = tokens
+                    .iter()
+                    .map(|token| match token {
+                        Value::String(token) if !token.is_empty() => Ok(token.clone()),
+                        _ => anyhow::bail!("admin_tokens must be non-empty strings"),
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+            }
+            Some(_) => anyhow::bail!("admin_tokens must be an array"),
+        }
+        for key in value.keys() {
+            if !matches!(
+                key.as_str(),
+                "log_level" | "max_connections" | "access_key" | "secret_key" | "admin_tokens"
+            ) {
+                tracing::warn!(key, "unknown runtime config key is ignored");
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Reload the file on every SIGHUP and, if set, every `refresh` to pick up rotated secrets
+pub async fn reload_on_sighup(
+    path: Option<PathBuf>,
+    defaults: RuntimeConfig,
+    secrets: Arc<Secrets>,
+    refresh: Option<Duration>,
+    config: watch::Sender<RuntimeConfig>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            tracing::error!(error = %err, "unable to subscribe to SIGHUP, runtime config reload is disabled");
+            return;
+        }
+    };
+    let path_display = path.as_ref().map(|path| path.display().to_string()).unwrap_or_default();
+    loop {
+        let signaled = match refresh {
+            Some(refresh) => tokio::select! {
+                signal = hangup.recv() => signal.is_some(),
+                _ = tokio::time::sleep(refresh) => true,
+            },
+            None => hangup.recv().await.is_some(),
+        };
+        if !signaled {
+            break;
+        }
+        match RuntimeConfig::load(path.as_ref(), &defaults, &secrets).await {
+            Ok(new) => {
+                if config.send_if_modified(|current| std::mem::replace(current, new.clone()) != new) {
+                    tracing::info!(path = %path_display, "runtime config has been reloaded");
+                } else {
+                    tracing::debug!(path = %path_display, "runtime config has not changed");
+                }
+            }
+            Err(err) => {
+                tracing::error!(error = %err, path = %path_display, "unable to reload runtime config, keeping the previous one");
+            }
+        }
+    }
+}
+
+/// Apply log level changes to the tracing subscriber
+pub async fn apply_log_level(mut config: watch::Receiver<RuntimeConfig>, handle: LogHandle) {
+    let mut current = config.borrow_and_update().log_level.clone();
+    while config.changed().await.is_ok() {
+        let log_level = config.borrow_and_update().log_level.clone();
+        if log_level == current {
+            continue;
+        }
+        let filter = match &log_level {
+            Some(log_level) => EnvFilter::new(log_level),
+            None => EnvFilter::from_default_env(),
+        };
+        if let Err(err) = handle.reload(filter) {
+            tracing::error!(error = %err, "unable to change the log level");
+            continue;
+        }
+        tracing::info!(log_level = log_level.as_deref().unwrap_or("RUST_LOG"), "log level has been changed");
+        current = log_level;
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use s3s::dto::ReplicationStatus;
+use tokio::io::AsyncWriteExt;
+use tracing::{debug_span, Instrument};
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{MetaStore, ReplicationTask};
+
+const BATCH_SIZE: i64 = 100;
+const IDLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Copies committed blobs from the primary backend to the secondary one.
+///
+/// Tasks are put into the `replication_queue` outbox in the same transaction that commits the object,
+/// so a crash between commit and copy only delays replication. There is a single target, PutBucketReplication
+/// accepts only rules whose destination bucket names it.
+pub struct ReplicationWorker {
+    db: Arc<dyn MetaStore>,
+    source: Arc<dyn BlobStore>,
+    target: Arc<dyn BlobStore>,
+}
+
+impl ReplicationWorker {
+    pub fn new(db: Arc<dyn MetaStore>, source: Arc<dyn BlobStore>, target: Arc<dyn BlobStore>) -> Self {
+        Self { db, source, target }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let tasks = match self.db.get_replication_tasks(BATCH_SIZE).await {
+                Ok(tasks) => tasks,
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to fetch replication tasks");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            if tasks.is_empty() {
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            for task in tasks {
+                let status = match self.replicate(&task).await {
+                    Ok(()) => {
+                        tracing::debug!(blob = %task.blob_id, destination = %task.destination_bucket, "blob has been replicated");
+                        ReplicationStatus::COMPLETE
+                    }
+                    Err(err) => {
+                        tracing::error!(error = %err, blob = %task.blob_id, "unable to replicate blob");
+                        ReplicationStatus::FAILED
+                    }
+                };
+
+                if let Err(err) = self.db.finish_replication_task(&task, status).await {
+                    tracing::error!(error = %err, blob = %task.blob_id, "unable to finish replication task");
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn replicate(&self, task: &ReplicationTask) -> anyhow::Result<()> {
+        let parts = self.db.get_blob_parts(&task.blob_id).await?;
+        if parts.is_empty() {
+            return self.copy(&task.blob_id.to_string(), task.size as u64).await;
+        }
+
+        // multipart blobs are stored as separate backend objects
+        for part in parts {
+            self.copy(&part.blob_id.to_string(), part.size as u64).await?;
+        }
+        Ok(())
+    }
+
+    async fn copy(&self, key: &str, size: u64) -> anyhow::Result<()> {
+        let mut reader = self.source.get_reader(key, 0, size).await?;
+        let mut writer = self.target.get_writer(key).await?;
+
+        while let Some(chunk) = reader.next().instrument(debug_span!("replication_read_chunk")).await {
+            let chunk = chunk?;
+            writer
+                .write_all(&chunk)
+                .instrument(debug_span!("replication_write_chunk"))
+                .await?;
+        }
+        writer.flush().instrument(debug_span!("replication_flush")).await?;
+        Ok(())
+    }
+}
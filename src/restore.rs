@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use time::Time;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{MetaStore, ObjectRestore, Timestamp};
+use crate::tiering::{self, ColdTier};
+
+const BATCH_SIZE: i64 = 100;
+const IDLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Expiry of a copy restored at `now`, rounded up to the next midnight UTC like S3 does
+pub(crate) fn restore_expiry(now: Timestamp, days: i32) -> Timestamp {
+    let expiry = now + time::Duration::days(days as i64);
+    match expiry.date().next_day() {
+        Some(date) => date.with_time(Time::MIDNIGHT).assume_utc(),
+        None => expiry,
+    }
+}
+
+/// Processes the restores requested with RestoreObject.
+///
+/// A restore moves the blob of the object from the cold tier back into the standard backend, so the object
+/// is read from the standard backend until the copy expires. Expired copies are moved to the cold tier again,
+/// the tiering worker skips them while the restore is active.
+pub struct RestoreWorker {
+    db: Arc<dyn MetaStore>,
+    standard: Arc<dyn BlobStore>,
+    cold: ColdTier,
+}
+
+impl RestoreWorker {
+    pub fn new(db: Arc<dyn MetaStore>, standard: Arc<dyn BlobStore>, cold: ColdTier) -> Self {
+        Self { db, standard, cold }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let res = async {
+                let pending = self.db.list_pending_restores(BATCH_SIZE).await?;
+                let expired = self.db.list_expired_restores(BATCH_SIZE).await?;
+                anyhow::Ok((pending, expired))
+            }
+            .await;
+            let (pending, expired) = match res {
+                Ok(restores) => restores,
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to list object restores");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            if pending.is_empty() && expired.is_empty() {
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            for restore in pending {
+                if let Err(err) = self.restore(&restore).await {
+                    tracing::error!(error = %err, bucket = restore.bucket, key = restore.oid, "unable to restore object");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                }
+            }
+            for restore in expired {
+                if let Err(err) = self.expire(&restore).await {
+                    tracing::error!(error = %err, bucket = restore.bucket, key = restore.oid, "unable to expire restored object");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn restore(&self, restore: &ObjectRestore) -> anyhow::Result<()> {
+        let blob = match self.db.load_object_metadata(&restore.bucket, &restore.oid, &None).await? {
+            Some((_, Some(blob))) if blob.id == restore.blob => blob,
+            // overwritten or deleted since the request
+            _ => {
+                return self
+                    .db
+                    .delete_object_restore(&restore.bucket, &restore.oid, &restore.blob)
+                    .await
+            }
+        };
+
+        let restored = match blob.storage_class {
+            None => blob,
+            Some(_) => match tiering::move_to_standard(self.db.as_ref(), self.standard.as_ref(), &self.cold, &blob).await? {
+                Some(restored) => restored,
+                None => {
+                    return self
+                        .db
+                        .delete_object_restore(&restore.bucket, &restore.oid, &restore.blob)
+                        .await;
+                }
+            },
+        };
+        let expires_at = restore_expiry(Timestamp::now_utc(), restore.days);
+        self.db
+            .set_object_restore(&restore.bucket, &restore.oid, &restored.id, restore.days, expires_at)
+            .await?;
+        tracing::debug!(blob = %restored.id, "object has been restored");
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn expire(&self, restore: &ObjectRestore) -> anyhow::Result<()> {
+        match self.db.load_object_metadata(&restore.bucket, &restore.oid, &None).await? {
+            Some((_, Some(blob))) if blob.id == restore.blob && blob.storage_class.is_none() => {
+                tiering::move_to_cold(self.db.as_ref(), self.standard.as_ref(), &self.cold, &blob).await?;
+            }
+            // overwritten or deleted while it was restored, a new blob belongs to the tiering worker
+            _ => {}
+        }
+        self.db
+            .delete_object_restore(&restore.bucket, &restore.oid, &restore.blob)
+            .await
+    }
+}
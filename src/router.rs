@@ -0,0 +1,62 @@
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{header, Request, Response};
+
+use crate::admin::AdminApi;
+
+/// Dispatches the requests of the S3 listener by the Host header.
+///
+/// Requests for the admin host are answered by the admin API with its own bearer token authentication, they
+/// never reach the S3 service. Everything else goes to the S3 service.
+#[derive(Clone)]
+pub struct EndpointRouter<S> {
+    inner: S,
+    admin: Option<(String, AdminApi)>,
+}
+
+impl<S> EndpointRouter<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner, admin: None }
+    }
+
+    /// Serve the admin API for requests to `host`
+    pub fn with_admin(mut self, host: String, admin: AdminApi) -> Self {
+        self.admin = Some((host.to_ascii_lowercase(), admin));
+        self
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for EndpointRouter<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        if let Some((host, admin)) = &self.admin {
+            if request_host(&req).is_some_and(|h| h.eq_ignore_ascii_case(host)) {
+                let admin = admin.clone();
+                return Box::pin(async move { Ok(admin.handle(req).await.map(s3s::Body::from)) });
+            }
+        }
+        Box::pin(self.inner.call(req))
+    }
+}
+
+/// Host without the port, HTTP/2 requests carry it in the URI authority
+fn request_host<B>(req: &Request<B>) -> Option<&str> {
+    let host = match req.headers().get(header::HOST) {
+        Some(host) => host.to_str().ok()?,
+        None => req.uri().host()?,
+    };
+    Some(host.rsplit_once(':').map_or(host, |(name, _)| name))
+}
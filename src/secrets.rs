@@ -0,0 +1,127 @@
+//! References to secrets in settings.
+//!
+//! A setting which holds a secret may name where to read it from instead of carrying it in plain text:
+//! - `file:<path>` the content of the file without trailing newlines, e.g. a Kubernetes or Docker secret mount
+//! - `vault:<path>#<field>` a field of a HashiCorp Vault secret, e.g. `vault:secret/data/s3s#secret_key` for the
+//!   KV v2 engine mounted at `secret`
+//!
+//! Any other value is taken literally. References are resolved when the setting is loaded, secrets of the
+//! runtime config are fetched again on every reload so that rotated secrets are picked up.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use serde_json::Value;
+
+const VAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Secrets of a load share one request per path, leases of dynamic secrets are respected if shorter
+const VAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+pub struct Secrets {
+    vault: Option<Vault>,
+}
+
+impl Secrets {
+    /// `token` may be a `file:` reference, e.g. the sink of a Vault agent, it is read again for every request so
+    /// that renewed tokens are used
+    pub fn with_vault(mut self, addr: String, token: String) -> Self {
+        self.vault = Some(Vault {
+            addr: addr.trim_end_matches('/').to_owned(),
+            token,
+            client: Client::new(),
+            cache: Mutex::default(),
+        });
+        self
+    }
+
+    pub async fn resolve(&self, value: &str) -> anyhow::Result<String> {
+        if let Some(path) = value.strip_prefix("file:") {
+            return read_file(path).await;
+        }
+        let Some(reference) = value.strip_prefix("vault:") else {
+            return Ok(value.to_owned());
+        };
+        let Some((path, field)) = reference.rsplit_once('#') else {
+            anyhow::bail!("vault reference {value} must name a field after #");
+        };
+        let Some(vault) = &self.vault else {
+            anyhow::bail!("vault reference {value} requires --vault-addr");
+        };
+        let secret = vault.read(path).await?;
+        // KV v2 nests the fields of the secret into data
+        let data = match &secret["data"] {
+            Value::Object(data) if data.get("metadata").is_some() => &secret["data"]["data"],
+            data => data,
+        };
+        match &data[field] {
+            Value::String(value) => Ok(value.clone()),
+            Value::Null => anyhow::bail!("vault secret {path} has no field {field}"),
+            _ => anyhow::bail!("field {field} of vault secret {path} is not a string"),
+        }
+    }
+
+    pub async fn resolve_opt(&self, value: Option<&str>) -> anyhow::Result<Option<String>> {
+        match value {
+            Some(value) => Ok(Some(self.resolve(value).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+async fn read_file(path: &str) -> anyhow::Result<String> {
+    let data = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|err| anyhow::anyhow!("unable to read secret file {path}: {err}"))?;
+    Ok(data.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Plain HTTP only, e.g. a Vault agent listening next to the gateway
+struct Vault {
+    addr: String,
+    token: String,
+    client: Client<HttpConnector>,
+    cache: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl Vault {
+    async fn read(&self, path: &str) -> anyhow::Result<Value> {
+        let path = path.trim_matches('/');
+        if let Some((expires_at, secret)) = self.cache.lock().expect("unable to lock mutex").get(path) {
+            if *expires_at > Instant::now() {
+                return Ok(secret.clone());
+            }
+        }
+
+        let token = match self.token.strip_prefix("file:") {
+            Some(token_path) => read_file(token_path).await?,
+            None => self.token.clone(),
+        };
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(format!("{}/v1/{path}", self.addr))
+            .header("x-vault-token", token)
+            .body(Body::empty())?;
+        let res = tokio::time::timeout(VAULT_TIMEOUT, self.client.request(req)).await??;
+        match res.status() {
+            StatusCode::OK => {}
+            StatusCode::NOT_FOUND => anyhow::bail!("vault secret {path} does not exist"),
+            status => anyhow::bail!("vault has responded with {status} for secret {path}"),
+        }
+        let body = hyper::body::to_bytes(res.into_body()).await?;
+        let secret: Value = serde_json::from_slice(&body)?;
+
+        let ttl = match secret["lease_duration"].as_u64() {
+            Some(lease) if lease > 0 => VAULT_CACHE_TTL.min(Duration::from_secs(lease)),
+            _ => VAULT_CACHE_TTL,
+        };
+        self.cache
+            .lock()
+            .expect("unable to lock mutex")
+            .insert(path.to_owned(), (Instant::now() + ttl, secret.clone()));
+        Ok(secret)
+    }
+}
@@ -0,0 +1,1079 @@
+//! Minimal SQL engine for SelectObjectContent.
+//!
+//! Supported syntax:
+//! `SELECT <* | COUNT(*) | expr [AS name], ...> FROM S3Object [[AS] alias] [WHERE expr] [LIMIT n]`
+//! where expressions consist of column references (`s.name`, `s._1`, nested JSON paths),
+//! string/number/boolean literals, comparisons, `LIKE`, `IS [NOT] NULL`, `AND`, `OR`, `NOT` and `CAST`.
+
+use std::cmp::Ordering;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use s3s::dto::*;
+use s3s::{S3Error, S3Result};
+
+type BlobStream = core::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, S3Error>> + Send + Sync>>;
+
+/// Records are never larger than this (the same limit as in AWS)
+const MAX_RECORD_SIZE: usize = 1024 * 1024;
+/// JSON documents are parsed as a whole so their size is limited
+const MAX_DOCUMENT_SIZE: usize = 64 * 1024 * 1024;
+/// Size of the payload of a single Records event
+const RECORDS_EVENT_SIZE: usize = 64 * 1024;
+
+fn select_error(code: &str, message: impl Into<String>) -> S3Error {
+    let mut err = S3Error::with_message(s3s::S3ErrorCode::Custom(code.to_owned().into()), message.into());
+    err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+    err
+}
+
+// ---------------------------------------------------------------------------
+// parser
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    QuotedIdent(String),
+    String(String),
+    Number(f64),
+    Symbol(&'static str),
+}
+
+fn tokenize(sql: &str) -> S3Result<Vec<Token>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let Ok(number) = text.parse() else {
+                return Err(select_error("ParseInvalidNumber", format!("Invalid number {text}")));
+            };
+            tokens.push(Token::Number(number));
+            continue;
+        }
+
+        if c == '\'' || c == '"' {
+            // quotes are escaped by doubling them
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(select_error("ParseExpectedTokenType", "Unterminated quoted string")),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::String(text)
+            } else {
+                Token::QuotedIdent(text)
+            });
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+        let symbol = ["<=", ">=", "<>", "!="]
+            .into_iter()
+            .find(|s| rest.starts_with(s))
+            .or_else(|| {
+                ["*", ",", ".", "(", ")", "=", "<", ">", "[", "]"]
+                    .into_iter()
+                    .find(|s| rest.starts_with(s))
+            });
+        let Some(symbol) = symbol else {
+            return Err(select_error("ParseUnexpectedToken", format!("Unexpected character {c}")));
+        };
+        i += symbol.len();
+        tokens.push(Token::Symbol(symbol));
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CastType {
+    Int,
+    Float,
+    String,
+    Bool,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    /// column name or nested JSON path
+    Column(Vec<String>),
+    /// 1-based CSV column index (`_1`)
+    Index(usize),
+    Literal(Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Cmp(CmpOp, Box<Expr>, Box<Expr>),
+    IsNull(Box<Expr>, bool),
+    Like(Box<Expr>, Box<Expr>, bool),
+    Cast(Box<Expr>, CastType),
+}
+
+#[derive(Debug)]
+enum Projection {
+    All,
+    Count,
+    Items(Vec<(Expr, String)>),
+}
+
+#[derive(Debug)]
+pub struct Query {
+    projection: Projection,
+    condition: Option<Expr>,
+    limit: Option<u64>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    alias: Option<String>,
+}
+
+impl Query {
+    pub fn parse(sql: &str) -> S3Result<Self> {
+        let mut p = Parser {
+            tokens: tokenize(sql)?,
+            pos: 0,
+            alias: None,
+        };
+        p.expect_keyword("SELECT")?;
+
+        // the alias is needed to resolve column references, so the projection is parsed after FROM
+        let projection_start = p.pos;
+        while p.pos < p.tokens.len() && !p.is_keyword("FROM") {
+            p.pos += 1;
+        }
+        p.expect_keyword("FROM")?;
+        let Some(Token::Ident(source)) = p.next() else {
+            return Err(select_error("ParseExpectedIdentForFrom", "Expected S3Object"));
+        };
+        if !source.eq_ignore_ascii_case("S3Object") {
+            return Err(select_error("ParseUnsupportedSyntax", "Only S3Object can be queried"));
+        }
+        if p.peek() == Some(&Token::Symbol("[")) {
+            p.pos += 1;
+            p.expect_symbol("*")?;
+            p.expect_symbol("]")?;
+        }
+        if p.is_keyword("AS") {
+            p.pos += 1;
+        }
+        if let Some(Token::Ident(alias)) = p.peek() {
+            if !["WHERE", "LIMIT"].iter().any(|k| alias.eq_ignore_ascii_case(k)) {
+                p.alias = Some(alias.clone());
+                p.pos += 1;
+            }
+        }
+        let where_start = p.pos;
+
+        p.pos = projection_start;
+        let projection = p.parse_projection()?;
+        if !p.is_keyword("FROM") {
+            return Err(p.unexpected());
+        }
+
+        p.pos = where_start;
+        let condition = if p.is_keyword("WHERE") {
+            p.pos += 1;
+            Some(p.parse_or()?)
+        } else {
+            None
+        };
+
+        let limit = if p.is_keyword("LIMIT") {
+            p.pos += 1;
+            match p.next() {
+                Some(Token::Number(n)) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+                _ => return Err(select_error("ParseInvalidTypeParam", "LIMIT expects a non-negative integer")),
+            }
+        } else {
+            None
+        };
+
+        if p.pos < p.tokens.len() {
+            return Err(p.unexpected());
+        }
+
+        Ok(Self {
+            projection,
+            condition,
+            limit,
+        })
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn unexpected(&self) -> S3Error {
+        match self.peek() {
+            Some(token) => select_error("ParseUnexpectedToken", format!("Unexpected token {token:?}")),
+            None => select_error("ParseUnexpectedTerm", "Unexpected end of expression"),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> S3Result<()> {
+        if !self.is_keyword(keyword) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> S3Result<()> {
+        if !self.is_symbol(symbol) {
+            return Err(self.unexpected());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn parse_projection(&mut self) -> S3Result<Projection> {
+        if self.is_symbol("*") {
+            self.pos += 1;
+            return Ok(Projection::All);
+        }
+        if self.is_keyword("COUNT") {
+            self.pos += 1;
+            self.expect_symbol("(")?;
+            self.expect_symbol("*")?;
+            self.expect_symbol(")")?;
+            return Ok(Projection::Count);
+        }
+
+        let mut items = Vec::new();
+        loop {
+            let expr = self.parse_or()?;
+            let name = if self.is_keyword("AS") {
+                self.pos += 1;
+                match self.next() {
+                    Some(Token::Ident(name)) | Some(Token::QuotedIdent(name)) => name,
+                    _ => return Err(select_error("ParseExpectedIdentForAlias", "Expected alias name")),
+                }
+            } else {
+                match &expr {
+                    Expr::Column(path) => path.last().cloned().unwrap_or_default(),
+                    _ => format!("_{}", items.len() + 1),
+                }
+            };
+            items.push((expr, name));
+
+            if !self.is_symbol(",") {
+                break;
+            }
+            self.pos += 1;
+        }
+        Ok(Projection::Items(items))
+    }
+
+    fn parse_or(&mut self) -> S3Result<Expr> {
+        let mut lhs = self.parse_and()?;
+        while self.is_keyword("OR") {
+            self.pos += 1;
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> S3Result<Expr> {
+        let mut lhs = self.parse_not()?;
+        while self.is_keyword("AND") {
+            self.pos += 1;
+            lhs = Expr::And(Box::new(lhs), Box::new(self.parse_not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> S3Result<Expr> {
+        if self.is_keyword("NOT") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_cmp()
+    }
+
+    fn parse_cmp(&mut self) -> S3Result<Expr> {
+        let lhs = self.parse_primary()?;
+
+        if self.is_keyword("IS") {
+            self.pos += 1;
+            let negated = self.is_keyword("NOT");
+            if negated {
+                self.pos += 1;
+            }
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull(Box::new(lhs), negated));
+        }
+
+        let negated = self.is_keyword("NOT");
+        if negated {
+            self.pos += 1;
+        }
+        if self.is_keyword("LIKE") {
+            self.pos += 1;
+            return Ok(Expr::Like(Box::new(lhs), Box::new(self.parse_primary()?), negated));
+        }
+        if negated {
+            return Err(self.unexpected());
+        }
+
+        let op = match self.peek() {
+            Some(Token::Symbol("=")) => CmpOp::Eq,
+            Some(Token::Symbol("!=")) | Some(Token::Symbol("<>")) => CmpOp::Ne,
+            Some(Token::Symbol("<")) => CmpOp::Lt,
+            Some(Token::Symbol("<=")) => CmpOp::Le,
+            Some(Token::Symbol(">")) => CmpOp::Gt,
+            Some(Token::Symbol(">=")) => CmpOp::Ge,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        Ok(Expr::Cmp(op, Box::new(lhs), Box::new(self.parse_primary()?)))
+    }
+
+    fn parse_primary(&mut self) -> S3Result<Expr> {
+        match self.next() {
+            Some(Token::String(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.parse_or()?;
+                self.expect_symbol(")")?;
+                Ok(expr)
+            }
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("TRUE") => Ok(Expr::Literal(Value::Bool(true))),
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("FALSE") => Ok(Expr::Literal(Value::Bool(false))),
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("NULL") => Ok(Expr::Literal(Value::Null)),
+            Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("CAST") && self.is_symbol("(") => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                self.expect_keyword("AS")?;
+                let Some(Token::Ident(ty)) = self.next() else {
+                    return Err(select_error("ParseExpectedTypeName", "Expected type name"));
+                };
+                let ty = match ty.to_ascii_uppercase().as_str() {
+                    "INT" | "INTEGER" | "BIGINT" => CastType::Int,
+                    "FLOAT" | "DECIMAL" | "NUMERIC" | "DOUBLE" | "REAL" => CastType::Float,
+                    "STRING" | "VARCHAR" | "CHAR" => CastType::String,
+                    "BOOL" | "BOOLEAN" => CastType::Bool,
+                    _ => return Err(select_error("ParseUnsupportedType", format!("Unsupported type {ty}"))),
+                };
+                self.expect_symbol(")")?;
+                Ok(Expr::Cast(Box::new(expr), ty))
+            }
+            Some(Token::Ident(ident)) | Some(Token::QuotedIdent(ident)) => {
+                let mut path = vec![ident];
+                while self.is_symbol(".") {
+                    self.pos += 1;
+                    match self.next() {
+                        Some(Token::Ident(ident)) | Some(Token::QuotedIdent(ident)) => path.push(ident),
+                        _ => return Err(select_error("ParseExpectedIdentForAt", "Expected column name")),
+                    }
+                }
+                self.column(path)
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    fn column(&self, mut path: Vec<String>) -> S3Result<Expr> {
+        let is_alias = |name: &str| {
+            name.eq_ignore_ascii_case("S3Object") || self.alias.as_ref().is_some_and(|a| a.eq_ignore_ascii_case(name))
+        };
+        if path.len() > 1 && is_alias(&path[0]) {
+            path.remove(0);
+        }
+
+        if let [name] = path.as_slice() {
+            if let Some(index) = name.strip_prefix('_').and_then(|n| n.parse::<usize>().ok()) {
+                if index == 0 {
+                    return Err(select_error("InvalidColumnIndex", "Column indexes start with 1"));
+                }
+                return Ok(Expr::Index(index));
+            }
+        }
+        Ok(Expr::Column(path))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// evaluation
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// JSON object or array
+    Json(serde_json::Value),
+}
+
+impl Value {
+    fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => n.as_f64().map_or(Value::Null, Value::Number),
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            other => Value::Json(other.clone()),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => serde_json::Value::from(*n as i64),
+            Value::Number(n) => serde_json::Value::from(*n),
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Json(v) => v.clone(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            other => other.to_json().to_string(),
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn cast(self, ty: CastType) -> S3Result<Value> {
+        let failed = || select_error("CastFailed", format!("Unable to cast {self:?} to {ty:?}"));
+        Ok(match (ty, &self) {
+            (_, Value::Null) => Value::Null,
+            (CastType::String, _) => Value::String(self.to_text()),
+            (CastType::Int, _) => Value::Number(self.as_number().ok_or_else(failed)?.trunc()),
+            (CastType::Float, _) => Value::Number(self.as_number().ok_or_else(failed)?),
+            (CastType::Bool, Value::Bool(_)) => self,
+            (CastType::Bool, Value::String(s)) if s.eq_ignore_ascii_case("true") => Value::Bool(true),
+            (CastType::Bool, Value::String(s)) if s.eq_ignore_ascii_case("false") => Value::Bool(false),
+            (CastType::Bool, _) => return Err(failed()),
+        })
+    }
+
+    /// Numbers are compared numerically, CSV fields are strings so they are converted when compared with a number.
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Json(a), Value::Json(b)) => (a == b).then_some(Ordering::Equal),
+            (a, b) => a.as_number()?.partial_cmp(&b.as_number()?),
+        }
+    }
+}
+
+/// Single input record
+pub enum Record<'a> {
+    Csv {
+        fields: Vec<String>,
+        header: Option<&'a [String]>,
+    },
+    Json(serde_json::Value),
+}
+
+impl Record<'_> {
+    fn column(&self, path: &[String]) -> Value {
+        match self {
+            Record::Csv { fields, header } => {
+                let ([name], Some(header)) = (path, header) else {
+                    return Value::Null;
+                };
+                header
+                    .iter()
+                    .position(|h| h == name)
+                    .or_else(|| header.iter().position(|h| h.eq_ignore_ascii_case(name)))
+                    .and_then(|i| fields.get(i))
+                    .map_or(Value::Null, |f| Value::String(f.clone()))
+            }
+            Record::Json(value) => {
+                let mut value = value;
+                for key in path {
+                    let Some(v) = value.get(key.as_str()) else {
+                        return Value::Null;
+                    };
+                    value = v;
+                }
+                Value::from_json(value)
+            }
+        }
+    }
+
+    fn index(&self, index: usize) -> Value {
+        match self {
+            Record::Csv { fields, .. } => fields.get(index - 1).map_or(Value::Null, |f| Value::String(f.clone())),
+            Record::Json(_) => Value::Null,
+        }
+    }
+}
+
+fn eval(expr: &Expr, record: &Record<'_>) -> S3Result<Value> {
+    Ok(match expr {
+        Expr::Column(path) => record.column(path),
+        Expr::Index(index) => record.index(*index),
+        Expr::Literal(value) => value.clone(),
+        Expr::Not(e) => match eval(e, record)? {
+            Value::Bool(b) => Value::Bool(!b),
+            _ => Value::Null,
+        },
+        Expr::And(a, b) => match (eval(a, record)?, eval(b, record)?) {
+            (Value::Bool(false), _) | (_, Value::Bool(false)) => Value::Bool(false),
+            (Value::Bool(true), Value::Bool(true)) => Value::Bool(true),
+            _ => Value::Null,
+        },
+        Expr::Or(a, b) => match (eval(a, record)?, eval(b, record)?) {
+            (Value::Bool(true), _) | (_, Value::Bool(true)) => Value::Bool(true),
+            (Value::Bool(false), Value::Bool(false)) => Value::Bool(false),
+            _ => Value::Null,
+        },
+        Expr::Cmp(op, a, b) => {
+            let Some(ordering) = eval(a, record)?.compare(&eval(b, record)?) else {
+                return Ok(Value::Null);
+            };
+            Value::Bool(match op {
+                CmpOp::Eq => ordering == Ordering::Equal,
+                CmpOp::Ne => ordering != Ordering::Equal,
+                CmpOp::Lt => ordering == Ordering::Less,
+                CmpOp::Le => ordering != Ordering::Greater,
+                CmpOp::Gt => ordering == Ordering::Greater,
+                CmpOp::Ge => ordering != Ordering::Less,
+            })
+        }
+        Expr::IsNull(e, negated) => Value::Bool((eval(e, record)? == Value::Null) != *negated),
+        Expr::Like(e, pattern, negated) => match (eval(e, record)?, eval(pattern, record)?) {
+            (Value::Null, _) | (_, Value::Null) => Value::Null,
+            (value, pattern) => {
+                let value: Vec<char> = value.to_text().chars().collect();
+                let pattern: Vec<char> = pattern.to_text().chars().collect();
+                Value::Bool(like(&value, &pattern) != *negated)
+            }
+        },
+        Expr::Cast(e, ty) => eval(e, record)?.cast(*ty)?,
+    })
+}
+
+/// SQL LIKE with `%` and `_` wildcards
+fn like(value: &[char], pattern: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('%', rest)) => (0..=value.len()).any(|i| like(&value[i..], rest)),
+        Some(('_', rest)) => !value.is_empty() && like(&value[1..], rest),
+        Some((c, rest)) => value.first() == Some(c) && like(&value[1..], rest),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// input and output formats
+
+#[derive(Debug)]
+enum InputFormat {
+    Csv {
+        field_delimiter: char,
+        record_delimiter: Vec<u8>,
+        quote: char,
+        quote_escape: char,
+        comments: Option<String>,
+        header_info: String,
+    },
+    JsonLines,
+    JsonDocument,
+}
+
+#[derive(Debug)]
+enum OutputFormat {
+    Csv {
+        field_delimiter: String,
+        record_delimiter: String,
+        quote: char,
+        quote_escape: char,
+        always_quote: bool,
+    },
+    Json {
+        record_delimiter: String,
+    },
+}
+
+fn single_char(value: Option<String>, default: char, name: &str) -> S3Result<char> {
+    let Some(value) = value else {
+        return Ok(default);
+    };
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(select_error("InvalidArgument", format!("{name} must be a single character"))),
+    }
+}
+
+impl InputFormat {
+    fn new(input: InputSerialization) -> S3Result<Self> {
+        if let Some(compression) = &input.compression_type {
+            if compression.as_str() != CompressionType::NONE {
+                return Err(s3s::s3_error!(NotImplemented, "Compressed objects are not supported"));
+            }
+        }
+        if input.parquet.is_some() {
+            return Err(s3s::s3_error!(NotImplemented, "Parquet objects are not supported"));
+        }
+
+        match (input.csv, input.json) {
+            (Some(csv), None) => {
+                let quote = single_char(csv.quote_character, '"', "QuoteCharacter")?;
+                Ok(InputFormat::Csv {
+                    field_delimiter: single_char(csv.field_delimiter, ',', "FieldDelimiter")?,
+                    record_delimiter: csv.record_delimiter.unwrap_or_else(|| "\n".to_owned()).into_bytes(),
+                    quote,
+                    quote_escape: single_char(csv.quote_escape_character, quote, "QuoteEscapeCharacter")?,
+                    comments: csv.comments.filter(|c| !c.is_empty()),
+                    header_info: csv
+                        .file_header_info
+                        .map_or_else(|| FileHeaderInfo::NONE.to_owned(), |h| h.as_str().to_owned()),
+                })
+            }
+            (None, Some(json)) => match json.type_.as_ref().map(|t| t.as_str()) {
+                Some(JSONType::LINES) => Ok(InputFormat::JsonLines),
+                Some(JSONType::DOCUMENT) | None => Ok(InputFormat::JsonDocument),
+                Some(other) => Err(select_error("InvalidJsonType", format!("Unknown JSON type {other}"))),
+            },
+            _ => Err(select_error("InvalidDataSource", "Exactly one input format must be specified")),
+        }
+    }
+
+    fn quote_byte(&self) -> Option<u8> {
+        match self {
+            InputFormat::Csv { quote, .. } if quote.is_ascii() => Some(*quote as u8),
+            _ => None,
+        }
+    }
+
+    fn record_delimiter(&self) -> &[u8] {
+        match self {
+            InputFormat::Csv { record_delimiter, .. } => record_delimiter,
+            _ => b"\n",
+        }
+    }
+}
+
+impl OutputFormat {
+    fn new(output: OutputSerialization) -> S3Result<Self> {
+        match (output.csv, output.json) {
+            (Some(csv), None) => {
+                let quote = single_char(csv.quote_character, '"', "QuoteCharacter")?;
+                Ok(OutputFormat::Csv {
+                    field_delimiter: csv.field_delimiter.unwrap_or_else(|| ",".to_owned()),
+                    record_delimiter: csv.record_delimiter.unwrap_or_else(|| "\n".to_owned()),
+                    quote,
+                    quote_escape: single_char(csv.quote_escape_character, quote, "QuoteEscapeCharacter")?,
+                    always_quote: csv.quote_fields.is_some_and(|q| q.as_str() == QuoteFields::ALWAYS),
+                })
+            }
+            (None, Some(json)) => Ok(OutputFormat::Json {
+                record_delimiter: json.record_delimiter.unwrap_or_else(|| "\n".to_owned()),
+            }),
+            _ => Err(select_error("InvalidDataSource", "Exactly one output format must be specified")),
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>, row: Vec<(String, Value)>) {
+        match self {
+            OutputFormat::Csv {
+                field_delimiter,
+                record_delimiter,
+                quote,
+                quote_escape,
+                always_quote,
+            } => {
+                for (i, (_, value)) in row.into_iter().enumerate() {
+                    if i > 0 {
+                        out.extend_from_slice(field_delimiter.as_bytes());
+                    }
+                    let text = value.to_text();
+                    let needs_quotes = *always_quote
+                        || text.contains(field_delimiter.as_str())
+                        || text.contains(record_delimiter.as_str())
+                        || text.contains(*quote);
+                    if needs_quotes {
+                        let escaped = text.replace(*quote, &format!("{quote_escape}{quote}"));
+                        out.extend_from_slice(format!("{quote}{escaped}{quote}").as_bytes());
+                    } else {
+                        out.extend_from_slice(text.as_bytes());
+                    }
+                }
+                out.extend_from_slice(record_delimiter.as_bytes());
+            }
+            OutputFormat::Json { record_delimiter } => {
+                let object: serde_json::Map<String, serde_json::Value> =
+                    row.into_iter().map(|(name, value)| (name, value.to_json())).collect();
+                out.extend_from_slice(serde_json::Value::Object(object).to_string().as_bytes());
+                out.extend_from_slice(record_delimiter.as_bytes());
+            }
+        }
+    }
+}
+
+fn parse_csv_fields(line: &str, delimiter: char, quote: char, escape: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == escape && chars.peek() == Some(&quote) {
+                field.push(quote);
+                chars.next();
+            } else if c == quote {
+                in_quotes = false;
+            } else {
+                field.push(c);
+            }
+        } else if c == quote {
+            in_quotes = true;
+        } else if c == delimiter {
+            fields.push(std::mem::take(&mut field));
+        } else {
+            field.push(c);
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Splits the input stream into records. Record delimiters inside quotes are ignored.
+struct Splitter {
+    buf: Vec<u8>,
+    pos: usize,
+    in_quotes: bool,
+}
+
+impl Splitter {
+    fn next_record(&mut self, delimiter: &[u8], quote: Option<u8>) -> Option<Vec<u8>> {
+        while self.pos < self.buf.len() {
+            if Some(self.buf[self.pos]) == quote {
+                self.in_quotes = !self.in_quotes;
+            } else if !self.in_quotes && self.buf[self.pos..].starts_with(delimiter) {
+                let record = self.buf[..self.pos].to_vec();
+                self.buf.drain(..self.pos + delimiter.len());
+                self.pos = 0;
+                return Some(record);
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    fn rest(&mut self) -> Option<Vec<u8>> {
+        (!self.buf.is_empty()).then(|| std::mem::take(&mut self.buf))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// execution
+
+struct Executor {
+    query: Query,
+    input: InputFormat,
+    output: OutputFormat,
+    header: Option<Vec<String>>,
+    /// the first CSV line has not been seen yet
+    first_line: bool,
+    matched: u64,
+    out: Vec<u8>,
+}
+
+impl Executor {
+    fn done(&self) -> bool {
+        self.query.limit.is_some_and(|limit| self.matched >= limit)
+    }
+
+    fn process(&mut self, raw: &[u8]) -> S3Result<()> {
+        if self.done() {
+            return Ok(());
+        }
+        let Ok(text) = std::str::from_utf8(raw) else {
+            return Err(select_error("InvalidTextEncoding", "Records must be UTF-8 encoded"));
+        };
+
+        match &self.input {
+            InputFormat::Csv {
+                field_delimiter,
+                quote,
+                quote_escape,
+                comments,
+                header_info,
+                ..
+            } => {
+                let text = text.strip_suffix('\r').unwrap_or(text);
+                if comments.as_ref().is_some_and(|c| text.starts_with(c.as_str())) {
+                    return Ok(());
+                }
+                let fields = parse_csv_fields(text, *field_delimiter, *quote, *quote_escape);
+                if std::mem::take(&mut self.first_line) && header_info.as_str() != FileHeaderInfo::NONE {
+                    if header_info.as_str() == FileHeaderInfo::USE {
+                        self.header = Some(fields);
+                    }
+                    return Ok(());
+                }
+                let header = self.header.take();
+                let res = self.process_record(&Record::Csv {
+                    fields,
+                    header: header.as_deref(),
+                });
+                self.header = header;
+                res
+            }
+            InputFormat::JsonLines => {
+                if text.trim().is_empty() {
+                    return Ok(());
+                }
+                match serde_json::from_str(text) {
+                    Ok(value) => self.process_record(&Record::Json(value)),
+                    Err(err) => Err(select_error("JSONParsingError", err.to_string())),
+                }
+            }
+            InputFormat::JsonDocument => {
+                for value in serde_json::Deserializer::from_str(text).into_iter::<serde_json::Value>() {
+                    match value {
+                        Ok(value) => self.process_record(&Record::Json(value))?,
+                        Err(err) => return Err(select_error("JSONParsingError", err.to_string())),
+                    }
+                    if self.done() {
+                        break;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn process_record(&mut self, record: &Record<'_>) -> S3Result<()> {
+        if let Some(condition) = &self.query.condition {
+            if eval(condition, record)? != Value::Bool(true) {
+                return Ok(());
+            }
+        }
+        self.matched += 1;
+
+        let row = match &self.query.projection {
+            Projection::Count => return Ok(()),
+            Projection::All => match record {
+                Record::Csv { fields, header } => fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, f)| {
+                        let name = header
+                            .and_then(|h| h.get(i))
+                            .cloned()
+                            .unwrap_or_else(|| format!("_{}", i + 1));
+                        (name, Value::String(f.clone()))
+                    })
+                    .collect(),
+                Record::Json(serde_json::Value::Object(object)) => {
+                    object.iter().map(|(k, v)| (k.clone(), Value::from_json(v))).collect()
+                }
+                Record::Json(other) => vec![("_1".to_owned(), Value::from_json(other))],
+            },
+            Projection::Items(items) => items
+                .iter()
+                .map(|(expr, name)| Ok((name.clone(), eval(expr, record)?)))
+                .collect::<S3Result<Vec<_>>>()?,
+        };
+        self.output.write(&mut self.out, row);
+        Ok(())
+    }
+
+    fn finish(&mut self) {
+        if let Projection::Count = self.query.projection {
+            self.output
+                .write(&mut self.out, vec![("_1".to_owned(), Value::Number(self.matched as f64))]);
+        }
+    }
+}
+
+/// Validated SelectObjectContent request
+#[derive(Debug)]
+pub struct Select {
+    query: Query,
+    input: InputFormat,
+    output: OutputFormat,
+}
+
+impl Select {
+    pub fn new(request: SelectObjectContentRequest) -> S3Result<Self> {
+        if request.expression_type.as_str() != ExpressionType::SQL {
+            return Err(select_error("InvalidExpressionType", "Only SQL expressions are supported"));
+        }
+        if request.scan_range.is_some() {
+            return Err(s3s::s3_error!(NotImplemented, "ScanRange is not supported"));
+        }
+
+        Ok(Self {
+            query: Query::parse(&request.expression)?,
+            input: InputFormat::new(request.input_serialization)?,
+            output: OutputFormat::new(request.output_serialization)?,
+        })
+    }
+
+    /// Evaluate the query over the blob and produce the event stream
+    pub fn run(self, body: BlobStream) -> SelectObjectContentEventStream {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        tokio::spawn(async move {
+            if let Err(err) = self.execute(body, &tx).await {
+                let _ = tx.send(Err(err)).await;
+            }
+        });
+        SelectObjectContentEventStream::new(futures::stream::poll_fn(move |cx| rx.poll_recv(cx)))
+    }
+
+    async fn execute(
+        self,
+        mut body: BlobStream,
+        tx: &tokio::sync::mpsc::Sender<S3Result<SelectObjectContentEvent>>,
+    ) -> S3Result<()> {
+        let quote = self.input.quote_byte();
+        let delimiter = self.input.record_delimiter().to_vec();
+        let is_document = matches!(self.input, InputFormat::JsonDocument);
+        let mut executor = Executor {
+            query: self.query,
+            input: self.input,
+            output: self.output,
+            header: None,
+            first_line: true,
+            matched: 0,
+            out: Vec::new(),
+        };
+        let mut splitter = Splitter {
+            buf: Vec::new(),
+            pos: 0,
+            in_quotes: false,
+        };
+        let mut scanned = 0;
+        let mut returned = 0;
+
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            scanned += chunk.len() as i64;
+            splitter.buf.extend_from_slice(&chunk);
+
+            if is_document {
+                if splitter.buf.len() > MAX_DOCUMENT_SIZE {
+                    return Err(select_error("OverMaxRecordSize", "JSON document is too large"));
+                }
+                continue;
+            }
+            while let Some(record) = splitter.next_record(&delimiter, quote) {
+                executor.process(&record)?;
+            }
+            if splitter.buf.len() > MAX_RECORD_SIZE {
+                return Err(select_error("OverMaxRecordSize", "Record is larger than 1 MiB"));
+            }
+
+            if executor.out.len() >= RECORDS_EVENT_SIZE {
+                returned += executor.out.len() as i64;
+                send_records(tx, std::mem::take(&mut executor.out)).await?;
+            }
+            if executor.done() {
+                break;
+            }
+        }
+        if let Some(record) = splitter.rest() {
+            executor.process(&record)?;
+        }
+        executor.finish();
+
+        if !executor.out.is_empty() {
+            returned += executor.out.len() as i64;
+            send_records(tx, executor.out).await?;
+        }
+
+        let stats = SelectObjectContentEvent::Stats(StatsEvent {
+            details: Some(Stats {
+                bytes_processed: scanned,
+                bytes_returned: returned,
+                bytes_scanned: scanned,
+            }),
+        });
+        let _ = tx.send(Ok(stats)).await;
+        let _ = tx.send(Ok(SelectObjectContentEvent::End(EndEvent {}))).await;
+        Ok(())
+    }
+}
+
+async fn send_records(tx: &tokio::sync::mpsc::Sender<S3Result<SelectObjectContentEvent>>, out: Vec<u8>) -> S3Result<()> {
+    let event = SelectObjectContentEvent::Records(RecordsEvent {
+        payload: Some(Bytes::from(out)),
+    });
+    // the client has gone away
+    tx.send(Ok(event))
+        .await
+        .map_err(|_| s3s::s3_error!(InternalError, "Select result receiver is closed"))
+}
@@ -1,282 +1,2182 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use futures::StreamExt;
 use hyper::body;
 use md5::{Digest, Md5};
+use s3s::auth::Credentials;
 use s3s::dto::*;
 use s3s::{s3_error, S3Request, S3Response, S3Result, S3};
 use tokio::io::AsyncWriteExt;
 use tracing::{debug_span, Instrument};
 use uuid::Uuid;
 
+use crate::blob_cache::BlobCache;
 use crate::blob_store::BlobStore;
-use crate::ceph_store::RadosBlobStore;
-use crate::meta_store::{Blob, ListOptions, ListResult, MetaStore};
-use crate::pg_database::PostgresDatabase;
+use crate::bucket_metrics::{BucketMetrics, Operation as MetricsOperation};
+use crate::ceph_store::{RadosBlobStore, RadosConfig};
+use crate::checksum::{self, ChecksumHasher};
+use crate::clock::{Clock, IdGenerator};
+use crate::error::StartupError;
+use crate::keys::{self, KeyPolicy};
+use crate::listing::ObjectLister;
+use crate::meta_store::{
+    Blob, BlobPart, CompletedPart, ContentHeaders, CreateBucketOptions, InventoryConfig, ListResult, MetaStore, MetricsConfig,
+    MultipartPart, MultipartUpload, ObjectRestore, PublicAccessBlock, ReplicationConfig, ReplicationRule, WriteAttributes,
+    WriteCondition,
+};
+use crate::owners::Owners;
+use crate::pg_database::{PostgresDatabase, RetryPolicy};
+use crate::policy::PolicyDocument;
+use crate::select::Select;
+use crate::tiering::{self, ColdTier};
+use crate::virtual_host::{self, DottedBuckets};
+
+/// ListBuckets returns the buckets of all users if set to `true` by a service account
+const ALL_BUCKETS_HEADER: &str = "x-s3s-rados-all-buckets";
+/// limits the buckets of all users to a single owner
+const BUCKET_OWNER_HEADER: &str = "x-s3s-rados-bucket-owner";
+/// usage of the bucket returned by HeadBucket if enabled
+const OBJECT_COUNT_HEADER: &str = "x-amz-bucket-object-count";
+const BYTES_USED_HEADER: &str = "x-amz-bucket-bytes-used";
+/// CopyObject renames the source key instead of copying the data if set to `true`
+pub(crate) const MOVE_HEADER: &str = "x-s3s-rados-move";
+/// token of the current object returned by reads and writes, it changes with every write of the key
+const VERSION_TOKEN_HEADER: &str = "x-s3s-rados-version-token";
+/// PutObject fails with 412 unless the version token of the current object matches
+const IF_VERSION_HEADER: &str = "x-amz-if-version-id";
+
+#[derive(Debug)]
+pub struct StoreConfig {
+    pub rados: RadosConfig,
+    pub clock: Arc<dyn Clock>,
+    pub ids: Arc<dyn IdGenerator>,
+    pub region: String,
+    /// maximum size of a single PutObject or UploadPart
+    pub max_object_size: i64,
+    /// maximum number of parts in a multipart upload
+    pub max_parts: i32,
+    /// how long deleted and overwritten blobs are kept before the GC removes them
+    pub trash_retention: Duration,
+    /// size of a range fetched by a single backend read of a large GET
+    pub read_chunk_size: u64,
+    /// number of ranges of a single GET fetched concurrently
+    pub read_concurrency: usize,
+    /// PutObject bodies larger than this are written as several backend objects, 0 disables it
+    pub offload_threshold: i64,
+    /// size of a backend object of an offloaded PutObject
+    pub offload_part_size: i64,
+    /// number of backend objects of a single PutObject written concurrently
+    pub offload_concurrency: usize,
+    /// size of a single sequential backend read
+    pub read_buffer_size: u64,
+    /// amount of data buffered by a writer before it is sent to the backend
+    pub write_buffer_size: usize,
+    /// number of idle write buffers kept for reuse
+    pub buffer_pool_size: usize,
+    /// number of buffers a single upload writes to the backend concurrently
+    pub write_queue_depth: usize,
+    /// database statements slower than this are logged
+    pub slow_query_threshold: Duration,
+    pub db_retry: RetryPolicy,
+    /// largest listing page served to service accounts, the S3 limit of 1000 keys applies if it is lower
+    pub extended_max_keys: i32,
+    /// time after which a listing page larger than 1000 keys is cut short
+    pub extended_list_timeout: Duration,
+    /// return the object count and size of the bucket from HeadBucket
+    pub bucket_stats_headers: bool,
+    /// time a PutObject commit waits for others to share its transaction, zero commits every object on its own
+    pub commit_batch_window: Duration,
+    /// largest number of objects committed in a single transaction
+    pub commit_batch_size: usize,
+    /// keys accepted for new objects
+    pub key_policy: KeyPolicy,
+    /// domain of virtual-hosted-style requests, used for object URLs in responses
+    pub base_domain: Option<String>,
+    pub dotted_buckets: DottedBuckets,
+    /// largest number of days of a RestoreObject
+    pub restore_max_days: i32,
+    /// the only destination bucket of replication rules, replication is disabled if not set
+    pub replication_destination: Option<String>,
+}
 
 #[derive(Debug)]
 pub struct RadosStore {
-    db: Box<dyn MetaStore>,
-    blob: Box<dyn BlobStore>,
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    /// tiering is disabled if not set
+    cold: Option<ColdTier>,
+    /// minimal interval between two recorded reads of an object
+    access_sample_interval: Duration,
+    /// local copy of hot blobs, disabled if not set
+    cache: Option<Arc<BlobCache>>,
+    owners: Owners,
+    bucket_metrics: BucketMetrics,
+    config: StoreConfig,
 }
 
 impl RadosStore {
-    pub async fn new() -> Self {
-        Self {
-            db: Box::new(PostgresDatabase::new().await),
-            blob: Box::new(RadosBlobStore::new().await),
+    pub async fn new(config: StoreConfig) -> Result<Self, StartupError> {
+        let blob: Arc<dyn BlobStore> = Arc::new(
+            RadosBlobStore::new(&config.rados)
+                .await?
+                .with_parallel_reads(config.read_chunk_size, config.read_concurrency)
+                .with_buffers(config.read_buffer_size, config.write_buffer_size, config.buffer_pool_size)
+                .with_write_queue_depth(config.write_queue_depth),
+        );
+        #[cfg(feature = "fault-injection")]
+        let blob: Arc<dyn BlobStore> = Arc::new(crate::faults::FaultyBlobStore::new(blob));
+        let mut db = PostgresDatabase::new(config.trash_retention, config.slow_query_threshold, config.db_retry)
+            .await?
+            .with_clock(config.clock.clone(), config.ids.clone());
+        if !config.commit_batch_window.is_zero() {
+            db = db.with_commit_batching(config.commit_batch_window, config.commit_batch_size);
         }
+        Ok(Self {
+            db: Arc::new(db),
+            blob,
+            cold: None,
+            access_sample_interval: Duration::ZERO,
+            cache: None,
+            owners: Owners::default(),
+            bucket_metrics: BucketMetrics::default(),
+            config,
+        })
     }
-}
 
-#[async_trait::async_trait]
-impl S3 for RadosStore {
-    #[tracing::instrument(level = "debug", skip_all)]
-    async fn create_bucket(&self, req: S3Request<CreateBucketInput>) -> S3Result<S3Response<CreateBucketOutput>> {
-        let Some(creds) = &req.credentials else {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+    /// Serve the blobs of the cold tier and record reads of objects for the tiering worker
+    pub fn with_cold_tier(mut self, cold: ColdTier, access_sample_interval: Duration) -> Self {
+        self.cold = Some(cold);
+        self.access_sample_interval = access_sample_interval;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: BlobCache) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    pub fn cache(&self) -> Option<Arc<BlobCache>> {
+        self.cache.clone()
+    }
+
+    pub fn cold_tier(&self) -> Option<ColdTier> {
+        self.cold.clone()
+    }
+
+    pub fn meta_store(&self) -> Arc<dyn MetaStore> {
+        self.db.clone()
+    }
+
+    pub fn blob_store(&self) -> Arc<dyn BlobStore> {
+        self.blob.clone()
+    }
+
+    /// Stream the request body to the blob store. Returns the number of bytes written and MD5 of the data.
+    ///
+    /// With `write_through` the data is also put into the cache, it is not worth it for parts and copies.
+    /// The additional checksum is calculated with `checksum` if it is given.
+    async fn write_blob(
+        &self,
+        id: &Uuid,
+        body: &mut StreamingBlob,
+        write_through: bool,
+        mut checksum: Option<&mut ChecksumHasher>,
+    ) -> S3Result<(i64, String)> {
+        // open rados file
+        let mut writer = try_!(self.blob.get_writer(&id.to_string()).await);
+        let mut fill = self.cache.as_ref().filter(|_| write_through).map(|cache| cache.fill(*id));
+        let mut md5_hash = <Md5 as Digest>::new();
+        let mut size = 0;
+        while let Some(chunk) = body.next().instrument(debug_span!("read_user_input")).await {
+            // the client has gone away or sent a malformed body, the partial data must not be committed
+            let chunk = chunk.map_err(|err| s3s::S3Error::with_source(s3s::S3ErrorCode::IncompleteBody, err))?;
+            md5_hash.update(chunk.as_ref());
+            if let Some(checksum) = checksum.as_deref_mut() {
+                checksum.update(chunk.as_ref());
+            }
+            size += chunk.len() as i64;
+            if size > self.config.max_object_size {
+                return Err(s3_error!(EntityTooLarge));
+            }
+
+            try_!(writer.write_all(&chunk).instrument(debug_span!("rados_write_chunk")).await);
+            if let Some(fill) = &mut fill {
+                fill.push(&chunk);
+            }
+        }
+        try_!(writer.flush().instrument(debug_span!("rados_flush_remainig")).await);
+
+        let etag = hex(md5_hash.finalize());
+        if let Some(fill) = fill {
+            fill.finish(size as u64, etag.clone());
+        }
+        Ok((size, etag))
+    }
+
+    /// Split the body into backend objects of `offload_part_size` which are written concurrently.
+    ///
+    /// Every part is registered in `temp`, so parts of a failed upload are collected. The additional checksum is
+    /// calculated with `checksum` if it is given.
+    async fn write_blob_parts(
+        &self,
+        body: &mut StreamingBlob,
+        content_length: i64,
+        temp: &mut TempBlobs,
+        mut checksum: Option<&mut ChecksumHasher>,
+    ) -> S3Result<(Blob, Vec<BlobPart>)> {
+        let part_size = self.config.offload_part_size as usize;
+        let mut parts: Vec<BlobPart> = Vec::new();
+        let mut writers = tokio::task::JoinSet::new();
+        let mut md5_hash = <Md5 as Digest>::new();
+        let mut size = 0;
+        let mut buf = bytes::BytesMut::with_capacity(part_size);
+
+        let res: S3Result<()> = async {
+            loop {
+                let chunk = body.next().instrument(debug_span!("read_user_input")).await;
+                if let Some(chunk) = chunk {
+                    let chunk = chunk.map_err(|err| s3s::S3Error::with_source(s3s::S3ErrorCode::IncompleteBody, err))?;
+                    md5_hash.update(chunk.as_ref());
+                    if let Some(checksum) = checksum.as_deref_mut() {
+                        checksum.update(chunk.as_ref());
+                    }
+                    size += chunk.len() as i64;
+                    if size > content_length {
+                        return Err(s3_error!(InvalidRequest, "Body is larger than Content-Length"));
+                    }
+                    buf.extend_from_slice(&chunk);
+                    if buf.len() < part_size {
+                        continue;
+                    }
+                } else if buf.is_empty() {
+                    break;
+                }
+
+                let data = buf.split_to(buf.len().min(part_size)).freeze();
+                let part = BlobPart {
+                    blob_id: self.config.ids.new_id(),
+                    size: data.len() as i64,
+                };
+                temp.add(&Blob::temporary(part.blob_id)).await?;
+                parts.push(part.clone());
+
+                if writers.len() >= self.config.offload_concurrency {
+                    if let Some(res) = writers.join_next().await {
+                        try_!(res)?;
+                    }
+                }
+                let blob_store = self.blob.clone();
+                writers.spawn(async move {
+                    let mut writer = blob_store.get_writer(&part.blob_id.to_string()).await?;
+                    try_!(writer.write_all(&data).await);
+                    try_!(writer.flush().await);
+                    Ok::<_, s3s::S3Error>(())
+                });
+            }
+
+            while let Some(res) = writers.join_next().await {
+                try_!(res)?;
+            }
+            if size != content_length {
+                return Err(s3_error!(IncompleteBody));
+            }
+            Ok(())
+        }
+        .await;
+
+        if let Err(err) = res {
+            // the parts must not be written after they are handed over to the GC
+            writers.shutdown().await;
+            return Err(err);
+        }
+
+        let blob = Blob {
+            id: self.config.ids.new_id(),
+            size,
+            parts: Some(parts.len() as i32),
+            part_size: Some(part_size as i64),
+            upload_timestamp: crate::meta_store::Timestamp::UNIX_EPOCH,
+            storage_class: None,
+            // the object was uploaded with a single request, so the etag is a plain MD5
+            etag: hex(md5_hash.finalize()),
+            checksum: None,
         };
+        Ok((blob, parts))
+    }
 
-        // TODO: get real user name
+    async fn public_access_block(&self, bucket: &str) -> S3Result<PublicAccessBlock> {
+        Ok(self.db.get_public_access_block(bucket).await?.unwrap_or_default())
+    }
+
+    /// Object ACLs are not stored, but requests are still validated against the bucket settings: ACLs are
+    /// disabled when the bucket owner owns every object and public ACLs fail while the bucket blocks them
+    async fn check_object_acl(
+        &self,
+        bucket: &crate::meta_store::Bucket,
+        acl: Option<&ObjectCannedACL>,
+        grants: [&Option<String>; 4],
+    ) -> S3Result<()> {
+        if bucket.object_ownership.as_deref() == Some(ObjectOwnership::BUCKET_OWNER_ENFORCED) {
+            // the owner having full control is the only ACL which is still accepted
+            let acl_allowed = acl.is_none_or(|acl| acl.as_str() == ObjectCannedACL::BUCKET_OWNER_FULL_CONTROL);
+            if !acl_allowed || grants.iter().any(|g| g.is_some()) {
+                let mut err = s3s::S3Error::with_message(
+                    s3s::S3ErrorCode::Custom("AccessControlListNotSupported".into()),
+                    "The bucket does not allow ACLs",
+                );
+                err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+                return Err(err);
+            }
+            return Ok(());
+        }
+
+        let public_canned = acl.is_some_and(|acl| {
+            [
+                ObjectCannedACL::PUBLIC_READ,
+                ObjectCannedACL::PUBLIC_READ_WRITE,
+                ObjectCannedACL::AUTHENTICATED_READ,
+            ]
+            .contains(&acl.as_str())
+        });
+        let public_grant = grants
+            .into_iter()
+            .flatten()
+            .any(|g| g.contains(ALL_USERS_GROUP) || g.contains(AUTHENTICATED_USERS_GROUP));
+        if (public_canned || public_grant) && self.public_access_block(&bucket.name).await?.block_public_acls {
+            return Err(s3_error!(AccessDenied, "Public ACLs are blocked by the bucket's public access block"));
+        }
+        Ok(())
+    }
+
+    /// Parts of an upload are written, and the upload is completed or aborted, by the user who has created it or
+    /// the owner of the bucket. Unknown uploads pass, they fail later as `NoSuchUpload` or are completed again.
+    async fn check_upload_initiator(&self, credentials: &Option<Credentials>, upload_id: &Uuid) -> S3Result<()> {
+        match self.db.get_multipart_upload(upload_id).await? {
+            Some(upload) => self.check_upload_access(credentials, &upload).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn check_upload_access(&self, credentials: &Option<Credentials>, upload: &MultipartUpload) -> S3Result<()> {
+        let Some(creds) = credentials else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        };
+        let Some(initiator) = &upload.initiator else {
+            return Ok(());
+        };
         let user = self.db.get_user_by_access_key(&creds.access_key).await?;
-        let _res = self.db.create_bucket(&user.id, &req.input.bucket).await?;
+        if user.id == *initiator {
+            return Ok(());
+        }
+        let owner = self.db.get_bucket_metadata(&upload.bucket).await?.map(|bucket| bucket.owner);
+        if owner.as_ref() == Some(&user.id) {
+            return Ok(());
+        }
+        Err(s3_error!(AccessDenied, "Only the initiator and the bucket owner may access the upload"))
+    }
 
-        let output = CreateBucketOutput::default(); // TODO: handle other fields
-        Ok(S3Response::new(output))
+    /// Whether the public grants of the canned ACL apply. The public access block may ignore them, or restrict a
+    /// bucket with a public policy to its owner.
+    async fn public_grants_apply(&self, bucket: &crate::meta_store::Bucket) -> S3Result<bool> {
+        let block = self.public_access_block(&bucket.name).await?;
+        if block.ignore_public_acls {
+            return Ok(false);
+        }
+        Ok(!block.restrict_public_buckets || !bucket.policy.as_deref().is_some_and(is_public_policy))
     }
 
-    #[tracing::instrument(level = "debug", skip_all)]
-    async fn delete_bucket(&self, req: S3Request<DeleteBucketInput>) -> S3Result<S3Response<DeleteBucketOutput>> {
-        if req.credentials.is_none() {
+    /// Returns `AccessDenied` unless the requester owns the bucket or its canned ACL grants reading to everyone or to
+    /// authenticated users
+    async fn check_bucket_read(&self, credentials: &Option<Credentials>, bucket: &crate::meta_store::Bucket) -> S3Result<()> {
+        let public_grants = self.public_grants_apply(bucket).await?;
+        let allowed = match (credentials, bucket.acl.as_str()) {
+            (_, BucketCannedACL::PUBLIC_READ | BucketCannedACL::PUBLIC_READ_WRITE) if public_grants => true,
+            (Some(_), BucketCannedACL::AUTHENTICATED_READ) if public_grants => true,
+            (Some(creds), _) => self.db.get_user_by_access_key(&creds.access_key).await?.id == bucket.owner,
+            (None, _) => false,
+        };
+        if !allowed {
             return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
         }
+        Ok(())
+    }
 
-        let Some(_bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+    /// Returns `AccessDenied` unless the requester owns the bucket or its canned ACL grants writing to everyone
+    async fn check_bucket_write(&self, credentials: &Option<Credentials>, bucket: &crate::meta_store::Bucket) -> S3Result<()> {
+        let public_grants = self.public_grants_apply(bucket).await?;
+        let allowed = match (credentials, bucket.acl.as_str()) {
+            (_, BucketCannedACL::PUBLIC_READ_WRITE) if public_grants => true,
+            (Some(creds), _) => self.db.get_user_by_access_key(&creds.access_key).await?.id == bucket.owner,
+            (None, _) => false,
         };
-        // TODO: check ownership
+        if !allowed {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
+        Ok(())
+    }
 
-        self.db.delete_bucket(&req.input.bucket).await?;
-        Ok(S3Response::new(DeleteBucketOutput {}))
+    /// Bucket of a request only the bucket owner may make. Returns `NoSuchBucket` if the bucket does not exist and
+    /// `AccessDenied` unless the request is signed by a key of the owner.
+    async fn owned_bucket(&self, credentials: &Option<Credentials>, bucket: &str) -> S3Result<crate::meta_store::Bucket> {
+        let Some(creds) = credentials else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        };
+        let Some(bucket) = self.db.get_bucket_metadata(bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        if self.db.get_user_by_access_key(&creds.access_key).await?.id != bucket.owner {
+            return Err(s3_error!(AccessDenied, "Only the bucket owner may access the bucket configuration"));
+        }
+        Ok(bucket)
     }
 
-    #[tracing::instrument(level = "debug", skip_all)]
-    async fn delete_object(&self, req: S3Request<DeleteObjectInput>) -> S3Result<S3Response<DeleteObjectOutput>> {
-        // check acl
-        // check bucket lock
-        //self.db.
+    /// Copy the data into a new temporary blob registered in `temp` which has to be committed by the caller
+    async fn copy_blob(&self, source: &Blob, temp: &mut TempBlobs) -> S3Result<Blob> {
+        let mut blob = Blob {
+            id: self.config.ids.new_id(),
+            size: source.size,
+            parts: None,
+            part_size: None,
+            upload_timestamp: crate::meta_store::Timestamp::UNIX_EPOCH,
+            storage_class: None,
+            etag: String::default(),
+            checksum: None,
+        };
+        temp.add(&blob).await?;
 
-        self.db
-            .delete_object_metadata(&req.input.bucket, &req.input.key, &req.input.version_id)
+        let reader = self.get_blob_reader(source).await?;
+        let (size, etag) = self
+            .write_blob(&blob.id, &mut StreamingBlob::wrap(reader), false, None)
             .await?;
+        if size != source.size {
+            return Err(s3_error!(InternalError, "Source object could not be read completely"));
+        }
+        blob.etag = etag;
+        // the copy is a single part, a composite checksum of a multipart source does not apply to it
+        blob.checksum = source.checksum.clone().filter(|c| !c.value.contains('-'));
+        Ok(blob)
+    }
 
-        Ok(S3Response::new(DeleteObjectOutput {
-            delete_marker: false, // TODO: handle versioned
-            request_charged: None,
-            version_id: None,
+    /// Largest listing page the client may request. Service accounts may exceed the S3 limit up to the
+    /// configured cap, it is ignored for other clients like AWS does.
+    async fn max_list_keys<I>(&self, req: &S3Request<I>) -> S3Result<i32> {
+        if self.config.extended_max_keys <= MAX_LIST_KEYS {
+            return Ok(MAX_LIST_KEYS);
+        }
+        match &req.credentials {
+            Some(creds) if self.db.is_service_account_key(&creds.access_key).await? => Ok(self.config.extended_max_keys),
+            _ => Ok(MAX_LIST_KEYS),
+        }
+    }
+
+    async fn get_blob_reader(
+        &self,
+        blob: &Blob,
+    ) -> S3Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>> {
+        let Some(cache) = &self.cache else {
+            return self.get_backend_reader(blob).await;
+        };
+        if let Some(reader) = cache.get(blob).await {
+            return Ok(reader);
+        }
+        let reader = self.get_backend_reader(blob).await?;
+        Ok(cache.read_through(blob, reader))
+    }
+
+    async fn get_backend_reader(
+        &self,
+        blob: &Blob,
+    ) -> S3Result<core::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, s3s::S3Error>> + Send + Sync>>> {
+        let store = tiering::blob_store(&self.blob, self.cold.as_ref(), blob.storage_class.as_deref())?;
+        if blob.parts.is_none() {
+            return store.get_reader(&blob.id.to_string(), 0, blob.size as u64).await;
+        }
+
+        let parts = self
+            .db
+            .get_blob_parts(&blob.id)
+            .await?
+            .into_iter()
+            .map(|p| (p.blob_id.to_string(), p.size as u64))
+            .collect();
+        store.get_parts_reader(parts, 0, blob.size as u64).await
+    }
+
+    /// Restore of the current blob of the object from the cold tier, pending or completed
+    async fn object_restore(&self, object: &crate::meta_store::Object, blob: &Blob) -> S3Result<Option<ObjectRestore>> {
+        if self.cold.is_none() {
+            return Ok(None);
+        }
+        let restore = self.db.get_object_restore(&object.bucket_name, &object.oid).await?;
+        Ok(restore.filter(|restore| restore.blob == blob.id))
+    }
+
+    /// Value of `x-amz-restore` for an object which has been restored from the cold tier
+    async fn restore_status(&self, object: &crate::meta_store::Object, blob: &Blob) -> S3Result<Option<Restore>> {
+        let Some(restore) = self.object_restore(object, blob).await? else {
+            return Ok(None);
+        };
+        Ok(Some(match restore.expires_at {
+            None => r#"ongoing-request="true""#.to_owned(),
+            Some(expires_at) => {
+                let expiry = http_date(expires_at);
+                let expiry = expiry.to_str().expect("an HTTP date is ASCII");
+                format!(r#"ongoing-request="false", expiry-date="{expiry}""#)
+            }
         }))
     }
 
+    fn record_metrics(&self, object: &crate::meta_store::Object, operation: MetricsOperation, bytes: i64) {
+        self.bucket_metrics.record(
+            &self.db,
+            &object.bucket_name,
+            &object.oid,
+            object.tagging.as_deref(),
+            operation,
+            bytes as u64,
+        );
+    }
+
+    /// Record the read for tiering. Only a sample is written, an object read again within the
+    /// sampling interval keeps the previous time.
+    async fn record_access(&self, object: &crate::meta_store::Object) {
+        if self.cold.is_none() {
+            return;
+        }
+        let now = self.config.clock.now();
+        if object.last_accessed.is_some_and(|t| now - t < self.access_sample_interval) {
+            return;
+        }
+        if let Err(err) = self.db.record_object_access(&object.bucket_name, &object.oid).await {
+            tracing::debug!(error = %err, bucket = object.bucket_name, key = object.oid, "unable to record object access");
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl S3 for RadosStore {
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn get_object(&self, req: S3Request<GetObjectInput>) -> S3Result<S3Response<GetObjectOutput>> {
-        //TODO: validate user
-        let Some((object, blob)) = self.db.load_object_metadata(&req.input.bucket, &req.input.key, &None).await? else {
-            return Err(s3_error!(NoSuchKey, "Key not found"));
-        };
+    async fn abort_multipart_upload(
+        &self,
+        req: S3Request<AbortMultipartUploadInput>,
+    ) -> S3Result<S3Response<AbortMultipartUploadOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
 
-        let Some(blob) = blob else {
-            return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
+        let upload_id = parse_upload_id(&req.input.upload_id)?;
+        self.check_upload_initiator(&req.credentials, &upload_id).await?;
+        self.db.abort_multipart_upload(&upload_id).await?;
+        Ok(S3Response::new(AbortMultipartUploadOutput::default()))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn complete_multipart_upload(
+        &self,
+        req: S3Request<CompleteMultipartUploadInput>,
+    ) -> S3Result<S3Response<CompleteMultipartUploadOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
+
+        let CompleteMultipartUploadInput {
+            bucket,
+            key,
+            multipart_upload,
+            upload_id,
+            ..
+        } = req.input;
+        let upload_id = parse_upload_id(&upload_id)?;
+        self.check_upload_initiator(&req.credentials, &upload_id).await?;
+
+        let Some(bucket_md) = self.db.get_bucket_metadata(&bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
         };
 
-        let bytes = self.blob.get_reader(&blob.id.to_string(), 0, blob.size as u64).await?;
-        let output = GetObjectOutput {
-            body: Some(StreamingBlob::wrap(bytes)),
-            content_length: blob.size,
-            content_range: None,
-            last_modified: Some(s3s::dto::Timestamp::from(time::OffsetDateTime::new_in_offset(
-                object.last_modified.date(),
-                object.last_modified.time(),
-                time::UtcOffset::UTC,
-            ))),
-            metadata: None, // TODO: handle metadata
+        let parts: Vec<CompletedPart> = multipart_upload
+            .and_then(|u| u.parts)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|p| {
+                Ok(CompletedPart {
+                    part_number: p.part_number,
+                    etag: p.e_tag,
+                    checksum: checksum::from_headers(p.checksum_crc32, p.checksum_crc32c, p.checksum_sha1, p.checksum_sha256)?,
+                })
+            })
+            .collect::<S3Result<_>>()?;
+
+        let blob = self
+            .db
+            .complete_multipart_upload(&bucket_md, &key, &upload_id, &parts)
+            .await?;
+        let checksum = checksum::to_dto(self.db.get_blob_checksums(&blob.id).await?.checksum);
+
+        let location = virtual_host::object_url(
+            &req.headers,
+            &req.uri,
+            self.config.base_domain.as_deref(),
+            self.config.dotted_buckets,
+            &bucket,
+            &key,
+        );
+        let output = CompleteMultipartUploadOutput {
+            location: Some(location),
+            bucket: Some(bucket),
+            key: Some(key),
             e_tag: Some(blob.etag),
-            // checksum_crc32: None,
-            // checksum_crc32c: None,
-            // checksum_sha1: None,
-            // checksum_sha256: None,
+            checksum_crc32: checksum.checksum_crc32,
+            checksum_crc32c: checksum.checksum_crc32c,
+            checksum_sha1: checksum.checksum_sha1,
+            checksum_sha256: checksum.checksum_sha256,
             ..Default::default()
         };
-        Ok(S3Response::new(output))
-        //Err(s3_error!(NotImplemented, "GetObject is not implemented yet"))
+        let mut res = S3Response::new(output);
+        res.headers
+            .insert(hyper::header::LAST_MODIFIED, http_date(blob.upload_timestamp));
+        Ok(res)
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
-    async fn head_bucket(&self, req: S3Request<HeadBucketInput>) -> S3Result<S3Response<HeadBucketOutput>> {
-        let Some(_bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
-            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+    async fn copy_object(&self, req: S3Request<CopyObjectInput>) -> S3Result<S3Response<CopyObjectOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
+
+        let CopyObjectInput {
+            acl,
+            bucket,
+            cache_control,
+            content_disposition,
+            content_encoding,
+            content_language,
+            content_type,
+            expires,
+            grant_full_control,
+            grant_read,
+            grant_read_acp,
+            grant_write_acp,
+            key,
+            copy_source,
+            copy_source_if_match,
+            copy_source_if_modified_since,
+            copy_source_if_none_match,
+            copy_source_if_unmodified_since,
+            metadata,
+            metadata_directive,
+            tagging,
+            tagging_directive,
+            storage_class,
+            ..
+        } = req.input;
+        if let Some(ref storage_class) = storage_class {
+            if storage_class.as_str() != StorageClass::STANDARD {
+                return Err(s3_error!(InvalidStorageClass));
+            }
+        }
+        let CopySource::Bucket {
+            bucket: source_bucket,
+            key: source_key,
+            version_id,
+        } = copy_source
+        else {
+            return Err(s3_error!(NotImplemented, "Access points are not supported"));
         };
-        // check ownership
-        Ok(S3Response::new(HeadBucketOutput {}))
-    }
+        if version_id.is_some() {
+            return Err(s3_error!(NotImplemented, "Versioning is not supported yet"));
+        }
+        keys::check_key(&key, self.config.key_policy)?;
 
-    #[tracing::instrument(level = "debug", skip_all)]
-    async fn head_object(&self, req: S3Request<HeadObjectInput>) -> S3Result<S3Response<HeadObjectOutput>> {
-        let Some((object, blob)) = self.db.load_object_metadata(&req.input.bucket, &req.input.key, &None).await? else {
-            return Err(s3_error!(NoSuchKey, "Key not found"));
+        let replace_metadata = match metadata_directive.as_ref().map(MetadataDirective::as_str) {
+            None | Some(MetadataDirective::COPY) => false,
+            Some(MetadataDirective::REPLACE) => true,
+            Some(other) => return Err(s3_error!(InvalidArgument, "Unknown metadata directive: {}", other)),
         };
+        let replace_tagging = match tagging_directive.as_ref().map(TaggingDirective::as_str) {
+            None | Some(TaggingDirective::COPY) => false,
+            Some(TaggingDirective::REPLACE) => true,
+            Some(other) => return Err(s3_error!(InvalidArgument, "Unknown tagging directive: {}", other)),
+        };
+        let tagging = parse_tagging(tagging)?;
+        let is_move = req.headers.get(MOVE_HEADER).is_some_and(|v| v == "true");
+        if is_move && *source_bucket != bucket {
+            return Err(s3_error!(InvalidRequest, "Objects can only be moved within a bucket"));
+        }
+        if is_move && *source_key == key {
+            return Err(s3_error!(InvalidRequest, "Objects can not be moved to themselves"));
+        }
+        if *source_bucket == bucket && *source_key == key && !replace_metadata {
+            return Err(s3_error!(
+                InvalidRequest,
+                "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata, storage class, website redirect location or encryption attributes."
+            ));
+        }
 
-        let Some(blob) = blob else {
+        let Some(bucket_md) = self.db.get_bucket_metadata(&bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_write(&req.credentials, &bucket_md).await?;
+        let grants = [&grant_full_control, &grant_read, &grant_read_acp, &grant_write_acp];
+        self.check_object_acl(&bucket_md, acl.as_ref(), grants).await?;
+        // the source is read on behalf of the requester, private objects of other users must not be copied
+        let Some(source_bucket_md) = self.db.get_bucket_metadata(&source_bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &source_bucket_md).await?;
+        let Some((source, source_blob)) = self.db.load_object_metadata(&source_bucket, &source_key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Source key not found"));
+        };
+        let Some(source_blob) = source_blob else {
             return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
         };
-        // check ownership
 
-        let output = HeadObjectOutput {
-            content_length: blob.size,
-            content_type: None,
-            last_modified: Some(s3s::dto::Timestamp::from(time::OffsetDateTime::new_in_offset(
-                object.last_modified.date(),
-                object.last_modified.time(),
-                time::UtcOffset::UTC,
-            ))),
-            metadata: None,
-            e_tag: Some(blob.etag),
+        // HTTP dates have a precision of a second
+        let source_modified = source.last_modified.unix_timestamp();
+        // If-Unmodified-Since and If-Modified-Since are ignored when combined with the matching ETag condition
+        let passed = match (&copy_source_if_match, copy_source_if_unmodified_since) {
+            (Some(condition), _) => etag_matches(condition, &source_blob.etag),
+            (None, Some(since)) => source_modified <= time::OffsetDateTime::from(since).unix_timestamp(),
+            (None, None) => true,
+        } && match (&copy_source_if_none_match, copy_source_if_modified_since) {
+            (Some(condition), _) => !etag_matches(condition, &source_blob.etag),
+            (None, Some(since)) => source_modified > time::OffsetDateTime::from(since).unix_timestamp(),
+            (None, None) => true,
+        };
+        if !passed {
+            return Err(s3_error!(PreconditionFailed));
+        }
+
+        let metadata = if replace_metadata { metadata } else { source.metadata };
+        let tagging = if replace_tagging { tagging } else { source.tagging };
+        // representation headers follow the metadata directive as in AWS
+        let content_headers = if replace_metadata {
+            content_headers(
+                content_type,
+                content_encoding,
+                content_language,
+                content_disposition,
+                cache_control,
+                expires,
+            )
+        } else {
+            source.content_headers
+        };
+        if is_move {
+            // the source key is removed, so reading the bucket is not enough
+            self.check_bucket_write(&req.credentials, &source_bucket_md).await?;
+            // the blob is taken over by the new key, nothing is copied
+            let last_modified = self
+                .db
+                .move_object(
+                    &bucket,
+                    &source_key,
+                    &key,
+                    &source_blob.id,
+                    WriteAttributes {
+                        metadata: metadata.as_ref(),
+                        tagging: tagging.as_deref(),
+                        headers: &content_headers,
+                    },
+                )
+                .await?;
+            let output = CopyObjectOutput {
+                copy_object_result: Some(CopyObjectResult {
+                    e_tag: Some(source_blob.etag),
+                    last_modified: Some(s3s::dto::Timestamp::from(last_modified)),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            return Ok(S3Response::new(output));
+        }
+
+        let mut temp = TempBlobs::new(self.db.clone());
+        let blob = self.copy_blob(&source_blob, &mut temp).await?;
+        let object = crate::meta_store::Object {
+            bucket_name: bucket,
+            oid: key,
+            version_id: None,
+            last_modified: crate::meta_store::Timestamp::UNIX_EPOCH,
+            blob_id: Some(blob.id),
+            metadata,
+            tagging,
+            replication_status: None,
+            last_accessed: None,
+            content_headers,
+        };
+        let last_modified = self
+            .db
+            .write_object_metadata_with_blob(&bucket_md, &object, &blob, None)
+            .await?;
+        temp.commit();
+
+        let output = CopyObjectOutput {
+            copy_object_result: Some(CopyObjectResult {
+                e_tag: Some(blob.etag),
+                last_modified: Some(s3s::dto::Timestamp::from(last_modified)),
+                ..Default::default()
+            }),
             ..Default::default()
         };
         Ok(S3Response::new(output))
     }
 
-    #[tracing::instrument(level = "info")]
-    async fn list_buckets(&self, req: S3Request<ListBucketsInput>) -> S3Result<S3Response<ListBucketsOutput>> {
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_bucket(&self, req: S3Request<CreateBucketInput>) -> S3Result<S3Response<CreateBucketOutput>> {
         let Some(creds) = &req.credentials else {
             return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
         };
 
-        // get user
+        let CreateBucketInput {
+            acl,
+            bucket,
+            create_bucket_configuration,
+            grant_full_control,
+            grant_read,
+            grant_read_acp,
+            grant_write,
+            grant_write_acp,
+            object_lock_enabled_for_bucket,
+            object_ownership,
+            ..
+        } = req.input;
+
+        // TODO: get real user name
         let user = self.db.get_user_by_access_key(&creds.access_key).await?;
+        // a location constraint may name the region of the gateway or the default region of the user
+        let region = user.default_region.as_deref().unwrap_or(&self.config.region);
+        let location = create_bucket_configuration.and_then(|c| c.location_constraint);
+        if let Some(location) = &location {
+            if location.as_str() != self.config.region && location.as_str() != region {
+                return Err(s3_error!(
+                    InvalidLocationConstraint,
+                    "The specified location-constraint is not valid, expecting '{}'",
+                    region
+                ));
+            }
+        }
+        let region = location.map_or_else(|| region.to_owned(), |location| location.as_str().to_owned());
+
+        let grants = [grant_full_control, grant_read, grant_read_acp, grant_write, grant_write_acp];
+        if grants.iter().any(Option::is_some) {
+            return Err(s3_error!(NotImplemented, "Explicit grant headers are not supported yet"));
+        }
+
+        let acl = acl.map_or(BucketCannedACL::PRIVATE.to_owned(), |acl| acl.as_str().to_owned());
+        let is_valid = [
+            BucketCannedACL::PRIVATE,
+            BucketCannedACL::PUBLIC_READ,
+            BucketCannedACL::PUBLIC_READ_WRITE,
+            BucketCannedACL::AUTHENTICATED_READ,
+        ]
+        .contains(&acl.as_str());
+        if !is_valid {
+            return Err(s3_error!(InvalidArgument, "Unsupported canned ACL: {acl}"));
+        }
+
+        if let Some(ref ownership) = object_ownership {
+            let is_valid = [
+                ObjectOwnership::BUCKET_OWNER_ENFORCED,
+                ObjectOwnership::BUCKET_OWNER_PREFERRED,
+                ObjectOwnership::OBJECT_WRITER,
+            ]
+            .contains(&ownership.as_str());
+            if !is_valid {
+                return Err(s3_error!(InvalidArgument, "Unsupported object ownership: {}", ownership.as_str()));
+            }
+
+            // ACLs are disabled when the bucket owner owns every object
+            if ownership.as_str() == ObjectOwnership::BUCKET_OWNER_ENFORCED && acl != BucketCannedACL::PRIVATE {
+                return Err(invalid_bucket_acl_with_ownership());
+            }
+        }
+
+        let options = CreateBucketOptions {
+            object_ownership: object_ownership.map(|o| o.as_str().to_owned()),
+            acl,
+            object_lock_enabled: object_lock_enabled_for_bucket.unwrap_or(false),
+            region,
+        };
+
+        let _res = self.db.create_bucket(&user.id, &bucket, &options).await?;
+
+        let output = CreateBucketOutput {
+            location: Some(format!("/{bucket}")),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn create_multipart_upload(
+        &self,
+        req: S3Request<CreateMultipartUploadInput>,
+    ) -> S3Result<S3Response<CreateMultipartUploadOutput>> {
+        let input = req.input;
+        if let Some(ref storage_class) = input.storage_class {
+            let is_valid = ["STANDARD"].contains(&storage_class.as_str());
+            if !is_valid {
+                return Err(s3_error!(InvalidStorageClass));
+            }
+        }
+        keys::check_key(&input.key, self.config.key_policy)?;
+
+        let Some(bucket) = self.db.get_bucket_metadata(&input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        // validate acl
+        let Some(creds) = &req.credentials else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        };
+        self.check_bucket_write(&req.credentials, &bucket).await?;
+        let grants = [
+            &input.grant_full_control,
+            &input.grant_read,
+            &input.grant_read_acp,
+            &input.grant_write_acp,
+        ];
+        self.check_object_acl(&bucket, input.acl.as_ref(), grants).await?;
+
+        let headers = content_headers(
+            input.content_type,
+            input.content_encoding,
+            input.content_language,
+            input.content_disposition,
+            input.cache_control,
+            input.expires,
+        );
+        let checksum_algorithm = input.checksum_algorithm;
+        if let Some(algorithm) = &checksum_algorithm {
+            ChecksumHasher::new(algorithm.as_str())?;
+        }
+        let initiator = self.db.get_user_by_access_key(&creds.access_key).await?;
+        let upload_id = self
+            .db
+            .create_multipart_upload(
+                &input.bucket,
+                &input.key,
+                &initiator.id,
+                &headers,
+                checksum_algorithm.as_ref().map(ChecksumAlgorithm::as_str),
+            )
+            .await?;
+
+        let output = CreateMultipartUploadOutput {
+            bucket: Some(input.bucket),
+            key: Some(input.key),
+            upload_id: Some(upload_id.to_string()),
+            checksum_algorithm,
+            ..Default::default()
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket(&self, req: S3Request<DeleteBucketInput>) -> S3Result<S3Response<DeleteBucketOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
+
+        let Some(_bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        // TODO: check ownership
+
+        self.db.delete_bucket(&req.input.bucket).await?;
+        Ok(S3Response::new(DeleteBucketOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_object(&self, req: S3Request<DeleteObjectInput>) -> S3Result<S3Response<DeleteObjectOutput>> {
+        let Some(bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_write(&req.credentials, &bucket).await?;
+        // TODO: check object lock
+
+        // objects of unversioned buckets have the version `null`, other versions would be ignored and the
+        // current object deleted instead
+        if req.input.version_id.as_deref().is_some_and(|v| v != "null") {
+            return Err(s3_error!(NotImplemented, "Versioning is not supported yet"));
+        }
+        self.db
+            .delete_object_metadata(&req.input.bucket, &req.input.key, &req.input.version_id)
+            .await?;
+        // the tags of the deleted object are not known, filters with tags do not count deletes
+        self.bucket_metrics
+            .record(&self.db, &req.input.bucket, &req.input.key, None, MetricsOperation::Delete, 0);
+
+        Ok(S3Response::new(DeleteObjectOutput {
+            delete_marker: false, // TODO: handle versioned
+            request_charged: None,
+            version_id: None,
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_acl(&self, req: S3Request<GetBucketAclInput>) -> S3Result<S3Response<GetBucketAclOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+        let owner = self.owners.resolve(self.db.as_ref(), &bucket.owner).await?;
+        let grantee = Grantee {
+            display_name: owner.display_name.clone(),
+            email_address: None,
+            id: owner.id.clone(),
+            type_: Type::from_static(Type::CANONICAL_USER),
+            uri: None,
+        };
+        let mut grants = vec![Grant {
+            grantee: Some(grantee),
+            permission: Some(Permission::from_static(Permission::FULL_CONTROL)),
+        }];
+
+        let group_grants: &[(&str, &str)] = match bucket.acl.as_str() {
+            BucketCannedACL::PUBLIC_READ => &[(ALL_USERS_GROUP, Permission::READ)],
+            BucketCannedACL::PUBLIC_READ_WRITE => &[(ALL_USERS_GROUP, Permission::READ), (ALL_USERS_GROUP, Permission::WRITE)],
+            BucketCannedACL::AUTHENTICATED_READ => &[(AUTHENTICATED_USERS_GROUP, Permission::READ)],
+            _ => &[],
+        };
+        grants.extend(group_grants.iter().map(|(uri, permission)| Grant {
+            grantee: Some(Grantee {
+                display_name: None,
+                email_address: None,
+                id: None,
+                type_: Type::from_static(Type::GROUP),
+                uri: Some((*uri).to_owned()),
+            }),
+            permission: Some(Permission::from((*permission).to_owned())),
+        }));
+
+        let output = GetBucketAclOutput {
+            grants: Some(grants),
+            owner: Some(owner),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_location(&self, req: S3Request<GetBucketLocationInput>) -> S3Result<S3Response<GetBucketLocationOutput>> {
+        let Some(bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+
+        // buckets in us-east-1 have an empty location constraint
+        let region = bucket.region.unwrap_or_else(|| self.config.region.clone());
+        let location_constraint = if region == "us-east-1" {
+            None
+        } else {
+            Some(BucketLocationConstraint::from(region))
+        };
+        Ok(S3Response::new(GetBucketLocationOutput { location_constraint }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_object(&self, req: S3Request<GetObjectInput>) -> S3Result<S3Response<GetObjectOutput>> {
+        let Some(bucket_md) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &bucket_md).await?;
+        let Some((object, blob)) = self.db.load_object_metadata(&req.input.bucket, &req.input.key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Key not found"));
+        };
+
+        let Some(blob) = blob else {
+            return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
+        };
+
+        // the stored headers may be overridden by the response-* query parameters of a signed request
+        let input = req.input;
+        let overridden = [
+            &input.response_cache_control,
+            &input.response_content_disposition,
+            &input.response_content_encoding,
+            &input.response_content_language,
+            &input.response_content_type,
+        ]
+        .iter()
+        .any(|v| v.is_some())
+            || input.response_expires.is_some();
+        if overridden && req.credentials.is_none() {
+            return Err(s3_error!(
+                InvalidRequest,
+                "Request specific response headers cannot be used for anonymous GET requests."
+            ));
+        }
+        let stored = object.content_headers.clone();
+        let headers = ContentHeaders {
+            content_type: input.response_content_type.or(stored.content_type),
+            content_encoding: input.response_content_encoding.or(stored.content_encoding),
+            content_language: input.response_content_language.or(stored.content_language),
+            content_disposition: input.response_content_disposition.or(stored.content_disposition),
+            cache_control: input.response_cache_control.or(stored.cache_control),
+            expires: input.response_expires.map(time::OffsetDateTime::from).or(stored.expires),
+        };
+        let content_type = parse_content_type(headers.content_type)?;
+
+        let bytes = self.get_blob_reader(&blob).await?;
+        self.record_access(&object).await;
+        self.record_metrics(&object, MetricsOperation::Get, blob.size);
+        let restore = self.restore_status(&object, &blob).await?;
+        let token = version_token(&blob.id);
+        let checksum = checksum::to_dto(blob.checksum.filter(|_| checksum::requested(input.checksum_mode.as_ref())));
+        let output = GetObjectOutput {
+            body: Some(StreamingBlob::wrap(bytes)),
+            content_length: blob.size,
+            content_range: None,
+            content_type,
+            content_encoding: headers.content_encoding,
+            content_language: headers.content_language,
+            content_disposition: headers.content_disposition,
+            cache_control: headers.cache_control,
+            expires: headers.expires.map(s3s::dto::Timestamp::from),
+            last_modified: Some(s3s::dto::Timestamp::from(object.last_modified)),
+            metadata: object.metadata,
+            e_tag: Some(blob.etag),
+            replication_status: object.replication_status.map(ReplicationStatus::from),
+            restore,
+            storage_class: blob.storage_class.map(StorageClass::from),
+            checksum_crc32: checksum.checksum_crc32,
+            checksum_crc32c: checksum.checksum_crc32c,
+            checksum_sha1: checksum.checksum_sha1,
+            checksum_sha256: checksum.checksum_sha256,
+            ..Default::default()
+        };
+        let mut res = S3Response::new(output);
+        res.headers.insert(VERSION_TOKEN_HEADER, token);
+        Ok(res)
+        //Err(s3_error!(NotImplemented, "GetObject is not implemented yet"))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_object_attributes(
+        &self,
+        req: S3Request<GetObjectAttributesInput>,
+    ) -> S3Result<S3Response<GetObjectAttributesOutput>> {
+        let input = req.input;
+        if input.version_id.is_some() {
+            return Err(s3_error!(NotImplemented, "Versioning is not supported yet"));
+        }
+        let Some(bucket_md) = self.db.get_bucket_metadata(&input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &bucket_md).await?;
+        let Some((object, blob)) = self.db.load_object_metadata(&input.bucket, &input.key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Key not found"));
+        };
+        let Some(blob) = blob else {
+            return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
+        };
+
+        let requested = |attribute: &str| input.object_attributes.iter().any(|a| a.as_str() == attribute);
+        let checksums = if requested(ObjectAttributes::CHECKSUM) || requested(ObjectAttributes::OBJECT_PARTS) {
+            self.db.get_blob_checksums(&blob.id).await?
+        } else {
+            crate::meta_store::BlobChecksums::default()
+        };
+
+        // only uploaded parts are reported, not the backend objects of large single part uploads. Parts are
+        // merged into a single backend object when the blob is moved to another tier.
+        let is_multipart = blob.etag.contains('-') && !checksums.parts.is_empty();
+        let object_parts = if requested(ObjectAttributes::OBJECT_PARTS) && is_multipart {
+            let marker = match &input.part_number_marker {
+                Some(marker) => marker
+                    .parse::<i32>()
+                    .map_err(|_| s3_error!(InvalidArgument, "Invalid part number marker: {}", marker))?,
+                None => 0,
+            };
+            let max_parts = input.max_parts.unwrap_or(MAX_LISTED_PARTS).clamp(0, MAX_LISTED_PARTS);
+            let total = checksums.parts.len() as i32;
+            let parts: Vec<ObjectPart> =
+                (1..)
+                    .zip(checksums.parts)
+                    .skip(marker.max(0) as usize)
+                    .take(max_parts as usize)
+                    .map(|(part_number, (size, value))| {
+                        let checksum = checksum::to_dto(checksums.checksum.as_ref().zip(value).map(|(c, value)| {
+                            crate::meta_store::Checksum {
+                                algorithm: c.algorithm.clone(),
+                                value,
+                            }
+                        }));
+                        ObjectPart {
+                            part_number,
+                            size,
+                            checksum_crc32: checksum.checksum_crc32,
+                            checksum_crc32c: checksum.checksum_crc32c,
+                            checksum_sha1: checksum.checksum_sha1,
+                            checksum_sha256: checksum.checksum_sha256,
+                        }
+                    })
+                    .collect();
+            let last = parts.last().map_or(marker, |p| p.part_number);
+            Some(GetObjectAttributesParts {
+                is_truncated: last < total,
+                max_parts,
+                next_part_number_marker: Some(last.to_string()),
+                part_number_marker: input.part_number_marker,
+                parts: Some(parts),
+                total_parts_count: total,
+            })
+        } else {
+            None
+        };
+
+        let output = GetObjectAttributesOutput {
+            checksum: checksums
+                .checksum
+                .filter(|_| requested(ObjectAttributes::CHECKSUM))
+                .map(|c| checksum::to_dto(Some(c))),
+            e_tag: Some(blob.etag).filter(|_| requested(ObjectAttributes::ETAG)),
+            last_modified: Some(s3s::dto::Timestamp::from(object.last_modified)),
+            object_parts,
+            object_size: if requested(ObjectAttributes::OBJECT_SIZE) {
+                blob.size
+            } else {
+                0
+            },
+            storage_class: requested(ObjectAttributes::STORAGE_CLASS)
+                .then(|| StorageClass::from(blob.storage_class.unwrap_or_else(|| StorageClass::STANDARD.to_owned()))),
+            ..Default::default()
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_object_lock_configuration(
+        &self,
+        req: S3Request<GetObjectLockConfigurationInput>,
+    ) -> S3Result<S3Response<GetObjectLockConfigurationOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+        if !bucket.object_lock_enabled {
+            let mut err = s3s::S3Error::with_message(
+                s3s::S3ErrorCode::Custom("ObjectLockConfigurationNotFoundError".into()),
+                "Object Lock configuration does not exist for this bucket",
+            );
+            err.set_status_code(hyper::StatusCode::NOT_FOUND);
+            return Err(err);
+        }
+
+        let output = GetObjectLockConfigurationOutput {
+            object_lock_configuration: Some(ObjectLockConfiguration {
+                object_lock_enabled: Some(ObjectLockEnabled::from_static(ObjectLockEnabled::ENABLED)),
+                rule: None, // TODO: default retention
+            }),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn head_bucket(&self, req: S3Request<HeadBucketInput>) -> S3Result<S3Response<HeadBucketOutput>> {
+        let Some(bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+
+        // SDKs discover the bucket region from the redirect, the region header is added by BucketRegion
+        let region = bucket.region.as_deref().unwrap_or(&self.config.region);
+        if region != self.config.region {
+            let mut err = s3s::S3Error::with_message(
+                s3s::S3ErrorCode::PermanentRedirect,
+                format!("The bucket is in the '{region}' region"),
+            );
+            err.set_status_code(hyper::StatusCode::MOVED_PERMANENTLY);
+            return Err(err);
+        }
+
+        // existing buckets which can not be accessed are reported as 403 rather than 404
+        self.check_bucket_read(&req.credentials, &bucket).await?;
+
+        let mut res = S3Response::new(HeadBucketOutput {});
+        if self.config.bucket_stats_headers {
+            if let Some(stats) = self.db.get_bucket_stats(&bucket.name).await? {
+                res.headers.insert(OBJECT_COUNT_HEADER, stats.objects.into());
+                res.headers.insert(BYTES_USED_HEADER, stats.bytes.into());
+            }
+        }
+        Ok(res)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn head_object(&self, req: S3Request<HeadObjectInput>) -> S3Result<S3Response<HeadObjectOutput>> {
+        let Some(bucket_md) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &bucket_md).await?;
+        let Some((object, blob)) = self.db.load_object_metadata(&req.input.bucket, &req.input.key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Key not found"));
+        };
+
+        let Some(blob) = blob else {
+            return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
+        };
+
+        self.record_metrics(&object, MetricsOperation::Head, 0);
+        let restore = self.restore_status(&object, &blob).await?;
+        let headers = object.content_headers;
+        let token = version_token(&blob.id);
+        let checksum = checksum::to_dto(
+            blob.checksum
+                .filter(|_| checksum::requested(req.input.checksum_mode.as_ref())),
+        );
+        let output = HeadObjectOutput {
+            content_length: blob.size,
+            content_type: parse_content_type(headers.content_type)?,
+            content_encoding: headers.content_encoding,
+            content_language: headers.content_language,
+            content_disposition: headers.content_disposition,
+            cache_control: headers.cache_control,
+            expires: headers.expires.map(s3s::dto::Timestamp::from),
+            last_modified: Some(s3s::dto::Timestamp::from(object.last_modified)),
+            metadata: object.metadata,
+            e_tag: Some(blob.etag),
+            replication_status: object.replication_status.map(ReplicationStatus::from),
+            restore,
+            storage_class: blob.storage_class.map(StorageClass::from),
+            checksum_crc32: checksum.checksum_crc32,
+            checksum_crc32c: checksum.checksum_crc32c,
+            checksum_sha1: checksum.checksum_sha1,
+            checksum_sha256: checksum.checksum_sha256,
+            ..Default::default()
+        };
+        let mut res = S3Response::new(output);
+        res.headers.insert(VERSION_TOKEN_HEADER, token);
+        Ok(res)
+    }
+
+    #[tracing::instrument(level = "info")]
+    async fn list_buckets(&self, req: S3Request<ListBucketsInput>) -> S3Result<S3Response<ListBucketsOutput>> {
+        let Some(creds) = &req.credentials else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        };
+
+        // get user
+        let user = self.db.get_user_by_access_key(&creds.access_key).await?;
+
+        // get buckets, service accounts may ask for the buckets of all users
+        let header = |name| req.headers.get(name).and_then(|v| v.to_str().ok());
+        let buckets = if header(ALL_BUCKETS_HEADER) == Some("true") {
+            if !self.db.is_service_account_key(&creds.access_key).await? {
+                return Err(s3_error!(AccessDenied, "Only service accounts may list the buckets of all users"));
+            }
+            self.db.list_all_buckets(header(BUCKET_OWNER_HEADER)).await?
+        } else {
+            self.db.list_buckets_by_user(&user.id).await?
+        };
+        let buckets = buckets
+            .into_iter()
+            .map(|b| s3s::dto::Bucket {
+                creation_date: Some(s3s::dto::Timestamp::from(b.creation_date)),
+                name: Some(b.name),
+            })
+            .collect();
+        let output = s3s::dto::ListBucketsOutput {
+            buckets: Some(buckets),
+            owner: Some(s3s::dto::Owner {
+                id: Some(user.id),
+                display_name: Some(user.name),
+            }),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    async fn list_object_versions(
+        &self,
+        req: S3Request<ListObjectVersionsInput>,
+    ) -> S3Result<S3Response<ListObjectVersionsOutput>> {
+        // let v2_resp = self.list_objects_v2(req.map_input(Into::into)).await?;
+
+        // Ok(v2_resp.map_output(|v2| ListObjectVersionsOutput {
+        //     versions: v2.contents,
+        //     delete_markers: Some(s3s::dto::DeleteMarkers::default()),
+        //     common_prefixes: v2.common_prefixes,
+        //     delimiter: v2.delimiter,
+        //     encoding_type: v2.encoding_type,
+        //     name: v2.name,
+        //     prefix: v2.prefix,
+        //     max_keys: v2.max_keys,
+        //     ..Default::default()
+        // }))
+        Err(s3_error!(NotImplemented, "Versioning is not supported yet"))
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn list_objects(&self, req: S3Request<ListObjectsInput>) -> S3Result<S3Response<ListObjectsOutput>> {
+        // V1 always returns the owner of the objects
+        let req = req.map_input(|v1| ListObjectsV2Input {
+            fetch_owner: Some(true),
+            ..v1.into()
+        });
+        let v2_resp = self.list_objects_v2(req).await?;
+
+        Ok(v2_resp.map_output(|v2| ListObjectsOutput {
+            contents: v2.contents,
+            common_prefixes: v2.common_prefixes,
+            delimiter: v2.delimiter,
+            encoding_type: v2.encoding_type,
+            name: v2.name,
+            prefix: v2.prefix,
+            max_keys: v2.max_keys,
+            is_truncated: v2.is_truncated,
+            next_marker: v2.next_continuation_token,
+            ..Default::default()
+        }))
+    }
+
+    #[tracing::instrument(level = "debug")]
+    async fn list_objects_v2(&self, req: S3Request<ListObjectsV2Input>) -> S3Result<S3Response<ListObjectsV2Output>> {
+        // the continuation token is the last key or common prefix of the previous page
+        let marker = req.input.continuation_token.clone().or_else(|| req.input.start_after.clone());
+        let params = [
+            ("Prefix", &req.input.prefix),
+            ("Delimiter", &req.input.delimiter),
+            ("Marker", &marker),
+        ];
+        for (name, value) in params {
+            if let Some(value) = value {
+                keys::check_chars(value, name)?;
+            }
+        }
+        let Some(bucket) = self.db.get_bucket_metadata(&req.input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &bucket).await?;
+        let max_keys = match req.input.max_keys {
+            Some(k) if k < 0 => return Err(s3_error!(InvalidArgument, "max-keys must not be negative")),
+            Some(k) if k > MAX_LIST_KEYS => k.min(self.max_list_keys(&req).await?),
+            Some(k) => k,
+            None => MAX_LIST_KEYS,
+        };
+        // pages larger than a single database page are fetched in several, limited by the timeout
+        let deadline = tokio::time::Instant::now() + self.config.extended_list_timeout;
+        let list_result = ObjectLister::new(
+            self.db.as_ref(),
+            &req.input.bucket,
+            &req.input.prefix,
+            req.input.delimiter.as_deref().unwrap_or_default(),
+        )
+        .with_marker(marker)
+        .collect(max_keys as u64, MAX_LIST_KEYS as u64, deadline)
+        .await?;
+
+        let ListResult {
+            objects,
+            common_prefixes,
+            marker,
+            version_marker,
+        } = list_result;
+
+        // the writer of an object is not tracked, every object is owned by the bucket owner
+        let owner = match req.input.fetch_owner {
+            Some(true) => Some(self.owners.resolve(self.db.as_ref(), &bucket.owner).await?),
+            _ => None,
+        };
+
+        // looked up for each object, so only when the client asks for it
+        let with_restore = req
+            .input
+            .optional_object_attributes
+            .iter()
+            .any(|a| a.as_str() == OptionalObjectAttributes::RESTORE_STATUS);
+        let mut restores = Vec::with_capacity(objects.len());
+        for (o, b) in &objects {
+            let restore = match b {
+                Some(b) if with_restore => self.object_restore(o, b).await?,
+                _ => None,
+            };
+            restores.push(restore.map(|restore| RestoreStatus {
+                is_restore_in_progress: restore.expires_at.is_none(),
+                restore_expiry_date: restore.expires_at.map(s3s::dto::Timestamp::from),
+            }));
+        }
+
+        let objects: Vec<s3s::dto::Object> = objects
+            .into_iter()
+            .zip(restores)
+            .map(|((o, b), restore_status)| s3s::dto::Object {
+                checksum_algorithm: None,
+                e_tag: if let Some(b) = &b { Some(b.etag.clone()) } else { None },
+                key: Some(o.oid),
+                last_modified: Some(s3s::dto::Timestamp::from(o.last_modified)),
+                owner: owner.as_ref().map(|owner| s3s::dto::Owner {
+                    display_name: owner.display_name.clone(),
+                    id: owner.id.clone(),
+                }),
+                restore_status,
+                size: if let Some(b) = &b { b.size } else { 0 },
+                storage_class: b.and_then(|b| b.storage_class).map(ObjectStorageClass::from),
+            })
+            .collect();
+
+        let common_prefixes = common_prefixes
+            .into_iter()
+            .map(|p| s3s::dto::CommonPrefix { prefix: Some(p) })
+            .collect();
+
+        let output = s3s::dto::ListObjectsV2Output {
+            common_prefixes: Some(common_prefixes),
+            key_count: objects.len() as i32,
+            contents: Some(objects),
+            delimiter: req.input.delimiter,
+            encoding_type: None,
+            is_truncated: marker.is_some(),
+            max_keys,
+            name: Some(req.input.bucket),
+            prefix: req.input.prefix,
+            request_charged: None,
+            continuation_token: req.input.continuation_token,
+            next_continuation_token: marker,
+            start_after: None,
+        };
+
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_replication(
+        &self,
+        req: S3Request<PutBucketReplicationInput>,
+    ) -> S3Result<S3Response<PutBucketReplicationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // every blob is copied into the single replication pool, other destinations can not be served
+        let Some(destination) = &self.config.replication_destination else {
+            return Err(s3_error!(InvalidRequest, "Replication is not enabled on this gateway"));
+        };
+        let ReplicationConfiguration { role, rules } = req.input.replication_configuration;
+        if rules.is_empty() {
+            return Err(s3_error!(MalformedXML, "At least one replication rule is required"));
+        }
+
+        let rules = rules
+            .into_iter()
+            .map(|r| {
+                let prefix = match r.filter {
+                    Some(ReplicationRuleFilter::Prefix(prefix)) => Some(prefix),
+                    Some(ReplicationRuleFilter::And(and)) => and.prefix,
+                    Some(_) => {
+                        return Err(s3_error!(NotImplemented, "Tag based replication filters are not supported"));
+                    }
+                    None => r.prefix,
+                };
+                let bucket = r
+                    .destination
+                    .bucket
+                    .strip_prefix(BUCKET_ARN_PREFIX)
+                    .unwrap_or(&r.destination.bucket);
+                if bucket != destination {
+                    return Err(s3_error!(InvalidRequest, "Destination bucket must be {}", destination));
+                }
+
+                Ok(ReplicationRule {
+                    id: r.id.unwrap_or_else(|| self.config.ids.new_id().to_string()),
+                    priority: r.priority,
+                    prefix: prefix.unwrap_or_default(),
+                    enabled: r.status.as_str() == ReplicationRuleStatus::ENABLED,
+                    destination_bucket: r.destination.bucket,
+                    storage_class: r.destination.storage_class.map(|c| c.as_str().to_owned()),
+                })
+            })
+            .collect::<S3Result<Vec<_>>>()?;
+
+        self.db
+            .put_bucket_replication(&req.input.bucket, &ReplicationConfig { role, rules })
+            .await?;
+        Ok(S3Response::new(PutBucketReplicationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_replication(
+        &self,
+        req: S3Request<GetBucketReplicationInput>,
+    ) -> S3Result<S3Response<GetBucketReplicationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(config) = self.db.get_bucket_replication(&req.input.bucket).await? else {
+            let mut err = s3s::S3Error::new(s3s::S3ErrorCode::Custom("ReplicationConfigurationNotFoundError".into()));
+            err.set_status_code(hyper::StatusCode::NOT_FOUND);
+            return Err(err);
+        };
+
+        let rules = config
+            .rules
+            .into_iter()
+            .map(|r| s3s::dto::ReplicationRule {
+                delete_marker_replication: None,
+                destination: Destination {
+                    access_control_translation: None,
+                    account: None,
+                    bucket: r.destination_bucket,
+                    encryption_configuration: None,
+                    metrics: None,
+                    replication_time: None,
+                    storage_class: r.storage_class.map(StorageClass::from),
+                },
+                existing_object_replication: None,
+                filter: Some(ReplicationRuleFilter::Prefix(r.prefix)),
+                id: Some(r.id),
+                prefix: None,
+                priority: r.priority,
+                source_selection_criteria: None,
+                status: ReplicationRuleStatus::from_static(if r.enabled {
+                    ReplicationRuleStatus::ENABLED
+                } else {
+                    ReplicationRuleStatus::DISABLED
+                }),
+            })
+            .collect();
+
+        Ok(S3Response::new(GetBucketReplicationOutput {
+            replication_configuration: Some(ReplicationConfiguration {
+                role: config.role,
+                rules,
+            }),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_replication(
+        &self,
+        req: S3Request<DeleteBucketReplicationInput>,
+    ) -> S3Result<S3Response<DeleteBucketReplicationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.delete_bucket_replication(&req.input.bucket).await?;
+        Ok(S3Response::new(DeleteBucketReplicationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_inventory_configuration(
+        &self,
+        req: S3Request<PutBucketInventoryConfigurationInput>,
+    ) -> S3Result<S3Response<PutBucketInventoryConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let input = req.input;
+        let config = input.inventory_configuration;
+        if config.id != input.id {
+            return Err(s3_error!(InvalidArgument, "Inventory id does not match the configuration"));
+        }
+
+        let destination = config.destination.s3_bucket_destination;
+        if destination.format.as_str() != InventoryFormat::CSV {
+            return Err(s3_error!(NotImplemented, "Only CSV inventory reports are supported"));
+        }
+        if destination.encryption.is_some() {
+            return Err(s3_error!(NotImplemented, "Inventory encryption is not supported"));
+        }
+        let destination_bucket = destination
+            .bucket
+            .strip_prefix(BUCKET_ARN_PREFIX)
+            .unwrap_or(&destination.bucket)
+            .to_owned();
+        if self.db.get_bucket_metadata(&destination_bucket).await?.is_none() {
+            return Err(s3_error!(InvalidArgument, "Inventory destination bucket does not exist"));
+        }
+
+        let config = InventoryConfig {
+            id: config.id,
+            enabled: config.is_enabled,
+            prefix: config.filter.map(|f| f.prefix),
+            destination_bucket,
+            destination_prefix: destination.prefix,
+            format: destination.format.as_str().to_owned(),
+            frequency: config.schedule.frequency.as_str().to_owned(),
+            included_versions: config.included_object_versions.as_str().to_owned(),
+        };
+        self.db.put_bucket_inventory(&input.bucket, &config).await?;
+        Ok(S3Response::new(PutBucketInventoryConfigurationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_inventory_configuration(
+        &self,
+        req: S3Request<GetBucketInventoryConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketInventoryConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(config) = self.db.get_bucket_inventory(&req.input.bucket, &req.input.id).await? else {
+            let mut err = s3s::S3Error::new(s3s::S3ErrorCode::Custom("NoSuchConfiguration".into()));
+            err.set_status_code(hyper::StatusCode::NOT_FOUND);
+            return Err(err);
+        };
+
+        Ok(S3Response::new(GetBucketInventoryConfigurationOutput {
+            inventory_configuration: Some(inventory_configuration(config)),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_bucket_inventory_configurations(
+        &self,
+        req: S3Request<ListBucketInventoryConfigurationsInput>,
+    ) -> S3Result<S3Response<ListBucketInventoryConfigurationsOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // a bucket can hold at most 1000 configurations so the list is never truncated
+        let configs = self.db.list_bucket_inventory(&req.input.bucket).await?;
+        Ok(S3Response::new(ListBucketInventoryConfigurationsOutput {
+            continuation_token: req.input.continuation_token,
+            inventory_configuration_list: Some(configs.into_iter().map(inventory_configuration).collect()),
+            is_truncated: false,
+            next_continuation_token: None,
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_inventory_configuration(
+        &self,
+        req: S3Request<DeleteBucketInventoryConfigurationInput>,
+    ) -> S3Result<S3Response<DeleteBucketInventoryConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.delete_bucket_inventory(&req.input.bucket, &req.input.id).await?;
+        Ok(S3Response::new(DeleteBucketInventoryConfigurationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_metrics_configuration(
+        &self,
+        req: S3Request<PutBucketMetricsConfigurationInput>,
+    ) -> S3Result<S3Response<PutBucketMetricsConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let input = req.input;
+        let config = input.metrics_configuration;
+        if config.id != input.id {
+            return Err(s3_error!(InvalidArgument, "Metrics id does not match the configuration"));
+        }
+        let (prefix, tags) = match config.filter {
+            None => (None, Vec::new()),
+            Some(MetricsFilter::Prefix(prefix)) => (Some(prefix), Vec::new()),
+            Some(MetricsFilter::Tag(tag)) => (None, vec![tag]),
+            Some(MetricsFilter::And(and)) if and.access_point_arn.is_none() => (and.prefix, and.tags.unwrap_or_default()),
+            Some(_) => {
+                return Err(s3_error!(NotImplemented, "Access points are not supported"));
+            }
+        };
+        let tagging = if tags.is_empty() {
+            None
+        } else {
+            let mut tagging = form_urlencoded::Serializer::new(String::new());
+            for tag in &tags {
+                tagging.append_pair(&tag.key, &tag.value);
+            }
+            Some(canonical_tagging(&tagging.finish(), MAX_BUCKET_TAGS, "Filter")?)
+        };
+        if self.db.get_bucket_metrics(&input.bucket, &config.id).await?.is_none()
+            && self.db.list_bucket_metrics(&input.bucket).await?.len() >= MAX_METRICS_CONFIGURATIONS
+        {
+            let mut err = s3s::S3Error::with_message(
+                s3s::S3ErrorCode::Custom("TooManyConfigurations".into()),
+                format!("A bucket can have at most {MAX_METRICS_CONFIGURATIONS} metrics configurations"),
+            );
+            err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+            return Err(err);
+        }
+
+        let config = MetricsConfig {
+            id: config.id,
+            prefix,
+            tagging,
+        };
+        self.db.put_bucket_metrics(&input.bucket, &config).await?;
+        self.bucket_metrics.invalidate(&input.bucket);
+        Ok(S3Response::new(PutBucketMetricsConfigurationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_metrics_configuration(
+        &self,
+        req: S3Request<GetBucketMetricsConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketMetricsConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(config) = self.db.get_bucket_metrics(&req.input.bucket, &req.input.id).await? else {
+            return Err(not_configured("NoSuchConfiguration"));
+        };
+        Ok(S3Response::new(GetBucketMetricsConfigurationOutput {
+            metrics_configuration: Some(metrics_configuration(config)),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn list_bucket_metrics_configurations(
+        &self,
+        req: S3Request<ListBucketMetricsConfigurationsInput>,
+    ) -> S3Result<S3Response<ListBucketMetricsConfigurationsOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // at most MAX_METRICS_CONFIGURATIONS so the list is never truncated
+        let configs = self.db.list_bucket_metrics(&req.input.bucket).await?;
+        Ok(S3Response::new(ListBucketMetricsConfigurationsOutput {
+            continuation_token: req.input.continuation_token,
+            is_truncated: false,
+            metrics_configuration_list: Some(configs.into_iter().map(metrics_configuration).collect()),
+            next_continuation_token: None,
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_metrics_configuration(
+        &self,
+        req: S3Request<DeleteBucketMetricsConfigurationInput>,
+    ) -> S3Result<S3Response<DeleteBucketMetricsConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        if self.db.get_bucket_metrics(&req.input.bucket, &req.input.id).await?.is_none() {
+            return Err(not_configured("NoSuchConfiguration"));
+        }
+        self.db.delete_bucket_metrics(&req.input.bucket, &req.input.id).await?;
+        self.bucket_metrics.invalidate(&req.input.bucket);
+        Ok(S3Response::new(DeleteBucketMetricsConfigurationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_ownership_controls(
+        &self,
+        req: S3Request<PutBucketOwnershipControlsInput>,
+    ) -> S3Result<S3Response<PutBucketOwnershipControlsOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let [rule] = req.input.ownership_controls.rules.as_slice() else {
+            return Err(s3_error!(MalformedXML, "Exactly one ownership controls rule is required"));
+        };
+        let ownership = rule.object_ownership.as_str();
+        let is_valid = [
+            ObjectOwnership::BUCKET_OWNER_ENFORCED,
+            ObjectOwnership::BUCKET_OWNER_PREFERRED,
+            ObjectOwnership::OBJECT_WRITER,
+        ]
+        .contains(&ownership);
+        if !is_valid {
+            return Err(s3_error!(MalformedXML, "Unsupported object ownership: {}", ownership));
+        }
+        if ownership == ObjectOwnership::BUCKET_OWNER_ENFORCED && bucket.acl != BucketCannedACL::PRIVATE {
+            return Err(invalid_bucket_acl_with_ownership());
+        }
+
+        self.db
+            .set_bucket_object_ownership(&req.input.bucket, Some(ownership))
+            .await?;
+        Ok(S3Response::new(PutBucketOwnershipControlsOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_ownership_controls(
+        &self,
+        req: S3Request<GetBucketOwnershipControlsInput>,
+    ) -> S3Result<S3Response<GetBucketOwnershipControlsOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(ownership) = bucket.object_ownership else {
+            let mut err = s3s::S3Error::new(s3s::S3ErrorCode::Custom("OwnershipControlsNotFoundError".into()));
+            err.set_status_code(hyper::StatusCode::NOT_FOUND);
+            return Err(err);
+        };
+
+        Ok(S3Response::new(GetBucketOwnershipControlsOutput {
+            ownership_controls: Some(OwnershipControls {
+                rules: vec![OwnershipControlsRule {
+                    object_ownership: ObjectOwnership::from(ownership),
+                }],
+            }),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_ownership_controls(
+        &self,
+        req: S3Request<DeleteBucketOwnershipControlsInput>,
+    ) -> S3Result<S3Response<DeleteBucketOwnershipControlsOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.set_bucket_object_ownership(&req.input.bucket, None).await?;
+        Ok(S3Response::new(DeleteBucketOwnershipControlsOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_accelerate_configuration(
+        &self,
+        req: S3Request<PutBucketAccelerateConfigurationInput>,
+    ) -> S3Result<S3Response<PutBucketAccelerateConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // the setting is only stored, the gateway has no accelerated endpoint
+        let status = match req.input.accelerate_configuration.status {
+            Some(status) if [BucketAccelerateStatus::ENABLED, BucketAccelerateStatus::SUSPENDED].contains(&status.as_str()) => {
+                status
+            }
+            _ => return Err(s3_error!(MalformedXML, "Accelerate status must be Enabled or Suspended")),
+        };
+        self.db.set_bucket_accelerate(&req.input.bucket, status.as_str()).await?;
+        Ok(S3Response::new(PutBucketAccelerateConfigurationOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_accelerate_configuration(
+        &self,
+        req: S3Request<GetBucketAccelerateConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketAccelerateConfigurationOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // a bucket which has never been configured has no status
+        Ok(S3Response::new(GetBucketAccelerateConfigurationOutput {
+            request_charged: None,
+            status: bucket.accelerate_status.map(BucketAccelerateStatus::from),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_request_payment(
+        &self,
+        req: S3Request<PutBucketRequestPaymentInput>,
+    ) -> S3Result<S3Response<PutBucketRequestPaymentOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // enforced by RequesterPaysAuth, requests are not billed
+        let payer = req.input.request_payment_configuration.payer;
+        if ![Payer::BUCKET_OWNER, Payer::REQUESTER].contains(&payer.as_str()) {
+            return Err(s3_error!(MalformedXML, "Payer must be BucketOwner or Requester"));
+        }
+        self.db.set_bucket_request_payer(&req.input.bucket, payer.as_str()).await?;
+        Ok(S3Response::new(PutBucketRequestPaymentOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_request_payment(
+        &self,
+        req: S3Request<GetBucketRequestPaymentInput>,
+    ) -> S3Result<S3Response<GetBucketRequestPaymentOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        Ok(S3Response::new(GetBucketRequestPaymentOutput {
+            payer: Some(Payer::from(bucket.request_payer)),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_tagging(&self, req: S3Request<PutBucketTaggingInput>) -> S3Result<S3Response<PutBucketTaggingOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let tagging = {
+            let mut tagging = form_urlencoded::Serializer::new(String::new());
+            for tag in &req.input.tagging.tag_set {
+                tagging.append_pair(&tag.key, &tag.value);
+            }
+            tagging.finish()
+        };
+        let tagging = canonical_tagging(&tagging, MAX_BUCKET_TAGS, "Bucket")?;
+        // an empty tag set removes the tags like DeleteBucketTagging
+        let tagging = Some(tagging).filter(|tagging| !tagging.is_empty());
+        self.db.set_bucket_tagging(&req.input.bucket, tagging.as_deref()).await?;
+        Ok(S3Response::new(PutBucketTaggingOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_tagging(&self, req: S3Request<GetBucketTaggingInput>) -> S3Result<S3Response<GetBucketTaggingOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(tagging) = bucket.tagging else {
+            return Err(not_configured("NoSuchTagSet"));
+        };
+        let tag_set = form_urlencoded::parse(tagging.as_bytes())
+            .map(|(key, value)| Tag {
+                key: key.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect();
+        Ok(S3Response::new(GetBucketTaggingOutput { tag_set }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_tagging(
+        &self,
+        req: S3Request<DeleteBucketTaggingInput>,
+    ) -> S3Result<S3Response<DeleteBucketTaggingOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.set_bucket_tagging(&req.input.bucket, None).await?;
+        Ok(S3Response::new(DeleteBucketTaggingOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_versioning(
+        &self,
+        req: S3Request<GetBucketVersioningInput>,
+    ) -> S3Result<S3Response<GetBucketVersioningOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // versioning has never been enabled on any bucket, which is reported without a status
+        Ok(S3Response::new(GetBucketVersioningOutput {
+            mfa_delete: None,
+            status: None,
+        }))
+    }
+
+    /// The policy is stored for the public access block, its statements do not grant or deny access
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_bucket_policy(&self, req: S3Request<PutBucketPolicyInput>) -> S3Result<S3Response<PutBucketPolicyOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let policy = match PolicyDocument::parse_bucket_policy(&req.input.policy) {
+            Ok(policy) => policy,
+            Err(err) => {
+                let mut err = s3s::S3Error::with_message(s3s::S3ErrorCode::Custom("MalformedPolicy".into()), err);
+                err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+                return Err(err);
+            }
+        };
+        if policy.is_public() && self.public_access_block(&req.input.bucket).await?.block_public_policy {
+            return Err(s3_error!(AccessDenied, "Public policies are blocked by the bucket's public access block"));
+        }
+        self.db.set_bucket_policy(&req.input.bucket, Some(&req.input.policy)).await?;
+        Ok(S3Response::new(PutBucketPolicyOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_policy(&self, req: S3Request<GetBucketPolicyInput>) -> S3Result<S3Response<GetBucketPolicyOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        let Some(policy) = bucket.policy else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucketPolicy));
+        };
+        Ok(S3Response::new(GetBucketPolicyOutput { policy: Some(policy) }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_policy(
+        &self,
+        req: S3Request<DeleteBucketPolicyInput>,
+    ) -> S3Result<S3Response<DeleteBucketPolicyOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.set_bucket_policy(&req.input.bucket, None).await?;
+        Ok(S3Response::new(DeleteBucketPolicyOutput {}))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_cors(&self, req: S3Request<GetBucketCorsInput>) -> S3Result<S3Response<GetBucketCorsOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // CORS configurations are not supported, so no bucket has one
+        Err(not_configured("NoSuchCORSConfiguration"))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_cors(&self, req: S3Request<DeleteBucketCorsInput>) -> S3Result<S3Response<DeleteBucketCorsOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // there is no Put counterpart, succeeding would claim a configuration the bucket can not have
+        Err(s3_error!(NotImplemented, "CORS configurations are not supported"))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_lifecycle_configuration(
+        &self,
+        req: S3Request<GetBucketLifecycleConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketLifecycleConfigurationOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        // lifecycle configurations are not supported, so no bucket has one
+        Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchLifecycleConfiguration))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_lifecycle(
+        &self,
+        req: S3Request<DeleteBucketLifecycleInput>,
+    ) -> S3Result<S3Response<DeleteBucketLifecycleOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        Err(s3_error!(NotImplemented, "Lifecycle configurations are not supported"))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_encryption(
+        &self,
+        req: S3Request<GetBucketEncryptionInput>,
+    ) -> S3Result<S3Response<GetBucketEncryptionOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
 
-        // get buckets
-        let buckets = self
-            .db
-            .list_buckets_by_user(&user.id)
-            .await?
-            .into_iter()
-            .map(|b| s3s::dto::Bucket {
-                creation_date: Some(s3s::dto::Timestamp::from(time::OffsetDateTime::new_in_offset(
-                    b.creation_date.date(),
-                    b.creation_date.time(),
-                    time::UtcOffset::UTC,
-                ))),
-                name: Some(b.name),
-            })
-            .collect();
-        let output = s3s::dto::ListBucketsOutput {
-            buckets: Some(buckets),
-            owner: Some(s3s::dto::Owner {
-                id: Some(user.id),
-                display_name: Some(user.name),
-            }),
-        };
-        Ok(S3Response::new(output))
+        // default encryption configurations are not supported, so no bucket has one
+        Err(not_configured("ServerSideEncryptionConfigurationNotFoundError"))
     }
 
-    async fn list_object_versions(
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_bucket_encryption(
         &self,
-        req: S3Request<ListObjectVersionsInput>,
-    ) -> S3Result<S3Response<ListObjectVersionsOutput>> {
-        // let v2_resp = self.list_objects_v2(req.map_input(Into::into)).await?;
-        
-        // Ok(v2_resp.map_output(|v2| ListObjectVersionsOutput {
-        //     versions: v2.contents,
-        //     delete_markers: Some(s3s::dto::DeleteMarkers::default()),
-        //     common_prefixes: v2.common_prefixes,
-        //     delimiter: v2.delimiter,
-        //     encoding_type: v2.encoding_type,
-        //     name: v2.name,
-        //     prefix: v2.prefix,
-        //     max_keys: v2.max_keys,
-        //     ..Default::default()
-        // }))
-        todo!()
+        req: S3Request<DeleteBucketEncryptionInput>,
+    ) -> S3Result<S3Response<DeleteBucketEncryptionOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        Err(s3_error!(NotImplemented, "Default encryption configurations are not supported"))
     }
 
-    #[tracing::instrument(level = "debug")]
-    async fn list_objects(&self, req: S3Request<ListObjectsInput>) -> S3Result<S3Response<ListObjectsOutput>> {
-        let v2_resp = self.list_objects_v2(req.map_input(Into::into)).await?;
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_bucket_policy_status(
+        &self,
+        req: S3Request<GetBucketPolicyStatusInput>,
+    ) -> S3Result<S3Response<GetBucketPolicyStatusOutput>> {
+        let bucket = self.owned_bucket(&req.credentials, &req.input.bucket).await?;
 
-        Ok(v2_resp.map_output(|v2| ListObjectsOutput {
-            contents: v2.contents,
-            common_prefixes: v2.common_prefixes,
-            delimiter: v2.delimiter,
-            encoding_type: v2.encoding_type,
-            name: v2.name,
-            prefix: v2.prefix,
-            max_keys: v2.max_keys,
-            is_truncated: v2.is_truncated,
-            next_marker: v2.continuation_token,
-            ..Default::default()
+        let Some(policy) = bucket.policy else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucketPolicy));
+        };
+        Ok(S3Response::new(GetBucketPolicyStatusOutput {
+            policy_status: Some(PolicyStatus {
+                is_public: is_public_policy(&policy),
+            }),
         }))
     }
 
-    #[tracing::instrument(level = "debug")]
-    async fn list_objects_v2(&self, req: S3Request<ListObjectsV2Input>) -> S3Result<S3Response<ListObjectsV2Output>> {
-        let list_result = self
-            .db
-            .list_objects(ListOptions {
-                bucket: &req.input.bucket,
-                prefix: &req.input.prefix,
-                delim: &req.input.delimiter.as_ref().map_or("/", |v| &v),
-                marker: &req.input.start_after,
-                max_keys: 1000,
-                with_versions: false,
-                version_marker: None,
-            })
-            .await?;
-
-        let ListResult {
-            objects,
-            common_prefixes,
-            marker,
-            version_marker,
-        } = list_result;
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn put_public_access_block(
+        &self,
+        req: S3Request<PutPublicAccessBlockInput>,
+    ) -> S3Result<S3Response<PutPublicAccessBlockOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
 
-        let objects: Vec<s3s::dto::Object> = objects
-            .into_iter()
-            .map(|(o, b)| s3s::dto::Object {
-                checksum_algorithm: None,
-                e_tag: if let Some(b) = &b { Some(b.etag.clone()) } else { None },
-                key: Some(o.oid),
-                last_modified: Some(s3s::dto::Timestamp::from(time::OffsetDateTime::new_in_offset(
-                    o.last_modified.date(),
-                    o.last_modified.time(),
-                    time::UtcOffset::UTC,
-                ))),
-                owner: None,
-                restore_status: None,
-                size: if let Some(b) = &b { b.size } else { 0 },
-                storage_class: None,
-            })
-            .collect();
+        let config = req.input.public_access_block_configuration;
+        let config = PublicAccessBlock {
+            block_public_acls: config.block_public_acls,
+            ignore_public_acls: config.ignore_public_acls,
+            block_public_policy: config.block_public_policy,
+            restrict_public_buckets: config.restrict_public_buckets,
+        };
+        self.db.put_public_access_block(&req.input.bucket, &config).await?;
+        Ok(S3Response::new(PutPublicAccessBlockOutput {}))
+    }
 
-        let common_prefixes = common_prefixes
-            .into_iter()
-            .map(|p| s3s::dto::CommonPrefix { prefix: Some(p) })
-            .collect();
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn get_public_access_block(
+        &self,
+        req: S3Request<GetPublicAccessBlockInput>,
+    ) -> S3Result<S3Response<GetPublicAccessBlockOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
 
-        let output = s3s::dto::ListObjectsV2Output {
-            common_prefixes: Some(common_prefixes),
-            key_count: objects.len() as i32,
-            contents: Some(objects),
-            delimiter: req.input.delimiter,
-            encoding_type: None,
-            is_truncated: marker.is_some(),
-            max_keys: 1000,
-            name: Some(req.input.bucket),
-            prefix: req.input.prefix,
-            request_charged: None,
-            continuation_token: req.input.continuation_token,
-            next_continuation_token: marker,
-            start_after: None,
+        let Some(config) = self.db.get_public_access_block(&req.input.bucket).await? else {
+            let mut err = s3s::S3Error::new(s3s::S3ErrorCode::Custom("NoSuchPublicAccessBlockConfiguration".into()));
+            err.set_status_code(hyper::StatusCode::NOT_FOUND);
+            return Err(err);
         };
 
-        Ok(S3Response::new(output))
+        Ok(S3Response::new(GetPublicAccessBlockOutput {
+            public_access_block_configuration: Some(PublicAccessBlockConfiguration {
+                block_public_acls: config.block_public_acls,
+                block_public_policy: config.block_public_policy,
+                ignore_public_acls: config.ignore_public_acls,
+                restrict_public_buckets: config.restrict_public_buckets,
+            }),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn delete_public_access_block(
+        &self,
+        req: S3Request<DeletePublicAccessBlockInput>,
+    ) -> S3Result<S3Response<DeletePublicAccessBlockOutput>> {
+        self.owned_bucket(&req.credentials, &req.input.bucket).await?;
+
+        self.db.delete_public_access_block(&req.input.bucket).await?;
+        Ok(S3Response::new(DeletePublicAccessBlockOutput {}))
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
@@ -297,6 +2197,14 @@ impl S3 for RadosStore {
             tracing::info!("request is unatharized");
             return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
         }
+        self.check_bucket_write(&req.credentials, &bucket_md).await?;
+        let grants = [
+            &input.grant_full_control,
+            &input.grant_read,
+            &input.grant_read_acp,
+            &input.grant_write_acp,
+        ];
+        self.check_object_acl(&bucket_md, input.acl.as_ref(), grants).await?;
 
         let PutObjectInput {
             body,
@@ -306,6 +2214,17 @@ impl S3 for RadosStore {
             tagging,
             content_length,
             content_md5,
+            content_type,
+            content_encoding,
+            content_language,
+            content_disposition,
+            cache_control,
+            expires,
+            checksum_algorithm,
+            checksum_crc32,
+            checksum_crc32c,
+            checksum_sha1,
+            checksum_sha256,
             ..
         } = input;
         // let Some(content_md5) = content_md5 else  {
@@ -315,72 +2234,606 @@ impl S3 for RadosStore {
             return Err(s3_error!(InvalidArgument, "content_length not provided"));
             // TODO: should not be an error
         };
+        if content_length > self.config.max_object_size {
+            return Err(s3_error!(EntityTooLarge));
+        }
+        keys::check_key(&key, self.config.key_policy)?;
+        let tagging = parse_tagging(tagging)?;
+        check_body_encoding(&req.headers)?;
+        let condition = write_condition(&req.headers)?;
+        let content_headers = content_headers(
+            content_type,
+            content_encoding,
+            content_language,
+            content_disposition,
+            cache_control,
+            expires,
+        );
+
+        let expected = checksum::from_headers(checksum_crc32, checksum_crc32c, checksum_sha1, checksum_sha256)?;
+        let mut hasher = checksum::hasher(expected.as_ref(), checksum_algorithm.as_ref().map(ChecksumAlgorithm::as_str))?;
 
         tracing::info!("Request validation is done");
         let Some(mut body) = body else { return Err(s3_error!(IncompleteBody)) };
 
+        if self.config.offload_threshold > 0 && content_length > self.config.offload_threshold {
+            let mut temp = TempBlobs::new(self.db.clone());
+            let (mut blob, parts) = self
+                .write_blob_parts(&mut body, content_length, &mut temp, hasher.as_mut())
+                .await?;
+            blob.checksum = checksum::verify(hasher, expected.as_ref())?;
+            let object = crate::meta_store::Object {
+                bucket_name: bucket,
+                oid: key,
+                version_id: None,
+                last_modified: crate::meta_store::Timestamp::UNIX_EPOCH,
+                blob_id: Some(blob.id),
+                metadata,
+                tagging,
+                replication_status: None,
+                last_accessed: None,
+                content_headers,
+            };
+            let last_modified = self
+                .db
+                .write_object_metadata_with_parts(&bucket_md, &object, &blob, &parts, condition.as_ref())
+                .await?;
+            temp.commit();
+            self.record_metrics(&object, MetricsOperation::Put, blob.size);
+
+            let checksum = checksum::to_dto(blob.checksum);
+            let output = PutObjectOutput {
+                e_tag: Some(blob.etag),
+                checksum_crc32: checksum.checksum_crc32,
+                checksum_crc32c: checksum.checksum_crc32c,
+                checksum_sha1: checksum.checksum_sha1,
+                checksum_sha256: checksum.checksum_sha256,
+                ..Default::default()
+            };
+            let mut res = S3Response::new(output);
+            res.headers.insert(hyper::header::LAST_MODIFIED, http_date(last_modified));
+            res.headers.insert(VERSION_TOKEN_HEADER, version_token(&blob.id));
+            return Ok(res);
+        }
+
         let mut new_blob = Blob {
-            id: Uuid::new_v4(),
+            id: self.config.ids.new_id(),
             size: content_length,
             parts: None,
             part_size: None,
-            upload_timestamp: crate::meta_store::Timestamp::MIN,
+            upload_timestamp: crate::meta_store::Timestamp::UNIX_EPOCH,
+            storage_class: None,
             etag: String::default(), // TODO get md5-hash as AWS does
+            checksum: None,
         };
-        self.db.write_temp_blob(&new_blob).await?;
+        let mut temp = TempBlobs::new(self.db.clone());
+        temp.add(&new_blob).await?;
         tracing::info!(blob=?new_blob, "temp blob has been written");
 
-        let res: Result<crate::meta_store::Object, s3s::S3Error> = {
+        let object = {
             // open rados file
-            let mut writer = try_!(self.blob.get_writer(&new_blob.id.to_string()).await);
-            let mut md5_hash = <Md5 as Digest>::new();
-            while let Some(chunk) = body.next().instrument(debug_span!("read_user_input")).await {
-                //let chunk = try_!(chunk);
-                let Ok(chunk) = chunk else {
-                    break;
-                    // return error
-                };
-                md5_hash.update(chunk.as_ref());
+            let (_size, etag) = self.write_blob(&new_blob.id, &mut body, true, hasher.as_mut()).await?;
+            new_blob.etag = etag;
+            new_blob.checksum = checksum::verify(hasher, expected.as_ref())?;
 
-                try_!(writer.write_all(&chunk).instrument(debug_span!("rados_write_chunk")).await);
-                // calk checksum
-                // write to the rados
-            }
-            try_!(writer.flush().instrument(debug_span!("rados_flush_remainig")).await);
-
-            // validate checksums
-            new_blob.etag = hex(md5_hash.finalize());
-
-            let object = crate::meta_store::Object {
+            let mut object = crate::meta_store::Object {
                 bucket_name: bucket,
                 oid: key,
                 version_id: None,
-                last_modified: crate::meta_store::Timestamp::MIN,
+                last_modified: crate::meta_store::Timestamp::UNIX_EPOCH,
                 blob_id: Some(new_blob.id),
-                metadata: metadata,
+                metadata,
+                tagging,
+                replication_status: None,
+                last_accessed: None,
+                content_headers,
             };
-            try_!(self.db.write_object_metadata_with_blob(&bucket_md, &object, &new_blob).await);
-            Ok(object)
-        };
-
-        let Ok(object) = res else {
-            // TODO: delete from rados
-            self.db.clean_temp_blob(&new_blob).await;
-            return Err(s3_error!(InternalError, "Unable to put object"));
+            object.last_modified = try_!(
+                self.db
+                    .write_object_metadata_with_blob(&bucket_md, &object, &new_blob, condition.as_ref())
+                    .await
+            );
+            object
         };
+        temp.commit();
+        self.record_metrics(&object, MetricsOperation::Put, new_blob.size);
 
+        let checksum = checksum::to_dto(new_blob.checksum);
         let output = PutObjectOutput {
             e_tag: Some(new_blob.etag),
-            checksum_crc32: None,
-            checksum_crc32c: None,
-            checksum_sha1: None,
-            checksum_sha256: None,
+            checksum_crc32: checksum.checksum_crc32,
+            checksum_crc32c: checksum.checksum_crc32c,
+            checksum_sha1: checksum.checksum_sha1,
+            checksum_sha256: checksum.checksum_sha256,
+            ..Default::default()
+        };
+        let mut res = S3Response::new(output);
+        res.headers
+            .insert(hyper::header::LAST_MODIFIED, http_date(object.last_modified));
+        res.headers.insert(VERSION_TOKEN_HEADER, version_token(&new_blob.id));
+        Ok(res)
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn restore_object(&self, req: S3Request<RestoreObjectInput>) -> S3Result<S3Response<RestoreObjectOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3_error!(AccessDenied, "Anonymous users can not restore objects"));
+        }
+        let input = req.input;
+        if input.version_id.as_deref().is_some_and(|v| v != "null") {
+            return Err(s3_error!(NotImplemented, "Versioning is not supported yet"));
+        }
+        let Some(request) = input.restore_request else {
+            return Err(s3_error!(MalformedXML, "RestoreRequest is missing"));
+        };
+        if request.type_.is_some() || request.select_parameters.is_some() || request.output_location.is_some() {
+            return Err(s3_error!(NotImplemented, "Select restores are not supported"));
+        }
+        if request.days < 1 || request.days > self.config.restore_max_days {
+            return Err(s3_error!(InvalidArgument, "Days must be between 1 and {}", self.config.restore_max_days));
+        }
+
+        let Some(bucket) = self.db.get_bucket_metadata(&input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        // restores cost backend work and change the expiry of restored copies, like s3:RestoreObject it needs write access
+        self.check_bucket_write(&req.credentials, &bucket).await?;
+        let Some((_, Some(blob))) = self.db.load_object_metadata(&input.bucket, &input.key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Key not found"));
+        };
+        match self.db.get_object_restore(&input.bucket, &input.key).await? {
+            Some(restore) if restore.blob == blob.id && restore.expires_at.is_none() => {
+                Err(s3_error!(RestoreAlreadyInProgress, "Object restore is already in progress"))
+            }
+            // a repeated request changes the expiry of the restored copy
+            Some(restore) if restore.blob == blob.id => {
+                let expires_at = crate::restore::restore_expiry(self.config.clock.now(), request.days);
+                self.db
+                    .set_object_restore(&input.bucket, &input.key, &blob.id, request.days, expires_at)
+                    .await?;
+                Ok(S3Response::new(RestoreObjectOutput::default()))
+            }
+            _ if self.cold.is_none() || blob.storage_class.is_none() => Err(s3_error!(
+                InvalidObjectState,
+                "Restore is not allowed for the object's current storage class"
+            )),
+            _ => {
+                self.db
+                    .create_object_restore(&input.bucket, &input.key, &blob.id, request.days)
+                    .await?;
+                Ok(S3Response::new(RestoreObjectOutput::default()))
+            }
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn select_object_content(
+        &self,
+        req: S3Request<SelectObjectContentInput>,
+    ) -> S3Result<S3Response<SelectObjectContentOutput>> {
+        let input = req.input;
+        if input.sse_customer_algorithm.is_some() {
+            return Err(s3_error!(NotImplemented, "SSE-C is not supported"));
+        }
+        // validate the query before reading the object
+        let select = Select::new(input.request)?;
+
+        let Some(bucket_md) = self.db.get_bucket_metadata(&input.bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_read(&req.credentials, &bucket_md).await?;
+        let Some((_object, blob)) = self.db.load_object_metadata(&input.bucket, &input.key, &None).await? else {
+            return Err(s3_error!(NoSuchKey, "Key not found"));
+        };
+        let Some(blob) = blob else {
+            return Err(s3_error!(NoSuchKey, "Versioning is not supported yet"));
+        };
+
+        let body = self.get_blob_reader(&blob).await?;
+        Ok(S3Response::new(SelectObjectContentOutput {
+            payload: Some(select.run(body)),
+        }))
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn upload_part(&self, req: S3Request<UploadPartInput>) -> S3Result<S3Response<UploadPartOutput>> {
+        if req.credentials.is_none() {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::AccessDenied));
+        }
+
+        let UploadPartInput {
+            body,
+            bucket,
+            key,
+            checksum_algorithm,
+            checksum_crc32,
+            checksum_crc32c,
+            checksum_sha1,
+            checksum_sha256,
+            content_length,
+            part_number,
+            upload_id,
+            ..
+        } = req.input;
+        if !(1..=self.config.max_parts).contains(&part_number) {
+            return Err(s3_error!(
+                InvalidArgument,
+                "Part number must be an integer between 1 and {}",
+                self.config.max_parts
+            ));
+        }
+        if content_length.is_some_and(|l| l > self.config.max_object_size) {
+            return Err(s3_error!(EntityTooLarge));
+        }
+        let upload_id = parse_upload_id(&upload_id)?;
+        // the upload is validated before the data is written, and again when the part is attached
+        let upload = self.db.get_multipart_upload(&upload_id).await?;
+        let Some(upload) = upload.filter(|upload| upload.bucket == bucket && upload.oid == key) else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchUpload));
+        };
+        let Some(bucket_md) = self.db.get_bucket_metadata(&bucket).await? else {
+            return Err(s3s::S3Error::new(s3s::S3ErrorCode::NoSuchBucket));
+        };
+        self.check_bucket_write(&req.credentials, &bucket_md).await?;
+        self.check_upload_access(&req.credentials, &upload).await?;
+        check_body_encoding(&req.headers)?;
+        let Some(mut body) = body else { return Err(s3_error!(IncompleteBody)) };
+        let expected = checksum::from_headers(checksum_crc32, checksum_crc32c, checksum_sha1, checksum_sha256)?;
+        let mut hasher = checksum::hasher(expected.as_ref(), checksum_algorithm.as_ref().map(ChecksumAlgorithm::as_str))?;
+
+        // every part is written to a new blob so that reuploading a part never corrupts the old one
+        let temp_blob = Blob {
+            id: self.config.ids.new_id(),
+            size: 0,
+            parts: None,
+            part_size: None,
+            upload_timestamp: crate::meta_store::Timestamp::UNIX_EPOCH,
+            storage_class: None,
+            etag: String::default(),
+            checksum: None,
+        };
+        let mut temp = TempBlobs::new(self.db.clone());
+        temp.add(&temp_blob).await?;
+
+        let (size, etag) = self.write_blob(&temp_blob.id, &mut body, false, hasher.as_mut()).await?;
+        let checksum = checksum::verify(hasher, expected.as_ref())?;
+        let part = MultipartPart {
+            part_number,
+            blob_id: temp_blob.id,
+            size,
+            etag,
+            checksum,
+        };
+        self.db.write_multipart_part(&bucket, &key, &upload_id, &part).await?;
+        temp.commit();
+
+        let checksum = checksum::to_dto(part.checksum);
+        let output = UploadPartOutput {
+            e_tag: Some(part.etag),
+            checksum_crc32: checksum.checksum_crc32,
+            checksum_crc32c: checksum.checksum_crc32c,
+            checksum_sha1: checksum.checksum_sha1,
+            checksum_sha256: checksum.checksum_sha256,
             ..Default::default()
         };
         Ok(S3Response::new(output))
     }
 }
 
+/// Temporary blobs written by a request. Unless the request commits them, their data is handed over to the GC
+/// when the guard is dropped: on errors, but also when the request future is dropped because the client has
+/// disconnected or the request deadline has passed.
+struct TempBlobs {
+    db: Arc<dyn MetaStore>,
+    blobs: Vec<Blob>,
+}
+
+impl TempBlobs {
+    fn new(db: Arc<dyn MetaStore>) -> Self {
+        Self { db, blobs: Vec::new() }
+    }
+
+    async fn add(&mut self, blob: &Blob) -> S3Result<()> {
+        self.db.write_temp_blob(blob).await?;
+        self.blobs.push(blob.clone());
+        Ok(())
+    }
+
+    /// The blobs are referenced by committed metadata now
+    fn commit(mut self) {
+        self.blobs.clear();
+    }
+}
+
+impl Drop for TempBlobs {
+    fn drop(&mut self) {
+        if self.blobs.is_empty() {
+            return;
+        }
+        let db = self.db.clone();
+        let blobs = std::mem::take(&mut self.blobs);
+        tokio::spawn(async move {
+            for blob in &blobs {
+                db.clean_temp_blob(blob).await;
+            }
+        });
+    }
+}
+
+const ALL_USERS_GROUP: &str = "http://acs.amazonaws.com/groups/global/AllUsers";
+const AUTHENTICATED_USERS_GROUP: &str = "http://acs.amazonaws.com/groups/global/AuthenticatedUsers";
+
+const BUCKET_ARN_PREFIX: &str = "arn:aws:s3:::";
+
+/// Last-Modified of a new object, the output of write operations has no field for it
+fn http_date(timestamp: crate::meta_store::Timestamp) -> hyper::header::HeaderValue {
+    let mut buf = Vec::new();
+    s3s::dto::Timestamp::from(timestamp)
+        .format(s3s::dto::TimestampFormat::HttpDate, &mut buf)
+        .expect("a valid timestamp can be formatted");
+    hyper::header::HeaderValue::from_bytes(&buf).expect("an HTTP date is a valid header value")
+}
+
+/// Representation headers of a write request in the form they are stored with the object
+fn content_headers(
+    content_type: Option<ContentType>,
+    content_encoding: Option<ContentEncoding>,
+    content_language: Option<ContentLanguage>,
+    content_disposition: Option<ContentDisposition>,
+    cache_control: Option<CacheControl>,
+    expires: Option<Expires>,
+) -> ContentHeaders {
+    ContentHeaders {
+        content_type: content_type.map(|t| t.to_string()),
+        content_encoding: content_encoding.and_then(strip_aws_chunked),
+        content_language,
+        content_disposition,
+        cache_control,
+        expires: expires.map(time::OffsetDateTime::from),
+    }
+}
+
+/// Content encoding of a streaming upload which only applies to the request body
+const AWS_CHUNKED: &str = "aws-chunked";
+
+/// Whether the request body uses the aws-chunked encoding
+fn is_aws_chunked(headers: &hyper::HeaderMap) -> bool {
+    headers
+        .get_all(hyper::header::CONTENT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case(AWS_CHUNKED))
+}
+
+/// s3s decodes aws-chunked bodies of signed streaming uploads after checking the signature of every chunk.
+/// Other chunked bodies (unsigned or with trailers) would be stored with the chunk framing, so they are
+/// rejected.
+fn check_body_encoding(headers: &hyper::HeaderMap) -> S3Result<()> {
+    if !is_aws_chunked(headers) {
+        return Ok(());
+    }
+    let payload = headers.get("x-amz-content-sha256").and_then(|v| v.to_str().ok());
+    if payload != Some("STREAMING-AWS4-HMAC-SHA256-PAYLOAD") {
+        return Err(s3_error!(
+            NotImplemented,
+            "Unsupported aws-chunked payload: {}",
+            payload.unwrap_or("missing x-amz-content-sha256")
+        ));
+    }
+    Ok(())
+}
+
+/// The encoding stored with the object, without aws-chunked which only describes the upload
+fn strip_aws_chunked(encoding: String) -> Option<String> {
+    let stored: Vec<_> = encoding
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case(AWS_CHUNKED))
+        .collect();
+    (!stored.is_empty()).then(|| stored.join(","))
+}
+
+fn parse_content_type(content_type: Option<String>) -> S3Result<Option<ContentType>> {
+    match content_type {
+        Some(t) => match t.parse() {
+            Ok(t) => Ok(Some(t)),
+            Err(_) => Err(s3_error!(InvalidArgument, "Invalid content type: {}", t)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn invalid_bucket_acl_with_ownership() -> s3s::S3Error {
+    let mut err = s3s::S3Error::with_message(
+        s3s::S3ErrorCode::Custom("InvalidBucketAclWithObjectOwnership".into()),
+        "Bucket cannot have ACLs set with ObjectOwnership's BucketOwnerEnforced setting",
+    );
+    err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+    err
+}
+
+/// Default and maximum number of parts returned by GetObjectAttributes
+const MAX_LISTED_PARTS: i32 = 1000;
+
+/// Limits of object and bucket tags, the same as in AWS
+const MAX_OBJECT_TAGS: usize = 10;
+const MAX_BUCKET_TAGS: usize = 50;
+/// the same limit as in AWS
+const MAX_METRICS_CONFIGURATIONS: usize = 1000;
+const MAX_TAG_KEY_LENGTH: usize = 128;
+const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+/// Validate the `x-amz-tagging` header (`key1=value1&key2=value2`) and bring it into canonical encoding
+pub(crate) fn parse_tagging(tagging: Option<String>) -> S3Result<Option<String>> {
+    tagging
+        .map(|tagging| canonical_tagging(&tagging, MAX_OBJECT_TAGS, "Object"))
+        .transpose()
+}
+
+/// `kind` names the tagged resource in the error messages
+fn canonical_tagging(tagging: &str, max_tags: usize, kind: &str) -> S3Result<String> {
+    let mut keys = std::collections::HashSet::new();
+    let mut canonical = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in form_urlencoded::parse(tagging.as_bytes()) {
+        if keys.len() == max_tags {
+            return Err(invalid_tag(format!("{kind} tags cannot be greater than {max_tags}")));
+        }
+        if key.is_empty() || key.chars().count() > MAX_TAG_KEY_LENGTH {
+            return Err(invalid_tag(format!("The TagKey you have provided is invalid: {key}")));
+        }
+        if value.chars().count() > MAX_TAG_VALUE_LENGTH {
+            return Err(invalid_tag(format!("The TagValue you have provided is invalid: {value}")));
+        }
+        let is_valid = |s: &str| {
+            s.chars()
+                .all(|c| c.is_alphanumeric() || c.is_whitespace() || "_.:/=+-@".contains(c))
+        };
+        if !is_valid(&key) || !is_valid(&value) {
+            return Err(invalid_tag(format!("The TagKey or TagValue you have provided is invalid: {key}")));
+        }
+        if key.starts_with("aws:") {
+            return Err(invalid_tag("Your TagKey cannot be prefixed with aws:".to_owned()));
+        }
+        if !keys.insert(key.clone()) {
+            return Err(invalid_tag("Cannot provide multiple Tags with the same key".to_owned()));
+        }
+        canonical.append_pair(&key, &value);
+    }
+    Ok(canonical.finish())
+}
+
+fn invalid_tag(message: String) -> s3s::S3Error {
+    let mut err = s3s::S3Error::with_message(s3s::S3ErrorCode::Custom("InvalidTag".into()), message);
+    err.set_status_code(hyper::StatusCode::BAD_REQUEST);
+    err
+}
+
+/// Documented error of a bucket sub-resource which has not been set, clients such as Terraform rely on the code
+/// Stored policies have been validated, one which does not parse any more is treated as public
+fn is_public_policy(policy: &str) -> bool {
+    PolicyDocument::parse_bucket_policy(policy).map_or(true, |policy| policy.is_public())
+}
+
+fn not_configured(code: &'static str) -> s3s::S3Error {
+    let mut err = s3s::S3Error::new(s3s::S3ErrorCode::Custom(code.into()));
+    err.set_status_code(hyper::StatusCode::NOT_FOUND);
+    err
+}
+
+/// Upper limit of keys returned by a single listing, the same as in AWS
+const MAX_LIST_KEYS: i32 = 1000;
+
+fn inventory_configuration(config: InventoryConfig) -> InventoryConfiguration {
+    InventoryConfiguration {
+        destination: InventoryDestination {
+            s3_bucket_destination: InventoryS3BucketDestination {
+                account_id: None,
+                bucket: format!("{BUCKET_ARN_PREFIX}{}", config.destination_bucket),
+                encryption: None,
+                format: InventoryFormat::from(config.format),
+                prefix: config.destination_prefix,
+            },
+        },
+        filter: config.prefix.map(|prefix| InventoryFilter { prefix }),
+        id: config.id,
+        included_object_versions: InventoryIncludedObjectVersions::from(config.included_versions),
+        is_enabled: config.enabled,
+        optional_fields: None,
+        schedule: InventorySchedule {
+            frequency: InventoryFrequency::from(config.frequency),
+        },
+    }
+}
+
+fn metrics_configuration(config: MetricsConfig) -> MetricsConfiguration {
+    let mut tags: Vec<Tag> = config
+        .tagging
+        .iter()
+        .flat_map(|tagging| form_urlencoded::parse(tagging.as_bytes()))
+        .map(|(key, value)| Tag {
+            key: key.into_owned(),
+            value: value.into_owned(),
+        })
+        .collect();
+    let filter = match (config.prefix, tags.len()) {
+        (None, 0) => None,
+        (Some(prefix), 0) => Some(MetricsFilter::Prefix(prefix)),
+        (None, 1) => tags.pop().map(MetricsFilter::Tag),
+        (prefix, _) => Some(MetricsFilter::And(MetricsAndOperator {
+            access_point_arn: None,
+            prefix,
+            tags: Some(tags),
+        })),
+    };
+    MetricsConfiguration { filter, id: config.id }
+}
+
+/// Condition of a PutObject on the current object, None for an unconditional write
+fn write_condition(headers: &hyper::HeaderMap) -> S3Result<Option<WriteCondition>> {
+    let header = |name| match headers.get(name).map(|v| v.to_str()) {
+        Some(Ok(value)) => Ok(Some(value.to_owned())),
+        Some(Err(_)) => Err(s3_error!(InvalidArgument, "Invalid {} header", name)),
+        None => Ok(None),
+    };
+    let if_match = header(hyper::header::IF_MATCH.as_str())?;
+    let if_none_match = header(hyper::header::IF_NONE_MATCH.as_str())?;
+    if if_none_match.as_deref().is_some_and(|v| v.trim() != "*") {
+        return Err(s3_error!(NotImplemented, "If-None-Match only supports * for writes"));
+    }
+    let version = match header(IF_VERSION_HEADER)? {
+        Some(token) => match Uuid::parse_str(token.trim()) {
+            Ok(version) => Some(version),
+            // a token the gateway never returned can not match the current object
+            Err(_) => return Err(s3_error!(PreconditionFailed, "Unknown version token: {}", token)),
+        },
+        None => None,
+    };
+    if if_match.is_none() && if_none_match.is_none() && version.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(WriteCondition {
+        if_match,
+        if_none_match,
+        version,
+    }))
+}
+
+fn version_token(blob_id: &Uuid) -> hyper::header::HeaderValue {
+    blob_id.to_string().parse().expect("a uuid is a valid header value")
+}
+
+/// Check an `If-Match`/`If-None-Match` condition, a list of quoted ETags or `*`
+pub(crate) fn etag_matches(condition: &str, etag: &str) -> bool {
+    condition.split(',').map(str::trim).any(|c| {
+        let c = c.trim_start_matches("W/").trim_matches('"');
+        c == "*" || c == etag.trim_matches('"')
+    })
+}
+
+fn parse_upload_id(upload_id: &str) -> S3Result<Uuid> {
+    Uuid::parse_str(upload_id).map_err(|_| s3s::S3Error::new(s3s::S3ErrorCode::NoSuchUpload))
+}
+
 fn hex(input: impl AsRef<[u8]>) -> String {
     hex_simd::encode_to_string(input.as_ref(), hex_simd::AsciiCase::Lower)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_conditions() {
+        let etag = "5eb63bbbe01eeed093cb22bb8f5acdc3";
+        assert!(etag_matches(r#""5eb63bbbe01eeed093cb22bb8f5acdc3""#, etag));
+        assert!(etag_matches(r#"W/"5eb63bbbe01eeed093cb22bb8f5acdc3""#, etag));
+        assert!(etag_matches(r#""other", "5eb63bbbe01eeed093cb22bb8f5acdc3""#, etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches(r#""d41d8cd98f00b204e9800998ecf8427e""#, etag));
+        assert!(etag_matches(
+            r#""72d27afac8e2fbd3662861b9d607a02c-2""#,
+            "72d27afac8e2fbd3662861b9d607a02c-2"
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{header, HeaderMap, Method, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+
+/// Adds the canonical request and the string to sign to `SignatureDoesNotMatch` errors like AWS does.
+///
+/// s3s does not expose the values it has signed, so they are rebuilt from the request head. The payload
+/// hash is taken from `x-amz-content-sha256` which is what s3s signs as well. Disabled by default because
+/// the response reveals the request details to the client.
+#[derive(Clone)]
+pub struct SignatureDebug<S> {
+    inner: S,
+    enabled: bool,
+}
+
+impl<S> SignatureDebug<S> {
+    pub fn new(inner: S, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for SignatureDebug<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        if !self.enabled {
+            return Box::pin(self.inner.call(req));
+        }
+        let signed = SignedRequest::new(req.method(), req.uri(), req.headers());
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            match signed {
+                Some(signed) if res.status() == StatusCode::FORBIDDEN => Ok(signed.explain(res).await),
+                _ => Ok(res),
+            }
+        })
+    }
+}
+
+/// Values a SigV4 client has signed
+struct SignedRequest {
+    canonical_request: String,
+    string_to_sign: String,
+}
+
+impl SignedRequest {
+    fn new(method: &Method, uri: &hyper::Uri, headers: &HeaderMap) -> Option<Self> {
+        let query: Vec<(String, String)> = uri
+            .query()
+            .map(|q| form_urlencoded::parse(q.as_bytes()).into_owned().collect())
+            .unwrap_or_default();
+        let query_param = |name: &str| query.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone());
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(ToOwned::to_owned);
+
+        let presigned = query_param("X-Amz-Signature").is_some();
+        let (credential, signed_headers, amz_date, payload) = if presigned {
+            (
+                query_param("X-Amz-Credential")?,
+                query_param("X-Amz-SignedHeaders")?,
+                query_param("X-Amz-Date")?,
+                "UNSIGNED-PAYLOAD".to_owned(),
+            )
+        } else {
+            let authorization = header(header::AUTHORIZATION.as_str())?;
+            let params = authorization.strip_prefix("AWS4-HMAC-SHA256")?;
+            let param = |name: &str| {
+                params
+                    .split(',')
+                    .find_map(|p| p.trim().strip_prefix(name))
+                    .map(ToOwned::to_owned)
+            };
+            (
+                param("Credential=")?,
+                param("SignedHeaders=")?,
+                header("x-amz-date")?,
+                header("x-amz-content-sha256").unwrap_or_else(|| hex_sha256(b"")),
+            )
+        };
+        // <access key>/<date>/<region>/s3/aws4_request
+        let (_, scope) = credential.split_once('/')?;
+
+        let mut canonical_request = format!("{method}\n");
+        let path = urlencoding::decode(uri.path()).ok()?;
+        uri_encode(&mut canonical_request, &path, false);
+        canonical_request.push('\n');
+
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .filter(|(k, _)| !(presigned && k == "X-Amz-Signature"))
+            .map(|(k, v)| {
+                let (mut name, mut value) = (String::new(), String::new());
+                uri_encode(&mut name, k, true);
+                uri_encode(&mut value, v, true);
+                (name, value)
+            })
+            .collect();
+        query.sort_by(|a, b| a.0.cmp(&b.0));
+        let query: Vec<String> = query.into_iter().map(|(k, v)| format!("{k}={v}")).collect();
+        canonical_request.push_str(&query.join("&"));
+        canonical_request.push('\n');
+
+        let mut names = Vec::new();
+        for name in signed_headers.split(';') {
+            for value in headers.get_all(name) {
+                canonical_request.push_str(&format!("{name}:{}\n", value.to_str().unwrap_or_default().trim()));
+                names.push(name);
+            }
+        }
+        canonical_request.push('\n');
+        canonical_request.push_str(&names.join(";"));
+        canonical_request.push('\n');
+        canonical_request.push_str(&payload);
+
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}", hex_sha256(canonical_request.as_bytes()));
+        Some(Self {
+            canonical_request,
+            string_to_sign,
+        })
+    }
+
+    /// Append the signed values to the error if the signature is wrong
+    async fn explain(self, res: Response<s3s::Body>) -> Response<s3s::Body> {
+        let (mut parts, body) = res.into_parts();
+        let body = match hyper::body::to_bytes(body).await {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::debug!(error = %err, "unable to read the error response");
+                return Response::from_parts(parts, s3s::Body::empty());
+            }
+        };
+        let text = String::from_utf8_lossy(&body);
+        if !text.contains("<Code>SignatureDoesNotMatch</Code>") {
+            return Response::from_parts(parts, s3s::Body::from(body));
+        }
+
+        tracing::info!(
+            canonical_request = %self.canonical_request,
+            string_to_sign = %self.string_to_sign,
+            "signature does not match"
+        );
+        let string_to_sign_bytes: Vec<String> = self.string_to_sign.bytes().map(|b| format!("{b:02x}")).collect();
+        let details = format!(
+            "<CanonicalRequest>{}</CanonicalRequest><StringToSign>{}</StringToSign><StringToSignBytes>{}</StringToSignBytes></Error>",
+            xml_escape(&self.canonical_request),
+            xml_escape(&self.string_to_sign),
+            string_to_sign_bytes.join(" ")
+        );
+        let text = text.replacen("</Error>", &details, 1);
+        parts.headers.insert(header::CONTENT_LENGTH, text.len().into());
+        Response::from_parts(parts, s3s::Body::from(text))
+    }
+}
+
+pub(crate) fn hex_sha256(data: &[u8]) -> String {
+    hex_simd::encode_to_string(Sha256::digest(data), hex_simd::AsciiCase::Lower)
+}
+
+/// URI encoding of the canonical request, the same as used by s3s
+pub(crate) fn uri_encode(output: &mut String, input: &str, encode_slash: bool) {
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'~' | b'.' => output.push(byte as char),
+            b'/' if !encode_slash => output.push('/'),
+            _ => output.push_str(&format!("%{byte:02X}")),
+        }
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,365 @@
+//! Logs, traces and metrics.
+//!
+//! Logs go to stdout. When an OTLP endpoint is configured traces, metrics and logs are exported there as well.
+
+use std::io::IsTerminal;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{Request, Response};
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::metrics::{Counter, Histogram, Unit};
+use opentelemetry::{Key, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::{
+    trace::{self, RandomIdGenerator, Sampler},
+    Resource,
+};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::alerts::Backlog;
+use crate::reload::LogHandle;
+
+const SERVICE_NAME: &str = "s3s_rados";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct TelemetryConfig {
+    /// OTLP gRPC endpoint (http://ip:port), nothing is exported if not set
+    pub otlp_endpoint: Option<String>,
+    /// fraction of traces started by the gateway which are exported
+    pub sample_ratio: f64,
+    pub metrics_interval: Duration,
+}
+
+static METER_PROVIDER: OnceLock<SdkMeterProvider> = OnceLock::new();
+
+/// Install the global subscriber. The returned handle changes the log filter.
+pub fn setup(
+    config: &TelemetryConfig,
+    log_level: Option<&str>,
+) -> Result<LogHandle, Box<dyn std::error::Error + Send + Sync + 'static>> {
+    let Some(endpoint) = &config.otlp_endpoint else {
+        let env_filter = match log_level {
+            Some(log_level) => EnvFilter::new(log_level),
+            None => EnvFilter::from_default_env(),
+        };
+        let (env_filter, handle) = tracing_subscriber::reload::Layer::new(env_filter);
+        let enable_color = std::io::stdout().is_terminal();
+
+        tracing_subscriber::Registry::default()
+            .with(env_filter)
+            .with(tracing_subscriber::fmt::layer().pretty().with_ansi(enable_color))
+            .try_init()?;
+        return Ok(handle);
+    };
+
+    let resource = Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]);
+    let exporter = || {
+        opentelemetry_otlp::new_exporter()
+            .tonic()
+            .with_endpoint(endpoint)
+            .with_timeout(EXPORT_TIMEOUT)
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter())
+        .with_trace_config(
+            trace::config()
+                // remote parents decide for themselves, otherwise traces would be broken in the middle
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(config.sample_ratio))))
+                .with_id_generator(RandomIdGenerator::default())
+                .with_max_events_per_span(64)
+                .with_max_attributes_per_span(16)
+                .with_max_events_per_span(16)
+                .with_resource(resource.clone()),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(opentelemetry_sdk::runtime::Tokio)
+        .with_exporter(exporter())
+        .with_resource(resource.clone())
+        .with_period(config.metrics_interval)
+        .build()?;
+    let _ = METER_PROVIDER.set(meter_provider);
+
+    let logger = opentelemetry_otlp::new_pipeline()
+        .logging()
+        .with_exporter(exporter())
+        .with_log_config(opentelemetry_sdk::logs::config().with_resource(resource))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    let (filter, handle) = tracing_subscriber::reload::Layer::new(EnvFilter::new(log_level.unwrap_or("debug")));
+    let fmt_layer = tracing_subscriber::fmt::layer();
+    let opentelemetry = tracing_opentelemetry::layer().with_tracer(tracer);
+    // events of the exporter itself would be exported again
+    let logs = LogBridge { logger }.with_filter(filter_fn(|meta| {
+        !["h2", "hyper", "tonic", "tower", "opentelemetry"]
+            .iter()
+            .any(|target| meta.target().starts_with(target))
+    }));
+    let registry = tracing_subscriber::Registry::default()
+        .with(filter)
+        .with(fmt_layer)
+        .with(opentelemetry)
+        .with(logs);
+    registry.try_init()?;
+
+    Ok(handle)
+}
+
+/// Flush the data which has not been exported yet
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+    opentelemetry::global::shutdown_logger_provider();
+    if let Some(provider) = METER_PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            tracing::error!(error = %err, "unable to shut down the meter provider");
+        }
+    }
+}
+
+pub struct Metrics {
+    pub request_duration: Histogram<f64>,
+    /// by query name, including the wait for a pooled connection
+    pub db_query_duration: Histogram<f64>,
+    /// blobs removed from the backend by the garbage collector
+    pub gc_removed: Counter<u64>,
+    pub gc_failed: Counter<u64>,
+    /// temporary blobs of committed uploads whose registration was left behind
+    pub temp_blobs_recovered: Counter<u64>,
+    /// temporary blobs of interrupted uploads handed over to the GC
+    pub temp_blobs_collected: Counter<u64>,
+    pub temp_blobs_leaked_bytes: Counter<u64>,
+    /// backlog limits which have been exceeded, by alert
+    pub alerts_triggered: Counter<u64>,
+    /// object requests matching a metrics configuration of the bucket, by bucket, filter and operation
+    pub bucket_requests: Counter<u64>,
+    pub bucket_bytes_downloaded: Counter<u64>,
+    pub bucket_bytes_uploaded: Counter<u64>,
+}
+
+/// Instruments are no-op unless metrics are exported
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| {
+        let meter = opentelemetry::global::meter(SERVICE_NAME);
+        Metrics {
+            request_duration: meter
+                .f64_histogram("http.server.request.duration")
+                .with_unit(Unit::new("s"))
+                .with_description("Duration of S3 requests until the response head is sent")
+                .init(),
+            db_query_duration: meter
+                .f64_histogram("db.query.duration")
+                .with_unit(Unit::new("s"))
+                .with_description("Duration of database queries including the wait for a connection")
+                .init(),
+            gc_removed: meter
+                .u64_counter("gc.blobs.removed")
+                .with_description("Blobs removed from the backend by the garbage collector")
+                .init(),
+            gc_failed: meter
+                .u64_counter("gc.blobs.failed")
+                .with_description("Blobs the garbage collector was unable to remove")
+                .init(),
+            temp_blobs_recovered: meter
+                .u64_counter("temp_blobs.recovered")
+                .with_description("Temporary blobs of committed uploads left registered by a crash")
+                .init(),
+            temp_blobs_collected: meter
+                .u64_counter("temp_blobs.collected")
+                .with_description("Temporary blobs of interrupted uploads handed over to the garbage collector")
+                .init(),
+            temp_blobs_leaked_bytes: meter
+                .u64_counter("temp_blobs.leaked")
+                .with_unit(Unit::new("By"))
+                .with_description("Backend data of interrupted uploads handed over to the garbage collector")
+                .init(),
+            alerts_triggered: meter
+                .u64_counter("alerts.triggered")
+                .with_description("Backlogs of the background workers which have exceeded their limit")
+                .init(),
+            bucket_requests: meter
+                .u64_counter("s3.bucket.requests")
+                .with_description("Object requests matching a metrics configuration of the bucket")
+                .init(),
+            bucket_bytes_downloaded: meter
+                .u64_counter("s3.bucket.bytes_downloaded")
+                .with_unit(Unit::new("By"))
+                .with_description("Object data returned by GET requests matching a metrics configuration")
+                .init(),
+            bucket_bytes_uploaded: meter
+                .u64_counter("s3.bucket.bytes_uploaded")
+                .with_unit(Unit::new("By"))
+                .with_description("Object data written by PUT requests matching a metrics configuration")
+                .init(),
+        }
+    })
+}
+
+/// Export the number of open and idle connections, a pool without idle connections makes queries wait
+pub fn observe_db_pool(pool: &sqlx::PgPool) {
+    let meter = opentelemetry::global::meter(SERVICE_NAME);
+    let open = pool.clone();
+    meter
+        .u64_observable_gauge("db.pool.connections")
+        .with_description("Open database connections")
+        .with_callback(move |observer| observer.observe(u64::from(open.size()), &[]))
+        .init();
+    let idle = pool.clone();
+    meter
+        .u64_observable_gauge("db.pool.idle")
+        .with_description("Idle database connections")
+        .with_callback(move |observer| observer.observe(idle.num_idle() as u64, &[]))
+        .init();
+}
+
+/// Export the backlogs last observed by the backlog monitor
+pub fn observe_backlog(backlog: Arc<Backlog>) {
+    let meter = opentelemetry::global::meter(SERVICE_NAME);
+    let gc = backlog.clone();
+    meter
+        .i64_observable_gauge("gc.backlog")
+        .with_description("Blobs waiting for the garbage collector whose trash retention has expired")
+        .with_callback(move |observer| observer.observe(gc.gc.load(Ordering::Relaxed), &[]))
+        .init();
+    let temp = backlog.clone();
+    meter
+        .i64_observable_gauge("temp_blobs.oldest_age")
+        .with_unit(Unit::new("s"))
+        .with_description("Age of the oldest temporary blob")
+        .with_callback(move |observer| observer.observe(temp.temp_blob_age.load(Ordering::Relaxed), &[]))
+        .init();
+    meter
+        .i64_observable_gauge("replication.failed")
+        .with_description("Objects whose replication has failed")
+        .with_callback(move |observer| observer.observe(backlog.replication_failed.load(Ordering::Relaxed), &[]))
+        .init();
+}
+
+/// Records the duration of every request by method and status code.
+#[derive(Clone)]
+pub struct RequestMetrics<S> {
+    inner: S,
+}
+
+impl<S> RequestMetrics<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for RequestMetrics<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let method = req.method().to_string();
+        let started = Instant::now();
+        let fut = self.inner.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            let status = match &res {
+                Ok(res) => i64::from(res.status().as_u16()),
+                Err(_) => 500,
+            };
+            metrics().request_duration.record(
+                started.elapsed().as_secs_f64(),
+                &[
+                    KeyValue::new("http.request.method", method),
+                    KeyValue::new("http.response.status_code", status),
+                ],
+            );
+            res
+        })
+    }
+}
+
+/// Forwards tracing events to the OTLP logger
+struct LogBridge {
+    logger: opentelemetry_sdk::logs::Logger,
+}
+
+impl<S: tracing::Subscriber> Layer<S> for LogBridge {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let meta = event.metadata();
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+
+        let severity = match *meta.level() {
+            tracing::Level::TRACE => Severity::Trace,
+            tracing::Level::DEBUG => Severity::Debug,
+            tracing::Level::INFO => Severity::Info,
+            tracing::Level::WARN => Severity::Warn,
+            tracing::Level::ERROR => Severity::Error,
+        };
+        let record = LogRecord::builder()
+            .with_timestamp(SystemTime::now())
+            .with_severity_number(severity)
+            .with_severity_text(meta.level().as_str())
+            .with_body(fields.message)
+            .with_attributes(fields.attributes)
+            .with_attribute("target", meta.target())
+            .build();
+        self.logger.emit(record);
+    }
+}
+
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    attributes: Vec<(Key, AnyValue)>,
+}
+
+impl EventFields {
+    fn push(&mut self, field: &tracing::field::Field, value: AnyValue) {
+        if field.name() == "message" {
+            if let AnyValue::String(value) = value {
+                self.message = value.to_string();
+                return;
+            }
+        }
+        self.attributes.push((Key::new(field.name()), value));
+    }
+}
+
+impl tracing::field::Visit for EventFields {
+    fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
+        self.push(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.push(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.push(field, AnyValue::Boolean(value));
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.push(field, value.to_owned().into());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.push(field, format!("{value:?}").into());
+    }
+}
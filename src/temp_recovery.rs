@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{MetaStore, TempBlobState};
+
+const INTERVAL: Duration = Duration::from_secs(600);
+const BATCH_SIZE: i64 = 1000;
+
+/// Reconciles temporary blobs left behind when the gateway has stopped between writing the data and
+/// committing the metadata.
+///
+/// Blobs registered longer than `max_age` ago are no longer written by any upload. If the metadata refers
+/// to such a blob the upload has been committed and only the registration is dropped, otherwise the data
+/// is handed over to the GC. The first pass runs at startup.
+pub struct TempBlobRecovery {
+    db: Arc<dyn MetaStore>,
+    blob: Arc<dyn BlobStore>,
+    max_age: Duration,
+}
+
+impl TempBlobRecovery {
+    pub fn new(db: Arc<dyn MetaStore>, blob: Arc<dyn BlobStore>, max_age: Duration) -> Self {
+        Self { db, blob, max_age }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(err) = self.recover().await {
+                tracing::error!(error = %err, "unable to recover temporary blobs");
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    async fn recover(&self) -> anyhow::Result<()> {
+        let (mut committed, mut collected, mut leaked_bytes) = (0, 0, 0);
+        loop {
+            let blobs = self.db.list_stale_temp_blobs(self.max_age, BATCH_SIZE).await?;
+            if blobs.is_empty() {
+                break;
+            }
+
+            for blob_id in blobs {
+                // the size is only reported, data which has never reached the backend is not found
+                let size = match self.blob.stat(&blob_id.to_string()).await {
+                    Ok((size, _)) => size,
+                    Err(_) => 0,
+                };
+                match self.db.recover_temp_blob(&blob_id).await? {
+                    TempBlobState::Committed => {
+                        committed += 1;
+                        crate::telemetry::metrics().temp_blobs_recovered.add(1, &[]);
+                    }
+                    TempBlobState::Collected => {
+                        collected += 1;
+                        leaked_bytes += size;
+                        crate::telemetry::metrics().temp_blobs_collected.add(1, &[]);
+                        crate::telemetry::metrics().temp_blobs_leaked_bytes.add(size, &[]);
+                    }
+                    TempBlobState::Gone => {}
+                }
+            }
+        }
+
+        if committed > 0 || collected > 0 {
+            tracing::info!(committed, collected, leaked_bytes, "temporary blobs have been recovered");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::blob_store::BlobStore;
+use crate::meta_store::{Blob, MetaStore};
+
+const BATCH_SIZE: i64 = 100;
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Cheaper backend which receives the blobs that have not been used for a while
+#[derive(Debug, Clone)]
+pub struct ColdTier {
+    /// storage class reported for the objects in the tier
+    pub storage_class: String,
+    pub store: Arc<dyn BlobStore>,
+}
+
+/// Backend holding the blobs of the storage class. Blobs without a class are in the standard backend,
+/// any class belongs to the cold tier so that renaming the class does not lose the existing blobs.
+pub fn blob_store<'a>(
+    standard: &'a Arc<dyn BlobStore>,
+    cold: Option<&'a ColdTier>,
+    storage_class: Option<&str>,
+) -> Result<&'a Arc<dyn BlobStore>, s3s::S3Error> {
+    match (storage_class, cold) {
+        (None, _) => Ok(standard),
+        (Some(_), Some(cold)) => Ok(&cold.store),
+        (Some(class), None) => Err(s3s::s3_error!(
+            InternalError,
+            "Blob of storage class {} is in the cold tier which is not configured",
+            class
+        )),
+    }
+}
+
+/// Moves blobs whose objects have not been read or written for `cold_after` to the cold tier.
+///
+/// Reads are recorded by the S3 service at most once per sampling interval, so the age of a hot object
+/// is off by up to that interval. The data is copied to a new blob of the cold tier which replaces the
+/// original one in the objects, the original blob is removed by the GC. Multipart blobs become a single
+/// backend object. Objects are not moved back on access, they are served from the cold tier.
+pub struct TieringWorker {
+    db: Arc<dyn MetaStore>,
+    source: Arc<dyn BlobStore>,
+    cold: ColdTier,
+    cold_after: Duration,
+}
+
+impl TieringWorker {
+    pub fn new(db: Arc<dyn MetaStore>, source: Arc<dyn BlobStore>, cold: ColdTier, cold_after: Duration) -> Self {
+        Self {
+            db,
+            source,
+            cold,
+            cold_after,
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            let blobs = match self.db.list_cold_blobs(self.cold_after, BATCH_SIZE).await {
+                Ok(blobs) => blobs,
+                Err(err) => {
+                    tracing::error!(error = %err, "unable to list cold blobs");
+                    tokio::time::sleep(IDLE_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            if blobs.is_empty() {
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+
+            for blob in blobs {
+                match move_to_cold(self.db.as_ref(), self.source.as_ref(), &self.cold, &blob).await {
+                    Ok(true) => tracing::debug!(blob = %blob.id, "blob has been moved to the cold tier"),
+                    Ok(false) => tracing::debug!(blob = %blob.id, "blob has been replaced during the transition"),
+                    Err(err) => {
+                        tracing::error!(error = %err, blob = %blob.id, "unable to move blob to the cold tier");
+                        // the blob would be picked up again right away
+                        tokio::time::sleep(IDLE_INTERVAL).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Copy a blob of the standard backend into the cold tier and attach the copy to the objects of the blob.
+/// Returns false if the blob is not used anymore.
+#[tracing::instrument(level = "debug", skip(db, standard, cold))]
+pub(crate) async fn move_to_cold(
+    db: &dyn MetaStore,
+    standard: &dyn BlobStore,
+    cold: &ColdTier,
+    blob: &Blob,
+) -> anyhow::Result<bool> {
+    let copy = Blob {
+        id: Uuid::new_v4(),
+        parts: None,
+        part_size: None,
+        storage_class: Some(cold.storage_class.clone()),
+        ..blob.clone()
+    };
+    db.write_temp_blob(&copy).await?;
+
+    let res = async {
+        copy_data(db, standard, cold.store.as_ref(), blob, &copy.id.to_string()).await?;
+        db.transition_blob(&blob.id, &copy).await
+    }
+    .await;
+
+    if !matches!(res, Ok(true)) {
+        // temp blobs are cleaned up in the standard backend only
+        if let Err(err) = cold.store.delete(&copy.id.to_string()).await {
+            tracing::warn!(error = %err, blob = %copy.id, "unable to remove the unused copy from the cold tier");
+        }
+        db.clean_temp_blob(&copy).await;
+    }
+    res
+}
+
+/// Copy a blob of the cold tier back into the standard backend and attach the copy to the objects of the blob.
+/// Returns the copy, None if the blob is not used anymore.
+#[tracing::instrument(level = "debug", skip(db, standard, cold))]
+pub(crate) async fn move_to_standard(
+    db: &dyn MetaStore,
+    standard: &dyn BlobStore,
+    cold: &ColdTier,
+    blob: &Blob,
+) -> anyhow::Result<Option<Blob>> {
+    // the copy keeps the etag and the upload time, like a blob moved to the cold tier
+    let copy = Blob {
+        id: Uuid::new_v4(),
+        parts: None,
+        part_size: None,
+        storage_class: None,
+        ..blob.clone()
+    };
+    db.write_temp_blob(&copy).await?;
+
+    let res = async {
+        copy_data(db, cold.store.as_ref(), standard, blob, &copy.id.to_string()).await?;
+        db.transition_blob(&blob.id, &copy).await
+    }
+    .await;
+
+    match res {
+        Ok(true) => Ok(Some(copy)),
+        res => {
+            db.clean_temp_blob(&copy).await;
+            res.map(|_| None)
+        }
+    }
+}
+
+async fn copy_data(db: &dyn MetaStore, from: &dyn BlobStore, to: &dyn BlobStore, blob: &Blob, key: &str) -> anyhow::Result<()> {
+    let size = blob.size as u64;
+    let parts = db.get_blob_parts(&blob.id).await?;
+    let mut reader = if parts.is_empty() {
+        from.get_reader(&blob.id.to_string(), 0, size).await?
+    } else {
+        let parts = parts.iter().map(|p| (p.blob_id.to_string(), p.size as u64)).collect();
+        from.get_parts_reader(parts, 0, size).await?
+    };
+
+    let mut writer = to.get_writer(key).await?;
+    let mut copied = 0;
+    while let Some(chunk) = reader.next().await {
+        let chunk = chunk?;
+        copied += chunk.len() as u64;
+        writer.write_all(&chunk).await?;
+    }
+    writer.flush().await?;
+    if copied != size {
+        anyhow::bail!("blob has {copied} bytes instead of {size}");
+    }
+    Ok(())
+}
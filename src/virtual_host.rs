@@ -0,0 +1,229 @@
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use hyper::service::Service;
+use hyper::{header, HeaderMap, Request, Response, StatusCode, Uri};
+use s3s::{S3Error, S3ErrorCode};
+
+use crate::post_policy::error_response;
+
+/// Handling of virtual-hosted-style requests to buckets with dots in their names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DottedBuckets {
+    /// serve them as any other bucket
+    Allow,
+    /// answer with `InvalidRequest`, the bucket stays reachable path-style
+    Reject,
+    /// answer with `301 PermanentRedirect` to the path-style URL
+    Redirect,
+}
+
+/// Applies [`DottedBuckets`] to virtual-hosted-style requests.
+///
+/// A wildcard certificate for `*.s3.example.org` does not cover `my.bucket.s3.example.org`, so TLS
+/// terminated in front of the gateway fails for such buckets or is not verified by clients. Path-style
+/// requests are not affected, dotted bucket names are always valid there.
+#[derive(Clone)]
+pub struct VirtualHost<S> {
+    inner: S,
+    base_domain: Option<String>,
+    dotted: DottedBuckets,
+}
+
+impl<S> VirtualHost<S> {
+    pub fn new(inner: S, base_domain: Option<String>, dotted: DottedBuckets) -> Self {
+        Self {
+            inner,
+            base_domain,
+            dotted,
+        }
+    }
+}
+
+impl<S> Service<Request<hyper::Body>> for VirtualHost<S>
+where
+    S: Service<Request<hyper::Body>, Response = Response<s3s::Body>> + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<hyper::Body>) -> Self::Future {
+        let Some(domain) = self.base_domain.as_deref() else {
+            return Box::pin(self.inner.call(req));
+        };
+        let bucket = request_host(&req).and_then(|host| host_bucket(host, domain));
+        let Some(bucket) = bucket.filter(|b| b.contains('.')) else {
+            return Box::pin(self.inner.call(req));
+        };
+        let res = match self.dotted {
+            DottedBuckets::Allow => return Box::pin(self.inner.call(req)),
+            DottedBuckets::Reject => {
+                let err = S3Error::with_message(
+                    S3ErrorCode::InvalidRequest,
+                    "Buckets with dots in their names must be addressed path-style",
+                );
+                error_response(&err)
+            }
+            DottedBuckets::Redirect => redirect_to_path_style(&req, domain, bucket),
+        };
+        Box::pin(async move { Ok(res) })
+    }
+}
+
+fn redirect_to_path_style<B>(req: &Request<B>, domain: &str, bucket: &str) -> Response<s3s::Body> {
+    // the scheme of the original request is not known behind a TLS terminating proxy
+    let mut location = format!("//{domain}/{bucket}{}", req.uri().path());
+    if let Some(query) = req.uri().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    let mut err = S3Error::with_message(
+        S3ErrorCode::PermanentRedirect,
+        "Buckets with dots in their names must be addressed path-style",
+    );
+    err.set_status_code(StatusCode::MOVED_PERMANENTLY);
+    let mut res = error_response(&err);
+    if let Ok(location) = header::HeaderValue::try_from(location) {
+        res.headers_mut().insert(header::LOCATION, location);
+    }
+    res
+}
+
+/// Host header of the request, HTTP/2 requests carry it in the URI authority
+fn request_host<B>(req: &Request<B>) -> Option<&str> {
+    host(req.headers(), req.uri())
+}
+
+fn host<'a>(headers: &'a HeaderMap, uri: &'a Uri) -> Option<&'a str> {
+    match headers.get(header::HOST) {
+        Some(host) => host.to_str().ok(),
+        None => uri.authority().map(|a| a.as_str()),
+    }
+}
+
+/// Canonical URL of an object, virtual-hosted-style under the base domain unless the bucket has dots in its name
+/// and such buckets are not served that way, path-style on the host of the request otherwise.
+///
+/// The scheme is taken from `X-Forwarded-Proto` of a TLS terminating proxy, the gateway itself serves HTTP.
+pub(crate) fn object_url(
+    headers: &HeaderMap,
+    uri: &Uri,
+    base_domain: Option<&str>,
+    dotted: DottedBuckets,
+    bucket: &str,
+    key: &str,
+) -> String {
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| matches!(*v, "http" | "https"))
+        .unwrap_or("http");
+    let key: Vec<_> = key.split('/').map(urlencoding::encode).collect();
+    let key = key.join("/");
+    match base_domain {
+        Some(domain) if !bucket.contains('.') || dotted == DottedBuckets::Allow => {
+            format!("{scheme}://{bucket}.{domain}/{key}")
+        }
+        Some(domain) => format!("{scheme}://{domain}/{bucket}/{key}"),
+        None => match host(headers, uri) {
+            Some(host) => format!("{scheme}://{host}/{bucket}/{key}"),
+            None => format!("/{bucket}/{key}"),
+        },
+    }
+}
+
+/// Bucket addressed by a virtual-hosted-style host name, None for requests to the base domain itself or
+/// to other hosts. The port of the host is ignored unless the base domain has one as well.
+pub(crate) fn host_bucket<'a>(host: &'a str, base_domain: &str) -> Option<&'a str> {
+    let host = if base_domain.contains(':') {
+        host
+    } else {
+        host.rsplit_once(':').map_or(host, |(name, _)| name)
+    };
+    let split = host.len().checked_sub(base_domain.len() + 1)?;
+    let (bucket, domain) = (host.get(..split)?, host.get(split..)?);
+    let domain = domain.strip_prefix('.')?;
+    (!bucket.is_empty() && domain.eq_ignore_ascii_case(base_domain)).then_some(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOMAIN: &str = "s3.example.org";
+
+    #[test]
+    fn host_bucket_of_base_domain() {
+        assert_eq!(host_bucket("bucket.s3.example.org", DOMAIN), Some("bucket"));
+        assert_eq!(host_bucket("my.bucket.s3.example.org", DOMAIN), Some("my.bucket"));
+        assert_eq!(host_bucket("s3.example.org", DOMAIN), None);
+        assert_eq!(host_bucket(".s3.example.org", DOMAIN), None);
+        assert_eq!(host_bucket("bucket.example.org", DOMAIN), None);
+        assert_eq!(host_bucket("bucket.s3.example.org.evil.com", DOMAIN), None);
+        assert_eq!(host_bucket("xs3.example.org", DOMAIN), None);
+    }
+
+    #[test]
+    fn host_bucket_with_port() {
+        assert_eq!(host_bucket("bucket.s3.example.org:9000", DOMAIN), Some("bucket"));
+        assert_eq!(host_bucket("s3.example.org:9000", DOMAIN), None);
+        assert_eq!(host_bucket("bucket.s3.example.org:9000", "s3.example.org:9000"), Some("bucket"));
+        assert_eq!(host_bucket("bucket.s3.example.org:9001", "s3.example.org:9000"), None);
+        assert_eq!(host_bucket("bucket.s3.example.org", "s3.example.org:9000"), None);
+    }
+
+    #[test]
+    fn host_bucket_is_case_insensitive_in_the_domain() {
+        assert_eq!(host_bucket("bucket.S3.Example.ORG", DOMAIN), Some("bucket"));
+        assert_eq!(host_bucket("bucket.s3.example.org", "S3.EXAMPLE.ORG"), Some("bucket"));
+        // the bucket is passed on as sent, invalid names are rejected later
+        assert_eq!(host_bucket("Bucket.s3.example.org", DOMAIN), Some("Bucket"));
+    }
+
+    #[test]
+    fn host_bucket_of_ip_hosts() {
+        assert_eq!(host_bucket("127.0.0.1", DOMAIN), None);
+        assert_eq!(host_bucket("127.0.0.1:9000", DOMAIN), None);
+        assert_eq!(host_bucket("[::1]", DOMAIN), None);
+        assert_eq!(host_bucket("[::1]:9000", DOMAIN), None);
+    }
+
+    #[test]
+    fn host_from_header_or_authority() {
+        let mut headers = HeaderMap::new();
+        let uri: Uri = "http://authority.example.org/bucket/key".parse().unwrap();
+        assert_eq!(host(&headers, &uri), Some("authority.example.org"));
+
+        headers.insert(header::HOST, "header.example.org:9000".parse().unwrap());
+        assert_eq!(host(&headers, &uri), Some("header.example.org:9000"));
+    }
+
+    #[test]
+    fn object_url_style() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "10.0.0.1:9000".parse().unwrap());
+        let uri = Uri::from_static("/");
+
+        let url = |domain, dotted, bucket| object_url(&headers, &uri, domain, dotted, bucket, "a b/c");
+        assert_eq!(url(Some(DOMAIN), DottedBuckets::Reject, "bucket"), "http://bucket.s3.example.org/a%20b/c");
+        assert_eq!(
+            url(Some(DOMAIN), DottedBuckets::Reject, "my.bucket"),
+            "http://s3.example.org/my.bucket/a%20b/c"
+        );
+        assert_eq!(
+            url(Some(DOMAIN), DottedBuckets::Allow, "my.bucket"),
+            "http://my.bucket.s3.example.org/a%20b/c"
+        );
+        assert_eq!(url(None, DottedBuckets::Allow, "bucket"), "http://10.0.0.1:9000/bucket/a%20b/c");
+
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        let url = object_url(&headers, &uri, Some(DOMAIN), DottedBuckets::Allow, "bucket", "key");
+        assert_eq!(url, "https://bucket.s3.example.org/key");
+    }
+}